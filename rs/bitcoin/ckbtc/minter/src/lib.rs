@@ -1,7 +1,7 @@
 use crate::address::BitcoinAddress;
 use crate::logs::{P0, P1};
 use crate::memo::Status;
-use crate::queries::WithdrawalFee;
+use crate::queries::{EstimateRetrieveBtcFeeError, RetrieveBtcFeeEstimate, WithdrawalFee};
 use crate::state::ReimbursementReason;
 use crate::tasks::schedule_after;
 use candid::{CandidType, Deserialize};
@@ -187,12 +187,14 @@ pub async fn estimate_fee_per_vbyte() -> Option<MillisatoshiPerByte> {
     match management::get_current_fees(btc_network).await {
         Ok(fees) => {
             if btc_network == Network::Regtest {
+                state::mutate_state(|s| s.update_fee_rate(DEFAULT_FEE, ic_cdk::api::time()));
                 return Some(DEFAULT_FEE);
             }
             if fees.len() >= 100 {
                 state::mutate_state(|s| {
                     s.last_fee_per_vbyte = fees.clone();
                     s.retrieve_btc_min_amount = compute_min_withdrawal_amount(fees[50]);
+                    s.update_fee_rate(fees[50], ic_cdk::api::time());
                 });
                 Some(fees[50])
             } else {
@@ -215,6 +217,107 @@ pub async fn estimate_fee_per_vbyte() -> Option<MillisatoshiPerByte> {
     }
 }
 
+/// Forms a batch of pending retrieve_btc requests and builds the transaction
+/// that pays them out. Returns None if there is no transaction to sign, in
+/// particular if the fee rate estimate of the minter is stale at time `now`.
+fn build_batch_transaction(
+    s: &mut state::CkBtcMinterState,
+    main_address: BitcoinAddress,
+    ecdsa_public_key: ECDSAPublicKey,
+    now: u64,
+) -> Option<SignTxRequest> {
+    let fee_millisatoshi_per_vbyte = match s.fresh_fee_per_vbyte(now) {
+        Some(fee) => fee,
+        None => {
+            log!(
+                P0,
+                "[submit_pending_requests]: the fee rate estimate is stale, waiting for a refresh"
+            );
+            return None;
+        }
+    };
+
+    let batch = s.build_batch(MAX_REQUESTS_PER_BATCH);
+
+    if batch.is_empty() {
+        return None;
+    }
+
+    let outputs: Vec<_> = batch
+        .iter()
+        .map(|req| (req.address.clone(), req.amount))
+        .collect();
+
+    match build_unsigned_transaction(
+        &mut s.available_utxos,
+        outputs,
+        main_address,
+        fee_millisatoshi_per_vbyte,
+    ) {
+        Ok((unsigned_tx, change_output, utxos)) => {
+            for req in batch.iter() {
+                s.push_in_flight_request(req.block_index, state::InFlightStatus::Signing);
+            }
+
+            Some(SignTxRequest {
+                key_name: s.ecdsa_key_name.clone(),
+                ecdsa_public_key,
+                change_output,
+                outpoint_account: filter_output_accounts(s, &unsigned_tx),
+                network: s.btc_network,
+                unsigned_tx,
+                requests: batch,
+                utxos,
+            })
+        }
+        Err(BuildTxError::AmountTooLow) => {
+            log!(P0,
+                "[submit_pending_requests]: dropping requests for total BTC amount {} to addresses {} (too low to cover the fees)",
+                tx::DisplayAmount(batch.iter().map(|req| req.amount).sum::<u64>()),
+                batch.iter().map(|req| req.address.display(s.btc_network)).collect::<Vec<_>>().join(",")
+            );
+
+            // There is no point in retrying the request because the
+            // amount is too low.
+            for request in batch {
+                state::audit::remove_retrieve_btc_request(s, request);
+            }
+            None
+        }
+        Err(BuildTxError::DustOutput { address, amount }) => {
+            log!(P0,
+                "[submit_pending_requests]: dropping a request for BTC amount {} to {} (too low to cover the fees)",
+                 tx::DisplayAmount(amount), address.display(s.btc_network)
+            );
+
+            let mut requests_to_put_back = vec![];
+            for request in batch {
+                if request.address == address && request.amount == amount {
+                    // Finalize the request that we cannot fulfill.
+                    state::audit::remove_retrieve_btc_request(s, request);
+                } else {
+                    // Keep the rest of the requests in the batch, we will
+                    // try to build a new transaction on the next iteration.
+                    requests_to_put_back.push(request);
+                }
+            }
+
+            s.push_from_in_flight_to_pending_requests(requests_to_put_back);
+
+            None
+        }
+        Err(BuildTxError::NotEnoughFunds) => {
+            log!(P0,
+                "[submit_pending_requests]: not enough funds to unsigned transaction for requests at block indexes [{}]",
+                batch.iter().map(|req| req.block_index.to_string()).collect::<Vec<_>>().join(",")
+            );
+
+            s.push_from_in_flight_to_pending_requests(batch);
+            None
+        }
+    }
+}
+
 /// Constructs and sends out signed bitcoin transactions for pending retrieve
 /// requests.
 async fn submit_pending_requests() {
@@ -224,6 +327,34 @@ async fn submit_pending_requests() {
         return;
     }
 
+    let (limit_reached, was_limit_reached) = state::mutate_state(|s| {
+        let limit_reached = s.in_flight_transaction_limit_reached();
+        (
+            limit_reached,
+            std::mem::replace(&mut s.in_flight_limit_reached, limit_reached),
+        )
+    });
+    if limit_reached {
+        if !was_limit_reached {
+            state::read_state(|s| {
+                log!(
+                    P0,
+                    "[submit_pending_requests]: keeping {} requests pending: {} submitted transactions are not confirmed yet (limit: {})",
+                    s.pending_retrieve_btc_requests.len(),
+                    s.submitted_transactions.len(),
+                    s.max_in_flight_transactions.unwrap_or_default(),
+                )
+            });
+        }
+        return;
+    }
+    if was_limit_reached {
+        log!(
+            P0,
+            "[submit_pending_requests]: the number of submitted transactions is below the limit again"
+        );
+    }
+
     let main_account = Account {
         owner: ic_cdk::id(),
         subaccount: None,
@@ -232,91 +363,11 @@ async fn submit_pending_requests() {
     let ecdsa_public_key = updates::get_btc_address::init_ecdsa_public_key().await;
     let main_address = address::account_to_bitcoin_address(&ecdsa_public_key, &main_account);
 
-    let fee_millisatoshi_per_vbyte = match estimate_fee_per_vbyte().await {
-        Some(fee) => fee,
-        None => return,
-    };
-
+    // If the refresh fails, we can still use the previous estimate as long as
+    // it is fresh enough.
+    let _ = estimate_fee_per_vbyte().await;
     let maybe_sign_request = state::mutate_state(|s| {
-        let batch = s.build_batch(MAX_REQUESTS_PER_BATCH);
-
-        if batch.is_empty() {
-            return None;
-        }
-
-        let outputs: Vec<_> = batch
-            .iter()
-            .map(|req| (req.address.clone(), req.amount))
-            .collect();
-
-        match build_unsigned_transaction(
-            &mut s.available_utxos,
-            outputs,
-            main_address,
-            fee_millisatoshi_per_vbyte,
-        ) {
-            Ok((unsigned_tx, change_output, utxos)) => {
-                for req in batch.iter() {
-                    s.push_in_flight_request(req.block_index, state::InFlightStatus::Signing);
-                }
-
-                Some(SignTxRequest {
-                    key_name: s.ecdsa_key_name.clone(),
-                    ecdsa_public_key,
-                    change_output,
-                    outpoint_account: filter_output_accounts(s, &unsigned_tx),
-                    network: s.btc_network,
-                    unsigned_tx,
-                    requests: batch,
-                    utxos,
-                })
-            }
-            Err(BuildTxError::AmountTooLow) => {
-                log!(P0,
-                    "[submit_pending_requests]: dropping requests for total BTC amount {} to addresses {} (too low to cover the fees)",
-                    tx::DisplayAmount(batch.iter().map(|req| req.amount).sum::<u64>()),
-                    batch.iter().map(|req| req.address.display(s.btc_network)).collect::<Vec<_>>().join(",")
-                );
-
-                // There is no point in retrying the request because the
-                // amount is too low.
-                for request in batch {
-                    state::audit::remove_retrieve_btc_request(s, request);
-                }
-                None
-            }
-            Err(BuildTxError::DustOutput { address, amount }) => {
-                log!(P0,
-                    "[submit_pending_requests]: dropping a request for BTC amount {} to {} (too low to cover the fees)",
-                     tx::DisplayAmount(amount), address.display(s.btc_network)
-                );
-
-                let mut requests_to_put_back = vec![];
-                for request in batch {
-                    if request.address == address && request.amount == amount {
-                        // Finalize the request that we cannot fulfill.
-                        state::audit::remove_retrieve_btc_request(s, request);
-                    } else {
-                        // Keep the rest of the requests in the batch, we will
-                        // try to build a new transaction on the next iteration.
-                        requests_to_put_back.push(request);
-                    }
-                }
-
-                s.push_from_in_flight_to_pending_requests(requests_to_put_back);
-
-                None
-            }
-            Err(BuildTxError::NotEnoughFunds) => {
-                log!(P0,
-                    "[submit_pending_requests]: not enough funds to unsigned transaction for requests at block indexes [{}]",
-                    batch.iter().map(|req| req.block_index.to_string()).collect::<Vec<_>>().join(",")
-                );
-
-                s.push_from_in_flight_to_pending_requests(batch);
-                None
-            }
-        }
+        build_batch_transaction(s, main_address, ecdsa_public_key, ic_cdk::api::time())
     });
 
     if let Some(req) = maybe_sign_request {
@@ -433,15 +484,23 @@ fn finalized_txids(candidates: &[state::SubmittedBtcTransaction], new_utxos: &[U
 async fn reimburse_failed_kyt() {
     let try_to_reimburse = state::read_state(|s| s.reimbursement_map.clone());
     for (burn_block_index, entry) in try_to_reimburse {
-        let (memo_status, kyt_fee) = match entry.reason {
-            ReimbursementReason::TaintedDestination { kyt_fee, .. } => (Status::Rejected, kyt_fee),
-            ReimbursementReason::CallFailed => (Status::CallFailed, 0),
-        };
-        let reimburse_memo = crate::memo::MintMemo::KytFail {
+        let kyt_fail_memo = |kyt_fee: u64, status: Status| crate::memo::MintMemo::KytFail {
             kyt_fee: Some(kyt_fee),
-            status: Some(memo_status),
+            status: Some(status),
             associated_burn_index: Some(burn_block_index),
         };
+        let (reimburse_memo, kyt_fee) = match entry.reason {
+            ReimbursementReason::TaintedDestination { kyt_fee, .. } => {
+                (kyt_fail_memo(kyt_fee, Status::Rejected), kyt_fee)
+            }
+            ReimbursementReason::CallFailed => (kyt_fail_memo(0, Status::CallFailed), 0),
+            ReimbursementReason::CancelledByUser => (
+                crate::memo::MintMemo::CancelledRetrieveBtc {
+                    associated_burn_index: Some(burn_block_index),
+                },
+                0,
+            ),
+        };
         if let Ok(block_index) = crate::updates::update_balance::mint(
             entry
                 .amount
@@ -596,9 +655,16 @@ async fn finalize_requests() {
     );
 
     // We shall use the latest fee estimate for replacement transactions.
-    let fee_per_vbyte = match estimate_fee_per_vbyte().await {
+    let _ = estimate_fee_per_vbyte().await;
+    let fee_per_vbyte = match state::read_state(|s| s.fresh_fee_per_vbyte(ic_cdk::api::time())) {
         Some(fee) => fee,
-        None => return,
+        None => {
+            log!(
+                P0,
+                "[finalize_requests]: the fee rate estimate is stale, waiting for a refresh"
+            );
+            return;
+        }
     };
 
     let key_name = state::read_state(|s| s.ecdsa_key_name.clone());
@@ -1018,6 +1084,63 @@ pub fn build_unsigned_transaction(
     ))
 }
 
+/// Estimates the fees of a withdrawal of `amount` satoshi to the `destination`
+/// address by building the transaction that would pay it out, without
+/// modifying the minter state.
+///
+/// The estimate assumes that the withdrawal is the only request in its batch.
+///
+/// Arguments:
+///   * `available_utxos` - the list of UTXOs available to the minter.
+///   * `destination` - the BTC address of the withdrawal.
+///   * `main_address` - the BTC address of the minter's main account.
+///   * `amount` - the withdrawal amount, including the KYT fee.
+///   * `fee_per_vbyte` - the current 50th percentile of BTC fees, in millisatoshi/byte.
+///   * `kyt_fee` - the KYT fee charged from the withdrawal amount.
+pub fn estimate_retrieve_btc_fee(
+    available_utxos: &BTreeSet<Utxo>,
+    destination: BitcoinAddress,
+    main_address: BitcoinAddress,
+    amount: u64,
+    fee_per_vbyte: u64,
+    kyt_fee: u64,
+) -> Result<RetrieveBtcFeeEstimate, EstimateRetrieveBtcFeeError> {
+    let retrieve_amount = amount
+        .checked_sub(kyt_fee)
+        .ok_or(EstimateRetrieveBtcFeeError::AmountTooLow)?;
+    if retrieve_amount == 0 {
+        return Err(EstimateRetrieveBtcFeeError::AmountTooLow);
+    }
+
+    // The transaction is built on a copy of the UTXOs, so the dry run does not
+    // affect the UTXOs available to the minter.
+    let mut utxos = available_utxos.clone();
+    let (unsigned_tx, _, _) = build_unsigned_transaction(
+        &mut utxos,
+        vec![(destination, retrieve_amount)],
+        main_address,
+        fee_per_vbyte,
+    )
+    .map_err(|err| match err {
+        BuildTxError::NotEnoughFunds => EstimateRetrieveBtcFeeError::InsufficientFunds,
+        BuildTxError::AmountTooLow | BuildTxError::DustOutput { .. } => {
+            EstimateRetrieveBtcFeeError::AmountTooLow
+        }
+    })?;
+
+    let inputs_value = unsigned_tx.inputs.iter().map(|i| i.value).sum::<u64>();
+    let outputs_value = unsigned_tx.outputs.iter().map(|o| o.value).sum::<u64>();
+    let bitcoin_fee = inputs_value - outputs_value;
+    let net_amount = unsigned_tx.outputs[0].value;
+    let minter_fee = retrieve_amount - net_amount - bitcoin_fee;
+
+    Ok(RetrieveBtcFeeEstimate {
+        bitcoin_fee,
+        minter_fee: minter_fee + kyt_fee,
+        net_amount,
+    })
+}
+
 /// Distributes an amount across the specified number of shares as fairly as
 /// possible.
 ///
@@ -1,7 +1,7 @@
 use crate::logs::P0;
-use crate::state::eventlog::{replay, Event};
+use crate::state::eventlog::{replay_sequenced, Event};
 use crate::state::{replace_state, Mode};
-use crate::storage::{count_events, events, record_event};
+use crate::storage::{count_events, record_event, sequenced_events};
 use candid::{CandidType, Deserialize};
 use ic_base_types::CanisterId;
 use ic_canister_log::log;
@@ -32,6 +32,22 @@ pub struct UpgradeArgs {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kyt_principal: Option<CanisterId>,
+
+    /// Maximum number of submitted transactions that are not yet confirmed.
+    /// Once the limit is reached, the minter keeps retrieve_btc requests
+    /// pending until one of the transactions confirms.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_in_flight_transactions: Option<u64>,
+
+    /// Maximum age in nanoseconds of the fee rate estimate that the minter
+    /// uses to build transactions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_rate_staleness_nanos: Option<u64>,
+
+    /// Additional KYT canisters that share the UTXO checks with the main KYT
+    /// canister. Replaces the current list if set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub additional_kyt_principals: Option<Vec<CanisterId>>,
 }
 
 pub fn post_upgrade(upgrade_args: Option<UpgradeArgs>) {
@@ -48,7 +64,7 @@ pub fn post_upgrade(upgrade_args: Option<UpgradeArgs>) {
 
     log!(P0, "[upgrade]: replaying {} events", count_events());
 
-    let state = replay(events()).unwrap_or_else(|e| {
+    let state = replay_sequenced(sequenced_events()).unwrap_or_else(|e| {
         ic_cdk::trap(&format!(
             "[upgrade]: failed to replay the event log: {:?}",
             e
@@ -3,16 +3,20 @@ use candid::Principal;
 use ic_canister_log::export as export_logs;
 use ic_canisters_http_types::{HttpRequest, HttpResponse, HttpResponseBuilder};
 use ic_cdk_macros::{init, post_upgrade, query, update};
+use ic_ckbtc_minter::address::{account_to_bitcoin_address, BitcoinAddress};
 use ic_ckbtc_minter::dashboard::build_dashboard;
 use ic_ckbtc_minter::lifecycle::upgrade::UpgradeArgs;
 use ic_ckbtc_minter::lifecycle::{self, init::MinterArg};
 use ic_ckbtc_minter::metrics::encode_metrics;
-use ic_ckbtc_minter::queries::{EstimateFeeArg, RetrieveBtcStatusRequest, WithdrawalFee};
+use ic_ckbtc_minter::queries::{
+    EstimateFeeArg, EstimateRetrieveBtcFeeArgs, EstimateRetrieveBtcFeeError,
+    RetrieveBtcFeeEstimate, RetrieveBtcStatusRequest, WithdrawalFee,
+};
 use ic_ckbtc_minter::state::{read_state, RetrieveBtcStatus};
 use ic_ckbtc_minter::tasks::{schedule_now, TaskType};
 use ic_ckbtc_minter::updates::retrieve_btc::{
-    RetrieveBtcArgs, RetrieveBtcError, RetrieveBtcOk, RetrieveBtcWithApprovalArgs,
-    RetrieveBtcWithApprovalError,
+    CancelRetrieveBtcError, RetrieveBtcArgs, RetrieveBtcError, RetrieveBtcOk,
+    RetrieveBtcWithApprovalArgs, RetrieveBtcWithApprovalError,
 };
 use ic_ckbtc_minter::updates::{
     self,
@@ -57,13 +61,13 @@ fn ok_or_die(result: Result<(), String>) {
 /// Checks that ckBTC minter state internally consistent.
 #[cfg(feature = "self_check")]
 fn check_invariants() -> Result<(), String> {
-    use ic_ckbtc_minter::state::eventlog::replay;
+    use ic_ckbtc_minter::state::eventlog::replay_sequenced;
 
     read_state(|s| {
         s.check_invariants()?;
 
-        let events: Vec<_> = storage::events().collect();
-        let recovered_state = replay(events.clone().into_iter())
+        let events: Vec<_> = storage::sequenced_events().collect();
+        let recovered_state = replay_sequenced(events.clone().into_iter())
             .unwrap_or_else(|e| panic!("failed to replay log {:?}: {:?}", events, e));
 
         recovered_state.check_invariants()?;
@@ -160,6 +164,13 @@ async fn retrieve_btc_with_approval(
     check_postcondition(updates::retrieve_btc::retrieve_btc_with_approval(args).await)
 }
 
+#[candid_method(update)]
+#[update]
+fn cancel_retrieve_btc(req: RetrieveBtcStatusRequest) -> Result<(), CancelRetrieveBtcError> {
+    check_anonymous_caller();
+    check_postcondition(updates::retrieve_btc::cancel_retrieve_btc(req.block_index))
+}
+
 #[candid_method(query)]
 #[query]
 fn retrieve_btc_status(req: RetrieveBtcStatusRequest) -> RetrieveBtcStatus {
@@ -186,6 +197,44 @@ fn estimate_withdrawal_fee(arg: EstimateFeeArg) -> WithdrawalFee {
     })
 }
 
+#[candid_method(query)]
+#[query]
+fn estimate_retrieve_btc_fee(
+    args: EstimateRetrieveBtcFeeArgs,
+) -> Result<RetrieveBtcFeeEstimate, EstimateRetrieveBtcFeeError> {
+    read_state(|s| {
+        let destination = BitcoinAddress::parse(&args.address, s.btc_network)
+            .map_err(|e| EstimateRetrieveBtcFeeError::MalformedAddress(e.to_string()))?;
+        let ecdsa_public_key = s.ecdsa_public_key.as_ref().ok_or_else(|| {
+            EstimateRetrieveBtcFeeError::TemporarilyUnavailable(
+                "the minter has not fetched its ECDSA public key yet".to_string(),
+            )
+        })?;
+        // The minter refuses to build transactions with a stale fee rate, so
+        // it does not estimate their fees with one either.
+        let fee_per_vbyte = s.fresh_fee_per_vbyte(ic_cdk::api::time()).ok_or_else(|| {
+            EstimateRetrieveBtcFeeError::TemporarilyUnavailable(
+                "the fee rate estimate is stale, retry later".to_string(),
+            )
+        })?;
+        let main_address = account_to_bitcoin_address(
+            ecdsa_public_key,
+            &Account {
+                owner: ic_cdk::id(),
+                subaccount: None,
+            },
+        );
+        ic_ckbtc_minter::estimate_retrieve_btc_fee(
+            &s.available_utxos,
+            destination,
+            main_address,
+            args.amount,
+            fee_per_vbyte,
+            s.kyt_fee,
+        )
+    })
+}
+
 #[candid_method(query)]
 #[query]
 fn get_minter_info() -> MinterInfo {
@@ -276,12 +325,9 @@ fn http_request(req: HttpRequest) -> HttpResponse {
 #[candid_method(query)]
 #[query]
 fn get_events(args: GetEventsArg) -> Vec<Event> {
-    const MAX_EVENTS_PER_QUERY: usize = 2000;
+    const MAX_EVENTS_PER_QUERY: u64 = 2000;
 
-    storage::events()
-        .skip(args.start as usize)
-        .take(MAX_EVENTS_PER_QUERY.min(args.length as usize))
-        .collect()
+    storage::get_events(args.start, MAX_EVENTS_PER_QUERY.min(args.length))
 }
 
 #[cfg(feature = "self_check")]
@@ -52,6 +52,24 @@ pub enum MintMemo<'a> {
         #[n(2)]
         associated_burn_index: Option<u64>,
     },
+    #[n(3)]
+    /// The minter converted several UTXOs of the same account to ckBTC in a
+    /// single mint. The UTXOs are listed in the minter event of the mint.
+    ConvertBatch {
+        #[n(0)]
+        /// The number of converted UTXOs.
+        utxo_count: Option<u32>,
+        #[n(1)]
+        /// The total KYT check fee.
+        kyt_fee: Option<u64>,
+    },
+    #[n(4)]
+    /// The minter reimbursed a retrieve_btc request that the user cancelled.
+    CancelledRetrieveBtc {
+        #[n(0)]
+        /// The BURN transaction index of the cancelled request.
+        associated_burn_index: Option<u64>,
+    },
 }
 
 #[derive(Decode, Encode, Debug, Eq, PartialEq)]
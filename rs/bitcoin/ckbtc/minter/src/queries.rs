@@ -16,3 +16,34 @@ pub struct WithdrawalFee {
     pub minter_fee: u64,
     pub bitcoin_fee: u64,
 }
+
+#[derive(CandidType, Deserialize)]
+pub struct EstimateRetrieveBtcFeeArgs {
+    /// The withdrawal amount, in satoshi, as it would be passed to `retrieve_btc`.
+    pub amount: u64,
+    /// The destination BTC address.
+    pub address: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RetrieveBtcFeeEstimate {
+    /// The fee paid to the Bitcoin network.
+    pub bitcoin_fee: u64,
+    /// The fee charged by the minter, including the KYT fee.
+    pub minter_fee: u64,
+    /// The amount that the destination address would receive.
+    pub net_amount: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum EstimateRetrieveBtcFeeError {
+    /// The destination address is not a valid address on the minter's network.
+    MalformedAddress(String),
+    /// The withdrawal amount does not cover the fees.
+    AmountTooLow,
+    /// The minter does not currently have enough UTXOs to pay out the amount.
+    InsufficientFunds,
+    /// The minter cannot estimate fees yet, e.g. because it has not fetched
+    /// its ECDSA public key or its fee rate estimate is stale.
+    TemporarilyUnavailable(String),
+}
@@ -48,6 +48,11 @@ macro_rules! ensure {
 /// history.
 const MAX_FINALIZED_REQUESTS: usize = 100;
 
+/// The maximum age of the fee rate estimate used to build transactions if
+/// the minter configuration does not specify one. The minter refreshes the
+/// estimate every hour.
+pub const DEFAULT_MAX_FEE_RATE_STALENESS_NANOS: u64 = 2 * 60 * 60 * 1_000_000_000;
+
 thread_local! {
     static __STATE: RefCell<Option<CkBtcMinterState>> = RefCell::default();
 }
@@ -71,6 +76,24 @@ pub struct RetrieveBtcRequest {
     #[serde(rename = "kyt_provider")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kyt_provider: Option<Principal>,
+    /// The account to reimburse if the request gets cancelled.
+    /// The field is optional because old retrieve_btc requests
+    /// didn't record it. Such requests cannot be cancelled.
+    #[serde(rename = "reimbursement_account")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reimbursement_account: Option<Account>,
+    /// The KYT fee charged for this request.
+    /// The field is optional because old retrieve_btc requests
+    /// didn't record it, they were charged the KYT fee of the minter.
+    #[serde(rename = "kyt_fee")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kyt_fee: Option<u64>,
+    /// The KYT canister that checked the destination address.
+    /// The field is optional because old retrieve_btc requests
+    /// didn't record it, they were checked by the main KYT canister.
+    #[serde(rename = "kyt_canister")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kyt_canister: Option<Principal>,
 }
 
 /// A transaction output storing the minter's change.
@@ -122,6 +145,15 @@ pub enum FinalizedStatus {
     },
 }
 
+/// A fee rate estimate together with the time at which the minter obtained it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRate {
+    /// Fee per vbyte in millisatoshi.
+    pub fee_per_vbyte: u64,
+    /// The IC time at which the minter obtained the estimate.
+    pub updated_at: u64,
+}
+
 /// The status of a Bitcoin transaction that the minter hasn't yet sent to the Bitcoin network.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InFlightStatus {
@@ -255,6 +287,21 @@ pub struct CkBtcMinterState {
     /// before being sent.
     pub max_time_in_queue_nanos: u64,
 
+    /// Maximum number of submitted transactions that are not yet confirmed.
+    /// There is no limit if `None`.
+    #[serde(default)]
+    pub max_in_flight_transactions: Option<u64>,
+
+    /// Maximum age in nanoseconds of the fee rate estimate used to build
+    /// transactions. Defaults to [DEFAULT_MAX_FEE_RATE_STALENESS_NANOS].
+    #[serde(default)]
+    pub max_fee_rate_staleness_nanos: Option<u64>,
+
+    /// The latest fee rate estimate. It is not part of the event log: the
+    /// minter refreshes it before building transactions.
+    #[serde(skip)]
+    pub last_fee_rate: Option<FeeRate>,
+
     /// Per-principal lock for update_balance
     pub update_balance_principals: BTreeSet<Principal>,
 
@@ -301,6 +348,16 @@ pub struct CkBtcMinterState {
     /// The principal of the KYT canister.
     pub kyt_principal: Option<CanisterId>,
 
+    /// Additional KYT canisters that share the UTXO checks with the main
+    /// KYT canister.
+    #[serde(default)]
+    pub additional_kyt_principals: Vec<CanisterId>,
+
+    /// The position in [CkBtcMinterState::kyt_principals] of the KYT canister
+    /// that will perform the next UTXO check.
+    #[serde(skip)]
+    pub next_kyt_principal_index: usize,
+
     /// The set of UTXOs unused in pending transactions.
     pub available_utxos: BTreeSet<Utxo>,
 
@@ -328,6 +385,11 @@ pub struct CkBtcMinterState {
     #[serde(skip)]
     pub is_distributing_fee: bool,
 
+    /// Whether the minter found the in-flight transaction limit reached the
+    /// last time it tried to submit requests, so that it logs only changes.
+    #[serde(skip)]
+    pub in_flight_limit_reached: bool,
+
     /// The mode in which the minter runs.
     pub mode: Mode,
 
@@ -367,6 +429,7 @@ pub enum ReimbursementReason {
         kyt_fee: u64,
     },
     CallFailed,
+    CancelledByUser,
 }
 
 impl CkBtcMinterState {
@@ -408,6 +471,9 @@ impl CkBtcMinterState {
             mode,
             kyt_principal,
             kyt_fee,
+            max_in_flight_transactions,
+            max_fee_rate_staleness_nanos,
+            additional_kyt_principals,
         }: UpgradeArgs,
     ) {
         if let Some(retrieve_btc_min_amount) = retrieve_btc_min_amount {
@@ -437,6 +503,44 @@ impl CkBtcMinterState {
         if let Some(kyt_fee) = kyt_fee {
             self.kyt_fee = kyt_fee;
         }
+        if let Some(max_in_flight_transactions) = max_in_flight_transactions {
+            self.max_in_flight_transactions = Some(max_in_flight_transactions);
+        }
+        if let Some(max_fee_rate_staleness_nanos) = max_fee_rate_staleness_nanos {
+            self.max_fee_rate_staleness_nanos = Some(max_fee_rate_staleness_nanos);
+        }
+        if let Some(additional_kyt_principals) = additional_kyt_principals {
+            self.additional_kyt_principals = additional_kyt_principals;
+        }
+    }
+
+    /// Returns the pool of KYT canisters: the main KYT canister followed by
+    /// the additional ones, without duplicates.
+    pub fn kyt_principals(&self) -> Vec<CanisterId> {
+        let mut principals: Vec<CanisterId> = vec![];
+        for principal in self
+            .kyt_principal
+            .iter()
+            .chain(self.additional_kyt_principals.iter())
+        {
+            if !principals.contains(principal) {
+                principals.push(*principal);
+            }
+        }
+        principals
+    }
+
+    /// Picks the KYT canister for the next UTXO check, going round-robin over
+    /// [CkBtcMinterState::kyt_principals].
+    /// Returns None if no KYT canister is configured.
+    pub fn next_kyt_principal(&mut self) -> Option<CanisterId> {
+        let principals = self.kyt_principals();
+        if principals.is_empty() {
+            return None;
+        }
+        let principal = principals[self.next_kyt_principal_index % principals.len()];
+        self.next_kyt_principal_index = (self.next_kyt_principal_index + 1) % principals.len();
+        Some(principal)
     }
 
     pub fn validate_config(&self) {
@@ -610,6 +714,14 @@ impl CkBtcMinterState {
         }
     }
 
+    /// Returns true if the number of submitted transactions that are not yet
+    /// confirmed reached the configured limit, in which case the minter must not
+    /// submit new transactions.
+    pub fn in_flight_transaction_limit_reached(&self) -> bool {
+        self.max_in_flight_transactions
+            .map_or(false, |max| self.submitted_transactions.len() as u64 >= max)
+    }
+
     /// Forms a batch of retrieve_btc requests that the minter can fulfill.
     pub fn build_batch(&mut self, max_size: usize) -> Vec<RetrieveBtcRequest> {
         let available_utxos_value = self.available_utxos.iter().map(|u| u.value).sum::<u64>();
@@ -628,6 +740,28 @@ impl CkBtcMinterState {
         batch
     }
 
+    /// Returns the latest fee per vbyte estimate if it is not older than the
+    /// maximum staleness at the given time, or `None` if the minter must refresh
+    /// the estimate before building transactions.
+    pub fn fresh_fee_per_vbyte(&self, now: u64) -> Option<u64> {
+        let fee_rate = self.last_fee_rate?;
+        (now.saturating_sub(fee_rate.updated_at) <= self.max_fee_rate_staleness())
+            .then_some(fee_rate.fee_per_vbyte)
+    }
+
+    fn max_fee_rate_staleness(&self) -> u64 {
+        self.max_fee_rate_staleness_nanos
+            .unwrap_or(DEFAULT_MAX_FEE_RATE_STALENESS_NANOS)
+    }
+
+    /// Sets the latest fee rate estimate, obtained at the given time.
+    pub fn update_fee_rate(&mut self, fee_per_vbyte: u64, updated_at: u64) {
+        self.last_fee_rate = Some(FeeRate {
+            fee_per_vbyte,
+            updated_at,
+        });
+    }
+
     /// Returns the total number of all retrieve_btc requests that we haven't
     /// finalized yet.
     pub fn count_incomplete_retrieve_btc_requests(&self) -> usize {
@@ -805,6 +939,45 @@ impl CkBtcMinterState {
         }
     }
 
+    /// Cancels the pending retrieve_btc request with the specified block index:
+    /// removes the request from the queue and schedules the reimbursement of the
+    /// burnt amount. The KYT fee charged for the request is reimbursed only as far
+    /// as the minter still owes it to the KYT provider, i.e., it did not distribute
+    /// it yet, so that the reimbursement never mints ckBTC a second time.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if there is no such pending request or if the
+    /// request has no reimbursement account.
+    fn cancel_pending_request(&mut self, block_index: u64) {
+        let request = self
+            .remove_pending_request(block_index)
+            .expect("BUG: attempted to cancel a non-pending retrieve_btc request");
+        let account = request.reimbursement_account.expect(
+            "BUG: attempted to cancel a retrieve_btc request without reimbursement account",
+        );
+
+        let mut refunded_kyt_fee = 0;
+        if let Some(kyt_provider) = request.kyt_provider {
+            if let Some(owed) = self.owed_kyt_amount.get_mut(&kyt_provider) {
+                refunded_kyt_fee = request.kyt_fee.unwrap_or(self.kyt_fee).min(*owed);
+                *owed -= refunded_kyt_fee;
+                if *owed == 0 {
+                    self.owed_kyt_amount.remove(&kyt_provider);
+                }
+            }
+        }
+
+        self.schedule_deposit_reimbursement(
+            block_index,
+            ReimburseDepositTask {
+                account,
+                amount: request.amount + refunded_kyt_fee,
+                reason: ReimbursementReason::CancelledByUser,
+            },
+        );
+    }
+
     /// Marks the specified retrieve_btc request as in-flight.
     ///
     /// # Panics
@@ -848,7 +1021,8 @@ impl CkBtcMinterState {
         }
         self.tokens_burned += request.amount;
         if let Some(kyt_provider) = request.kyt_provider {
-            *self.owed_kyt_amount.entry(kyt_provider).or_insert(0) += self.kyt_fee;
+            *self.owed_kyt_amount.entry(kyt_provider).or_insert(0) +=
+                request.kyt_fee.unwrap_or(self.kyt_fee);
         }
         self.pending_retrieve_btc_requests.push(request);
     }
@@ -936,6 +1110,67 @@ impl CkBtcMinterState {
         }
     }
 
+    /// Returns the outcome of the KYT check of the UTXO with the given outpoint,
+    /// or `None` if the minter has no record of checking that UTXO.
+    ///
+    /// NOTE: Clean UTXOs are only remembered until the minter sees them again in
+    /// an [add_utxos] call, after which this function returns `None` for them.
+    pub fn utxo_check_status(&self, txid: &Txid, vout: u32) -> Option<UtxoCheckStatus> {
+        let has_outpoint = |utxo: &Utxo| &utxo.outpoint.txid == txid && utxo.outpoint.vout == vout;
+        if let Some((_, status, _)) = self
+            .checked_utxos
+            .iter()
+            .find_map(|(utxo, check)| has_outpoint(utxo).then_some(check))
+        {
+            return Some(*status);
+        }
+        self.quarantined_utxos
+            .iter()
+            .any(has_outpoint)
+            .then_some(UtxoCheckStatus::Tainted)
+    }
+
+    /// Lists every UTXO that went through a KYT check along with the check uuid,
+    /// whether the UTXO was clean, and the KYT provider that checked it.
+    /// If a UTXO was checked multiple times, only the last check is listed.
+    ///
+    /// The minter forgets clean UTXOs once it mints them, so the checks are
+    /// rebuilt from the `CheckedUtxo` events of the given event log and from
+    /// the cached [CkBtcMinterState::checked_utxos].
+    pub fn list_checked_utxos(
+        &self,
+        events: impl Iterator<Item = eventlog::Event>,
+    ) -> Vec<(Utxo, String, bool, Principal)> {
+        let mut checks = BTreeMap::new();
+        for event in events {
+            if let eventlog::Event::CheckedUtxo {
+                utxo,
+                uuid,
+                clean,
+                kyt_provider,
+                ..
+            } = event
+            {
+                // Old events have no provider, the main KYT canister checked the UTXO.
+                if let Some(kyt_provider) =
+                    kyt_provider.or_else(|| self.kyt_principal.map(Principal::from))
+                {
+                    checks.insert(utxo, (uuid, clean, kyt_provider));
+                }
+            }
+        }
+        for (utxo, (uuid, status, kyt_provider)) in &self.checked_utxos {
+            checks.insert(
+                utxo.clone(),
+                (uuid.clone(), status.is_clean(), *kyt_provider),
+            );
+        }
+        checks
+            .into_iter()
+            .map(|(utxo, (uuid, clean, kyt_provider))| (utxo, uuid, clean, kyt_provider))
+            .collect()
+    }
+
     /// Decreases the owed amount for the given provider by the amount.
     /// Returns an error if the distributed amount exceeds the amount owed to the provider.
     ///
@@ -977,7 +1212,7 @@ impl CkBtcMinterState {
             } => {
                 *self.owed_kyt_amount.entry(kyt_provider).or_insert(0) += kyt_fee;
             }
-            ReimbursementReason::CallFailed => {}
+            ReimbursementReason::CallFailed | ReimbursementReason::CancelledByUser => {}
         }
         self.reimbursement_map
             .insert(burn_block_index, reimburse_deposit_task);
@@ -1043,6 +1278,18 @@ impl CkBtcMinterState {
 
         ensure_eq!(self.kyt_fee, other.kyt_fee, "kyt_fee does not match");
 
+        ensure_eq!(
+            self.max_in_flight_transactions,
+            other.max_in_flight_transactions,
+            "max_in_flight_transactions does not match"
+        );
+
+        ensure_eq!(
+            self.max_fee_rate_staleness_nanos,
+            other.max_fee_rate_staleness_nanos,
+            "max_fee_rate_staleness_nanos does not match"
+        );
+
         ensure_eq!(
             self.owed_kyt_amount,
             other.owed_kyt_amount,
@@ -1055,6 +1302,12 @@ impl CkBtcMinterState {
             "kyt_principal does not match"
         );
 
+        ensure_eq!(
+            self.additional_kyt_principals,
+            other.additional_kyt_principals,
+            "additional_kyt_principals does not match"
+        );
+
         let my_txs = as_sorted_vec(self.submitted_transactions.iter().cloned(), |tx| tx.txid);
         let other_txs = as_sorted_vec(other.submitted_transactions.iter().cloned(), |tx| tx.txid);
         ensure_eq!(my_txs, other_txs, "submitted_transactions do not match");
@@ -1110,6 +1363,9 @@ impl From<InitArgs> for CkBtcMinterState {
                 .min_confirmations
                 .unwrap_or(crate::lifecycle::init::DEFAULT_MIN_CONFIRMATIONS),
             max_time_in_queue_nanos: args.max_time_in_queue_nanos,
+            max_in_flight_transactions: None,
+            max_fee_rate_staleness_nanos: None,
+            last_fee_rate: None,
             update_balance_principals: Default::default(),
             retrieve_btc_principals: Default::default(),
             retrieve_btc_min_amount: args.retrieve_btc_min_amount,
@@ -1125,12 +1381,15 @@ impl From<InitArgs> for CkBtcMinterState {
             tokens_burned: 0,
             ledger_id: args.ledger_id,
             kyt_principal: args.kyt_principal,
+            additional_kyt_principals: Default::default(),
+            next_kyt_principal_index: 0,
             available_utxos: Default::default(),
             outpoint_account: Default::default(),
             utxos_state_addresses: Default::default(),
             finalized_utxos: Default::default(),
             is_timer_running: false,
             is_distributing_fee: false,
+            in_flight_limit_reached: false,
             mode: args.mode,
             last_fee_per_vbyte: vec![1; 100],
             kyt_fee: args
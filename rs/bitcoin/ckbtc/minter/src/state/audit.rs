@@ -15,10 +15,16 @@ pub fn accept_retrieve_btc_request(state: &mut CkBtcMinterState, request: Retrie
     record_event(&Event::AcceptedRetrieveBtcRequest(request.clone()));
     state.pending_retrieve_btc_requests.push(request.clone());
     if let Some(kyt_provider) = request.kyt_provider {
-        *state.owed_kyt_amount.entry(kyt_provider).or_insert(0) += state.kyt_fee;
+        *state.owed_kyt_amount.entry(kyt_provider).or_insert(0) +=
+            request.kyt_fee.unwrap_or(state.kyt_fee);
     }
 }
 
+pub fn cancel_retrieve_btc(state: &mut CkBtcMinterState, block_index: u64) {
+    record_event(&Event::CancelledRetrieveBtc { block_index });
+    state.cancel_pending_request(block_index);
+}
+
 pub fn add_utxos(
     state: &mut CkBtcMinterState,
     mint_txid: Option<u64>,
@@ -69,12 +75,14 @@ pub fn mark_utxo_checked(
     uuid: String,
     status: UtxoCheckStatus,
     kyt_provider: Principal,
+    kyt_canister: Option<Principal>,
 ) {
     record_event(&Event::CheckedUtxo {
         utxo: utxo.clone(),
         uuid: uuid.clone(),
         clean: status.is_clean(),
         kyt_provider: Some(kyt_provider),
+        kyt_canister,
     });
     state.mark_utxo_checked(utxo.clone(), uuid, status, kyt_provider);
 }
@@ -124,6 +132,7 @@ pub fn retrieve_btc_kyt_failed(
     address: String,
     amount: u64,
     kyt_provider: Principal,
+    kyt_canister: Principal,
     uuid: String,
     block_index: u64,
 ) {
@@ -132,6 +141,7 @@ pub fn retrieve_btc_kyt_failed(
         address,
         amount,
         kyt_provider,
+        kyt_canister: Some(kyt_canister),
         uuid,
         block_index,
     });
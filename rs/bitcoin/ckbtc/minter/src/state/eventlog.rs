@@ -9,6 +9,8 @@ use candid::Principal;
 use ic_btc_interface::{Txid, Utxo};
 use icrc_ledger_types::icrc1::account::Account;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
 
 #[derive(candid::CandidType, Deserialize)]
 pub struct GetEventsArg {
@@ -57,6 +59,15 @@ pub enum Event {
         block_index: u64,
     },
 
+    /// Indicates that the user cancelled a pending retrieve_btc request.
+    /// The minter reimburses the burnt amount, including the KYT fee if it
+    /// did not distribute it to the KYT provider yet.
+    #[serde(rename = "cancelled_retrieve_btc")]
+    CancelledRetrieveBtc {
+        #[serde(rename = "block_index")]
+        block_index: u64,
+    },
+
     /// Indicates that the minter sent out a new transaction to the Bitcoin
     /// network.
     #[serde(rename = "sent_transaction")]
@@ -119,6 +130,9 @@ pub enum Event {
         uuid: String,
         clean: bool,
         kyt_provider: Option<Principal>,
+        /// The KYT canister that performed the check.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        kyt_canister: Option<Principal>,
     },
 
     /// Indicates that the given UTXO's value is too small to pay for a KYT check.
@@ -152,6 +166,9 @@ pub enum Event {
         uuid: String,
         /// The KYT provider responsible for the failed check.
         kyt_provider: Principal,
+        /// The KYT canister that performed the failed check.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        kyt_canister: Option<Principal>,
         /// The block index where the failed check occurred.
         block_index: u64,
     },
@@ -185,6 +202,37 @@ pub enum ReplayLogError {
     EmptyLog,
     /// The event log is inconsistent.
     InconsistentLog(String),
+    /// The sequence numbers of the events are not consecutive,
+    /// i.e. some events are missing or out of order.
+    UnexpectedSequenceNumber { expected: u64, actual: u64 },
+}
+
+/// Reconstructs the minter state from an event log, checking that the sequence
+/// numbers of the events are consecutive starting from zero. Events without a
+/// sequence number were recorded by minter versions that did not assign them.
+pub fn replay_sequenced(
+    events: impl Iterator<Item = (Option<u64>, Event)>,
+) -> Result<CkBtcMinterState, ReplayLogError> {
+    let mut sequence_error = None;
+    let mut expected = 0_u64;
+    let checked_events = events.map_while(|(seq, event)| {
+        if let Some(seq) = seq {
+            if seq != expected {
+                sequence_error = Some(ReplayLogError::UnexpectedSequenceNumber {
+                    expected,
+                    actual: seq,
+                });
+                return None;
+            }
+            expected += 1;
+        }
+        Some(event)
+    });
+    let result = replay(checked_events);
+    match sequence_error {
+        Some(error) => Err(error),
+        None => result,
+    }
 }
 
 /// Reconstructs the minter state from an event log.
@@ -225,6 +273,15 @@ pub fn replay(mut events: impl Iterator<Item = Event>) -> Result<CkBtcMinterStat
                     state: FinalizedStatus::AmountTooLow,
                 })
             }
+            Event::CancelledRetrieveBtc { block_index } => {
+                if !state.has_pending_request(block_index) {
+                    return Err(ReplayLogError::InconsistentLog(format!(
+                        "Attempted to cancel a non-pending retrieve_btc request {}",
+                        block_index
+                    )));
+                }
+                state.cancel_pending_request(block_index);
+            }
             Event::SentBtcTransaction {
                 request_block_indices,
                 txid,
@@ -296,6 +353,7 @@ pub fn replay(mut events: impl Iterator<Item = Event>) -> Result<CkBtcMinterStat
                 uuid,
                 clean,
                 kyt_provider,
+                ..
             } => {
                 let kyt_provider =
                     match kyt_provider.or_else(|| state.kyt_principal.map(Principal::from)) {
@@ -354,3 +412,177 @@ pub fn replay(mut events: impl Iterator<Item = Event>) -> Result<CkBtcMinterStat
 
     Ok(state)
 }
+
+/// Restricts the events replayed by [replay_events_filtered] to a single subject.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventFilter {
+    /// Events touching the deposits, withdrawals and reimbursements of the account.
+    Account(Account),
+    /// Events touching the Bitcoin transaction with the given id, either a
+    /// transaction sent by the minter or the transaction of a deposited UTXO.
+    Txid(Txid),
+    /// Events touching the ledger blocks in the range, i.e., retrieve_btc
+    /// requests and reimbursements identified by their burn block index.
+    BlockIndexRange(RangeInclusive<u64>),
+}
+
+/// The subjects that an event must touch to be replayed.
+///
+/// Besides the filter subject, the set contains the requests, transactions and
+/// UTXOs linked to it, so that the filtered log stays replayable.
+#[derive(Default)]
+struct Subjects {
+    account: Option<Account>,
+    block_range: Option<RangeInclusive<u64>>,
+    block_indices: BTreeSet<u64>,
+    txids: BTreeSet<Txid>,
+    utxos: BTreeSet<Utxo>,
+}
+
+impl Subjects {
+    fn new(filter: &EventFilter) -> Self {
+        let mut subjects = Self::default();
+        match filter {
+            EventFilter::Account(account) => subjects.account = Some(*account),
+            EventFilter::Txid(txid) => {
+                subjects.txids.insert(*txid);
+            }
+            EventFilter::BlockIndexRange(range) => subjects.block_range = Some(range.clone()),
+        }
+        subjects
+    }
+
+    fn has_block_index(&self, block_index: u64) -> bool {
+        self.block_indices.contains(&block_index)
+            || self
+                .block_range
+                .as_ref()
+                .map_or(false, |range| range.contains(&block_index))
+    }
+
+    fn has_utxo(&self, utxo: &Utxo) -> bool {
+        self.utxos.contains(utxo) || self.txids.contains(&utxo.outpoint.txid)
+    }
+
+    /// Returns true if the event should be replayed.
+    fn touches(&self, event: &Event) -> bool {
+        match event {
+            // Events that do not belong to any subject but configure the minter.
+            Event::Init(_) | Event::Upgrade(_) => true,
+            Event::ReceivedUtxos {
+                to_account, utxos, ..
+            } => {
+                self.account.as_ref() == Some(to_account)
+                    || utxos.iter().any(|utxo| self.has_utxo(utxo))
+            }
+            Event::AcceptedRetrieveBtcRequest(req) => {
+                self.has_block_index(req.block_index)
+                    || (self.account.is_some() && req.reimbursement_account == self.account)
+            }
+            Event::RemovedRetrieveBtcRequest { block_index }
+            | Event::CancelledRetrieveBtc { block_index }
+            | Event::RetrieveBtcKytFailed { block_index, .. } => self.has_block_index(*block_index),
+            Event::SentBtcTransaction {
+                request_block_indices,
+                txid,
+                utxos,
+                ..
+            } => {
+                self.txids.contains(txid)
+                    || request_block_indices
+                        .iter()
+                        .any(|block_index| self.has_block_index(*block_index))
+                    || utxos.iter().any(|utxo| self.utxos.contains(utxo))
+            }
+            Event::ReplacedBtcTransaction {
+                old_txid, new_txid, ..
+            } => self.txids.contains(old_txid) || self.txids.contains(new_txid),
+            Event::ConfirmedBtcTransaction { txid } => self.txids.contains(txid),
+            Event::CheckedUtxo { utxo, .. } | Event::IgnoredUtxo { utxo } => self.has_utxo(utxo),
+            // KYT fees accumulate over all the checks, a partial view cannot account for them.
+            Event::DistributedKytFee { .. } => false,
+            Event::ScheduleDepositReimbursement {
+                account,
+                burn_block_index,
+                ..
+            } => self.account.as_ref() == Some(account) || self.has_block_index(*burn_block_index),
+            Event::ReimbursedFailedDeposit {
+                burn_block_index, ..
+            } => self.has_block_index(*burn_block_index),
+        }
+    }
+
+    /// Adds the subjects linked to the event, returns true if the set grew.
+    fn extend(&mut self, event: &Event) -> bool {
+        let mut grew = false;
+        match event {
+            Event::ReceivedUtxos { utxos, .. } => {
+                for utxo in utxos {
+                    grew |= self.utxos.insert(utxo.clone());
+                }
+            }
+            Event::AcceptedRetrieveBtcRequest(req) => {
+                grew |= self.block_indices.insert(req.block_index);
+            }
+            Event::SentBtcTransaction {
+                request_block_indices,
+                txid,
+                ..
+            } => {
+                grew |= self.txids.insert(*txid);
+                for block_index in request_block_indices {
+                    grew |= self.block_indices.insert(*block_index);
+                }
+            }
+            Event::ReplacedBtcTransaction {
+                old_txid, new_txid, ..
+            } => {
+                grew |= self.txids.insert(*old_txid);
+                grew |= self.txids.insert(*new_txid);
+            }
+            Event::ScheduleDepositReimbursement {
+                burn_block_index, ..
+            } => {
+                grew |= self.block_indices.insert(*burn_block_index);
+            }
+            _ => {}
+        }
+        grew
+    }
+}
+
+/// Reconstructs a partial view of the minter state from the events touching
+/// the subject of the filter, e.g., to inspect the history of one account
+/// without replaying an unrelated prefix of the log into a full state.
+///
+/// The events linked to the subject (e.g., the transactions spending the UTXOs
+/// of the account and the other requests batched into them) are replayed as
+/// well, so the filtered log stays consistent.
+///
+/// IMPORTANT: the result is NOT a complete minter state. It lacks the
+/// balances, requests and transactions of the other subjects, as well as the
+/// distributed KYT fees, so the state invariants do not necessarily hold.
+/// Never use it in place of the state returned by [replay].
+pub fn replay_events_filtered(
+    events: impl Iterator<Item = Event>,
+    filter: &EventFilter,
+) -> Result<CkBtcMinterState, ReplayLogError> {
+    let events: Vec<Event> = events.collect();
+
+    // Links between events can point both ways in the log (a transaction is
+    // sent after the requests it pays), so we iterate until the subjects settle.
+    let mut subjects = Subjects::new(filter);
+    loop {
+        let mut grew = false;
+        for event in events.iter() {
+            if subjects.touches(event) {
+                grew |= subjects.extend(event);
+            }
+        }
+        if !grew {
+            break;
+        }
+    }
+
+    replay(events.into_iter().filter(|event| subjects.touches(event)))
+}
@@ -8,6 +8,8 @@ use std::cell::RefCell;
 
 const LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(0);
 const LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(1);
+const SEQUENCE_INDEX_MEMORY_ID: MemoryId = MemoryId::new(2);
+const SEQUENCE_DATA_MEMORY_ID: MemoryId = MemoryId::new(3);
 
 type VMem = VirtualMemory<DefaultMemoryImpl>;
 type EventLog = StableLog<Vec<u8>, VMem, VMem>;
@@ -27,6 +29,19 @@ thread_local! {
                   ).expect("failed to initialize stable log")
               )
         );
+
+    /// The sequence numbers of the events, as pairs of the position of the event in the
+    /// event log and its sequence number. They are kept apart from the events so that
+    /// minter versions without sequence numbers can still decode the event log.
+    static SEQUENCE_NUMBERS: RefCell<EventLog> = MEMORY_MANAGER
+        .with(|m|
+              RefCell::new(
+                  StableLog::init(
+                      m.borrow().get(SEQUENCE_INDEX_MEMORY_ID),
+                      m.borrow().get(SEQUENCE_DATA_MEMORY_ID)
+                  ).expect("failed to initialize stable log")
+              )
+        );
 }
 
 pub struct EventIterator {
@@ -57,6 +72,38 @@ impl Iterator for EventIterator {
     }
 }
 
+pub struct SequencedEventIterator {
+    events: EventIterator,
+    buf: Vec<u8>,
+    seq_pos: u64,
+}
+
+impl Iterator for SequencedEventIterator {
+    type Item = (Option<u64>, Event);
+
+    fn next(&mut self) -> Option<(Option<u64>, Event)> {
+        let pos = self.events.pos;
+        let event = self.events.next()?;
+        // Sequence numbers of events that are no longer at their recorded position are
+        // skipped, which leaves a gap in the sequence numbers for the replay to detect.
+        let seq = SEQUENCE_NUMBERS.with(|seqs| {
+            let seqs = seqs.borrow();
+            while let Ok(()) = seqs.read_entry(self.seq_pos, &mut self.buf) {
+                let (event_pos, seq) = decode_sequence_number(&self.buf);
+                if event_pos > pos {
+                    break;
+                }
+                self.seq_pos = self.seq_pos.saturating_add(1);
+                if event_pos == pos {
+                    return Some(seq);
+                }
+            }
+            None
+        });
+        Some((seq, event))
+    }
+}
+
 /// Encodes an event into a byte array.
 fn encode_event(event: &Event) -> Vec<u8> {
     let mut buf = Vec::new();
@@ -71,6 +118,21 @@ fn decode_event(buf: &[u8]) -> Event {
     ciborium::de::from_reader(buf).expect("failed to decode a minter event")
 }
 
+/// Encodes the sequence number of the event at position `pos` in the event log.
+fn encode_sequence_number(pos: u64, seq: u64) -> Vec<u8> {
+    let mut buf = Vec::new();
+    ciborium::ser::into_writer(&(pos, seq), &mut buf)
+        .expect("failed to encode an event sequence number");
+    buf
+}
+
+/// # Panics
+///
+/// This function panics if the decoding fails.
+fn decode_sequence_number(buf: &[u8]) -> (u64, u64) {
+    ciborium::de::from_reader(buf).expect("failed to decode an event sequence number")
+}
+
 /// Returns an iterator over all minter events.
 pub fn events() -> impl Iterator<Item = Event> {
     EventIterator {
@@ -79,18 +141,75 @@ pub fn events() -> impl Iterator<Item = Event> {
     }
 }
 
+/// Returns an iterator over all minter events and their sequence numbers.
+/// Events recorded by minter versions without sequence numbers have none.
+pub fn sequenced_events() -> SequencedEventIterator {
+    SequencedEventIterator {
+        events: EventIterator {
+            buf: vec![],
+            pos: 0,
+        },
+        buf: vec![],
+        seq_pos: 0,
+    }
+}
+
+/// Returns at most `length` events starting from the event at position
+/// `start`. Returns an empty vector if `start` is past the end of the log.
+pub fn get_events(start: u64, length: u64) -> Vec<Event> {
+    EventIterator {
+        buf: vec![],
+        pos: start,
+    }
+    .take(length.try_into().unwrap_or(usize::MAX))
+    .collect()
+}
+
 /// Returns the current number of events in the log.
 pub fn count_events() -> u64 {
     EVENTS.with(|events| events.borrow().len())
 }
 
-/// Records a new minter event.
+/// Records a new minter event with the next sequence number, so that the replay
+/// can detect reordered or missing events. The sequence numbers are counted
+/// independently of the position in the log, which also includes the events
+/// recorded by minter versions without sequence numbers.
 pub fn record_event(event: &Event) {
-    let bytes = encode_event(event);
+    let pos = EVENTS.with(|events| {
+        events
+            .borrow()
+            .append(&encode_event(event))
+            .expect("failed to append an entry to the event log")
+    });
+    SEQUENCE_NUMBERS.with(|seqs| {
+        let seqs = seqs.borrow();
+        let seq = match seqs.len().checked_sub(1) {
+            Some(last) => {
+                let (_, last_seq) = decode_sequence_number(
+                    &seqs.get(last).expect("BUG: missing last sequence number"),
+                );
+                last_seq + 1
+            }
+            None => 0,
+        };
+        seqs.append(&encode_sequence_number(pos, seq))
+            .expect("failed to append an entry to the sequence number log")
+    });
+}
+
+/// Appends an event as encoded by minter versions without sequence numbers.
+#[cfg(test)]
+pub fn record_unsequenced_event(event: &Event) {
     EVENTS.with(|events| {
         events
             .borrow()
-            .append(&bytes)
+            .append(&encode_event(event))
             .expect("failed to append an entry to the event log")
     });
 }
+
+/// Returns the raw bytes of the event at position `pos` in the log.
+#[cfg(test)]
+pub fn raw_event(pos: u64) -> Option<Vec<u8>> {
+    EVENTS.with(|events| events.borrow().get(pos))
+}
@@ -1,7 +1,18 @@
+use crate::lifecycle::upgrade::UpgradeArgs;
+use crate::memo::MintMemo;
+use crate::state::eventlog::{
+    replay, replay_events_filtered, replay_sequenced, Event, EventFilter, ReplayLogError,
+};
+use crate::state::{
+    audit, FeeRate, InFlightStatus, ReimburseDepositTask, ReimbursementReason, UtxoCheckStatus,
+};
+use crate::storage;
+use crate::updates::retrieve_btc::{check_cancellable, CancelRetrieveBtcError};
+use crate::updates::update_balance::{record_minted_batch, MintBatch};
 use crate::MINTER_FEE_CONSTANT;
 use crate::{
-    address::BitcoinAddress, build_unsigned_transaction, estimate_fee, fake_sign, greedy,
-    signature::EncodedSignature, tx, BuildTxError,
+    address::BitcoinAddress, build_batch_transaction, build_unsigned_transaction, estimate_fee,
+    fake_sign, greedy, signature::EncodedSignature, tx, BuildTxError, ECDSAPublicKey,
 };
 use crate::{
     lifecycle::init::InitArgs,
@@ -10,6 +21,7 @@ use crate::{
         SubmittedBtcTransaction,
     },
 };
+use assert_matches::assert_matches;
 use bitcoin::network::constants::Network as BtcNetwork;
 use bitcoin::util::psbt::serialize::{Deserialize, Serialize};
 use candid::Principal;
@@ -42,6 +54,20 @@ fn dummy_utxo_from_value(v: u64) -> Utxo {
     }
 }
 
+fn default_init_args() -> InitArgs {
+    InitArgs {
+        btc_network: Network::Regtest.into(),
+        ecdsa_key_name: "".to_string(),
+        retrieve_btc_min_amount: 0,
+        ledger_id: CanisterId::from_u64(42),
+        max_time_in_queue_nanos: 0,
+        min_confirmations: None,
+        mode: Mode::GeneralAvailability,
+        kyt_fee: None,
+        kyt_principal: None,
+    }
+}
+
 fn address_to_script_pubkey(address: &BitcoinAddress) -> bitcoin::Script {
     let address_string = address.display(Network::Mainnet);
     let btc_address = bitcoin::Address::from_str(&address_string).unwrap();
@@ -308,6 +334,646 @@ fn blocklist_is_sorted() {
     }
 }
 
+fn sequenced_events(seqs: &[u64]) -> Vec<(Option<u64>, Event)> {
+    let init = Event::Init(default_init_args());
+    seqs.iter()
+        .enumerate()
+        .map(|(i, seq)| {
+            let event = if i == 0 {
+                init.clone()
+            } else {
+                Event::Upgrade(UpgradeArgs::default())
+            };
+            (Some(*seq), event)
+        })
+        .collect()
+}
+
+#[test]
+fn should_replay_consecutive_events() {
+    let state = replay_sequenced(sequenced_events(&[0, 1, 2]).into_iter())
+        .expect("failed to replay consecutive events");
+    state.check_invariants().expect("invariant check failed");
+}
+
+#[test]
+fn should_detect_out_of_order_events() {
+    for (seqs, expected, actual) in [
+        (vec![0, 2, 1], 1, 2),
+        (vec![1, 0], 0, 1),
+        (vec![0, 1, 1], 2, 1),
+    ] {
+        assert_matches!(
+            replay_sequenced(sequenced_events(&seqs).into_iter()).map(|_| ()),
+            Err(ReplayLogError::UnexpectedSequenceNumber { expected: e, actual: a })
+                if e == expected && a == actual,
+            "unexpected replay result for sequence numbers {:?}",
+            seqs
+        );
+    }
+}
+
+#[test]
+fn should_replay_events_recorded_without_sequence_numbers() {
+    let mut events = sequenced_events(&[0, 1, 2]);
+    events.insert(0, (None, Event::Init(default_init_args())));
+    events.insert(2, (None, Event::Upgrade(UpgradeArgs::default())));
+    events.push((None, Event::Upgrade(UpgradeArgs::default())));
+
+    let state = replay_sequenced(events.into_iter())
+        .expect("failed to replay events without sequence numbers");
+    state.check_invariants().expect("invariant check failed");
+}
+
+#[test]
+fn should_keep_the_event_encoding_of_previous_minter_versions() {
+    let upgrade = |kyt_fee: u64| {
+        Event::Upgrade(UpgradeArgs {
+            kyt_fee: Some(kyt_fee),
+            ..UpgradeArgs::default()
+        })
+    };
+    // Events recorded before the introduction of sequence numbers, and during
+    // a rollback to such a minter version.
+    storage::record_unsequenced_event(&Event::Init(default_init_args()));
+    storage::record_event(&upgrade(1));
+    storage::record_unsequenced_event(&upgrade(2));
+    storage::record_event(&upgrade(3));
+
+    for pos in 0..4 {
+        let bytes = storage::raw_event(pos).expect("missing event");
+        let event: Event = ciborium::de::from_reader(bytes.as_slice())
+            .expect("failed to decode the event as a previous minter version");
+        assert_eq!(Some(event), storage::get_events(pos, 1).pop());
+    }
+    assert_eq!(
+        storage::sequenced_events().collect::<Vec<_>>(),
+        vec![
+            (None, Event::Init(default_init_args())),
+            (Some(0), upgrade(1)),
+            (None, upgrade(2)),
+            (Some(1), upgrade(3)),
+        ]
+    );
+    replay_sequenced(storage::sequenced_events()).expect("failed to replay the event log");
+}
+
+#[test]
+fn should_replay_only_the_events_of_the_filtered_account() {
+    let alice = Account {
+        owner: Principal::from_slice(&[1; 29]),
+        subaccount: None,
+    };
+    let bob = Account {
+        owner: Principal::from_slice(&[2; 29]),
+        subaccount: None,
+    };
+    let alice_utxos: Vec<_> = (1..=3).map(|i| dummy_utxo_from_value(i * 10_000)).collect();
+    let bob_utxos: Vec<_> = (4..=5).map(|i| dummy_utxo_from_value(i * 10_000)).collect();
+
+    let init = Event::Init(default_init_args());
+    let events = vec![
+        init,
+        Event::ReceivedUtxos {
+            mint_txid: Some(1),
+            to_account: alice,
+            utxos: alice_utxos.clone(),
+        },
+        Event::ReceivedUtxos {
+            mint_txid: Some(2),
+            to_account: bob,
+            utxos: bob_utxos.clone(),
+        },
+        Event::AcceptedRetrieveBtcRequest(RetrieveBtcRequest {
+            amount: 20_000,
+            address: BitcoinAddress::P2wpkhV0([2; 20]),
+            block_index: 3,
+            received_at: 0,
+            kyt_provider: None,
+            reimbursement_account: Some(bob),
+            kyt_fee: None,
+            kyt_canister: None,
+        }),
+    ];
+
+    let full_state = replay(events.clone().into_iter()).expect("failed to replay the event log");
+    assert_eq!(full_state.utxos_state_addresses.len(), 2);
+    assert_eq!(full_state.pending_retrieve_btc_requests.len(), 1);
+
+    let alice_state = replay_events_filtered(events.into_iter(), &EventFilter::Account(alice))
+        .expect("failed to replay the filtered event log");
+    assert_eq!(
+        alice_state.utxos_state_addresses,
+        BTreeMap::from([(alice, alice_utxos.iter().cloned().collect())])
+    );
+    assert_eq!(
+        alice_state.available_utxos,
+        alice_utxos.into_iter().collect::<BTreeSet<_>>()
+    );
+    assert_eq!(alice_state.pending_retrieve_btc_requests, vec![]);
+}
+
+#[test]
+fn should_page_through_the_event_log() {
+    for (seq, event) in sequenced_events(&[0, 1, 2, 3, 4, 5, 6]) {
+        let event = match event {
+            Event::Upgrade(args) => Event::Upgrade(UpgradeArgs {
+                kyt_fee: seq,
+                ..args
+            }),
+            event => event,
+        };
+        storage::record_event(&event);
+    }
+    let all_events: Vec<_> = storage::events().collect();
+    assert_eq!(all_events.len(), 7);
+    assert_eq!(storage::count_events(), 7);
+
+    for page_size in 1..=8 {
+        let mut paged_events = vec![];
+        let mut start = 0;
+        loop {
+            let page = storage::get_events(start, page_size);
+            assert!(page.len() as u64 <= page_size);
+            if page.is_empty() {
+                break;
+            }
+            start += page.len() as u64;
+            paged_events.extend(page);
+        }
+        assert_eq!(paged_events, all_events, "page size {}", page_size);
+    }
+
+    assert_eq!(storage::get_events(7, 10), vec![]);
+    assert_eq!(storage::get_events(100, 10), vec![]);
+    assert_eq!(storage::get_events(u64::MAX, u64::MAX), vec![]);
+    assert_eq!(storage::get_events(0, 0), vec![]);
+    assert_eq!(storage::get_events(5, u64::MAX), all_events[5..].to_vec());
+}
+
+#[test]
+fn should_return_utxo_check_status() {
+    let mut state = CkBtcMinterState::from(default_init_args());
+    let kyt_provider = Principal::from_slice(&[1; 29]);
+    let clean_utxo = dummy_utxo_from_value(100_000);
+    let tainted_utxo = dummy_utxo_from_value(200_000);
+    let unknown_utxo = dummy_utxo_from_value(300_000);
+
+    audit::mark_utxo_checked(
+        &mut state,
+        &clean_utxo,
+        "uuid-1".to_string(),
+        UtxoCheckStatus::Clean,
+        kyt_provider,
+        None,
+    );
+    audit::mark_utxo_checked(
+        &mut state,
+        &tainted_utxo,
+        "uuid-2".to_string(),
+        UtxoCheckStatus::Tainted,
+        kyt_provider,
+        None,
+    );
+
+    let status = |utxo: &Utxo| state.utxo_check_status(&utxo.outpoint.txid, utxo.outpoint.vout);
+    assert_eq!(status(&clean_utxo), Some(UtxoCheckStatus::Clean));
+    assert_eq!(status(&tainted_utxo), Some(UtxoCheckStatus::Tainted));
+    assert_eq!(status(&unknown_utxo), None);
+    assert_eq!(
+        state.utxo_check_status(&clean_utxo.outpoint.txid, clean_utxo.outpoint.vout + 1),
+        None
+    );
+}
+
+#[test]
+fn should_list_checked_utxos() {
+    let init_args = default_init_args();
+    storage::record_event(&Event::Init(init_args.clone()));
+    let mut state = CkBtcMinterState::from(init_args);
+    let account = Account {
+        owner: Principal::from_slice(&[3; 29]),
+        subaccount: None,
+    };
+    let clean_provider = Principal::from_slice(&[1; 29]);
+    let tainted_provider = Principal::from_slice(&[2; 29]);
+    let clean_utxo = dummy_utxo_from_value(100_000);
+    let tainted_utxo = dummy_utxo_from_value(200_000);
+
+    audit::mark_utxo_checked(
+        &mut state,
+        &clean_utxo,
+        "uuid-1".to_string(),
+        UtxoCheckStatus::Clean,
+        clean_provider,
+        None,
+    );
+    audit::mark_utxo_checked(
+        &mut state,
+        &tainted_utxo,
+        "uuid-2".to_string(),
+        UtxoCheckStatus::Tainted,
+        tainted_provider,
+        None,
+    );
+
+    let expected_checks = vec![
+        (
+            clean_utxo.clone(),
+            "uuid-1".to_string(),
+            true,
+            clean_provider,
+        ),
+        (tainted_utxo, "uuid-2".to_string(), false, tainted_provider),
+    ];
+    assert_eq!(state.list_checked_utxos(storage::events()), expected_checks);
+
+    // Minting the clean UTXO evicts it from the cache of checked UTXOs, but
+    // its check is still listed.
+    audit::add_utxos(&mut state, Some(1), account, vec![clean_utxo.clone()]);
+    assert!(!state.checked_utxos.contains_key(&clean_utxo));
+    assert_eq!(state.list_checked_utxos(storage::events()), expected_checks);
+}
+
+#[test]
+fn should_mint_utxos_of_one_account_in_a_single_batch() {
+    let mut state = CkBtcMinterState::from(default_init_args());
+    let account = Account {
+        owner: Principal::from_slice(&[1; 29]),
+        subaccount: None,
+    };
+    let kyt_fee = 1_000;
+    let utxos: Vec<_> = (1..=5).map(|i| dummy_utxo_from_value(i * 10_000)).collect();
+
+    let batch = MintBatch::new(account, utxos.clone(), kyt_fee);
+
+    assert_eq!(batch.amount(), 150_000 - 5 * kyt_fee);
+    assert_eq!(
+        batch.memo(),
+        MintMemo::ConvertBatch {
+            utxo_count: Some(5),
+            kyt_fee: Some(5 * kyt_fee),
+        }
+    );
+
+    let events_before = storage::count_events();
+    record_minted_batch(&mut state, batch, 7);
+
+    let new_events: Vec<_> = storage::events().skip(events_before as usize).collect();
+    assert_eq!(
+        new_events,
+        vec![Event::ReceivedUtxos {
+            mint_txid: Some(7),
+            to_account: account,
+            utxos: utxos.clone(),
+        }]
+    );
+    assert_eq!(
+        state.utxos_state_addresses.get(&account),
+        Some(&utxos.into_iter().collect())
+    );
+}
+
+#[test]
+fn should_identify_single_utxo_in_mint_memo() {
+    let account = Account {
+        owner: Principal::from_slice(&[1; 29]),
+        subaccount: None,
+    };
+    let utxo = dummy_utxo_from_value(10_000);
+
+    let batch = MintBatch::new(account, vec![utxo.clone()], 1_000);
+
+    assert_eq!(batch.amount(), 9_000);
+    assert_eq!(
+        batch.memo(),
+        MintMemo::Convert {
+            txid: Some(utxo.outpoint.txid.as_ref()),
+            vout: Some(utxo.outpoint.vout),
+            kyt_fee: Some(1_000),
+        }
+    );
+}
+
+#[test]
+fn should_keep_requests_pending_while_in_flight_limit_is_reached() {
+    let mut state = CkBtcMinterState::from(default_init_args());
+    state.upgrade(UpgradeArgs {
+        max_in_flight_transactions: Some(1),
+        ..UpgradeArgs::default()
+    });
+    let account = Account {
+        owner: Principal::from_slice(&[1; 29]),
+        subaccount: None,
+    };
+    state.add_utxos(
+        account,
+        vec![
+            dummy_utxo_from_value(1_000_000),
+            dummy_utxo_from_value(2_000_000),
+        ],
+    );
+    let request = |block_index: u64| RetrieveBtcRequest {
+        amount: 500_000,
+        address: BitcoinAddress::P2wpkhV0([2; 20]),
+        block_index,
+        received_at: 0,
+        kyt_provider: None,
+        reimbursement_account: None,
+        kyt_fee: None,
+        kyt_canister: None,
+    };
+
+    assert!(!state.in_flight_transaction_limit_reached());
+
+    let fee_per_vbyte = 10_000;
+    let (tx, change_output, used_utxos) = build_unsigned_transaction(
+        &mut state.available_utxos,
+        vec![(request(0).address, request(0).amount)],
+        BitcoinAddress::P2wpkhV0([3; 20]),
+        fee_per_vbyte,
+    )
+    .expect("failed to build transaction");
+    let txid = tx.txid();
+    state.push_submitted_transaction(SubmittedBtcTransaction {
+        requests: vec![request(0)],
+        txid,
+        used_utxos,
+        submitted_at: 0,
+        change_output: Some(change_output),
+        fee_per_vbyte: Some(fee_per_vbyte),
+    });
+    state.push_back_pending_request(request(1));
+
+    // The new request could form a batch, but the minter must keep it
+    // pending until the in-flight transaction confirms.
+    assert!(state.can_form_a_batch(1, 1));
+    assert!(state.in_flight_transaction_limit_reached());
+    assert_eq!(state.pending_retrieve_btc_requests, vec![request(1)]);
+
+    state.finalize_transaction(&txid);
+
+    assert!(!state.in_flight_transaction_limit_reached());
+    assert_eq!(state.build_batch(1), vec![request(1)]);
+}
+
+#[test]
+fn should_require_a_fresh_fee_rate_to_build_transactions() {
+    let init_args = default_init_args();
+    let upgrade_args = UpgradeArgs {
+        max_fee_rate_staleness_nanos: Some(1_000),
+        ..UpgradeArgs::default()
+    };
+    storage::record_event(&Event::Init(init_args.clone()));
+    storage::record_event(&Event::Upgrade(upgrade_args.clone()));
+    let mut state = CkBtcMinterState::from(init_args);
+    state.upgrade(upgrade_args);
+
+    // There is no estimate yet.
+    assert_eq!(state.fresh_fee_per_vbyte(0), None);
+
+    state.update_fee_rate(5_000, 10);
+    assert_eq!(state.fresh_fee_per_vbyte(10), Some(5_000));
+    assert_eq!(state.fresh_fee_per_vbyte(1_010), Some(5_000));
+
+    // The estimate is stale until the minter refreshes it.
+    assert_eq!(state.fresh_fee_per_vbyte(1_011), None);
+
+    state.update_fee_rate(6_000, 2_000);
+    assert_eq!(state.fresh_fee_per_vbyte(2_500), Some(6_000));
+    assert_eq!(
+        state.last_fee_rate,
+        Some(FeeRate {
+            fee_per_vbyte: 6_000,
+            updated_at: 2_000,
+        })
+    );
+
+    // The estimate is not recorded in the event log.
+    let replayed_state = replay(storage::events()).expect("failed to replay the event log");
+    assert_eq!(replayed_state.check_semantically_eq(&state), Ok(()));
+    assert_eq!(replayed_state.last_fee_rate, None);
+}
+
+#[test]
+fn should_not_build_transactions_while_the_fee_rate_is_stale() {
+    let mut state = CkBtcMinterState::from(default_init_args());
+    state.upgrade(UpgradeArgs {
+        max_fee_rate_staleness_nanos: Some(1_000),
+        ..UpgradeArgs::default()
+    });
+    let account = Account {
+        owner: Principal::from_slice(&[1; 29]),
+        subaccount: None,
+    };
+    state.add_utxos(account, vec![dummy_utxo_from_value(1_000_000)]);
+    let request = RetrieveBtcRequest {
+        amount: 500_000,
+        address: BitcoinAddress::P2wpkhV0([2; 20]),
+        block_index: 0,
+        received_at: 0,
+        kyt_provider: None,
+        reimbursement_account: None,
+        kyt_fee: None,
+        kyt_canister: None,
+    };
+    state.push_back_pending_request(request.clone());
+    let ecdsa_public_key = ECDSAPublicKey {
+        public_key: vec![2; 33],
+        chain_code: vec![0; 32],
+    };
+    let main_address = BitcoinAddress::P2wpkhV0([3; 20]);
+
+    // There is no estimate yet.
+    assert!(build_batch_transaction(
+        &mut state,
+        main_address.clone(),
+        ecdsa_public_key.clone(),
+        0
+    )
+    .is_none());
+
+    state.update_fee_rate(5_000, 10);
+    assert!(build_batch_transaction(
+        &mut state,
+        main_address.clone(),
+        ecdsa_public_key.clone(),
+        1_011
+    )
+    .is_none());
+    assert_eq!(state.pending_retrieve_btc_requests, vec![request.clone()]);
+    assert_eq!(state.available_utxos.len(), 1);
+
+    state.update_fee_rate(5_000, 1_011);
+    let sign_request = build_batch_transaction(&mut state, main_address, ecdsa_public_key, 1_011)
+        .expect("failed to build a transaction with a fresh fee rate");
+    assert_eq!(sign_request.requests, vec![request]);
+    assert!(state.pending_retrieve_btc_requests.is_empty());
+}
+
+fn new_state_with_cancellable_request(
+    kyt_provider: Principal,
+    account: Account,
+) -> (CkBtcMinterState, RetrieveBtcRequest) {
+    let mut state = CkBtcMinterState::from(InitArgs {
+        kyt_fee: Some(1_000),
+        ..default_init_args()
+    });
+    let request = RetrieveBtcRequest {
+        amount: 500_000,
+        address: BitcoinAddress::P2wpkhV0([2; 20]),
+        block_index: 7,
+        received_at: 0,
+        kyt_provider: Some(kyt_provider),
+        reimbursement_account: Some(account),
+        kyt_fee: Some(1_000),
+        kyt_canister: None,
+    };
+    audit::accept_retrieve_btc_request(&mut state, request.clone());
+
+    (state, request)
+}
+
+#[test]
+fn should_cancel_pending_retrieve_btc_request() {
+    let kyt_provider = Principal::from_slice(&[1; 29]);
+    let user = Principal::from_slice(&[2; 29]);
+    let account = Account {
+        owner: user,
+        subaccount: Some([3; 32]),
+    };
+    let (mut state, request) = new_state_with_cancellable_request(kyt_provider, account);
+    assert_eq!(state.owed_kyt_amount.get(&kyt_provider), Some(&1_000));
+
+    assert_eq!(
+        check_cancellable(&state, Principal::from_slice(&[4; 29]), request.block_index),
+        Err(CancelRetrieveBtcError::NotOwner)
+    );
+    assert_eq!(
+        check_cancellable(&state, user, request.block_index + 1),
+        Err(CancelRetrieveBtcError::UnknownRequest)
+    );
+    assert_eq!(check_cancellable(&state, user, request.block_index), Ok(()));
+
+    audit::cancel_retrieve_btc(&mut state, request.block_index);
+
+    assert_eq!(state.pending_retrieve_btc_requests, vec![]);
+    assert_eq!(
+        state.retrieve_btc_status(request.block_index),
+        RetrieveBtcStatus::Unknown
+    );
+    assert_eq!(state.owed_kyt_amount.get(&kyt_provider), None);
+    assert_eq!(
+        state.reimbursement_map.get(&request.block_index),
+        Some(&ReimburseDepositTask {
+            account,
+            amount: request.amount + 1_000,
+            reason: ReimbursementReason::CancelledByUser,
+        })
+    );
+    assert_eq!(
+        check_cancellable(&state, user, request.block_index),
+        Err(CancelRetrieveBtcError::UnknownRequest)
+    );
+}
+
+#[test]
+fn should_not_reimburse_distributed_kyt_fee_of_cancelled_request() {
+    let kyt_provider = Principal::from_slice(&[1; 29]);
+    let user = Principal::from_slice(&[2; 29]);
+    let account = Account {
+        owner: user,
+        subaccount: None,
+    };
+
+    for (distributed, refunded_kyt_fee) in [(600, 400), (1_000, 0)] {
+        let (mut state, request) = new_state_with_cancellable_request(kyt_provider, account);
+        audit::distributed_kyt_fee(&mut state, kyt_provider, distributed, 0)
+            .expect("failed to distribute the KYT fee");
+        // The KYT fee of the minter changed after the request was accepted.
+        state.kyt_fee = 5_000;
+
+        audit::cancel_retrieve_btc(&mut state, request.block_index);
+
+        assert_eq!(state.owed_kyt_amount.get(&kyt_provider), None);
+        assert_eq!(
+            state.reimbursement_map.get(&request.block_index),
+            Some(&ReimburseDepositTask {
+                account,
+                amount: request.amount + refunded_kyt_fee,
+                reason: ReimbursementReason::CancelledByUser,
+            }),
+            "distributed KYT fee: {}",
+            distributed
+        );
+    }
+}
+
+#[test]
+fn should_not_cancel_request_without_reimbursement_account() {
+    let user = Principal::from_slice(&[2; 29]);
+    let mut state = CkBtcMinterState::from(default_init_args());
+    // Requests accepted before the minter recorded reimbursement accounts.
+    let request = RetrieveBtcRequest {
+        amount: 500_000,
+        address: BitcoinAddress::P2wpkhV0([2; 20]),
+        block_index: 7,
+        received_at: 0,
+        kyt_provider: Some(Principal::from_slice(&[1; 29])),
+        reimbursement_account: None,
+        kyt_fee: None,
+        kyt_canister: None,
+    };
+    state.push_back_pending_request(request.clone());
+
+    assert_eq!(
+        check_cancellable(&state, user, request.block_index),
+        Err(CancelRetrieveBtcError::NotCancellable)
+    );
+}
+
+#[test]
+fn should_not_cancel_submitted_retrieve_btc_request() {
+    let kyt_provider = Principal::from_slice(&[1; 29]);
+    let user = Principal::from_slice(&[2; 29]);
+    let account = Account {
+        owner: user,
+        subaccount: None,
+    };
+    let (mut state, request) = new_state_with_cancellable_request(kyt_provider, account);
+    state.add_utxos(account, vec![dummy_utxo_from_value(1_000_000)]);
+
+    let batch = state.build_batch(1);
+    assert_eq!(batch, vec![request.clone()]);
+    state.push_in_flight_request(request.block_index, InFlightStatus::Signing);
+    assert_eq!(
+        check_cancellable(&state, user, request.block_index),
+        Err(CancelRetrieveBtcError::AlreadySubmitted)
+    );
+
+    let (tx, change_output, used_utxos) = build_unsigned_transaction(
+        &mut state.available_utxos,
+        vec![(request.address.clone(), request.amount)],
+        BitcoinAddress::P2wpkhV0([3; 20]),
+        10_000,
+    )
+    .expect("failed to build transaction");
+    state.push_submitted_transaction(SubmittedBtcTransaction {
+        requests: batch,
+        txid: tx.txid(),
+        used_utxos,
+        submitted_at: 0,
+        change_output: Some(change_output),
+        fee_per_vbyte: Some(10_000),
+    });
+    assert_eq!(
+        check_cancellable(&state, user, request.block_index),
+        Err(CancelRetrieveBtcError::AlreadySubmitted)
+    );
+    assert_eq!(state.owed_kyt_amount.get(&kyt_provider), Some(&1_000));
+    assert!(state.reimbursement_map.is_empty());
+}
+
 fn arb_amount() -> impl Strategy<Value = Satoshi> {
     1..10_000_000_000u64
 }
@@ -405,6 +1071,9 @@ fn arb_retrieve_btc_requests(
                 block_index,
                 received_at,
                 kyt_provider: provider.map(|id| Principal::from(CanisterId::from_u64(id).get())),
+                reimbursement_account: None,
+                kyt_fee: None,
+                kyt_canister: None,
             },
         );
     pvec(request_strategy, num).prop_map(|mut reqs| {
@@ -636,6 +1305,54 @@ proptest! {
         prop_assert_eq!(utxos.iter().map(|u| u.value).sum::<u64>(), total_value - inputs_value);
     }
 
+    #[test]
+    fn retrieve_btc_fee_estimate_matches_built_transaction(
+        mut utxos in btree_set(arb_utxo(5_000u64..1_000_000_000), 1..20),
+        dst_pkhash in uniform20(any::<u8>()),
+        main_pkhash in uniform20(any::<u8>()),
+        fee_per_vbyte in 1000..2000u64,
+    ) {
+        prop_assume!(dst_pkhash != main_pkhash);
+
+        let kyt_fee = crate::lifecycle::init::DEFAULT_KYT_FEE;
+        let total_value = utxos.iter().map(|u| u.value).sum::<u64>();
+        let amount = total_value / 2 + kyt_fee;
+        let available_utxos = utxos.clone();
+
+        let estimate = crate::estimate_retrieve_btc_fee(
+            &utxos,
+            BitcoinAddress::P2wpkhV0(dst_pkhash),
+            BitcoinAddress::P2wpkhV0(main_pkhash),
+            amount,
+            fee_per_vbyte,
+            kyt_fee,
+        )
+        .expect("failed to estimate the fee");
+
+        // The dry run does not consume the UTXOs.
+        prop_assert_eq!(&utxos, &available_utxos);
+
+        let (unsigned_tx, change_output, _) = build_unsigned_transaction(
+            &mut utxos,
+            vec![(BitcoinAddress::P2wpkhV0(dst_pkhash), amount - kyt_fee)],
+            BitcoinAddress::P2wpkhV0(main_pkhash),
+            fee_per_vbyte
+        )
+        .expect("failed to build transaction");
+
+        let inputs_value = unsigned_tx.inputs.iter().map(|input| input.value).sum::<u64>();
+        let outputs_value = unsigned_tx.outputs.iter().map(|output| output.value).sum::<u64>();
+        let change = inputs_value - (amount - kyt_fee);
+
+        prop_assert_eq!(estimate.bitcoin_fee, inputs_value - outputs_value);
+        prop_assert_eq!(estimate.net_amount, unsigned_tx.outputs[0].value);
+        prop_assert_eq!(estimate.minter_fee, change_output.value - change + kyt_fee);
+        prop_assert_eq!(
+            estimate.net_amount + estimate.bitcoin_fee + estimate.minter_fee,
+            amount
+        );
+    }
+
     #[test]
     fn check_output_order(
         mut utxos in btree_set(arb_utxo(1_000_000u64..1_000_000_000), 1..20),
@@ -1039,3 +1756,67 @@ proptest! {
         );
     }
 }
+
+#[test]
+fn should_distribute_kyt_checks_across_kyt_canisters() {
+    let kyt_canisters: Vec<CanisterId> = (1..=3).map(CanisterId::from_u64).collect();
+    // Each KYT canister uses the API key of a different provider.
+    let kyt_providers: Vec<Principal> = (1..=3).map(|i| Principal::from_slice(&[i; 29])).collect();
+    let init_args = InitArgs {
+        kyt_fee: Some(100),
+        kyt_principal: Some(kyt_canisters[0]),
+        ..default_init_args()
+    };
+    let upgrade_args = UpgradeArgs {
+        // The main KYT canister is not used twice if it is also listed here.
+        additional_kyt_principals: Some(vec![kyt_canisters[1], kyt_canisters[0], kyt_canisters[2]]),
+        ..UpgradeArgs::default()
+    };
+    storage::record_event(&Event::Init(init_args.clone()));
+    storage::record_event(&Event::Upgrade(upgrade_args.clone()));
+    let mut state = CkBtcMinterState::from(init_args);
+    state.upgrade(upgrade_args);
+    assert_eq!(state.kyt_principals(), kyt_canisters);
+
+    for i in 0..6 {
+        let kyt_canister = state.next_kyt_principal().unwrap();
+        assert_eq!(kyt_canister, kyt_canisters[i % 3]);
+        audit::mark_utxo_checked(
+            &mut state,
+            &dummy_utxo_from_value(i as u64 + 1),
+            format!("uuid-{}", i),
+            UtxoCheckStatus::Clean,
+            kyt_providers[i % 3],
+            Some(kyt_canister.get().into()),
+        );
+    }
+
+    let expected_owed_kyt_amount: BTreeMap<Principal, u64> = kyt_providers
+        .iter()
+        .map(|kyt_provider| (*kyt_provider, 200))
+        .collect();
+    assert_eq!(state.owed_kyt_amount, expected_owed_kyt_amount);
+
+    let checked_by: Vec<_> = storage::events()
+        .filter_map(|event| match event {
+            Event::CheckedUtxo {
+                kyt_provider,
+                kyt_canister,
+                ..
+            } => Some((kyt_provider, kyt_canister)),
+            _ => None,
+        })
+        .collect();
+    let expected_checked_by: Vec<_> = (0..6)
+        .map(|i| {
+            (
+                Some(kyt_providers[i % 3]),
+                Some(Principal::from(kyt_canisters[i % 3].get())),
+            )
+        })
+        .collect();
+    assert_eq!(checked_by, expected_checked_by);
+
+    let replayed_state = replay(storage::events()).expect("failed to replay the event log");
+    assert_eq!(replayed_state.check_semantically_eq(&state), Ok(()));
+}
@@ -8,7 +8,9 @@ use crate::tasks::{schedule_now, TaskType};
 use crate::{
     address::{account_to_bitcoin_address, BitcoinAddress, ParseAddressError},
     guard::{retrieve_btc_guard, GuardError},
-    state::{self, mutate_state, read_state, RetrieveBtcRequest},
+    state::{
+        self, mutate_state, read_state, CkBtcMinterState, RetrieveBtcRequest, RetrieveBtcStatus,
+    },
 };
 use candid::{CandidType, Deserialize, Nat, Principal};
 use ic_base_types::PrincipalId;
@@ -54,6 +56,25 @@ pub struct RetrieveBtcOk {
     pub block_index: u64,
 }
 
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub enum CancelRetrieveBtcError {
+    /// The minter has no record of a retrieve_btc request with this block index.
+    UnknownRequest,
+
+    /// The request was made by another principal.
+    NotOwner,
+
+    /// The request is already part of a Bitcoin transaction or finalized.
+    AlreadySubmitted,
+
+    /// The request was made before the minter recorded the account to
+    /// reimburse, so it cannot be cancelled.
+    NotCancellable,
+
+    /// The minter does not accept cancellations at the moment, retry later.
+    TemporarilyUnavailable(String),
+}
+
 pub enum ErrorCode {
     // The retrieval address didn't pass the KYT check.
     TaintedAddress = 1,
@@ -195,7 +216,7 @@ pub async fn retrieve_btc(args: RetrieveBtcArgs) -> Result<RetrieveBtcOk, Retrie
         return Err(RetrieveBtcError::InsufficientFunds { balance });
     }
 
-    let (uuid, status, kyt_provider) =
+    let (uuid, status, kyt_provider, kyt_canister) =
         kyt_check_address(caller, args.address.clone(), args.amount).await?;
 
     match status {
@@ -222,6 +243,7 @@ pub async fn retrieve_btc(args: RetrieveBtcArgs) -> Result<RetrieveBtcOk, Retrie
                     args.address,
                     args.amount,
                     kyt_provider,
+                    kyt_canister,
                     uuid,
                     block_index,
                 )
@@ -253,6 +275,12 @@ pub async fn retrieve_btc(args: RetrieveBtcArgs) -> Result<RetrieveBtcOk, Retrie
         block_index,
         received_at: ic_cdk::api::time(),
         kyt_provider: Some(kyt_provider),
+        reimbursement_account: Some(Account {
+            owner: caller,
+            subaccount: None,
+        }),
+        kyt_fee: Some(kyt_fee),
+        kyt_canister: Some(kyt_canister),
     };
 
     log!(
@@ -335,7 +363,7 @@ pub async fn retrieve_btc_with_approval(
 
     match kyt_check_address(caller, args.address.clone(), args.amount).await {
         Ok(kyt_result) => {
-            let (_uuid, status, kyt_provider) = kyt_result;
+            let (_uuid, status, kyt_provider, kyt_canister) = kyt_result;
             match status {
                 BtcAddressCheckStatus::Tainted => {
                     mutate_state(|s| {
@@ -375,6 +403,12 @@ pub async fn retrieve_btc_with_approval(
                 block_index,
                 received_at: ic_cdk::api::time(),
                 kyt_provider: Some(kyt_provider),
+                reimbursement_account: Some(Account {
+                    owner: caller,
+                    subaccount: args.from_subaccount,
+                }),
+                kyt_fee: Some(kyt_fee),
+                kyt_canister: Some(kyt_canister),
             };
 
             mutate_state(|s| state::audit::accept_retrieve_btc_request(s, request));
@@ -416,6 +450,63 @@ pub async fn retrieve_btc_with_approval(
     }
 }
 
+/// Cancels a retrieve_btc request that the minter did not include in a Bitcoin
+/// transaction yet and reimburses the burnt ckBTC, including the KYT fee if
+/// the minter did not distribute it to the KYT provider yet.
+pub fn cancel_retrieve_btc(block_index: u64) -> Result<(), CancelRetrieveBtcError> {
+    let caller = ic_cdk::caller();
+
+    read_state(|s| s.mode.is_withdrawal_available_for(&caller))
+        .map_err(CancelRetrieveBtcError::TemporarilyUnavailable)?;
+
+    mutate_state(|s| {
+        check_cancellable(s, caller, block_index)?;
+        state::audit::cancel_retrieve_btc(s, block_index);
+        Ok(())
+    })?;
+
+    log!(
+        P1,
+        "cancelled the retrieve btc request with block_index = {}",
+        block_index
+    );
+
+    schedule_now(TaskType::ProcessLogic);
+
+    Ok(())
+}
+
+/// Returns Ok if the caller can cancel the retrieve_btc request with the given block index.
+pub fn check_cancellable(
+    state: &CkBtcMinterState,
+    caller: Principal,
+    block_index: u64,
+) -> Result<(), CancelRetrieveBtcError> {
+    match state.retrieve_btc_status(block_index) {
+        RetrieveBtcStatus::Pending => {}
+        RetrieveBtcStatus::Unknown => return Err(CancelRetrieveBtcError::UnknownRequest),
+        RetrieveBtcStatus::Signing
+        | RetrieveBtcStatus::Sending { .. }
+        | RetrieveBtcStatus::Submitted { .. }
+        | RetrieveBtcStatus::AmountTooLow
+        | RetrieveBtcStatus::Confirmed { .. } => {
+            return Err(CancelRetrieveBtcError::AlreadySubmitted)
+        }
+    }
+    let owner = state
+        .pending_retrieve_btc_requests
+        .iter()
+        .find(|req| req.block_index == block_index)
+        .ok_or(CancelRetrieveBtcError::UnknownRequest)?
+        .reimbursement_account
+        .ok_or(CancelRetrieveBtcError::NotCancellable)?
+        .owner;
+    if owner != caller {
+        return Err(CancelRetrieveBtcError::NotOwner);
+    }
+    Ok(())
+}
+
 async fn balance_of(user: Principal) -> Result<u64, RetrieveBtcError> {
     let client = ICRC1Client {
         runtime: CdkRuntime,
@@ -586,13 +677,15 @@ pub enum BtcAddressCheckStatus {
     Tainted,
 }
 
+/// Checks the address with the next KYT canister of the pool and returns the
+/// external id of the check, its outcome, the KYT provider and the KYT canister.
 async fn kyt_check_address(
     caller: Principal,
     address: String,
     amount: u64,
-) -> Result<(String, BtcAddressCheckStatus, Principal), RetrieveBtcError> {
-    let kyt_principal = read_state(|s| {
-        s.kyt_principal
+) -> Result<(String, BtcAddressCheckStatus, Principal, Principal), RetrieveBtcError> {
+    let kyt_principal = mutate_state(|s| {
+        s.next_kyt_principal()
             .expect("BUG: upgrade procedure must ensure that the KYT principal is set")
             .get()
             .into()
@@ -618,12 +711,14 @@ async fn kyt_check_address(
                     response.external_id,
                     BtcAddressCheckStatus::Tainted,
                     response.provider,
+                    kyt_principal,
                 ))
             } else {
                 Ok((
                     response.external_id,
                     BtcAddressCheckStatus::Clean,
                     response.provider,
+                    kyt_principal,
                 ))
             }
         }
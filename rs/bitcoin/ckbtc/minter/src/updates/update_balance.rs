@@ -190,6 +190,10 @@ pub async fn update_balance(
 
     let kyt_fee = read_state(|s| s.kyt_fee);
     let mut utxo_statuses: Vec<UtxoStatus> = vec![];
+    let mut clean_utxos: Vec<Utxo> = vec![];
+    // The clean UTXOs are minted even if the KYT check of another UTXO fails,
+    // the error is reported after the mint.
+    let mut kyt_error = None;
     for utxo in new_utxos {
         if utxo.value <= kyt_fee {
             mutate_state(|s| crate::state::audit::ignore_utxo(s, utxo.clone()));
@@ -203,75 +207,169 @@ pub async fn update_balance(
             utxo_statuses.push(UtxoStatus::ValueTooSmall(utxo));
             continue;
         }
-        let (uuid, status, kyt_provider) = kyt_check_utxo(caller_account.owner, &utxo).await?;
+        let (uuid, status, kyt_provider, kyt_canister) =
+            match kyt_check_utxo(caller_account.owner, &utxo).await {
+                Ok(check) => check,
+                Err(err) => {
+                    kyt_error.get_or_insert(err);
+                    continue;
+                }
+            };
         mutate_state(|s| {
-            crate::state::audit::mark_utxo_checked(s, &utxo, uuid.clone(), status, kyt_provider);
+            crate::state::audit::mark_utxo_checked(
+                s,
+                &utxo,
+                uuid.clone(),
+                status,
+                kyt_provider,
+                kyt_canister,
+            );
         });
         if status == UtxoCheckStatus::Tainted {
             utxo_statuses.push(UtxoStatus::Tainted(utxo.clone()));
             continue;
         }
-        let amount = utxo.value - kyt_fee;
-        let memo = MintMemo::Convert {
-            txid: Some(utxo.outpoint.txid.as_ref()),
-            vout: Some(utxo.outpoint.vout),
-            kyt_fee: Some(kyt_fee),
-        };
-
-        match mint(amount, caller_account, crate::memo::encode(&memo).into()).await {
+        clean_utxos.push(utxo);
+    }
+
+    if !clean_utxos.is_empty() {
+        let batch = MintBatch::new(caller_account, clean_utxos, kyt_fee);
+        let amount = batch.amount();
+        match mint(
+            amount,
+            caller_account,
+            crate::memo::encode(&batch.memo()).into(),
+        )
+        .await
+        {
             Ok(block_index) => {
                 log!(
                     P1,
-                    "Minted {amount} {token_name} for account {caller_account} corresponding to utxo {} with value {}",
-                    DisplayOutpoint(&utxo.outpoint),
-                    DisplayAmount(utxo.value),
+                    "Minted {amount} {token_name} for account {caller_account} corresponding to utxos {}",
+                    batch
+                        .utxos
+                        .iter()
+                        .map(|utxo| format!(
+                            "{} with value {}",
+                            DisplayOutpoint(&utxo.outpoint),
+                            DisplayAmount(utxo.value)
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(", "),
                 );
-                state::mutate_state(|s| {
-                    state::audit::add_utxos(
-                        s,
-                        Some(block_index),
-                        caller_account,
-                        vec![utxo.clone()],
-                    )
-                });
-                utxo_statuses.push(UtxoStatus::Minted {
+                utxo_statuses.extend(batch.utxos.iter().map(|utxo| UtxoStatus::Minted {
                     block_index,
-                    utxo,
-                    minted_amount: amount,
-                });
+                    minted_amount: utxo.value - kyt_fee,
+                    utxo: utxo.clone(),
+                }));
+                state::mutate_state(|s| record_minted_batch(s, batch, block_index));
             }
             Err(err) => {
                 log!(
                     P0,
-                    "Failed to mint ckBTC for UTXO {}: {:?}",
-                    DisplayOutpoint(&utxo.outpoint),
+                    "Failed to mint ckBTC for UTXOs {}: {:?}",
+                    batch
+                        .utxos
+                        .iter()
+                        .map(|utxo| DisplayOutpoint(&utxo.outpoint).to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
                     err
                 );
-                utxo_statuses.push(UtxoStatus::Checked(utxo));
+                utxo_statuses.extend(batch.utxos.into_iter().map(UtxoStatus::Checked));
             }
         }
     }
 
     schedule_now(TaskType::ProcessLogic);
-    Ok(utxo_statuses)
+    match kyt_error {
+        Some(err) => Err(err),
+        None => Ok(utxo_statuses),
+    }
+}
+
+/// Clean UTXOs of a single account that the minter converts to ckBTC with a
+/// single ledger transfer, instead of one transfer per UTXO.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MintBatch {
+    pub account: Account,
+    pub utxos: Vec<Utxo>,
+    pub kyt_fee: u64,
+}
+
+impl MintBatch {
+    /// # Panics
+    ///
+    /// This function panics if `utxos` is empty or if the value of a UTXO
+    /// does not cover the KYT fee.
+    pub fn new(account: Account, utxos: Vec<Utxo>, kyt_fee: u64) -> Self {
+        assert!(!utxos.is_empty(), "BUG: cannot mint an empty batch");
+        assert!(
+            utxos.iter().all(|utxo| utxo.value > kyt_fee),
+            "BUG: UTXO values must cover the KYT fee"
+        );
+        Self {
+            account,
+            utxos,
+            kyt_fee,
+        }
+    }
+
+    /// The amount to mint: the total value of the UTXOs minus the KYT fee of each UTXO.
+    pub fn amount(&self) -> u64 {
+        self.utxos
+            .iter()
+            .map(|utxo| utxo.value - self.kyt_fee)
+            .sum()
+    }
+
+    /// The memo of the mint transaction. The memo of a batch with a single UTXO
+    /// identifies that UTXO, the memo of a larger batch records the number of
+    /// UTXOs, which the `ReceivedUtxos` event of the mint lists.
+    pub fn memo(&self) -> MintMemo<'_> {
+        match self.utxos.as_slice() {
+            [utxo] => MintMemo::Convert {
+                txid: Some(utxo.outpoint.txid.as_ref()),
+                vout: Some(utxo.outpoint.vout),
+                kyt_fee: Some(self.kyt_fee),
+            },
+            utxos => MintMemo::ConvertBatch {
+                utxo_count: Some(utxos.len() as u32),
+                kyt_fee: Some(self.kyt_fee * utxos.len() as u64),
+            },
+        }
+    }
+}
+
+/// Records that the UTXOs of the batch were minted in the ledger transaction
+/// `block_index`, using a single event for the whole batch.
+pub fn record_minted_batch(
+    state: &mut state::CkBtcMinterState,
+    batch: MintBatch,
+    block_index: u64,
+) {
+    state::audit::add_utxos(state, Some(block_index), batch.account, batch.utxos);
 }
 
+/// Checks the UTXO with the next KYT canister of the pool and returns the
+/// external id of the check, its outcome, the KYT provider and the KYT canister.
+/// The KYT canister is None if the minter already checked the UTXO.
 async fn kyt_check_utxo(
     caller: Principal,
     utxo: &Utxo,
-) -> Result<(String, UtxoCheckStatus, Principal), UpdateBalanceError> {
-    let kyt_principal = read_state(|s| {
-        s.kyt_principal
+) -> Result<(String, UtxoCheckStatus, Principal, Option<Principal>), UpdateBalanceError> {
+    if let Some((uuid, status, api_key_owner)) = read_state(|s| s.checked_utxos.get(utxo).cloned())
+    {
+        return Ok((uuid, status, api_key_owner, None));
+    }
+
+    let kyt_principal = mutate_state(|s| {
+        s.next_kyt_principal()
             .expect("BUG: upgrade procedure must ensure that the KYT principal is set")
             .get()
             .into()
     });
 
-    if let Some((uuid, status, api_key_owner)) = read_state(|s| s.checked_utxos.get(utxo).cloned())
-    {
-        return Ok((uuid, status, api_key_owner));
-    }
-
     match fetch_utxo_alerts(kyt_principal, caller, utxo)
         .await
         .map_err(|call_err| {
@@ -292,12 +390,14 @@ async fn kyt_check_utxo(
                     response.external_id,
                     UtxoCheckStatus::Tainted,
                     response.provider,
+                    Some(kyt_principal),
                 ))
             } else {
                 Ok((
                     response.external_id,
                     UtxoCheckStatus::Clean,
                     response.provider,
+                    Some(kyt_principal),
                 ))
             }
         }
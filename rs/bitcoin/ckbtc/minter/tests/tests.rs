@@ -204,6 +204,9 @@ fn test_wrong_upgrade_parameter() {
         mode: Some(Mode::ReadOnly),
         kyt_principal: None,
         kyt_fee: None,
+        max_in_flight_transactions: None,
+        max_fee_rate_staleness_nanos: None,
+        additional_kyt_principals: None,
     };
     let minter_arg = MinterArg::Upgrade(Some(upgrade_args));
     if env
@@ -232,6 +235,9 @@ fn test_upgrade_read_only() {
         mode: Some(Mode::ReadOnly),
         kyt_principal: Some(CanisterId::from(0)),
         kyt_fee: None,
+        max_in_flight_transactions: None,
+        max_fee_rate_staleness_nanos: None,
+        additional_kyt_principals: None,
     };
     let minter_arg = MinterArg::Upgrade(Some(upgrade_args));
     env.upgrade_canister(minter_id, minter_wasm(), Encode!(&minter_arg).unwrap())
@@ -302,6 +308,9 @@ fn test_upgrade_restricted() {
         mode: Some(Mode::RestrictedTo(vec![authorized_principal])),
         kyt_fee: None,
         kyt_principal: Some(CanisterId::from(0)),
+        max_in_flight_transactions: None,
+        max_fee_rate_staleness_nanos: None,
+        additional_kyt_principals: None,
     };
     let minter_arg = MinterArg::Upgrade(Some(upgrade_args));
     env.upgrade_canister(minter_id, minter_wasm(), Encode!(&minter_arg).unwrap())
@@ -357,6 +366,9 @@ fn test_upgrade_restricted() {
         mode: Some(Mode::DepositsRestrictedTo(vec![authorized_principal])),
         kyt_principal: Some(CanisterId::from(0)),
         kyt_fee: None,
+        max_in_flight_transactions: None,
+        max_fee_rate_staleness_nanos: None,
+        additional_kyt_principals: None,
     };
     env.upgrade_canister(minter_id, minter_wasm(), Encode!(&upgrade_args).unwrap())
         .expect("Failed to upgrade the minter canister");
@@ -700,13 +712,12 @@ impl CkBtcSetup {
         .unwrap()
     }
 
-    pub fn deposit_utxo(&self, account: impl Into<Account>, utxo: Utxo) {
+    pub fn update_balance(
+        &self,
+        account: impl Into<Account>,
+    ) -> Result<Vec<UtxoStatus>, UpdateBalanceError> {
         let account = account.into();
-        let deposit_address = self.get_btc_address(account);
-
-        self.push_utxo(deposit_address, utxo.clone());
-
-        let utxo_status = Decode!(
+        Decode!(
             &assert_reply(
                 self.env
                     .execute_ingress_as(
@@ -723,7 +734,16 @@ impl CkBtcSetup {
             ),
             Result<Vec<UtxoStatus>, UpdateBalanceError>
         )
-        .unwrap();
+        .unwrap()
+    }
+
+    pub fn deposit_utxo(&self, account: impl Into<Account>, utxo: Utxo) {
+        let account = account.into();
+        let deposit_address = self.get_btc_address(account);
+
+        self.push_utxo(deposit_address, utxo.clone());
+
+        let utxo_status = self.update_balance(account);
 
         assert_eq!(
             utxo_status.unwrap(),
@@ -1880,3 +1900,72 @@ fn test_retrieve_btc_with_approval_fail() {
         Nat::from(deposit_value - 2 * KYT_FEE - TRANSFER_FEE)
     );
 }
+
+#[test]
+fn test_mint_clean_utxos_in_a_single_batch() {
+    use ic_ckbtc_minter::memo::MintMemo;
+
+    let ckbtc = CkBtcSetup::new();
+    let user = Principal::from(ckbtc.caller);
+    let deposit_address = ckbtc.get_btc_address(user);
+    let utxo = |vout: u32, value: u64| Utxo {
+        height: 0,
+        outpoint: OutPoint {
+            txid: range_to_txid(1..=32),
+            vout,
+        },
+        value,
+    };
+
+    // The first UTXO passes the KYT check, but the ledger cannot mint it.
+    let checked_utxo = utxo(0, 100_000);
+    ckbtc.push_utxo(deposit_address.clone(), checked_utxo.clone());
+    assert_matches!(ckbtc.env.stop_canister(ckbtc.ledger_id), Ok(_));
+    assert_eq!(
+        ckbtc.update_balance(user),
+        Ok(vec![UtxoStatus::Checked(checked_utxo.clone())])
+    );
+    assert_matches!(ckbtc.env.start_canister(ckbtc.ledger_id), Ok(_));
+
+    // The KYT canister fails for the second UTXO. The minter still mints the
+    // first one, whose check result it recorded, before reporting the error.
+    let unchecked_utxo = utxo(1, 200_000);
+    ckbtc.push_utxo(deposit_address.clone(), unchecked_utxo.clone());
+    assert_matches!(ckbtc.env.stop_canister(ckbtc.kyt_id), Ok(_));
+    assert_matches!(
+        ckbtc.update_balance(user),
+        Err(UpdateBalanceError::TemporarilyUnavailable(_))
+    );
+    assert_eq!(
+        ckbtc.balance_of(user),
+        Nat::from(checked_utxo.value - KYT_FEE)
+    );
+    assert_matches!(ckbtc.env.start_canister(ckbtc.kyt_id), Ok(_));
+
+    // All new clean UTXOs of the account are minted with a single transfer.
+    let new_utxos = vec![utxo(2, 300_000), utxo(3, 400_000)];
+    for new_utxo in &new_utxos {
+        ckbtc.push_utxo(deposit_address.clone(), new_utxo.clone());
+    }
+    let statuses = ckbtc
+        .update_balance(user)
+        .expect("failed to update balance");
+    assert_eq!(statuses.len(), 3);
+    assert!(statuses
+        .iter()
+        .all(|status| matches!(status, UtxoStatus::Minted { block_index: 1, .. })));
+    assert_eq!(ckbtc.balance_of(user), Nat::from(1_000_000 - 4 * KYT_FEE));
+
+    let res = ckbtc.get_transactions(GetTransactionsRequest {
+        start: 1.into(),
+        length: 1.into(),
+    });
+    let memo = res.transactions[0].mint.clone().unwrap().memo.unwrap();
+    assert_eq!(
+        minicbor::decode::<MintMemo>(&memo.0).expect("failed to decode memo"),
+        MintMemo::ConvertBatch {
+            utxo_count: Some(3),
+            kyt_fee: Some(3 * KYT_FEE),
+        }
+    );
+}
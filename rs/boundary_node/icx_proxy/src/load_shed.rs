@@ -0,0 +1,182 @@
+use std::{num::NonZeroUsize, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use clap::Args;
+use hyper::{header::RETRY_AFTER, StatusCode};
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+use tokio::sync::Semaphore;
+
+/// The options for shedding load when the replica is slow
+#[derive(Args)]
+pub struct LoadShedOpts {
+    /// Maximum number of requests forwarded to the replica concurrently.
+    /// Requests beyond this limit are rejected with 503 until in-flight ones complete.
+    /// By default, the number of in-flight requests is not limited.
+    #[clap(long)]
+    max_concurrent_upstream_requests: Option<NonZeroUsize>,
+
+    /// The number of seconds clients are asked to wait (with Retry-After)
+    /// before retrying a request that was shed.
+    #[clap(long, default_value = "1")]
+    load_shed_retry_after_secs: u64,
+}
+
+/// Limits the number of requests that are in flight to the replica, shared by all the requests.
+#[derive(Clone)]
+pub struct ConcurrencyLimiter {
+    permits: Arc<Semaphore>,
+    retry_after_secs: u64,
+    shed: Counter<u64>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(
+        meter: &Meter,
+        max_concurrent_requests: NonZeroUsize,
+        retry_after_secs: u64,
+    ) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent_requests.get())),
+            retry_after_secs,
+            shed: meter
+                .u64_counter("http_requests_shed")
+                .with_description(
+                    "Counts HTTP requests rejected because too many requests were in flight to the replica",
+                )
+                .init(),
+        }
+    }
+}
+
+pub fn setup(opts: LoadShedOpts, meter: &Meter) -> Option<ConcurrencyLimiter> {
+    opts.max_concurrent_upstream_requests
+        .map(|max_concurrent_requests| {
+            ConcurrencyLimiter::new(
+                meter,
+                max_concurrent_requests,
+                opts.load_shed_retry_after_secs,
+            )
+        })
+}
+
+pub async fn with_concurrency_limit_middleware(
+    State(limiter): State<ConcurrencyLimiter>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    // The permit is held until the upstream call completes.
+    let _permit = match limiter.permits.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => {
+            limiter.shed.add(1, &[KeyValue::new("status", "503")]);
+
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, limiter.retry_after_secs.to_string())],
+                "Service Unavailable",
+            )
+                .into_response();
+        }
+    };
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, sync::Arc};
+
+    use axum::{body::Body, http::Request, middleware, routing::get, Router};
+    use hyper::{header::RETRY_AFTER, StatusCode};
+    use opentelemetry::{metrics::MeterProvider as _, sdk::metrics::MeterProvider};
+    use opentelemetry_prometheus::exporter;
+    use prometheus::Registry;
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    use super::{with_concurrency_limit_middleware, ConcurrencyLimiter};
+
+    fn concurrency_limiter(
+        registry: &Registry,
+        max_concurrent_requests: usize,
+    ) -> ConcurrencyLimiter {
+        let exporter = exporter().with_registry(registry.clone()).build().unwrap();
+        let meter = MeterProvider::builder()
+            .with_reader(exporter)
+            .build()
+            .meter("icx_proxy");
+        ConcurrencyLimiter::new(
+            &meter,
+            NonZeroUsize::new(max_concurrent_requests).unwrap(),
+            1,
+        )
+    }
+
+    fn shed(registry: &Registry) -> f64 {
+        registry
+            .gather()
+            .iter()
+            .filter(|family| family.get_name().starts_with("http_requests_shed"))
+            .flat_map(|family| family.get_metric())
+            .map(|metric| metric.get_counter().get_value())
+            .sum()
+    }
+
+    fn request() -> Request<Body> {
+        Request::builder().uri("/").body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn exceeding_concurrency_limit_yields_503() {
+        let registry = Registry::new();
+        // Requests stay in flight until they are released.
+        let entered = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let handler = {
+            let entered = entered.clone();
+            let release = release.clone();
+            move || {
+                let entered = entered.clone();
+                let release = release.clone();
+                async move {
+                    entered.notify_one();
+                    release.notified().await;
+                    "ok"
+                }
+            }
+        };
+        let router = Router::new()
+            .route("/", get(handler))
+            .layer(middleware::from_fn_with_state(
+                concurrency_limiter(&registry, 1),
+                with_concurrency_limit_middleware,
+            ));
+
+        let in_flight = tokio::spawn(router.clone().oneshot(request()));
+        entered.notified().await;
+
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+        assert_eq!(shed(&registry), 1.0);
+
+        release.notify_one();
+        let response = in_flight.await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Once the in-flight request completed, requests are accepted again.
+        release.notify_one();
+        let response = router.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(shed(&registry), 1.0);
+    }
+}
@@ -16,9 +16,11 @@ mod domain_addr;
 mod error;
 mod http;
 mod http_client;
+mod load_shed;
 mod logging;
 mod metrics;
 mod proxy;
+mod rate_limit;
 mod validate;
 
 use crate::{
@@ -100,6 +102,14 @@ struct Opts {
     /// The options for metrics
     #[clap(flatten)]
     metrics: metrics::MetricsOpts,
+
+    /// The options for rate limiting
+    #[clap(flatten)]
+    rate_limit: rate_limit::RateLimitOpts,
+
+    /// The options for shedding load when the replica is slow
+    #[clap(flatten)]
+    load_shed: load_shed::LoadShedOpts,
 }
 
 fn main() -> Result<(), anyhow::Error> {
@@ -114,6 +124,8 @@ fn main() -> Result<(), anyhow::Error> {
         debug,
         log,
         metrics,
+        rate_limit,
+        load_shed,
         root_key,
     } = Opts::parse();
 
@@ -129,6 +141,12 @@ fn main() -> Result<(), anyhow::Error> {
     // Setup Metrics
     let (meter, metrics) = metrics::setup(metrics);
 
+    // Setup Rate Limiting
+    let rate_limiter = rate_limit::setup(rate_limit, &meter);
+
+    // Setup Load Shedding
+    let concurrency_limiter = load_shed::setup(load_shed, &meter);
+
     // Setup Canister ID Resolver
     let resolver = canister_id::setup(canister_id::CanisterIdOpts {
         canister_alias,
@@ -145,6 +163,8 @@ fn main() -> Result<(), anyhow::Error> {
             validator,
             client,
             meter: meter.clone(),
+            rate_limiter,
+            concurrency_limiter,
         },
         proxy::ProxyOpts {
             address,
@@ -18,7 +18,7 @@ use candid::Principal;
 use clap::Args;
 use futures::task::{Context as FutContext, Poll};
 use http_body::Body as HttpBody;
-use hyper::http::header::HeaderMap;
+use hyper::http::header::{HeaderMap, CONTENT_LENGTH};
 use hyper::{self, StatusCode};
 use ic_agent::Agent;
 use opentelemetry::{
@@ -29,6 +29,7 @@ use opentelemetry::{
 use opentelemetry_prometheus::exporter;
 
 use prometheus::{Encoder as PrometheusEncoder, Registry, TextEncoder};
+use tracing::warn;
 
 use crate::http::request::HttpRequest;
 use crate::http::response::HttpResponse;
@@ -305,20 +306,45 @@ impl HttpMetricParams {
     }
 }
 
+/// Returns the request size declared in the `Content-Length` header, if any.
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Determines the size of a request. The declared `Content-Length` is used for requests whose body
+/// was never read (e.g. rejected early), while the number of bytes actually read takes precedence
+/// for fully read, non-streaming requests.
+fn request_size(declared: Option<u64>, request_ctx: Option<&RequestContext>) -> u64 {
+    match (declared, request_ctx) {
+        (Some(declared), Some(ctx)) if !ctx.streaming_request && declared != ctx.request_size => {
+            warn!(
+                "Request declared a Content-Length of {declared} bytes but had {} bytes",
+                ctx.request_size
+            );
+            ctx.request_size
+        }
+        (Some(declared), _) => declared,
+        (None, Some(ctx)) => ctx.request_size,
+        (None, None) => 0,
+    }
+}
+
 pub async fn with_metrics_middleware(
     State(metric_params): State<HttpMetricParams>,
     request: Request<Body>,
     next: Next<Body>,
 ) -> impl IntoResponse {
     let start = Instant::now();
+    let declared_request_size = content_length(request.headers());
     let response = next.run(request).await;
     let proc_duration = start.elapsed().as_secs_f64();
 
-    let request_ctx = response
-        .extensions()
-        .get::<RequestContext>()
-        .cloned()
-        .unwrap_or_default();
+    let request_ctx = response.extensions().get::<RequestContext>().cloned();
+    let request_size = request_size(declared_request_size, request_ctx.as_ref());
+    let request_ctx = request_ctx.unwrap_or_default();
 
     let HttpMetricParams {
         request_sizer,
@@ -337,7 +363,7 @@ pub async fn with_metrics_middleware(
             KeyValue::new("body_fully_read", fully_read.to_string()),
         ];
 
-        request_sizer.record(request_ctx.request_size, labels);
+        request_sizer.record(request_size, labels);
         response_sizer.record(bytes_sent, labels);
         durationer.record(proc_duration, labels);
         durationer_full.record(start.elapsed().as_secs_f64(), labels);
@@ -345,3 +371,76 @@ pub async fn with_metrics_middleware(
 
     Response::from_parts(parts, body)
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body, http::Request, middleware, response::IntoResponse, routing::post, Router,
+    };
+    use hyper::{header::CONTENT_LENGTH, StatusCode};
+    use opentelemetry::{metrics::MeterProvider as _, sdk::metrics::MeterProvider};
+    use opentelemetry_prometheus::exporter;
+    use prometheus::Registry;
+    use tower::ServiceExt;
+
+    use super::{request_size, with_metrics_middleware, HttpMetricParams, RequestContext};
+
+    #[test]
+    fn request_size_prefers_actual_bytes_for_non_streaming_requests() {
+        let ctx = RequestContext {
+            request_size: 10,
+            streaming_request: false,
+        };
+        assert_eq!(request_size(Some(20), Some(&ctx)), 10);
+        assert_eq!(request_size(None, Some(&ctx)), 10);
+
+        let streaming_ctx = RequestContext {
+            request_size: 10,
+            streaming_request: true,
+        };
+        assert_eq!(request_size(Some(20), Some(&streaming_ctx)), 20);
+
+        assert_eq!(request_size(Some(20), None), 20);
+        assert_eq!(request_size(None, None), 0);
+    }
+
+    #[tokio::test]
+    async fn records_declared_size_of_unread_request() {
+        let registry = Registry::new();
+        let meter = MeterProvider::builder()
+            .with_reader(exporter().with_registry(registry.clone()).build().unwrap())
+            .build()
+            .meter("icx_proxy");
+        // The handler rejects the request without reading its body.
+        let router = Router::new()
+            .route(
+                "/",
+                post(|| async { StatusCode::TOO_MANY_REQUESTS.into_response() }),
+            )
+            .layer(middleware::from_fn_with_state(
+                HttpMetricParams::new(&meter),
+                with_metrics_middleware,
+            ));
+
+        let request = Request::post("/")
+            .header(CONTENT_LENGTH, "1234")
+            .body(Body::from(vec![0; 1234]))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        // Metrics are recorded once the response body was sent.
+        hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        let request_sizes: Vec<_> = registry
+            .gather()
+            .into_iter()
+            .filter(|family| family.get_name().starts_with("http_request_size"))
+            .flat_map(|family| family.get_metric().to_vec())
+            .map(|metric| {
+                let histogram = metric.get_histogram();
+                (histogram.get_sample_count(), histogram.get_sample_sum())
+            })
+            .collect();
+        assert_eq!(request_sizes, vec![(1, 1234.0)]);
+    }
+}
@@ -10,8 +10,10 @@ use tracing::{error, info};
 use crate::{
     canister_id::ResolverState,
     http_client::{Body, HyperService},
+    load_shed::{with_concurrency_limit_middleware, ConcurrencyLimiter},
     logging::add_trace_layer,
     metrics::{with_metrics_middleware, HttpMetricParams},
+    rate_limit::{with_rate_limit_middleware, RateLimiter},
     validate::Validate,
     DomainAddr,
 };
@@ -79,6 +81,8 @@ pub struct SetupArgs<V, C> {
     pub resolver: ResolverState,
     pub client: C,
     pub meter: Meter,
+    pub rate_limiter: Option<RateLimiter>,
+    pub concurrency_limiter: Option<ConcurrencyLimiter>,
 }
 
 pub fn setup<C: HyperService<Body> + 'static>(
@@ -133,12 +137,25 @@ pub fn setup<C: HyperService<Body> + 'static>(
     let http_metrics = HttpMetricParams::new(&args.meter);
     let metrics_layer = middleware::from_fn_with_state(http_metrics, with_metrics_middleware);
 
+    let mut router = Router::new().fallback_service(agent_service);
+    if let Some(concurrency_limiter) = args.concurrency_limiter {
+        // Applied within the rate limiting layer, so that rate limited requests don't take up
+        // any of the permits.
+        router = router.layer(middleware::from_fn_with_state(
+            concurrency_limiter,
+            with_concurrency_limit_middleware,
+        ));
+    }
+    if let Some(rate_limiter) = args.rate_limiter {
+        // Applied within the metrics layer, so that rejected requests are measured as well.
+        router = router.layer(middleware::from_fn_with_state(
+            rate_limiter,
+            with_rate_limit_middleware,
+        ));
+    }
+
     Ok(Runner {
-        router: add_trace_layer(
-            Router::new()
-                .fallback_service(agent_service)
-                .layer(metrics_layer),
-        ),
+        router: add_trace_layer(router.layer(metrics_layer)),
         address: opts.address,
         fetch_root_keys,
     })
@@ -0,0 +1,323 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use clap::Args;
+use hyper::{header::RETRY_AFTER, StatusCode};
+use opentelemetry::{
+    metrics::{Counter, Meter},
+    KeyValue,
+};
+
+/// Past this many clients seen within one refill period, the buckets of the clients seen before
+/// are forgotten, so that a flood from many IPs cannot grow the buckets without bound.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// The options for rate limiting
+#[derive(Args)]
+pub struct RateLimitOpts {
+    /// Maximum sustained number of requests per second accepted from a single client IP.
+    /// By default, requests are not rate limited.
+    #[clap(long)]
+    rate_limit_per_second: Option<NonZeroU32>,
+
+    /// Maximum number of requests a single client IP can send in a burst,
+    /// on top of the sustained rate.
+    #[clap(long, default_value = "100")]
+    rate_limit_burst: NonZeroU32,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// The token buckets split into two generations. When the current generation is older than the
+/// time it takes to refill an empty bucket, it replaces the previous one. The buckets dropped this
+/// way are full again, so forgetting them does not change the outcome for their clients.
+struct Buckets {
+    /// The buckets of the clients seen since `current_since`.
+    current: HashMap<IpAddr, TokenBucket>,
+    /// The buckets of the clients seen in the previous generation, but not since.
+    previous: HashMap<IpAddr, TokenBucket>,
+    current_since: Instant,
+}
+
+/// Per-client-IP token buckets, shared by all the requests.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate_per_second: f64,
+    burst: f64,
+    refill_period: Duration,
+    buckets: Arc<Mutex<Buckets>>,
+    rejections: Counter<u64>,
+}
+
+impl RateLimiter {
+    pub fn new(meter: &Meter, rate_per_second: NonZeroU32, burst: NonZeroU32) -> Self {
+        Self {
+            rate_per_second: rate_per_second.get() as f64,
+            burst: burst.get() as f64,
+            refill_period: Duration::from_secs_f64(
+                burst.get() as f64 / rate_per_second.get() as f64,
+            ),
+            buckets: Arc::new(Mutex::new(Buckets {
+                current: HashMap::new(),
+                previous: HashMap::new(),
+                current_since: Instant::now(),
+            })),
+            rejections: meter
+                .u64_counter("http_requests_rate_limited")
+                .with_description("Counts HTTP requests rejected because of rate limiting")
+                .init(),
+        }
+    }
+
+    /// Takes a token from the bucket of the given client.
+    /// Returns how long the client should wait before retrying if the bucket is empty.
+    fn acquire(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if now.saturating_duration_since(buckets.current_since) >= self.refill_period
+            || (buckets.current.len() >= MAX_TRACKED_CLIENTS
+                && !buckets.current.contains_key(&client))
+        {
+            buckets.previous = std::mem::take(&mut buckets.current);
+            buckets.current_since = now;
+        }
+
+        let Buckets {
+            current, previous, ..
+        } = &mut *buckets;
+        let bucket = current.entry(client).or_insert_with(|| {
+            previous.remove(&client).unwrap_or(TokenBucket {
+                tokens: self.burst,
+                last_refill: now,
+            })
+        });
+        let tokens = self.refill(bucket, now);
+
+        if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - tokens) / self.rate_per_second,
+            ))
+        }
+    }
+
+    /// Adds the tokens accumulated since the last refill, up to the burst size.
+    fn refill(&self, bucket: &mut TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * self.rate_per_second).min(self.burst);
+        bucket.last_refill = now;
+        bucket.tokens
+    }
+}
+
+pub fn setup(opts: RateLimitOpts, meter: &Meter) -> Option<RateLimiter> {
+    opts.rate_limit_per_second
+        .map(|rate_per_second| RateLimiter::new(meter, rate_per_second, opts.rate_limit_burst))
+}
+
+pub async fn with_rate_limit_middleware(
+    State(rate_limiter): State<RateLimiter>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let client = match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => addr.ip(),
+        None => return next.run(request).await,
+    };
+
+    match rate_limiter.acquire(client, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            rate_limiter
+                .rejections
+                .add(1, &[KeyValue::new("status", "429")]);
+
+            // Retry-After only supports whole seconds.
+            let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after_secs.to_string())],
+                "Too Many Requests",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        num::NonZeroU32,
+        time::{Duration, Instant},
+    };
+
+    use axum::{body::Body, extract::ConnectInfo, http::Request, middleware, routing::get, Router};
+    use hyper::{header::RETRY_AFTER, StatusCode};
+    use opentelemetry::{metrics::MeterProvider as _, sdk::metrics::MeterProvider};
+    use opentelemetry_prometheus::exporter;
+    use prometheus::Registry;
+    use tower::ServiceExt;
+
+    use super::{with_rate_limit_middleware, RateLimiter, MAX_TRACKED_CLIENTS};
+
+    fn rate_limiter(registry: &Registry, rate_per_second: u32, burst: u32) -> RateLimiter {
+        let exporter = exporter().with_registry(registry.clone()).build().unwrap();
+        let meter = MeterProvider::builder()
+            .with_reader(exporter)
+            .build()
+            .meter("icx_proxy");
+        RateLimiter::new(
+            &meter,
+            NonZeroU32::new(rate_per_second).unwrap(),
+            NonZeroU32::new(burst).unwrap(),
+        )
+    }
+
+    fn rejections(registry: &Registry) -> f64 {
+        registry
+            .gather()
+            .iter()
+            .filter(|family| family.get_name().starts_with("http_requests_rate_limited"))
+            .flat_map(|family| family.get_metric())
+            .map(|metric| metric.get_counter().get_value())
+            .sum()
+    }
+
+    fn request_from(ip: IpAddr) -> Request<Body> {
+        let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(ip, 1234)));
+        request
+    }
+
+    #[test]
+    fn refills_tokens_over_time() {
+        let limiter = rate_limiter(&Registry::new(), 2, 1);
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let now = Instant::now();
+
+        assert_eq!(limiter.acquire(client, now), Ok(()));
+        assert_eq!(
+            limiter.acquire(client, now),
+            Err(Duration::from_millis(500))
+        );
+        assert_eq!(
+            limiter.acquire(client, now + Duration::from_millis(500)),
+            Ok(())
+        );
+    }
+
+    fn tracked_clients(limiter: &RateLimiter) -> (usize, usize) {
+        let buckets = limiter.buckets.lock().unwrap();
+        (buckets.current.len(), buckets.previous.len())
+    }
+
+    #[test]
+    fn forgets_clients_not_seen_for_a_refill_period() {
+        // An empty bucket is full again after 2 seconds.
+        let limiter = rate_limiter(&Registry::new(), 1, 2);
+        let client = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let other_client = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let now = Instant::now();
+
+        assert_eq!(limiter.acquire(client, now), Ok(()));
+        assert_eq!(limiter.acquire(client, now), Ok(()));
+        assert_eq!(
+            limiter.acquire(client, now + Duration::from_millis(1_500)),
+            Ok(())
+        );
+
+        // A new generation starts, but the client keeps its bucket.
+        assert_eq!(
+            limiter.acquire(other_client, now + Duration::from_secs(2)),
+            Ok(())
+        );
+        assert_eq!(tracked_clients(&limiter), (1, 1));
+        assert_eq!(
+            limiter.acquire(client, now + Duration::from_secs(2)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.acquire(client, now + Duration::from_secs(2)),
+            Err(Duration::from_secs(1))
+        );
+
+        // The client is forgotten after a whole generation without requests.
+        assert_eq!(
+            limiter.acquire(other_client, now + Duration::from_secs(4)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.acquire(other_client, now + Duration::from_secs(6)),
+            Ok(())
+        );
+        assert_eq!(tracked_clients(&limiter), (1, 1));
+        assert!(!limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .previous
+            .contains_key(&client));
+    }
+
+    #[test]
+    fn bounds_the_number_of_tracked_clients() {
+        let limiter = rate_limiter(&Registry::new(), 1, 100);
+        let now = Instant::now();
+
+        for i in 0..=MAX_TRACKED_CLIENTS as u32 {
+            assert_eq!(limiter.acquire(IpAddr::V4(Ipv4Addr::from(i)), now), Ok(()));
+        }
+        assert_eq!(tracked_clients(&limiter), (1, MAX_TRACKED_CLIENTS));
+    }
+
+    #[tokio::test]
+    async fn exceeding_rate_yields_429() {
+        let registry = Registry::new();
+        let router =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    rate_limiter(&registry, 1, 2),
+                    with_rate_limit_middleware,
+                ));
+        let client = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let other_client = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+        for _ in 0..2 {
+            let response = router.clone().oneshot(request_from(client)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(rejections(&registry), 0.0);
+
+        let response = router.clone().oneshot(request_from(client)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+        assert_eq!(rejections(&registry), 1.0);
+
+        // Other clients have their own budget.
+        let response = router.oneshot(request_from(other_client)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(rejections(&registry), 1.0);
+    }
+}
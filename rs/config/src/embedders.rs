@@ -17,6 +17,9 @@ pub(crate) const MAX_GLOBALS: usize = 300;
 pub(crate) const MAX_FUNCTIONS: usize = 50000;
 // The maximum number of custom sections allowed in a Wasm module.
 pub(crate) const MAX_CUSTOM_SECTIONS: usize = 16;
+// The maximum number of custom sections allowed in a Wasm module on subnets
+// used for development, where tools embed source maps and other metadata.
+pub const MAX_CUSTOM_SECTIONS_FOR_DEVELOPMENT: usize = 64;
 // The total size of the exported custom sections in bytes.
 // The size should not exceed 1MiB.
 pub(crate) const MAX_CUSTOM_SECTIONS_SIZE: NumBytes = NumBytes::new(1048576);
@@ -39,6 +42,11 @@ pub(crate) const DEFAULT_COST_TO_COMPILE_WASM_INSTRUCTION: NumInstructions =
 /// The number of rayon threads used by wasmtime to compile wasm binaries
 const DEFAULT_WASMTIME_RAYON_COMPILATION_THREADS: usize = 10;
 
+/// The bounds on the number of rayon compilation threads derived from the
+/// number of cores of the host, see `Config::with_auto_compile_threads()`.
+pub(crate) const MIN_AUTO_RAYON_COMPILATION_THREADS: usize = 2;
+pub(crate) const MAX_AUTO_RAYON_COMPILATION_THREADS: usize = 32;
+
 /// Sandbox process eviction does not activate if the number of sandbox
 /// processes is below this threshold.
 pub(crate) const DEFAULT_MIN_SANDBOX_COUNT: usize = 500;
@@ -47,6 +55,14 @@ pub(crate) const DEFAULT_MIN_SANDBOX_COUNT: usize = 500;
 /// always below this threshold.
 pub(crate) const DEFAULT_MAX_SANDBOX_COUNT: usize = 2_000;
 
+/// The sandbox process eviction thresholds of System subnets, see
+/// `Config::for_subnet_type()`. System subnets host few canisters, some of
+/// them heavy (e.g. the NNS governance canister with its large heap), so a
+/// lower number of sandbox processes keeps the memory of the replica in check
+/// while still keeping all their canisters warm.
+pub(crate) const SYSTEM_SUBNET_MIN_SANDBOX_COUNT: usize = 100;
+pub(crate) const SYSTEM_SUBNET_MAX_SANDBOX_COUNT: usize = 500;
+
 /// A sandbox process may be evicted after it has been idle for this
 /// duration and sandbox process eviction is activated.
 pub(crate) const DEFAULT_MAX_SANDBOX_IDLE_TIME: Duration = Duration::from_secs(30 * 60);
@@ -56,7 +72,8 @@ const KiB: u64 = 1024;
 #[allow(non_upper_case_globals)]
 const GiB: u64 = KiB * KiB * KiB;
 // Maximum number of stable memory dirty pages that a single message execution
-// is allowed to produce.
+// is allowed to produce. See `Config::stable_memory_dirty_byte_limit()` for the
+// corresponding limit in bytes.
 pub const STABLE_MEMORY_DIRTY_PAGE_LIMIT: u64 = 8 * GiB / (PAGE_SIZE as u64);
 // Maximum number of stable memory pages that a single message execution
 // is allowed to access.
@@ -70,6 +87,10 @@ pub struct FeatureFlags {
     /// Track dirty pages with a write barrier instead of the signal handler.
     pub write_barrier: FlagStatus,
     pub wasm_native_stable_memory: FlagStatus,
+    /// If this flag is enabled, then the hypervisor raises the custom section
+    /// limit to `MAX_CUSTOM_SECTIONS_FOR_DEVELOPMENT`. Meant for replicas
+    /// used for development.
+    pub development_custom_section_limits: FlagStatus,
 }
 
 impl FeatureFlags {
@@ -78,6 +99,7 @@ impl FeatureFlags {
             rate_limiting_of_debug_prints: FlagStatus::Enabled,
             write_barrier: FlagStatus::Disabled,
             wasm_native_stable_memory: FlagStatus::Enabled,
+            development_custom_section_limits: FlagStatus::Disabled,
         }
     }
 }
@@ -88,6 +110,14 @@ impl Default for FeatureFlags {
     }
 }
 
+/// Sandbox process eviction thresholds configured for a subnet type, see
+/// `Config::for_subnet_type()`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SandboxCounts {
+    pub min_sandbox_count: usize,
+    pub max_sandbox_count: usize,
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum MeteringType {
     Old,
@@ -156,6 +186,16 @@ pub struct Config {
     /// duration and sandbox process eviction is activated.
     pub max_sandbox_idle_time: Duration,
 
+    /// Sandbox process eviction thresholds of System subnets, overriding
+    /// `min_sandbox_count` and `max_sandbox_count`.
+    #[serde(default)]
+    pub system_subnet_sandbox_counts: Option<SandboxCounts>,
+
+    /// Sandbox process eviction thresholds of (Verified)Application subnets,
+    /// overriding `min_sandbox_count` and `max_sandbox_count`.
+    #[serde(default)]
+    pub application_subnet_sandbox_counts: Option<SandboxCounts>,
+
     /// The type of the local subnet. The default value here should be replaced
     /// with the correct value at runtime when the hypervisor is created.
     pub subnet_type: SubnetType,
@@ -169,6 +209,16 @@ pub struct Config {
     /// If this flag is enabled, then execution of a slice will produce a log
     /// entry with the number of executed instructions and the duration.
     pub trace_execution: FlagStatus,
+
+    /// If this flag is disabled, then compiled modules are not cached and each
+    /// installation recompiles the Wasm module, e.g. to catch nondeterministic
+    /// code generation in tests.
+    #[serde(default = "default_compilation_cache")]
+    pub compilation_cache: FlagStatus,
+}
+
+fn default_compilation_cache() -> FlagStatus {
+    FlagStatus::Enabled
 }
 
 impl Config {
@@ -191,11 +241,105 @@ impl Config {
             min_sandbox_count: DEFAULT_MIN_SANDBOX_COUNT,
             max_sandbox_count: DEFAULT_MAX_SANDBOX_COUNT,
             max_sandbox_idle_time: DEFAULT_MAX_SANDBOX_IDLE_TIME,
+            system_subnet_sandbox_counts: None,
+            application_subnet_sandbox_counts: None,
             subnet_type: SubnetType::Application,
             dirty_page_overhead: NumInstructions::new(0),
             trace_execution: FlagStatus::Disabled,
+            compilation_cache: FlagStatus::Enabled,
         }
     }
+
+    /// Returns the config with the raised custom section limit of development
+    /// subnets, i.e. `MAX_CUSTOM_SECTIONS_FOR_DEVELOPMENT`. The limit is only
+    /// raised, never lowered.
+    pub fn with_development_custom_section_limits(self) -> Self {
+        Self {
+            max_custom_sections: self
+                .max_custom_sections
+                .max(MAX_CUSTOM_SECTIONS_FOR_DEVELOPMENT),
+            ..self
+        }
+    }
+
+    /// Returns the config with `num_rayon_compilation_threads` set to the
+    /// available parallelism of the host, clamped between
+    /// `MIN_AUTO_RAYON_COMPILATION_THREADS` and `MAX_AUTO_RAYON_COMPILATION_THREADS`.
+    /// Falls back to the default number of threads if the available
+    /// parallelism cannot be determined. Setting the field explicitly
+    /// afterwards overrides the detected value.
+    pub fn with_auto_compile_threads(self) -> Self {
+        let num_rayon_compilation_threads = std::thread::available_parallelism()
+            .map(|parallelism| {
+                parallelism.get().clamp(
+                    MIN_AUTO_RAYON_COMPILATION_THREADS,
+                    MAX_AUTO_RAYON_COMPILATION_THREADS,
+                )
+            })
+            .unwrap_or(DEFAULT_WASMTIME_RAYON_COMPILATION_THREADS);
+        Self {
+            num_rayon_compilation_threads,
+            ..self
+        }
+    }
+
+    /// Returns the config for a subnet of the given type, i.e. with
+    /// `subnet_type` set and the sandbox process eviction thresholds of that
+    /// subnet type. The thresholds configured for the subnet type take
+    /// precedence, then the configured `min_sandbox_count` and
+    /// `max_sandbox_count`. Only if neither is configured, System subnets use
+    /// `SYSTEM_SUBNET_MIN_SANDBOX_COUNT` and `SYSTEM_SUBNET_MAX_SANDBOX_COUNT`.
+    pub fn for_subnet_type(self, subnet_type: SubnetType) -> Self {
+        let configured = match subnet_type {
+            SubnetType::System => self.system_subnet_sandbox_counts,
+            SubnetType::Application | SubnetType::VerifiedApplication => {
+                self.application_subnet_sandbox_counts
+            }
+        };
+        let has_default_counts = self.min_sandbox_count == DEFAULT_MIN_SANDBOX_COUNT
+            && self.max_sandbox_count == DEFAULT_MAX_SANDBOX_COUNT;
+        let (min_sandbox_count, max_sandbox_count) = match (configured, subnet_type) {
+            (Some(counts), _) => (counts.min_sandbox_count, counts.max_sandbox_count),
+            (None, SubnetType::System) if has_default_counts => (
+                SYSTEM_SUBNET_MIN_SANDBOX_COUNT,
+                SYSTEM_SUBNET_MAX_SANDBOX_COUNT,
+            ),
+            (None, _) => (self.min_sandbox_count, self.max_sandbox_count),
+        };
+        Self {
+            subnet_type,
+            min_sandbox_count,
+            max_sandbox_count,
+            ..self
+        }
+    }
+
+    /// Checks that `min_sandbox_count` does not exceed `max_sandbox_count`.
+    /// A `max_sandbox_count` of 0 is valid: sandbox processes are then evicted
+    /// as soon as they become idle.
+    pub fn validate_sandbox_counts(&self) -> Result<(), String> {
+        if self.max_sandbox_count > 0 && self.min_sandbox_count > self.max_sandbox_count {
+            return Err(format!(
+                "min_sandbox_count {} exceeds max_sandbox_count {}",
+                self.min_sandbox_count, self.max_sandbox_count
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns `stable_memory_dirty_page_limit` in bytes, i.e. the maximum
+    /// amount of stable memory that a single message execution is allowed to
+    /// dirty. With the default page limit of `STABLE_MEMORY_DIRTY_PAGE_LIMIT`
+    /// this is 8 GiB. Saturates at `u64::MAX` if the configured page limit is
+    /// too large to be expressed in bytes.
+    pub fn stable_memory_dirty_byte_limit(&self) -> NumBytes {
+        NumBytes::new(
+            self.stable_memory_dirty_page_limit
+                .get()
+                .checked_mul(PAGE_SIZE as u64)
+                .unwrap_or(u64::MAX),
+        )
+    }
 }
 
 impl Default for Config {
@@ -203,3 +347,181 @@ impl Default for Config {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_memory_dirty_byte_limit_is_8_gib_by_default() {
+        assert_eq!(
+            Config::default().stable_memory_dirty_byte_limit(),
+            NumBytes::new(8 * GiB)
+        );
+    }
+
+    #[test]
+    fn stable_memory_dirty_byte_limit_saturates() {
+        let config = Config {
+            stable_memory_dirty_page_limit: NumPages::new(u64::MAX),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.stable_memory_dirty_byte_limit(),
+            NumBytes::new(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn development_custom_section_limits_only_raise_the_limit() {
+        assert_eq!(
+            Config::default()
+                .with_development_custom_section_limits()
+                .max_custom_sections,
+            MAX_CUSTOM_SECTIONS_FOR_DEVELOPMENT
+        );
+        let config = Config {
+            max_custom_sections: 1000,
+            ..Config::default()
+        };
+        assert_eq!(
+            config
+                .with_development_custom_section_limits()
+                .max_custom_sections,
+            1000
+        );
+    }
+
+    #[test]
+    fn compilation_cache_is_enabled_by_default() {
+        assert_eq!(Config::new().compilation_cache, FlagStatus::Enabled);
+        assert_eq!(Config::default().compilation_cache, FlagStatus::Enabled);
+    }
+
+    #[test]
+    fn compilation_cache_round_trips_through_serde() {
+        for compilation_cache in [FlagStatus::Enabled, FlagStatus::Disabled] {
+            let config = Config {
+                compilation_cache,
+                ..Config::default()
+            };
+            let serialized = json5::to_string(&config).unwrap();
+            assert_eq!(json5::from_str::<Config>(&serialized).unwrap(), config);
+        }
+    }
+
+    #[test]
+    fn compilation_cache_defaults_to_enabled_in_old_configs() {
+        let config = Config {
+            compilation_cache: FlagStatus::Disabled,
+            ..Config::default()
+        };
+        let serialized = json5::to_string(&config).unwrap();
+        let old_serialized = serialized.replace(r#","compilation_cache":"Disabled""#, "");
+        assert_ne!(old_serialized, serialized);
+
+        let deserialized = json5::from_str::<Config>(&old_serialized).unwrap();
+
+        assert_eq!(deserialized.compilation_cache, FlagStatus::Enabled);
+        assert_eq!(deserialized, Config::default());
+    }
+
+    #[test]
+    fn system_and_application_subnets_have_different_valid_sandbox_counts() {
+        let system = Config::default().for_subnet_type(SubnetType::System);
+        let application = Config::default().for_subnet_type(SubnetType::Application);
+
+        assert_eq!(system.subnet_type, SubnetType::System);
+        assert_eq!(application.subnet_type, SubnetType::Application);
+        assert_eq!(system.validate_sandbox_counts(), Ok(()));
+        assert_eq!(application.validate_sandbox_counts(), Ok(()));
+        assert_ne!(
+            (system.min_sandbox_count, system.max_sandbox_count),
+            (application.min_sandbox_count, application.max_sandbox_count)
+        );
+        assert_eq!(
+            Config::default()
+                .for_subnet_type(SubnetType::VerifiedApplication)
+                .validate_sandbox_counts(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn configured_sandbox_counts_are_not_overwritten() {
+        let tuned = Config {
+            min_sandbox_count: 7,
+            max_sandbox_count: 42,
+            ..Config::default()
+        };
+        for subnet_type in [
+            SubnetType::System,
+            SubnetType::Application,
+            SubnetType::VerifiedApplication,
+        ] {
+            let config = tuned.clone().for_subnet_type(subnet_type);
+            assert_eq!(
+                (config.min_sandbox_count, config.max_sandbox_count),
+                (7, 42)
+            );
+        }
+
+        let overridden = Config {
+            system_subnet_sandbox_counts: Some(SandboxCounts {
+                min_sandbox_count: 1,
+                max_sandbox_count: 2,
+            }),
+            application_subnet_sandbox_counts: Some(SandboxCounts {
+                min_sandbox_count: 3,
+                max_sandbox_count: 4,
+            }),
+            ..tuned
+        };
+        let system = overridden.clone().for_subnet_type(SubnetType::System);
+        assert_eq!((system.min_sandbox_count, system.max_sandbox_count), (1, 2));
+        let application = overridden.for_subnet_type(SubnetType::VerifiedApplication);
+        assert_eq!(
+            (application.min_sandbox_count, application.max_sandbox_count),
+            (3, 4)
+        );
+    }
+
+    #[test]
+    fn invalid_sandbox_counts_are_rejected() {
+        let config = Config {
+            min_sandbox_count: 10,
+            max_sandbox_count: 5,
+            ..Config::default()
+        };
+        assert!(config.validate_sandbox_counts().is_err());
+    }
+
+    #[test]
+    fn zero_sandbox_counts_are_valid() {
+        let config = Config {
+            min_sandbox_count: 0,
+            max_sandbox_count: 0,
+            ..Config::default()
+        };
+        assert_eq!(config.validate_sandbox_counts(), Ok(()));
+
+        let config = Config {
+            max_sandbox_count: 0,
+            ..Config::default()
+        };
+        assert_eq!(config.validate_sandbox_counts(), Ok(()));
+    }
+
+    #[test]
+    fn auto_compile_threads_are_within_bounds() {
+        let num_threads = Config::default()
+            .with_auto_compile_threads()
+            .num_rayon_compilation_threads;
+        assert!(
+            (MIN_AUTO_RAYON_COMPILATION_THREADS..=MAX_AUTO_RAYON_COMPILATION_THREADS)
+                .contains(&num_threads),
+            "{} compilation threads are out of bounds",
+            num_threads
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::{Arc, Mutex};
 
 use crate::SerializedModule;
+use ic_config::flag_status::FlagStatus;
 use ic_interfaces::execution_environment::HypervisorResult;
 use ic_types::NumBytes;
 use ic_utils_lru_cache::LruCache;
@@ -10,12 +11,23 @@ use ic_wasm_types::{CanisterModule, WasmHash};
 /// that it can be used again without recompiling.
 pub struct CompilationCache {
     cache: Mutex<LruCache<WasmHash, HypervisorResult<Arc<SerializedModule>>>>,
+    status: FlagStatus,
 }
 
 impl CompilationCache {
     pub fn new(capacity: NumBytes) -> Self {
         Self {
             cache: Mutex::new(LruCache::new(capacity)),
+            status: FlagStatus::Enabled,
+        }
+    }
+
+    /// Returns a cache that never stores anything, so that every lookup misses
+    /// and each installation recompiles the module.
+    pub fn disabled() -> Self {
+        Self {
+            cache: Mutex::new(LruCache::new(NumBytes::new(0))),
+            status: FlagStatus::Disabled,
         }
     }
 
@@ -24,6 +36,9 @@ impl CompilationCache {
         canister_module: &CanisterModule,
         serialized_module: HypervisorResult<Arc<SerializedModule>>,
     ) {
+        if self.status == FlagStatus::Disabled {
+            return;
+        }
         self.cache
             .lock()
             .unwrap()
@@ -34,6 +49,9 @@ impl CompilationCache {
         &self,
         canister_module: &CanisterModule,
     ) -> Option<HypervisorResult<Arc<SerializedModule>>> {
+        if self.status == FlagStatus::Disabled {
+            return None;
+        }
         self.cache
             .lock()
             .unwrap()
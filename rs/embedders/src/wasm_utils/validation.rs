@@ -1090,21 +1090,29 @@ fn validate_custom_section(
                 name.to_string(),
                 CustomSection::new(visibility, payload.to_vec()),
             );
-
-            // Check that adding a new custom section does not exceed `max_custom_sections`
-            // allowed.
-            if validated_custom_sections.len() > config.max_custom_sections {
-                return Err(WasmValidationError::TooManyCustomSections {
-                    defined: validated_custom_sections.len(),
-                    allowed: config.max_custom_sections,
-                });
-            }
         }
     }
 
+    validate_custom_sections_count(validated_custom_sections.len(), config)?;
+
     Ok(WasmMetadata::new(validated_custom_sections))
 }
 
+/// Checks that a module defining `defined` custom sections with a visibility
+/// level does not exceed the `max_custom_sections` of the given config.
+pub fn validate_custom_sections_count(
+    defined: usize,
+    config: &EmbeddersConfig,
+) -> Result<(), WasmValidationError> {
+    if defined > config.max_custom_sections {
+        return Err(WasmValidationError::TooManyCustomSections {
+            defined,
+            allowed: config.max_custom_sections,
+        });
+    }
+    Ok(())
+}
+
 fn new_wasm_function_complexity(body: &Body<'_>) -> Complexity {
     use Operator::*;
 
@@ -700,6 +700,40 @@ fn can_reject_module_with_too_many_custom_sections() {
     );
 }
 
+#[test]
+fn can_raise_the_custom_sections_limit() {
+    let mut module = wasm_encoder::Module::new();
+    let names: Vec<_> = (0..17).map(|i| format!("icp:public name{}", i)).collect();
+    for name in &names {
+        module.section(&wasm_encoder::CustomSection {
+            name: Cow::Borrowed(name),
+            data: Cow::Borrowed(&[0, 1]),
+        });
+    }
+    let wasm = BinaryEncodedWasm::new(module.finish());
+
+    let err = validate_wasm_binary(&wasm, &EmbeddersConfig::default()).unwrap_err();
+    assert_eq!(
+        err,
+        WasmValidationError::TooManyCustomSections {
+            defined: 17,
+            allowed: 16
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Wasm module defined 17 custom sections with `icp:public` or `icp:private` visibility, \
+         which exceeds the maximum number allowed 16."
+    );
+
+    let validation_details = validate_wasm_binary(
+        &wasm,
+        &EmbeddersConfig::default().with_development_custom_section_limits(),
+    )
+    .unwrap();
+    assert_eq!(validation_details.wasm_metadata.custom_sections().len(), 17);
+}
+
 #[test]
 fn can_reject_module_with_custom_sections_too_big() {
     let content = vec![0, 1, 6, 5, 6, 7, 4, 6];
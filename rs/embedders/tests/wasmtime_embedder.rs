@@ -803,6 +803,59 @@ mod test {
         assert_eq!(stats.read_before_write_count, 1);
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn write_barrier_and_signal_handler_report_the_same_dirty_pages() {
+        let wat = r#"
+            (module
+                (import "ic0" "stable_grow"
+                    (func $ic0_stable_grow (param $pages i32) (result i32)))
+                (import "ic0" "stable_write"
+                    (func $ic0_stable_write (param $offset i32) (param $src i32) (param $size i32)))
+
+                (func $test (export "canister_update test")
+                    ;; dirty heap pages 0, 3 and 4, and read page 2 without writing it
+                    (i32.store (i32.const 10) (i32.const 72))
+                    (i32.store (i32.const 12288) (i32.const 101))
+                    (i64.store (i32.const 16380) (i64.const 108))
+                    (drop (i32.load (i32.const 8192)))
+
+                    (drop (call $ic0_stable_grow (i32.const 1)))
+                    ;; dirty stable pages 0, 1 and 7
+                    (call $ic0_stable_write (i32.const 4094) (i32.const 10) (i32.const 5))
+                    (call $ic0_stable_write (i32.const 28672) (i32.const 10) (i32.const 5))
+                )
+                (table funcref (elem $test))
+                (memory (export "memory") 5)
+            )"#;
+        let dirty_pages = WasmtimeInstanceBuilder::new()
+            .with_wat(wat)
+            .with_api_type(ic_system_api::ApiType::update(
+                mock_time(),
+                vec![],
+                Cycles::zero(),
+                PrincipalId::new_user_test_id(0),
+                0.into(),
+            ))
+            .dirty_pages_by_tracking(FuncRef::Method(WasmMethod::Update("test".to_string())));
+
+        assert_eq!(dirty_pages.write_barrier, dirty_pages.signal_handler);
+        assert_eq!(
+            dirty_pages.signal_handler.heap,
+            [0, 3, 4]
+                .into_iter()
+                .map(ic_replicated_state::PageIndex::new)
+                .collect()
+        );
+        assert_eq!(
+            dirty_pages.signal_handler.stable,
+            [0, 1, 7]
+                .into_iter()
+                .map(ic_replicated_state::PageIndex::new)
+                .collect()
+        );
+    }
+
     #[test]
     fn stable_write_and_read() {
         let wat = r#"
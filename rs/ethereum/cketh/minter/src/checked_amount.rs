@@ -84,6 +84,18 @@ impl<Unit> CheckedAmountOf<Unit> {
     pub fn as_f64(&self) -> f64 {
         self.0.as_f64()
     }
+
+    /// Returns the same amount in another unit, e.g., to use the pending transaction count
+    /// of an address as the nonce of its next transaction.
+    /// ```
+    /// use ic_cketh_minter::numeric::{TransactionCount, TransactionNonce};
+    ///
+    /// let count = TransactionCount::from(0x3d8_u32);
+    /// assert_eq!(count.change_units(), TransactionNonce::from(0x3d8_u32));
+    /// ```
+    pub fn change_units<NewUnit>(self) -> CheckedAmountOf<NewUnit> {
+        CheckedAmountOf::<NewUnit>::from_inner(self.0)
+    }
 }
 
 macro_rules! impl_from {
@@ -189,7 +201,7 @@ impl<Unit> Serialize for CheckedAmountOf<Unit> {
 
 impl<'de, Unit> Deserialize<'de> for CheckedAmountOf<Unit> {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        ethnum::u256::deserialize(deserializer).map(Self::from_inner)
+        crate::serde_quantity::deserialize(deserializer).map(Self::from_inner)
     }
 }
 
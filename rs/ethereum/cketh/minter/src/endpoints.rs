@@ -1,11 +1,15 @@
-use crate::eth_rpc::into_nat;
-use crate::transactions::EthWithdrawalRequest;
+use crate::eth_logs::ReceivedEthEvent;
+use crate::eth_rpc::{into_nat, HttpOutcallError, JsonRpcResult};
+use crate::eth_rpc_client::MultiCallError;
+use crate::numeric::Wei;
+use crate::state;
+use crate::transactions::{self, EthWithdrawalRequest};
 use crate::tx::TransactionPrice;
-use candid::{CandidType, Deserialize, Nat};
+use candid::{CandidType, Deserialize, Nat, Principal};
 use icrc_ledger_types::icrc2::transfer_from::TransferFromError;
 use minicbor::{Decode, Encode};
 use serde::Serialize;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct Eip1559TransactionPrice {
@@ -25,6 +29,140 @@ impl From<TransactionPrice> for Eip1559TransactionPrice {
         }
     }
 }
+/// Compares the ckETH in circulation against the ETH held by the minter.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Solvency {
+    /// ETH balance of the minter's address, in wei.
+    pub eth_balance: Nat,
+    /// Total supply of ckETH, in wei.
+    pub cketh_supply: Nat,
+    /// Withdrawals whose ckETH was burned but whose transaction is not confirmed yet, in wei.
+    pub pending_withdrawals: Nat,
+    pub balance: SolvencyBalance,
+}
+
+/// How far the scanning of deposits lags behind the latest block.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScanningProgress {
+    pub last_scanned_block: Nat,
+    pub latest_block: Nat,
+    pub blocks_behind: Nat,
+    pub healthy: bool,
+}
+
+impl From<state::ScanningProgress> for ScanningProgress {
+    fn from(progress: state::ScanningProgress) -> Self {
+        Self {
+            last_scanned_block: progress.last_scanned_block.into(),
+            latest_block: progress.latest_block.into(),
+            blocks_behind: progress.blocks_behind.into(),
+            healthy: progress.healthy,
+        }
+    }
+}
+
+/// Difference between the ETH held by the minter and its liabilities, i.e.,
+/// the ckETH supply and the pending withdrawals.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum SolvencyBalance {
+    Surplus(Nat),
+    Deficit(Nat),
+}
+
+impl Solvency {
+    pub fn new(eth_balance: Wei, cketh_supply: Wei, pending_withdrawals: Wei) -> Self {
+        let liabilities = cketh_supply
+            .checked_add(pending_withdrawals)
+            .expect("BUG: ckETH liabilities overflow");
+        let balance = match eth_balance.checked_sub(liabilities) {
+            Some(surplus) => SolvencyBalance::Surplus(surplus.into()),
+            None => SolvencyBalance::Deficit(
+                liabilities
+                    .checked_sub(eth_balance)
+                    .expect("BUG: liabilities exceed balance")
+                    .into(),
+            ),
+        };
+        Self {
+            eth_balance: eth_balance.into(),
+            cketh_supply: cketh_supply.into(),
+            pending_withdrawals: pending_withdrawals.into(),
+            balance,
+        }
+    }
+}
+
+/// A deposit that the minter will credit, see `preview_deposits`.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingDeposit {
+    pub transaction_hash: String,
+    pub block_number: Nat,
+    pub log_index: Nat,
+    pub from_address: String,
+    /// Amount of ckWei that will be minted.
+    pub value: Nat,
+    pub principal: Principal,
+}
+
+/// The Ethereum deposit credited by a ckETH mint.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MintSource {
+    pub transaction_hash: String,
+    pub block_number: Nat,
+    pub log_index: Nat,
+}
+
+impl From<&ReceivedEthEvent> for MintSource {
+    fn from(event: &ReceivedEthEvent) -> Self {
+        Self {
+            transaction_hash: event.transaction_hash.to_string(),
+            block_number: event.block_number.into(),
+            log_index: event.log_index.into(),
+        }
+    }
+}
+
+impl From<ReceivedEthEvent> for PendingDeposit {
+    fn from(event: ReceivedEthEvent) -> Self {
+        Self {
+            transaction_hash: event.transaction_hash.to_string(),
+            block_number: event.block_number.into(),
+            log_index: event.log_index.into(),
+            from_address: event.from_address.to_string(),
+            value: event.value.into(),
+            principal: event.principal,
+        }
+    }
+}
+
+/// A transaction issued by the minter that was mined but whose execution failed,
+/// see `failed_transactions`.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FailedTransaction {
+    pub ledger_burn_index: Nat,
+    pub nonce: Nat,
+    pub transaction_hash: String,
+    pub block_number: Nat,
+    pub gas_used: Nat,
+    /// Price paid for each unit of gas, in wei.
+    pub effective_gas_price: Nat,
+    pub revert_reason: Option<String>,
+}
+
+impl From<&transactions::FailedTransaction> for FailedTransaction {
+    fn from(tx: &transactions::FailedTransaction) -> Self {
+        Self {
+            ledger_burn_index: Nat::from(tx.ledger_burn_index.get()),
+            nonce: tx.nonce.into(),
+            transaction_hash: tx.transaction_hash.to_string(),
+            block_number: tx.block_number.into(),
+            gas_used: into_nat(tx.gas_used),
+            effective_gas_price: tx.effective_gas_price.into(),
+            revert_reason: tx.revert_reason.clone(),
+        }
+    }
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct EthTransaction {
     pub transaction_hash: String,
@@ -85,6 +223,212 @@ impl Display for RetrieveEthStatus {
     }
 }
 
+/// Error returned to clients when the minter could not get a consistent answer
+/// from the Ethereum JSON-RPC providers.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EndpointError {
+    pub code: EndpointErrorCode,
+    /// Human-readable details, for troubleshooting only.
+    pub message: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointErrorCode {
+    /// The HTTPS outcalls to all providers failed in the same way, e.g. because the providers
+    /// are unreachable. Retrying the request later may succeed.
+    ProvidersUnavailable,
+    /// All providers rejected the JSON-RPC request with the same error.
+    JsonRpcError,
+    /// The providers returned different results, so that none of them can be trusted.
+    InconsistentResults,
+}
+
+impl<T: Debug> From<MultiCallError<T>> for EndpointError {
+    fn from(error: MultiCallError<T>) -> Self {
+        match error {
+            MultiCallError::ConsistentHttpOutcallError(error) => Self {
+                code: EndpointErrorCode::ProvidersUnavailable,
+                message: http_outcall_error_message(&error),
+            },
+            MultiCallError::ConsistentJsonRpcError { code, message } => Self {
+                code: EndpointErrorCode::JsonRpcError,
+                message: format!("JSON-RPC error {code}: {message}"),
+            },
+            MultiCallError::InconsistentResults(results) => Self {
+                code: EndpointErrorCode::InconsistentResults,
+                message: format!("inconsistent results from the JSON-RPC providers: {results:?}"),
+            },
+        }
+    }
+}
+
+/// Describes an HTTPS outcall error without the response body,
+/// which may be arbitrarily large and is not needed to troubleshoot.
+fn http_outcall_error_message(error: &HttpOutcallError) -> String {
+    match error {
+        HttpOutcallError::IcError { code, message } => {
+            format!("HTTPS outcall failed: {code:?} {message}")
+        }
+        HttpOutcallError::InvalidHttpJsonRpcResponse {
+            status,
+            parsing_error,
+            ..
+        } => format!(
+            "invalid JSON-RPC response with status {status}{}",
+            parsing_error
+                .as_ref()
+                .map(|e| format!(": {e}"))
+                .unwrap_or_default()
+        ),
+        HttpOutcallError::UnexpectedJsonRpcResponseId { expected, actual } => {
+            format!("unexpected JSON-RPC response id: expected {expected}, got {actual}")
+        }
+        HttpOutcallError::Timeout { after } => format!("no response after {after:?}"),
+    }
+}
+
+/// The last time the minter could not get a consistent answer from the JSON-RPC providers,
+/// see `last_rpc_failure`.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RpcFailure {
+    /// The JSON-RPC method that was called, e.g. `eth_getLogs`.
+    pub method: String,
+    /// Time of the failure in nanoseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub error: MultiRpcError,
+}
+
+impl RpcFailure {
+    pub fn new<T: Debug>(method: &str, timestamp: u64, error: &MultiCallError<T>) -> Self {
+        Self {
+            method: method.to_string(),
+            timestamp,
+            error: MultiRpcError::from(error),
+        }
+    }
+}
+
+/// Candid representation of the errors of calls to multiple JSON-RPC providers.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum MultiRpcError {
+    /// The HTTPS outcalls to all providers failed in the same way.
+    ConsistentHttpOutcallError(String),
+    /// All providers rejected the JSON-RPC request with the same error.
+    ConsistentJsonRpcError { code: i64, message: String },
+    /// The providers returned different results, listed by provider.
+    InconsistentResults(Vec<ProviderResult>),
+}
+
+/// What a single JSON-RPC provider returned.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProviderResult {
+    /// URL of the provider, with any API key redacted.
+    pub url: String,
+    pub result: ProviderOutcome,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ProviderOutcome {
+    /// Debug representation of the returned value.
+    Ok(String),
+    JsonRpcError {
+        code: i64,
+        message: String,
+    },
+    HttpOutcallError(String),
+}
+
+impl<T: Debug> From<&MultiCallError<T>> for MultiRpcError {
+    fn from(error: &MultiCallError<T>) -> Self {
+        match error {
+            MultiCallError::ConsistentHttpOutcallError(error) => {
+                Self::ConsistentHttpOutcallError(http_outcall_error_message(error))
+            }
+            MultiCallError::ConsistentJsonRpcError { code, message } => {
+                Self::ConsistentJsonRpcError {
+                    code: *code,
+                    message: message.clone(),
+                }
+            }
+            MultiCallError::InconsistentResults(results) => Self::InconsistentResults(
+                results
+                    .iter()
+                    .map(|(provider, result)| ProviderResult {
+                        url: redact_url(provider.url()),
+                        result: match result {
+                            Ok(JsonRpcResult::Result(value)) => {
+                                ProviderOutcome::Ok(format!("{value:?}"))
+                            }
+                            Ok(JsonRpcResult::Error { code, message }) => {
+                                ProviderOutcome::JsonRpcError {
+                                    code: *code,
+                                    message: message.clone(),
+                                }
+                            }
+                            Err(error) => {
+                                ProviderOutcome::HttpOutcallError(http_outcall_error_message(error))
+                            }
+                        },
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Hides the credentials that may be embedded in a provider URL, so that it can be shown
+/// publicly: the user info, the values of the query parameters, and the path segments that
+/// look like API keys, i.e., long tokens containing digits.
+pub(crate) fn redact_url(url: &str) -> String {
+    const REDACTED: &str = "[REDACTED]";
+
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (format!("{scheme}://"), rest),
+        None => (String::new(), url),
+    };
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    let host = match authority.rsplit_once('@') {
+        Some((_user_info, host)) => format!("{REDACTED}@{host}"),
+        None => authority.to_string(),
+    };
+    let path = path
+        .split('/')
+        .map(|segment| {
+            let looks_like_key = segment.len() >= 20
+                && segment.chars().any(|c| c.is_ascii_digit())
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if looks_like_key {
+                REDACTED
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    let query = query
+        .map(|query| {
+            let params: Vec<_> = query
+                .split('&')
+                .map(|param| match param.split_once('=') {
+                    Some((name, _value)) => format!("{name}={REDACTED}"),
+                    None => param.to_string(),
+                })
+                .collect();
+            format!("?{}", params.join("&"))
+        })
+        .unwrap_or_default();
+    format!("{scheme}{host}{path}{query}")
+}
+
 #[derive(CandidType)]
 pub enum WithdrawalError {
     AmountTooLow { min_withdrawal_amount: Nat },
@@ -3,20 +3,78 @@ mod tests;
 
 use crate::address::Address;
 use crate::eth_rpc::{FixedSizeData, Hash, LogEntry};
-use crate::eth_rpc_client::EthRpcClient;
+use crate::eth_rpc_client::{EthRpcClient, MultiCallError};
 use crate::logs::{DEBUG, INFO};
+use crate::metrics::mutate_metrics;
 use crate::numeric::{BlockNumber, LogIndex, Wei};
 use crate::state::{read_state, State};
-use candid::Principal;
+use candid::{CandidType, Principal};
 use hex_literal::hex;
 use ic_canister_log::log;
 use minicbor::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+/// Certain RPC providers require that the number of blocks queried by `eth_getLogs`
+/// is no greater than 1024.
+const MAX_BLOCK_SPREAD: u64 = 1024;
+
 pub(crate) const RECEIVED_ETH_EVENT_TOPIC: [u8; 32] =
     hex!("257e057bb61920d8d0ed2cb7b720ac7f9c513cd1110bc9fa543079154f45f435");
 
+/// The deposit events emitted by the helper smart contract that the minter scrapes.
+/// Configurable so that the minter can be migrated to a helper contract emitting different events.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositEventConfig {
+    /// Hash of the event signature, i.e. the first topic of the event log entries.
+    pub topic: FixedSizeData,
+    /// How to decode the log entries of the event.
+    pub abi: DepositEventAbi,
+}
+
+impl Default for DepositEventConfig {
+    fn default() -> Self {
+        Self {
+            topic: FixedSizeData(RECEIVED_ETH_EVENT_TOPIC),
+            abi: DepositEventAbi::ReceivedEth,
+        }
+    }
+}
+
+impl DepositEventConfig {
+    /// Decodes the log entries of the configured deposit event.
+    /// Log entries whose first topic is not the configured one are ignored.
+    pub fn parse_log_entries(
+        &self,
+        entries: Vec<LogEntry>,
+    ) -> (Vec<ReceivedEthEvent>, Vec<ReceivedEthEventError>) {
+        let (ok, not_ok): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .filter(|entry| entry.topics.first() == Some(&self.topic))
+            .map(|entry| match self.abi {
+                DepositEventAbi::ReceivedEth => ReceivedEthEvent::try_from(entry),
+            })
+            .partition(Result::is_ok);
+        let valid_transactions: Vec<ReceivedEthEvent> =
+            ok.into_iter().map(Result::unwrap).collect();
+        let errors: Vec<ReceivedEthEventError> =
+            not_ok.into_iter().map(Result::unwrap_err).collect();
+        (valid_transactions, errors)
+    }
+}
+
+/// The supported layouts of deposit events.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, CandidType, Encode, Decode,
+)]
+#[cbor(index_only)]
+pub enum DepositEventAbi {
+    /// `event ReceivedEth(address indexed from, uint256 value, bytes32 indexed principal)`
+    #[default]
+    #[n(0)]
+    ReceivedEth,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
 pub struct ReceivedEthEvent {
     #[n(0)]
@@ -73,11 +131,38 @@ impl ReceivedEthEvent {
     }
 }
 
+/// Why a range of blocks is scanned for deposit events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogScan {
+    /// Regular scan of the blocks following the last scraped block.
+    Forward,
+    /// Explicit rescan of already scraped blocks requested by a controller,
+    /// to recover deposits that were missed.
+    Reprocess,
+}
+
+/// Returns true if a scan starting at block `from` does not reprocess blocks up to
+/// `last_scraped_block_number`, whose deposits were already processed.
+/// Only a [`LogScan::Reprocess`] may go back below the cursor.
+pub fn is_scan_allowed(
+    last_scraped_block_number: BlockNumber,
+    from: BlockNumber,
+    scan: LogScan,
+) -> bool {
+    match scan {
+        LogScan::Forward => from > last_scraped_block_number,
+        LogScan::Reprocess => true,
+    }
+}
+
+/// Fetches the deposit events emitted by the helper smart contract between the blocks `from` and
+/// `to` (inclusive), querying at most [`MAX_BLOCK_SPREAD`] blocks per `eth_getLogs` call.
 pub async fn last_received_eth_events(
     contract_address: Address,
     from: BlockNumber,
     to: BlockNumber,
-) -> (Vec<ReceivedEthEvent>, Vec<ReceivedEthEventError>) {
+    scan: LogScan,
+) -> Result<(Vec<ReceivedEthEvent>, Vec<ReceivedEthEventError>), MultiCallError<Vec<LogEntry>>> {
     use crate::eth_rpc::GetLogsParam;
 
     if from > to {
@@ -87,23 +172,36 @@ pub async fn last_received_eth_events(
         ));
     }
 
+    let last_scraped_block_number = read_state(|s| s.last_scraped_block_number);
+    if !is_scan_allowed(last_scraped_block_number, from, scan) {
+        log!(
+            INFO,
+            "[last_received_eth_events]: ignoring stale scan of blocks {:?} to {:?}: logs up to block {:?} were already processed",
+            from,
+            to,
+            last_scraped_block_number
+        );
+        return Ok((vec![], vec![]));
+    }
+
+    let deposit_event = read_state(|s| s.deposit_event.clone());
     let result: Vec<LogEntry> = read_state(EthRpcClient::from_state)
-        .eth_get_logs(GetLogsParam {
-            from_block: from.into(),
-            to_block: to.into(),
-            address: vec![contract_address],
-            topics: vec![FixedSizeData(RECEIVED_ETH_EVENT_TOPIC)],
-        })
-        .await
-        .expect("HTTP call failed");
+        .eth_get_logs_in_chunks(
+            GetLogsParam {
+                from_block: from.into(),
+                to_block: to.into(),
+                address: vec![contract_address],
+                topics: vec![deposit_event.topic.clone()],
+            },
+            from,
+            to,
+            MAX_BLOCK_SPREAD,
+            read_state(State::reduction_strategy),
+        )
+        .await?;
+    mutate_metrics(|m| m.eth_logs_scanned += result.len() as u64);
 
-    let (ok, not_ok): (Vec<_>, Vec<_>) = result
-        .into_iter()
-        .map(ReceivedEthEvent::try_from)
-        .partition(Result::is_ok);
-    let valid_transactions: Vec<ReceivedEthEvent> = ok.into_iter().map(Result::unwrap).collect();
-    let errors: Vec<ReceivedEthEventError> = not_ok.into_iter().map(Result::unwrap_err).collect();
-    (valid_transactions, errors)
+    Ok(deposit_event.parse_log_entries(result))
 }
 
 pub fn report_transaction_error(state: &mut State, error: ReceivedEthEventError) {
@@ -30,6 +30,39 @@ mod mint_transaction {
         );
     }
 
+    #[test]
+    fn should_find_deposit_of_mint() {
+        let mut state = dummy_state();
+        let first = received_eth_event();
+        let second = ReceivedEthEvent {
+            log_index: LogIndex::from(30_u8),
+            ..received_eth_event()
+        };
+        for (event, block_index) in [(&first, 7_u64), (&second, 8_u64)] {
+            state.record_event_to_mint(event.clone());
+            state.record_successful_mint(MintedEvent {
+                deposit_event: event.clone(),
+                mint_block_index: LedgerMintIndex::new(block_index),
+            });
+        }
+
+        let minted = state
+            .find_minted_event(LedgerMintIndex::new(8))
+            .expect("mint should be found");
+        assert_eq!(
+            minted.deposit_event.transaction_hash,
+            second.transaction_hash
+        );
+        assert_eq!(minted.deposit_event.log_index, LogIndex::from(30_u8));
+        assert_eq!(
+            state
+                .find_minted_event(LedgerMintIndex::new(7))
+                .map(|minted| minted.source()),
+            Some(first.source())
+        );
+        assert_eq!(state.find_minted_event(LedgerMintIndex::new(9)), None);
+    }
+
     #[test]
     fn should_allow_minting_events_with_equal_txhash() {
         let mut state = dummy_state();
@@ -99,6 +132,133 @@ mod mint_transaction {
         assert_eq!(state.invalid_events[&event.source()], error);
     }
 
+    #[test]
+    fn should_preview_deposits_that_will_be_minted() {
+        let mut state = dummy_state();
+        let minted = ReceivedEthEvent {
+            log_index: LogIndex::from(1_u8),
+            ..received_eth_event()
+        };
+        let to_mint = ReceivedEthEvent {
+            log_index: LogIndex::from(2_u8),
+            ..received_eth_event()
+        };
+        let invalid = ReceivedEthEvent {
+            log_index: LogIndex::from(3_u8),
+            ..received_eth_event()
+        };
+        let not_scraped_yet = ReceivedEthEvent {
+            log_index: LogIndex::from(4_u8),
+            ..received_eth_event()
+        };
+        state.record_event_to_mint(minted.clone());
+        state.record_successful_mint(MintedEvent {
+            deposit_event: minted.clone(),
+            mint_block_index: LedgerMintIndex::new(1),
+        });
+        state.record_event_to_mint(to_mint.clone());
+        state.record_invalid_deposit(
+            invalid.source(),
+            EventSourceError::InvalidEvent("bad".to_string()),
+        );
+
+        let preview = state.preview_deposits(vec![
+            minted,
+            to_mint.clone(),
+            invalid,
+            not_scraped_yet.clone(),
+        ]);
+
+        assert_eq!(
+            preview.iter().cloned().collect::<Vec<_>>(),
+            vec![to_mint.clone(), not_scraped_yet.clone()]
+        );
+        // Previewing does not record anything.
+        assert_eq!(
+            state.events_to_mint.iter().cloned().collect::<Vec<_>>(),
+            vec![to_mint.clone()]
+        );
+
+        // The real scraping and minting credit exactly the previewed deposits.
+        state.record_event_to_mint(not_scraped_yet);
+        assert_eq!(state.events_to_mint, preview);
+        for (index, event) in preview.iter().enumerate() {
+            state.record_successful_mint(MintedEvent {
+                deposit_event: event.clone(),
+                mint_block_index: LedgerMintIndex::new(2 + index as u64),
+            });
+        }
+        for event in &preview {
+            assert_eq!(
+                state
+                    .minted_events
+                    .get(&event.source())
+                    .map(|minted| &minted.deposit_event),
+                Some(event)
+            );
+        }
+        assert!(state.events_to_mint.is_empty());
+    }
+
+    #[test]
+    fn should_only_record_missed_deposits_when_reprocessing() {
+        let mut state = dummy_state();
+        let minted = ReceivedEthEvent {
+            log_index: LogIndex::from(1_u8),
+            ..received_eth_event()
+        };
+        let to_mint = ReceivedEthEvent {
+            log_index: LogIndex::from(2_u8),
+            ..received_eth_event()
+        };
+        let invalid = ReceivedEthEvent {
+            log_index: LogIndex::from(3_u8),
+            ..received_eth_event()
+        };
+        let missed = ReceivedEthEvent {
+            log_index: LogIndex::from(4_u8),
+            ..received_eth_event()
+        };
+        state.record_event_to_mint(minted.clone());
+        state.record_successful_mint(MintedEvent {
+            deposit_event: minted.clone(),
+            mint_block_index: LedgerMintIndex::new(1),
+        });
+        state.record_event_to_mint(to_mint.clone());
+        state.record_invalid_deposit(
+            invalid.source(),
+            EventSourceError::InvalidEvent("bad".to_string()),
+        );
+        let processed_range = vec![minted, to_mint.clone(), invalid];
+
+        // Reprocessing an already processed range mints nothing.
+        assert_eq!(
+            state.record_missed_deposits(processed_range.clone()),
+            vec![]
+        );
+        assert_eq!(
+            state.events_to_mint.iter().cloned().collect::<Vec<_>>(),
+            vec![to_mint.clone()]
+        );
+
+        let mut range_with_missed_deposit = processed_range;
+        range_with_missed_deposit.push(missed.clone());
+        assert_eq!(
+            state.record_missed_deposits(range_with_missed_deposit.clone()),
+            vec![missed.clone()]
+        );
+        assert_eq!(
+            state.events_to_mint.iter().cloned().collect::<Vec<_>>(),
+            vec![to_mint, missed]
+        );
+
+        // Reprocessing the same range again is idempotent.
+        assert_eq!(
+            state.record_missed_deposits(range_with_missed_deposit),
+            vec![]
+        );
+    }
+
     #[test]
     fn should_have_readable_debug_representation() {
         let expected = "ReceivedEthEvent { \
@@ -123,6 +283,7 @@ mod mint_transaction {
             ethereum_block_height: Default::default(),
             minimum_withdrawal_amount: wei_from_milli_ether(10).into(),
             next_transaction_nonce: Default::default(),
+            min_provider_agreement: None,
         })
         .expect("init args should be valid")
     }
@@ -264,3 +425,109 @@ mod parse_principal_from_slice {
         principal_bytes
     }
 }
+
+mod deposit_event_config {
+    use crate::eth_logs::{DepositEventAbi, DepositEventConfig, ReceivedEthEvent};
+    use crate::eth_rpc::{FixedSizeData, LogEntry};
+    use std::str::FromStr;
+
+    const NEW_TOPIC: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+
+    #[test]
+    fn should_decode_only_logs_matching_configured_topic() {
+        let config = DepositEventConfig {
+            topic: FixedSizeData::from_str(NEW_TOPIC).unwrap(),
+            abi: DepositEventAbi::ReceivedEth,
+        };
+        let matching_entry = log_entry(NEW_TOPIC, "0x27");
+        let other_entry = log_entry(
+            "0x257e057bb61920d8d0ed2cb7b720ac7f9c513cd1110bc9fa543079154f45f435",
+            "0x28",
+        );
+
+        let (events, errors) = config.parse_log_entries(vec![other_entry, matching_entry.clone()]);
+
+        assert_eq!(
+            events,
+            vec![ReceivedEthEvent::try_from(matching_entry).unwrap()]
+        );
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn should_match_received_eth_event_by_default() {
+        let default_entry = log_entry(
+            "0x257e057bb61920d8d0ed2cb7b720ac7f9c513cd1110bc9fa543079154f45f435",
+            "0x27",
+        );
+
+        let (events, errors) = DepositEventConfig::default()
+            .parse_log_entries(vec![default_entry, log_entry(NEW_TOPIC, "0x28")]);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(errors, vec![]);
+    }
+
+    fn log_entry(topic: &str, log_index: &str) -> LogEntry {
+        serde_json::from_str(&format!(
+            r#"{{
+            "address": "0xb44b5e756a894775fc32eddf3314bb1b1944dc34",
+            "topics": [
+                "{topic}",
+                "0x000000000000000000000000dd2851cdd40ae6536831558dd46db62fac7a844d",
+                "0x09efcdab00000000000100000000000000000000000000000000000000000000"
+            ],
+            "data": "0x000000000000000000000000000000000000000000000000002386f26fc10000",
+            "blockNumber": "0x3ca487",
+            "transactionHash": "0x705f826861c802b407843e99af986cfde8749b669e5e0a5a150f4350bcaa9bc3",
+            "transactionIndex": "0x22",
+            "blockHash": "0x8436209a391f7bc076123616ecb229602124eb6c1007f5eae84df8e098885d3c",
+            "logIndex": "{log_index}",
+            "removed": false
+        }}"#
+        ))
+        .unwrap()
+    }
+}
+
+mod scan_cursor {
+    use crate::eth_logs::{is_scan_allowed, LogScan};
+    use crate::numeric::BlockNumber;
+
+    const LAST_SCRAPED_BLOCK_NUMBER: BlockNumber = BlockNumber::new(3_956_206);
+
+    #[test]
+    fn should_ignore_stale_rescan() {
+        for from in [
+            BlockNumber::new(0),
+            BlockNumber::new(3_956_205),
+            LAST_SCRAPED_BLOCK_NUMBER,
+        ] {
+            assert!(!is_scan_allowed(
+                LAST_SCRAPED_BLOCK_NUMBER,
+                from,
+                LogScan::Forward
+            ));
+        }
+    }
+
+    #[test]
+    fn should_proceed_with_forward_scan() {
+        for from in [BlockNumber::new(3_956_207), BlockNumber::new(4_000_000)] {
+            assert!(is_scan_allowed(
+                LAST_SCRAPED_BLOCK_NUMBER,
+                from,
+                LogScan::Forward
+            ));
+        }
+    }
+
+    #[test]
+    fn should_allow_rescan_when_reprocessing() {
+        assert!(is_scan_allowed(
+            LAST_SCRAPED_BLOCK_NUMBER,
+            BlockNumber::new(3_956_000),
+            LogScan::Reprocess
+        ));
+    }
+}
@@ -6,8 +6,9 @@ use crate::endpoints::CandidBlockTag;
 use crate::eth_rpc_client::responses::TransactionReceipt;
 use crate::eth_rpc_error::{sanitize_send_raw_transaction_result, Parser};
 use crate::logs::{DEBUG, TRACE_HTTP};
-use crate::numeric::{BlockNumber, LogIndex, TransactionCount, TransactionNonce, Wei};
-use crate::state::{mutate_state, State};
+use crate::metrics::mutate_metrics;
+use crate::numeric::{BlockNumber, ChainId, LogIndex, TransactionCount, TransactionNonce, Wei};
+use crate::state::{mutate_state, read_state, State};
 use candid::{candid_method, CandidType, Principal};
 use ethnum;
 use ic_canister_log::log;
@@ -19,8 +20,10 @@ use ic_cdk::api::management_canister::http_request::{
 use ic_cdk_macros::query;
 use minicbor::{Decode, Encode};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter, LowerHex, UpperHex};
+use std::time::Duration;
 
 #[cfg(test)]
 mod tests;
@@ -38,6 +41,15 @@ const HTTP_MAX_SIZE: u64 = 2_000_000;
 
 pub const MAX_PAYLOAD_SIZE: u64 = HTTP_MAX_SIZE - HEADER_SIZE_LIMIT;
 
+// Number of nodes of the subnet the minter is deployed on, which determines the price of HTTPS outcalls.
+const SUBNET_SIZE: u128 = 34;
+
+// Upper bound on the size of the requests sent by the minter (URL, headers, JSON-RPC payload
+// and transform context), used to quote outcalls before the request is built.
+const REQUEST_SIZE_ESTIMATE: u64 = 1024;
+
+pub type Cycles = u128;
+
 pub type Quantity = ethnum::u256;
 
 pub fn into_nat(quantity: Quantity) -> candid::Nat {
@@ -180,6 +192,7 @@ pub struct Transaction {
     pub block_number: Option<BlockNumber>,
 
     /// Gas provided by the sender.
+    #[serde(deserialize_with = "crate::serde_quantity::deserialize")]
     pub gas: Quantity,
 
     /// Gas price provided by the sender in Wei.
@@ -205,6 +218,10 @@ pub struct Transaction {
 
     /// Integer of the transactions index position in the block.
     /// None if the transaction is pending.
+    #[serde(
+        default,
+        deserialize_with = "crate::serde_quantity::deserialize_option"
+    )]
     pub transaction_index: Option<Quantity>,
 
     /// Value transferred in Wei.
@@ -246,6 +263,9 @@ pub enum BlockTag {
     /// See
     /// https://www.alchemy.com/overviews/ethereum-commitment-levels#what-are-ethereum-commitment-levels.
     Finalized,
+    /// The pending state, i.e., the latest block together with the transactions in the mempool.
+    /// Only meaningful for queries on an account's state, e.g., its transaction count.
+    Pending,
 }
 
 impl From<CandidBlockTag> for BlockTag {
@@ -287,11 +307,25 @@ impl std::str::FromStr for BlockSpec {
             "latest" => BlockTag::Latest,
             "safe" => BlockTag::Safe,
             "finalized" => BlockTag::Finalized,
+            "pending" => BlockTag::Pending,
             _ => return Err(format!("unknown block tag '{s}'")),
         }))
     }
 }
 
+impl TryFrom<BlockSpec> for BlockNumber {
+    type Error = BlockTag;
+
+    /// Returns the block number of the block specification,
+    /// or the block tag if the block is not specified by its number.
+    fn try_from(value: BlockSpec) -> Result<Self, Self::Error> {
+        match value {
+            BlockSpec::Number(block_number) => Ok(block_number),
+            BlockSpec::Tag(tag) => Err(tag),
+        }
+    }
+}
+
 /// Parameters of the [`eth_getLogs`](https://ethereum.org/en/developers/docs/apis/json-rpc/#eth_getlogs) call.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -498,6 +532,9 @@ pub enum ResponseTransform {
     TransactionReceipt,
     FeeHistory,
     SendRawTransaction,
+    /// Replies to a batch of requests: each reply is transformed with the given transform, if any,
+    /// and the replies are sorted by id since providers may return them in any order.
+    Batch(Option<Box<ResponseTransform>>),
 }
 
 impl ResponseTransform {
@@ -524,6 +561,25 @@ impl ResponseTransform {
             Self::SendRawTransaction => {
                 sanitize_send_raw_transaction_result(body_bytes, Parser::new())
             }
+            Self::Batch(reply_transform) => {
+                let mut replies: Vec<serde_json::Value> = match serde_json::from_slice(body_bytes) {
+                    Ok(replies) => replies,
+                    Err(_) => return,
+                };
+                if let Some(reply_transform) = reply_transform {
+                    for reply in replies.iter_mut() {
+                        let mut reply_bytes =
+                            serde_json::to_vec(reply).expect("BUG: failed to serialize reply");
+                        reply_transform.apply(&mut reply_bytes);
+                        if let Ok(transformed) = serde_json::from_slice(&reply_bytes) {
+                            *reply = transformed;
+                        }
+                    }
+                }
+                replies.sort_by_key(|reply| reply.get("id").and_then(serde_json::Value::as_u64));
+                *body_bytes =
+                    serde_json::to_vec(&replies).expect("BUG: failed to serialize replies");
+            }
         }
     }
 }
@@ -568,6 +624,28 @@ pub enum HttpOutcallError {
         body: String,
         parsing_error: Option<String>,
     },
+    /// The `id` of the JSON-RPC response does not match the `id` of the request,
+    /// which means that the response does not answer the request that was sent.
+    UnexpectedJsonRpcResponseId { expected: u64, actual: u64 },
+    /// The provider did not reply within the given time,
+    /// after which its response, if any, is ignored.
+    Timeout { after: Duration },
+}
+
+impl HttpOutcallError {
+    /// Whether the same request may succeed if retried, e.g. after a connection failure or
+    /// when the provider is temporarily overloaded. Errors caused by the content of the
+    /// response, such as a malformed body, are not expected to go away on retry.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HttpOutcallError::IcError { code, .. } => *code == RejectionCode::SysTransient,
+            HttpOutcallError::InvalidHttpJsonRpcResponse { status, .. } => {
+                *status == 429 || *status >= 500
+            }
+            HttpOutcallError::UnexpectedJsonRpcResponseId { .. } => false,
+            HttpOutcallError::Timeout { .. } => true,
+        }
+    }
 }
 
 pub type HttpOutcallResult<T> = Result<T, HttpOutcallError>;
@@ -582,7 +660,7 @@ pub fn are_errors_consistent<T: PartialEq>(
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ResponseSizeEstimate(u64);
 
 impl ResponseSizeEstimate {
@@ -598,7 +676,8 @@ impl ResponseSizeEstimate {
         self.0
     }
 
-    /// Returns a higher estimate for the payload size.
+    /// Returns a higher estimate for the payload size, e.g. to retry a call whose response
+    /// did not fit into the current estimate.
     pub fn adjust(self) -> Self {
         Self(self.0.max(1024).saturating_mul(2).min(MAX_PAYLOAD_SIZE))
     }
@@ -610,6 +689,25 @@ impl fmt::Display for ResponseSizeEstimate {
     }
 }
 
+/// Returns the cycles cost of a single HTTPS outcall, given the size of the request in bytes and
+/// the `max_response_bytes` of the response. Details of the pricing can be found here:
+/// https://internetcomputer.org/docs/current/developer-docs/gas-cost#special-features
+pub fn http_request_cost(request_size: u64, max_response_bytes: u64) -> Cycles {
+    (3_000_000 + 60_000 * SUBNET_SIZE) * SUBNET_SIZE
+        + 400 * SUBNET_SIZE * request_size as u128
+        + 800 * SUBNET_SIZE * max_response_bytes as u128
+}
+
+/// Returns the cycles cost of querying `num_providers` providers, one HTTPS outcall each,
+/// with a response of at most `response_size_estimate` bytes (plus headers).
+pub fn outcall_cost(num_providers: usize, response_size_estimate: ResponseSizeEstimate) -> Cycles {
+    num_providers as u128
+        * http_request_cost(
+            REQUEST_SIZE_ESTIMATE,
+            response_size_estimate.get() + HEADER_SIZE_LIMIT,
+        )
+}
+
 pub trait HttpResponsePayload {
     fn response_transform() -> Option<ResponseTransform> {
         None
@@ -620,6 +718,8 @@ impl<T: HttpResponsePayload> HttpResponsePayload for Option<T> {}
 
 impl HttpResponsePayload for TransactionCount {}
 
+impl HttpResponsePayload for ChainId {}
+
 /// Calls a JSON-RPC method on an Ethereum node at the specified URL.
 pub async fn call<I, O>(
     url: impl Into<String>,
@@ -639,102 +739,268 @@ where
         id: 1,
     };
     let url = url.into();
+    response_size_estimate =
+        read_state(|s| s.response_size_estimate(&eth_method, response_size_estimate));
+    let transform = O::response_transform();
 
     loop {
         rpc_request.id = mutate_state(State::next_request_id);
         let payload = serde_json::to_string(&rpc_request).unwrap();
-        log!(
-            TRACE_HTTP,
-            "Calling url: {}, with payload: {payload}",
-            url.clone()
-        );
-
-        let effective_size_estimate = response_size_estimate.get() + HEADER_SIZE_LIMIT;
-        let transform_op = O::response_transform()
-            .as_ref()
-            .map(|t| {
-                let mut buf = vec![];
-                ciborium::ser::into_writer(t, &mut buf).unwrap();
-                buf
-            })
-            .unwrap_or_default();
-
-        let request = CanisterHttpRequestArgument {
-            url: url.clone(),
-            max_response_bytes: Some(effective_size_estimate),
-            method: HttpMethod::POST,
-            headers: vec![HttpHeader {
-                name: "Content-Type".to_string(),
-                value: "application/json".to_string(),
-            }],
-            body: Some(payload.as_bytes().to_vec()),
-            transform: Some(TransformContext::from_name(
-                "cleanup_response".to_owned(),
-                transform_op,
-            )),
+        let response = match http_outcall(&url, payload, transform.as_ref(), response_size_estimate)
+            .await
+        {
+            Ok(response) => response,
+            Err((code, message)) if is_response_too_large(code, &message) => {
+                let new_estimate = response_size_estimate.adjust();
+                if response_size_estimate == new_estimate {
+                    return Err(HttpOutcallError::IcError { code, message });
+                }
+                log!(DEBUG, "The {eth_method} response didn't fit into {response_size_estimate} bytes, retrying with {new_estimate}");
+                response_size_estimate = new_estimate;
+                continue;
+            }
+            Err((code, message)) => return Err(HttpOutcallError::IcError { code, message }),
         };
 
-        // Details of the values used in the following lines can be found here:
-        // https://internetcomputer.org/docs/current/developer-docs/production/computation-and-storage-costs
-        let base_cycles = 400_000_000u128 + 100_000u128 * (2 * effective_size_estimate as u128);
+        let response_sizes = mutate_metrics(|m| {
+            m.observe_response_size(&eth_method, response.body.len() as u64);
+            m.response_sizes.get(&eth_method).cloned()
+        });
+        if let Some(response_sizes) = response_sizes {
+            mutate_state(|s| s.tune_response_size_estimate(&eth_method, &response_sizes));
+        }
 
-        const BASE_SUBNET_SIZE: u128 = 13;
-        const SUBNET_SIZE: u128 = 34;
-        let cycles = base_cycles * SUBNET_SIZE / BASE_SUBNET_SIZE;
+        let http_status_code = successful_http_status_code(&response)?;
+        return parse_json_rpc_reply(rpc_request.id, http_status_code, &response.body);
+    }
+}
 
-        let response: HttpResponse = match call_with_payment128(
-            Principal::management_canister(),
-            "http_request",
-            (request,),
-            cycles,
+/// Calls a JSON-RPC method on an Ethereum node at the specified URL once for each of the given
+/// parameters, with a single HTTPS outcall carrying a JSON-RPC batch request, which is cheaper
+/// than one outcall per request.
+/// `response_size_estimate` is the estimate for the reply to a single request.
+///
+/// The returned results are aligned with `params_list`, i.e., the i-th element is the result for
+/// the i-th parameters. Replies are correlated with the requests by their `id` rather than by
+/// their position, since providers may return the replies of a batch in any order.
+pub async fn batch_call<I, O>(
+    url: impl Into<String>,
+    method: impl Into<String>,
+    params_list: Vec<I>,
+    response_size_estimate: ResponseSizeEstimate,
+) -> HttpOutcallResult<Vec<JsonRpcResult<O>>>
+where
+    I: Serialize,
+    O: DeserializeOwned + HttpResponsePayload,
+{
+    if params_list.is_empty() {
+        return Ok(vec![]);
+    }
+    let eth_method = method.into();
+    let url = url.into();
+    let rpc_requests: Vec<_> = params_list
+        .into_iter()
+        .map(|params| JsonRpcRequest {
+            jsonrpc: "2.0",
+            params,
+            method: eth_method.clone(),
+            id: mutate_state(State::next_request_id),
+        })
+        .collect();
+    let request_ids: Vec<u64> = rpc_requests.iter().map(|request| request.id).collect();
+    let payload = serde_json::to_string(&rpc_requests).unwrap();
+    let single_reply_estimate =
+        read_state(|s| s.response_size_estimate(&eth_method, response_size_estimate));
+    let mut response_size_estimate = ResponseSizeEstimate::new(
+        single_reply_estimate
+            .get()
+            .saturating_mul(request_ids.len() as u64)
+            .min(MAX_PAYLOAD_SIZE),
+    );
+    let transform = ResponseTransform::Batch(O::response_transform().map(Box::new));
+
+    let response = loop {
+        match http_outcall(
+            &url,
+            payload.clone(),
+            Some(&transform),
+            response_size_estimate,
         )
         .await
         {
-            Ok((response,)) => response,
-            Err((code, message))
-                if code == RejectionCode::SysFatal && message.contains("body size limit") =>
-            {
+            Ok(response) => break response,
+            Err((code, message)) if is_response_too_large(code, &message) => {
                 let new_estimate = response_size_estimate.adjust();
                 if response_size_estimate == new_estimate {
                     return Err(HttpOutcallError::IcError { code, message });
                 }
-                log!(DEBUG, "The {eth_method} response didn't fit into {response_size_estimate} bytes, retrying with {new_estimate}");
+                log!(DEBUG, "The batch of {} {eth_method} responses didn't fit into {response_size_estimate} bytes, retrying with {new_estimate}", request_ids.len());
                 response_size_estimate = new_estimate;
-                continue;
             }
             Err((code, message)) => return Err(HttpOutcallError::IcError { code, message }),
-        };
-
-        log!(
-            TRACE_HTTP,
-            "Got response: {} from url: {} with status: {}",
-            String::from_utf8_lossy(&response.body),
-            url,
-            response.status
-        );
-
-        // JSON-RPC responses over HTTP should have a 2xx status code,
-        // even if the contained JsonRpcResult is an error.
-        // If the server is not available, it will sometimes (wrongly) return HTML that will fail parsing as JSON.
-        let http_status_code = http_status_code(&response);
-        if !is_successful_http_code(&http_status_code) {
-            return Err(HttpOutcallError::InvalidHttpJsonRpcResponse {
-                status: http_status_code,
-                body: String::from_utf8_lossy(&response.body).to_string(),
-                parsing_error: None,
-            });
         }
+    };
 
-        let reply: JsonRpcReply<O> = serde_json::from_slice(&response.body).map_err(|e| {
-            HttpOutcallError::InvalidHttpJsonRpcResponse {
-                status: http_status_code,
-                body: String::from_utf8_lossy(&response.body).to_string(),
-                parsing_error: Some(e.to_string()),
-            }
-        })?;
+    // The estimates are tuned per reply, so the batch counts as one reply of the average size.
+    let average_reply_size = response.body.len() as u64 / request_ids.len() as u64;
+    let response_sizes = mutate_metrics(|m| {
+        m.observe_response_size(&eth_method, average_reply_size);
+        m.response_sizes.get(&eth_method).cloned()
+    });
+    if let Some(response_sizes) = response_sizes {
+        mutate_state(|s| s.tune_response_size_estimate(&eth_method, &response_sizes));
+    }
+
+    let http_status_code = successful_http_status_code(&response)?;
+    parse_json_rpc_batch_reply(&request_ids, http_status_code, &response.body)
+}
+
+/// Sends the given JSON-RPC payload to `url` in a single HTTPS outcall, paying for a response
+/// body of at most `response_size_estimate` bytes.
+async fn http_outcall(
+    url: &str,
+    payload: String,
+    transform: Option<&ResponseTransform>,
+    response_size_estimate: ResponseSizeEstimate,
+) -> Result<HttpResponse, (RejectionCode, String)> {
+    log!(TRACE_HTTP, "Calling url: {url}, with payload: {payload}");
+
+    let effective_size_estimate = response_size_estimate.get() + HEADER_SIZE_LIMIT;
+    let transform_op = transform
+        .map(|t| {
+            let mut buf = vec![];
+            ciborium::ser::into_writer(t, &mut buf).unwrap();
+            buf
+        })
+        .unwrap_or_default();
+    let transform_op_size = transform_op.len();
+
+    let request = CanisterHttpRequestArgument {
+        url: url.to_string(),
+        max_response_bytes: Some(effective_size_estimate),
+        method: HttpMethod::POST,
+        headers: vec![HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        }],
+        body: Some(payload.as_bytes().to_vec()),
+        transform: Some(TransformContext::from_name(
+            "cleanup_response".to_owned(),
+            transform_op,
+        )),
+    };
 
-        return Ok(reply.result);
-    }
+    let request_size = request.url.len()
+        + request
+            .headers
+            .iter()
+            .map(|header| header.name.len() + header.value.len())
+            .sum::<usize>()
+        + payload.len()
+        + "cleanup_response".len()
+        + transform_op_size;
+    let cycles = http_request_cost(request_size as u64, effective_size_estimate);
+
+    let (response,): (HttpResponse,) = call_with_payment128(
+        Principal::management_canister(),
+        "http_request",
+        (request,),
+        cycles,
+    )
+    .await?;
+
+    log!(
+        TRACE_HTTP,
+        "Got response: {} from url: {} with status: {}",
+        String::from_utf8_lossy(&response.body),
+        url,
+        response.status
+    );
+    Ok(response)
+}
+
+/// Whether the HTTPS outcall was rejected because the response exceeded `max_response_bytes`.
+fn is_response_too_large(code: RejectionCode, message: &str) -> bool {
+    code == RejectionCode::SysFatal && message.contains("body size limit")
+}
+
+/// Returns the status code of the HTTP response if it is successful.
+/// JSON-RPC responses over HTTP should have a 2xx status code,
+/// even if the contained JsonRpcResult is an error.
+/// If the server is not available, it will sometimes (wrongly) return HTML that will fail parsing as JSON.
+fn successful_http_status_code(response: &HttpResponse) -> HttpOutcallResult<u16> {
+    let http_status_code = http_status_code(response);
+    if !is_successful_http_code(&http_status_code) {
+        return Err(HttpOutcallError::InvalidHttpJsonRpcResponse {
+            status: http_status_code,
+            body: String::from_utf8_lossy(&response.body).to_string(),
+            parsing_error: None,
+        });
+    }
+    Ok(http_status_code)
+}
+
+/// Parses the body of a successful HTTP response as the reply to the JSON-RPC request
+/// with the given `id`.
+fn parse_json_rpc_reply<O: DeserializeOwned>(
+    request_id: u64,
+    http_status_code: u16,
+    body: &[u8],
+) -> HttpOutcallResult<JsonRpcResult<O>> {
+    let reply: JsonRpcReply<O> =
+        serde_json::from_slice(body).map_err(|e| HttpOutcallError::InvalidHttpJsonRpcResponse {
+            status: http_status_code,
+            body: String::from_utf8_lossy(body).to_string(),
+            parsing_error: Some(e.to_string()),
+        })?;
+    if reply.id != request_id {
+        return Err(HttpOutcallError::UnexpectedJsonRpcResponseId {
+            expected: request_id,
+            actual: reply.id,
+        });
+    }
+    Ok(reply.result)
+}
+
+/// Parses the body of a successful HTTP response as the replies to a batch of JSON-RPC requests
+/// with the given ids, in any order. The results are returned in the order of `request_ids`.
+/// Each request must have exactly one reply.
+fn parse_json_rpc_batch_reply<O: DeserializeOwned>(
+    request_ids: &[u64],
+    http_status_code: u16,
+    body: &[u8],
+) -> HttpOutcallResult<Vec<JsonRpcResult<O>>> {
+    let invalid_reply = |parsing_error: String| HttpOutcallError::InvalidHttpJsonRpcResponse {
+        status: http_status_code,
+        body: String::from_utf8_lossy(body).to_string(),
+        parsing_error: Some(parsing_error),
+    };
+    let replies: Vec<JsonRpcReply<O>> =
+        serde_json::from_slice(body).map_err(|e| invalid_reply(e.to_string()))?;
+    let mut results: BTreeMap<u64, JsonRpcResult<O>> = BTreeMap::new();
+    for reply in replies {
+        if !request_ids.contains(&reply.id) || results.contains_key(&reply.id) {
+            let expected = request_ids
+                .iter()
+                .find(|id| !results.contains_key(*id))
+                .or(request_ids.first())
+                .copied()
+                .unwrap_or_default();
+            return Err(HttpOutcallError::UnexpectedJsonRpcResponseId {
+                expected,
+                actual: reply.id,
+            });
+        }
+        results.insert(reply.id, reply.result);
+    }
+    request_ids
+        .iter()
+        .map(|id| {
+            results
+                .remove(id)
+                .ok_or_else(|| invalid_reply(format!("missing reply to request with id {id}")))
+        })
+        .collect()
 }
 
 fn http_status_code(response: &HttpResponse) -> u16 {
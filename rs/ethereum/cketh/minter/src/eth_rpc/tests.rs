@@ -1,4 +1,5 @@
 use super::*;
+use assert_matches::assert_matches;
 
 fn check_response_normalization<O: HttpResponsePayload>(left: &str, right: &str) {
     fn add_envelope(reply: &str) -> Vec<u8> {
@@ -342,3 +343,242 @@ fn transaction_receipt_normalization() {
     }"#,
     );
 }
+
+#[test]
+fn should_accept_reply_with_matching_id() {
+    let body = br#"{"jsonrpc":"2.0","id":42,"result":"0x1"}"#;
+
+    assert_eq!(
+        parse_json_rpc_reply::<String>(42, 200, body),
+        Ok(JsonRpcResult::Result("0x1".to_string()))
+    );
+}
+
+#[test]
+fn should_reject_reply_with_mismatched_id() {
+    let body = br#"{"jsonrpc":"2.0","id":41,"result":"0x1"}"#;
+
+    assert_eq!(
+        parse_json_rpc_reply::<String>(42, 200, body),
+        Err(HttpOutcallError::UnexpectedJsonRpcResponseId {
+            expected: 42,
+            actual: 41
+        })
+    );
+}
+
+#[test]
+fn should_reject_reply_without_id() {
+    let body = br#"{"jsonrpc":"2.0","result":"0x1"}"#;
+
+    assert_matches!(
+        parse_json_rpc_reply::<String>(42, 200, body),
+        Err(HttpOutcallError::InvalidHttpJsonRpcResponse {
+            status: 200,
+            parsing_error: Some(_),
+            ..
+        })
+    );
+}
+
+mod batch_reply {
+    use crate::eth_rpc::{
+        parse_json_rpc_batch_reply, HttpOutcallError, JsonRpcResult, ResponseTransform,
+    };
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn should_correlate_mixed_batch_replies_by_id() {
+        // Replies in a different order than the requests, with one error.
+        let body = br#"[
+            {"jsonrpc":"2.0","id":12,"result":"0x3"},
+            {"jsonrpc":"2.0","id":10,"result":"0x1"},
+            {"jsonrpc":"2.0","id":11,"error":{"code":-32602,"message":"invalid argument 0"}}
+        ]"#;
+
+        assert_eq!(
+            parse_json_rpc_batch_reply::<String>(&[10, 11, 12], 200, body),
+            Ok(vec![
+                JsonRpcResult::Result("0x1".to_string()),
+                JsonRpcResult::Error {
+                    code: -32602,
+                    message: "invalid argument 0".to_string()
+                },
+                JsonRpcResult::Result("0x3".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_reject_batch_with_missing_reply() {
+        let body = br#"[{"jsonrpc":"2.0","id":10,"result":"0x1"}]"#;
+
+        assert_matches!(
+            parse_json_rpc_batch_reply::<String>(&[10, 11], 200, body),
+            Err(HttpOutcallError::InvalidHttpJsonRpcResponse {
+                status: 200,
+                parsing_error: Some(e),
+                ..
+            }) if e == "missing reply to request with id 11"
+        );
+    }
+
+    #[test]
+    fn should_reject_batch_with_unexpected_or_duplicate_id() {
+        let unexpected = br#"[
+            {"jsonrpc":"2.0","id":10,"result":"0x1"},
+            {"jsonrpc":"2.0","id":42,"result":"0x2"}
+        ]"#;
+        let duplicate = br#"[
+            {"jsonrpc":"2.0","id":10,"result":"0x1"},
+            {"jsonrpc":"2.0","id":10,"result":"0x2"}
+        ]"#;
+
+        for (body, actual) in [(&unexpected[..], 42), (&duplicate[..], 10)] {
+            assert_eq!(
+                parse_json_rpc_batch_reply::<String>(&[10, 11], 200, body),
+                Err(HttpOutcallError::UnexpectedJsonRpcResponseId {
+                    expected: 11,
+                    actual
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn should_reject_single_reply_to_batch() {
+        let body =
+            br#"{"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"invalid request"}}"#;
+
+        assert_matches!(
+            parse_json_rpc_batch_reply::<String>(&[10, 11], 200, body),
+            Err(HttpOutcallError::InvalidHttpJsonRpcResponse {
+                status: 200,
+                parsing_error: Some(_),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn should_sort_batch_replies_by_id_when_transforming() {
+        let mut left =
+            br#"[{"jsonrpc":"2.0","id":11,"result":"0x2"},{"jsonrpc":"2.0","id":10,"result":"0x1"}]"#
+                .to_vec();
+        let mut right =
+            br#"[{"id":10,"result":"0x1","jsonrpc":"2.0"},{"result":"0x2","jsonrpc":"2.0","id":11}]"#
+                .to_vec();
+
+        ResponseTransform::Batch(None).apply(&mut left);
+        ResponseTransform::Batch(None).apply(&mut right);
+
+        assert_eq!(left, right);
+        assert_eq!(
+            parse_json_rpc_batch_reply::<String>(&[10, 11], 200, &left),
+            Ok(vec![
+                JsonRpcResult::Result("0x1".to_string()),
+                JsonRpcResult::Result("0x2".to_string()),
+            ])
+        );
+    }
+}
+
+mod outcall_cost {
+    use crate::eth_rpc::{http_request_cost, outcall_cost, ResponseSizeEstimate};
+
+    #[test]
+    fn should_match_documented_pricing_for_a_34_node_subnet() {
+        // Base fee: (3_000_000 + 60_000 * 34) * 34.
+        assert_eq!(http_request_cost(0, 0), 171_360_000);
+        // Each request byte costs 400 * 34 cycles and each response byte 800 * 34 cycles.
+        assert_eq!(http_request_cost(1, 0), 171_360_000 + 13_600);
+        assert_eq!(http_request_cost(0, 1), 171_360_000 + 27_200);
+        assert_eq!(
+            http_request_cost(500, 2_000_000),
+            171_360_000 + 500 * 13_600 + 2_000_000 * 27_200
+        );
+    }
+
+    #[test]
+    fn should_quote_one_outcall_per_provider() {
+        // Requests are estimated to 1024 bytes and 2048 bytes of headers are added to the
+        // response size estimate.
+        let single_provider = 171_360_000 + 1024 * 13_600 + (1024 + 2048) * 27_200;
+        assert_eq!(
+            outcall_cost(1, ResponseSizeEstimate::new(1024)),
+            single_provider
+        );
+        assert_eq!(
+            outcall_cost(4, ResponseSizeEstimate::new(1024)),
+            4 * single_provider
+        );
+        assert_eq!(outcall_cost(0, ResponseSizeEstimate::new(1024)), 0);
+    }
+}
+
+mod quantity_encoding {
+    use crate::eth_rpc::{Block, Quantity, Transaction};
+    use crate::numeric::{BlockNumber, Wei};
+
+    #[test]
+    fn should_deserialize_block_from_hex_decimal_and_number_encodings() {
+        let expected = Block {
+            number: BlockNumber::new(0x3a17c2),
+            base_fee_per_gas: Wei::new(0x8cd3ef8a),
+        };
+        for json in [
+            r#"{"number":"0x3a17c2","baseFeePerGas":"0x8cd3ef8a"}"#,
+            r#"{"number":"3807170","baseFeePerGas":"2362699658"}"#,
+            r#"{"number":3807170,"baseFeePerGas":2362699658}"#,
+        ] {
+            let block: Block = serde_json::from_str(json).unwrap();
+            assert_eq!(block, expected, "unexpected block from {json}");
+        }
+    }
+
+    #[test]
+    fn should_deserialize_transaction_quantities_from_all_encodings() {
+        let transaction = |gas: &str, transaction_index: &str| {
+            let json = format!(
+                r#"{{
+                    "blockHash": "0x82005d2f17b251900968f01b0ed482cb49b7e1d797342bc504904d442b64dbe4",
+                    "blockNumber": "0x4132ec",
+                    "gas": {gas},
+                    "gasPrice": "0xfefbee3e",
+                    "from": "0x1789f79e95324a47c5fd6693071188e82e9a3558",
+                    "hash": "0x0e59bd032b9b22aca5e2784e4cf114783512db00988c716cf17a1cc755a0a93d",
+                    "input": "0x",
+                    "nonce": "0x5",
+                    "to": "0xdd2851cdd40ae6536831558dd46db62fac7a844d",
+                    "transactionIndex": {transaction_index},
+                    "value": "0x0"
+                }}"#
+            );
+            serde_json::from_str::<Transaction>(&json).unwrap()
+        };
+
+        let hex = transaction(r#""0x5208""#, r#""0x32""#);
+        let decimal = transaction(r#""21000""#, r#""50""#);
+        let number = transaction("21000", "50");
+
+        assert_eq!(hex.gas, Quantity::new(21_000));
+        assert_eq!(hex.transaction_index, Some(Quantity::new(50)));
+        assert_eq!(hex, decimal);
+        assert_eq!(hex, number);
+        assert_eq!(transaction(r#""0x5208""#, "null").transaction_index, None);
+    }
+
+    #[test]
+    fn should_reject_invalid_quantities() {
+        for json in [
+            r#"{"number":"0xzz","baseFeePerGas":"0x1"}"#,
+            r#"{"number":"12ab","baseFeePerGas":"0x1"}"#,
+            r#"{"number":-1,"baseFeePerGas":"0x1"}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<Block>(json).is_err(),
+                "unexpectedly parsed {json}"
+            );
+        }
+    }
+}
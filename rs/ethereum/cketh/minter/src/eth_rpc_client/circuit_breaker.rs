@@ -0,0 +1,81 @@
+use crate::eth_rpc_client::RpcNodeProvider;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Number of consecutive failures after which a provider is skipped.
+pub const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long a provider is skipped before being tried again.
+pub const COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Whether a provider is queried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The provider is queried as usual.
+    Closed,
+    /// The provider failed too many times in a row and is skipped until the cooldown elapsed.
+    Open,
+    /// The cooldown elapsed and the provider is queried again: a single success closes the
+    /// circuit while a single failure opens it again.
+    HalfOpen,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ProviderFailures {
+    /// Number of failed calls since the last successful one.
+    consecutive: u32,
+    /// Time in nanoseconds since the Unix epoch of the last failure that opened the circuit.
+    opened_at: Option<u64>,
+}
+
+/// Tracks consecutive failures of the JSON-RPC providers to temporarily skip providers
+/// that keep failing, instead of wasting cycles and latency on them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    failures: BTreeMap<RpcNodeProvider, ProviderFailures>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn record_success(&mut self, provider: RpcNodeProvider) {
+        self.failures.remove(&provider);
+    }
+
+    /// Records a failed call to the provider at the given time,
+    /// in nanoseconds since the Unix epoch.
+    pub(crate) fn record_failure(&mut self, provider: RpcNodeProvider, now: u64) {
+        let failures = self.failures.entry(provider).or_default();
+        failures.consecutive = failures.consecutive.saturating_add(1);
+        if failures.consecutive >= FAILURE_THRESHOLD {
+            failures.opened_at = Some(now);
+        }
+    }
+
+    pub(crate) fn state(&self, provider: &RpcNodeProvider, now: u64) -> CircuitState {
+        match self.failures.get(provider).and_then(|f| f.opened_at) {
+            None => CircuitState::Closed,
+            Some(opened_at) => {
+                let cooldown = COOLDOWN.as_nanos() as u64;
+                if now < opened_at.saturating_add(cooldown) {
+                    CircuitState::Open
+                } else {
+                    CircuitState::HalfOpen
+                }
+            }
+        }
+    }
+
+    /// Providers that must currently be skipped.
+    pub(crate) fn open_providers(&self, now: u64) -> impl Iterator<Item = RpcNodeProvider> + '_ {
+        self.failures
+            .keys()
+            .filter(move |provider| self.state(provider, now) == CircuitState::Open)
+            .cloned()
+    }
+
+    /// Number of failed calls to each provider since its last successful one.
+    pub(crate) fn consecutive_failures(&self) -> impl Iterator<Item = (&RpcNodeProvider, u32)> {
+        self.failures
+            .iter()
+            .map(|(provider, failures)| (provider, failures.consecutive))
+    }
+}
@@ -0,0 +1,90 @@
+use futures::future::LocalBoxFuture;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Source of the current time for time-dependent logic of the client,
+/// such as backing off before retrying.
+/// Abstracted away to be able to control time in tests.
+pub trait Clock: Debug {
+    /// Current time in nanoseconds since the Unix epoch.
+    fn now(&self) -> u64;
+
+    /// Future that completes once the given duration elapsed.
+    fn sleep(&self, duration: Duration) -> LocalBoxFuture<'static, ()>;
+}
+
+/// Reads the time from the IC system API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IcClock;
+
+impl Clock for IcClock {
+    fn now(&self) -> u64 {
+        ic_cdk::api::time()
+    }
+
+    fn sleep(&self, duration: Duration) -> LocalBoxFuture<'static, ()> {
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        let timer = ClearTimerOnDrop(ic_cdk_timers::set_timer(duration, move || {
+            let _ = sender.send(());
+        }));
+        Box::pin(async move {
+            let _timer = timer;
+            let _ = receiver.await;
+        })
+    }
+}
+
+/// Cancels a timer once its future is dropped, e.g. when a call raced against a timeout
+/// completed first, so that timers do not pile up for calls that are long gone.
+/// Clearing a timer that already fired has no effect.
+struct ClearTimerOnDrop(ic_cdk_timers::TimerId);
+
+impl Drop for ClearTimerOnDrop {
+    fn drop(&mut self) {
+        ic_cdk_timers::clear_timer(self.0);
+    }
+}
+
+/// Clock that only moves forward when told to, or when sleeping.
+/// Sleeping advances the clock by the slept duration and completes as soon as it is polled,
+/// i.e., time passes instantly once all the other futures of a test are pending.
+/// Clones share the same time, so that a test can keep a handle on a clock given to a client.
+#[cfg(test)]
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    now: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now: u64) -> Self {
+        let clock = Self::default();
+        clock.set(now);
+        clock
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, duration: std::time::Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).expect("BUG: duration too large");
+        self.set(
+            self.now()
+                .checked_add(nanos)
+                .expect("BUG: clock overflowed"),
+        );
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+
+    fn sleep(&self, duration: Duration) -> LocalBoxFuture<'static, ()> {
+        let clock = self.clone();
+        Box::pin(async move { clock.advance(duration) })
+    }
+}
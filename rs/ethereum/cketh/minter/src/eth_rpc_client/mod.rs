@@ -1,52 +1,260 @@
+use crate::address::Address;
 use crate::eth_rpc;
 use crate::eth_rpc::{
-    are_errors_consistent, Block, BlockSpec, FeeHistory, FeeHistoryParams, GetLogsParam, Hash,
-    HttpOutcallError, HttpOutcallResult, HttpResponsePayload, JsonRpcResult, LogEntry,
-    ResponseSizeEstimate, SendRawTransactionResult, Transaction,
+    are_errors_consistent, outcall_cost, Block, BlockSpec, BlockTag, FeeHistory, FeeHistoryParams,
+    GetLogsParam, Hash, HttpOutcallError, HttpOutcallResult, HttpResponsePayload, JsonRpcResult,
+    LogEntry, ResponseSizeEstimate, SendRawTransactionResult, Transaction,
 };
-use crate::eth_rpc_client::providers::{RpcNodeProvider, MAINNET_PROVIDERS, SEPOLIA_PROVIDERS};
-use crate::eth_rpc_client::requests::GetTransactionCountParams;
+use crate::eth_rpc_client::clock::{Clock, IcClock};
+use crate::eth_rpc_client::providers::{
+    RpcNodeProvider, MAINNET_FALLBACK_PROVIDERS, MAINNET_PROVIDERS, SEPOLIA_FALLBACK_PROVIDERS,
+    SEPOLIA_PROVIDERS,
+};
+use crate::eth_rpc_client::requests::{GetBalanceParams, GetTransactionCountParams};
 use crate::eth_rpc_client::responses::TransactionReceipt;
+use crate::eth_rpc_error::{ErrorParser, Parser, SendRawTransactionError};
 use crate::lifecycle::EthereumNetwork;
 use crate::logs::{DEBUG, INFO};
-use crate::numeric::TransactionCount;
-use crate::state::State;
+use crate::metrics::mutate_metrics;
+use crate::numeric::{BlockNumber, ChainId, TransactionCount, Wei};
+use crate::state::{mutate_state, State};
+use futures::future::{Either, LocalBoxFuture};
+use futures::stream::{FuturesUnordered, StreamExt};
 use ic_canister_log::log;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
+use std::time::Duration;
 
+pub mod circuit_breaker;
+pub mod clock;
 mod providers;
 pub mod requests;
 pub mod responses;
 
+pub use providers::ProviderGroup;
+pub(crate) use providers::{parse_provider_override, RpcNodeProvider};
+
 #[cfg(test)]
 mod tests;
 
+/// Maximum time to wait for the reply of a single provider in a parallel call, so that a hung
+/// provider does not stall the whole call until the HTTPS outcall itself times out.
+const DEFAULT_PROVIDER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `eth_getLogs` may scan many blocks and can legitimately take longer than other methods.
+const GET_LOGS_PROVIDER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Sends JSON-RPC requests to an Ethereum node.
+/// Abstracted away to be able to test the client without making HTTPS outcalls.
+pub trait RpcTransport: Debug {
+    fn call<'a, I, O>(
+        &'a self,
+        provider: RpcNodeProvider,
+        method: String,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+    ) -> LocalBoxFuture<'a, HttpOutcallResult<JsonRpcResult<O>>>
+    where
+        I: Serialize + 'a,
+        O: DeserializeOwned + HttpResponsePayload + 'a;
+
+    /// Sends one request per element of `params_list` in a single JSON-RPC batch,
+    /// see [`eth_rpc::batch_call`].
+    fn batch_call<'a, I, O>(
+        &'a self,
+        provider: RpcNodeProvider,
+        method: String,
+        params_list: Vec<I>,
+        response_size_estimate: ResponseSizeEstimate,
+    ) -> LocalBoxFuture<'a, HttpOutcallResult<Vec<JsonRpcResult<O>>>>
+    where
+        I: Serialize + 'a,
+        O: DeserializeOwned + HttpResponsePayload + 'a;
+}
+
+/// Sends JSON-RPC requests with HTTPS outcalls,
+/// and records the outcome of each call in the minter state.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct EthRpcClient {
+pub struct DefaultTransport;
+
+impl RpcTransport for DefaultTransport {
+    fn call<'a, I, O>(
+        &'a self,
+        provider: RpcNodeProvider,
+        method: String,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+    ) -> LocalBoxFuture<'a, HttpOutcallResult<JsonRpcResult<O>>>
+    where
+        I: Serialize + 'a,
+        O: DeserializeOwned + HttpResponsePayload + 'a,
+    {
+        Box::pin(async move {
+            let result = eth_rpc::call(
+                provider.url().to_string(),
+                method,
+                params,
+                response_size_estimate,
+            )
+            .await;
+            mutate_state(|s| {
+                s.record_provider_call(&provider, &result);
+                s.record_provider_availability(&provider, &result, IcClock.now());
+            });
+            result
+        })
+    }
+
+    fn batch_call<'a, I, O>(
+        &'a self,
+        provider: RpcNodeProvider,
+        method: String,
+        params_list: Vec<I>,
+        response_size_estimate: ResponseSizeEstimate,
+    ) -> LocalBoxFuture<'a, HttpOutcallResult<Vec<JsonRpcResult<O>>>>
+    where
+        I: Serialize + 'a,
+        O: DeserializeOwned + HttpResponsePayload + 'a,
+    {
+        Box::pin(async move {
+            let result = eth_rpc::batch_call(
+                provider.url().to_string(),
+                method,
+                params_list,
+                response_size_estimate,
+            )
+            .await;
+            let outcome: HttpOutcallResult<JsonRpcResult<()>> = result
+                .as_ref()
+                .map(|_| JsonRpcResult::Result(()))
+                .map_err(Clone::clone);
+            mutate_state(|s| {
+                s.record_provider_call(&provider, &outcome);
+                s.record_provider_availability(&provider, &outcome, IcClock.now());
+            });
+            result
+        })
+    }
+}
+
+/// The providers of the given group for the given network.
+pub(crate) fn group_providers(
     chain: EthereumNetwork,
+    group: ProviderGroup,
+) -> &'static [RpcNodeProvider] {
+    match (chain, group) {
+        (EthereumNetwork::Mainnet, ProviderGroup::Primary) => &MAINNET_PROVIDERS,
+        (EthereumNetwork::Mainnet, ProviderGroup::Fallback) => &MAINNET_FALLBACK_PROVIDERS,
+        (EthereumNetwork::Sepolia, ProviderGroup::Primary) => &SEPOLIA_PROVIDERS,
+        (EthereumNetwork::Sepolia, ProviderGroup::Fallback) => &SEPOLIA_FALLBACK_PROVIDERS,
+    }
 }
 
-impl EthRpcClient {
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthRpcClient<T = DefaultTransport, C = IcClock> {
+    chain: EthereumNetwork,
+    transport: T,
+    clock: C,
+    /// Whether the canister is too low on cycles to query all providers in parallel,
+    /// in which case parallel calls fall back to querying the providers sequentially.
+    budget_constrained: bool,
+    /// The set of providers that are queried.
+    provider_group: ProviderGroup,
+    /// Providers that are queried instead of the ones of `provider_group`, if set.
+    provider_override: Option<Vec<RpcNodeProvider>>,
+    /// How often calls that opt into retries are retried when all providers failed transiently.
+    retry_policy: RetryPolicy,
+    /// Providers that failed too many times in a row to be queried by sequential calls,
+    /// see [`circuit_breaker::CircuitBreaker`].
+    skipped_providers: BTreeSet<RpcNodeProvider>,
+}
+
+impl EthRpcClient<DefaultTransport, IcClock> {
     const fn new(chain: EthereumNetwork) -> Self {
-        Self { chain }
+        Self {
+            chain,
+            transport: DefaultTransport,
+            clock: IcClock,
+            budget_constrained: false,
+            provider_group: ProviderGroup::Primary,
+            provider_override: None,
+            retry_policy: RetryPolicy::NO_RETRY,
+            skipped_providers: BTreeSet::new(),
+        }
     }
 
-    pub const fn from_state(state: &State) -> Self {
-        Self::new(state.ethereum_network())
+    pub fn from_state(state: &State) -> Self {
+        Self::from_state_with(
+            state,
+            ic_cdk::api::canister_balance128(),
+            DefaultTransport,
+            IcClock,
+        )
     }
+}
 
-    fn providers(&self) -> &[RpcNodeProvider] {
-        match self.chain {
-            EthereumNetwork::Mainnet => &MAINNET_PROVIDERS,
-            EthereumNetwork::Sepolia => &SEPOLIA_PROVIDERS,
+impl<T: RpcTransport, C: Clock> EthRpcClient<T, C> {
+    /// Configures the client as per the given state, where `cycles_balance` is the current
+    /// cycle balance of the canister.
+    fn from_state_with(state: &State, cycles_balance: u128, transport: T, clock: C) -> Self {
+        let budget_constrained = state
+            .sequential_fallback_cycles_threshold
+            .map_or(false, |threshold| cycles_balance < threshold);
+        let skipped_providers = state.circuit_breaker.open_providers(clock.now()).collect();
+        Self {
+            chain: state.ethereum_network(),
+            transport,
+            clock,
+            budget_constrained,
+            provider_group: state.active_provider_group,
+            provider_override: state.provider_override.clone(),
+            retry_policy: RetryPolicy::NO_RETRY,
+            skipped_providers,
         }
     }
 
+    /// Retries [`Self::eth_fee_history`] and [`Self::eth_send_raw_transaction`] according to
+    /// the given policy when all providers failed with transient errors.
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// The providers to query.
+    /// Skipped providers are still part of them, since the agreement between all providers is
+    /// what makes the results of parallel calls, such as logs, receipts or the finalized block,
+    /// trustworthy.
+    fn providers(&self) -> Vec<RpcNodeProvider> {
+        match &self.provider_override {
+            Some(providers) => providers.clone(),
+            None => group_providers(self.chain, self.provider_group).to_vec(),
+        }
+    }
+
+    /// The providers to query one after the other, without the skipped ones,
+    /// unless all of them are skipped since a call needs at least one provider.
+    fn sequential_providers(&self) -> Vec<RpcNodeProvider> {
+        let providers = self.providers();
+        let available: Vec<_> = providers
+            .iter()
+            .filter(|provider| !self.skipped_providers.contains(provider))
+            .cloned()
+            .collect();
+        if available.is_empty() {
+            return providers;
+        }
+        available
+    }
+
     /// Query all providers in sequence until one returns an ok result
-    /// (which could still be a JsonRpcResult::Error).
+    /// (which could still be a JsonRpcResult::Error), and return it along with the provider
+    /// that produced it.
+    /// A provider failing with a transient error is retried once before moving on to the
+    /// next provider, while a permanent error moves on immediately.
     /// If none of the providers return an ok result, return the last error.
     /// This method is useful in case a provider is temporarily down but should only be for
     /// querying data that is **not** critical since the returned value comes from a single provider.
@@ -55,41 +263,107 @@ impl EthRpcClient {
         method: impl Into<String> + Clone,
         params: I,
         response_size_estimate: ResponseSizeEstimate,
-    ) -> HttpOutcallResult<JsonRpcResult<O>>
+    ) -> (RpcNodeProvider, HttpOutcallResult<JsonRpcResult<O>>)
     where
         I: Serialize + Clone,
         O: DeserializeOwned + HttpResponsePayload + Debug,
     {
-        let mut last_result: Option<HttpOutcallResult<JsonRpcResult<O>>> = None;
-        for provider in self.providers() {
+        self.sequential_call(method, params, response_size_estimate)
+            .await
+            .pop()
+            .unwrap_or_else(|| panic!("BUG: No providers in RPC client {:?}", self))
+    }
+
+    /// Same as [`Self::sequential_call_until_ok`], but returns the results of all the providers
+    /// that were queried, in the order in which they were queried.
+    async fn sequential_call<I, O>(
+        &self,
+        method: impl Into<String> + Clone,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+    ) -> Vec<(RpcNodeProvider, HttpOutcallResult<JsonRpcResult<O>>)>
+    where
+        I: Serialize + Clone,
+        O: DeserializeOwned + HttpResponsePayload + Debug,
+    {
+        let mut results = vec![];
+        for provider in &self.sequential_providers() {
             log!(
                 DEBUG,
                 "[sequential_call_until_ok]: calling provider: {:?}",
                 provider
             );
-            let result = eth_rpc::call(
-                provider.url().to_string(),
-                method.clone(),
-                params.clone(),
-                response_size_estimate,
-            )
-            .await;
+            let call = || {
+                self.transport.call(
+                    provider.clone(),
+                    method.clone().into(),
+                    params.clone(),
+                    response_size_estimate,
+                )
+            };
+            let result = match call().await {
+                Err(e) if e.is_transient() => {
+                    log!(
+                        INFO,
+                        "Querying provider {provider:?} returned transient error {e:?}, retrying",
+                    );
+                    call().await
+                }
+                result => result,
+            };
             match result {
-                Ok(JsonRpcResult::Result(value)) => return Ok(JsonRpcResult::Result(value)),
+                Ok(JsonRpcResult::Result(value)) => {
+                    results.push((provider.clone(), Ok(JsonRpcResult::Result(value))));
+                    return results;
+                }
                 Ok(json_rpc_error @ JsonRpcResult::Error { .. }) => {
                     log!(
                         INFO,
                         "Provider {provider:?} returned JSON-RPC error {json_rpc_error:?}",
                     );
-                    last_result = Some(Ok(json_rpc_error));
+                    results.push((provider.clone(), Ok(json_rpc_error)));
                 }
                 Err(e) => {
                     log!(INFO, "Querying provider {provider:?} returned error {e:?}");
-                    last_result = Some(Err(e));
+                    mutate_metrics(|m| m.record_provider_error(provider.metric_label()));
+                    results.push((provider.clone(), Err(e)));
                 }
             };
         }
-        last_result.unwrap_or_else(|| panic!("BUG: No providers in RPC client {:?}", self))
+        results
+    }
+
+    /// Same as [`Self::sequential_call`], but walks the list of providers again as allowed by the
+    /// client's retry policy when all of them failed with transient errors.
+    async fn sequential_call_with_retries<I, O>(
+        &self,
+        method: impl Into<String> + Clone,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+    ) -> MultiCallResults<O>
+    where
+        I: Serialize + Clone,
+        O: DeserializeOwned + HttpResponsePayload + Debug,
+    {
+        let mut attempt = 1;
+        loop {
+            let results = self
+                .sequential_call(method.clone(), params.clone(), response_size_estimate)
+                .await;
+            let all_transient = results
+                .iter()
+                .all(|(_provider, result)| matches!(result, Err(e) if e.is_transient()));
+            if !all_transient || attempt >= self.retry_policy.max_attempts {
+                return MultiCallResults::from_non_empty_iter(results);
+            }
+            let delay = self.retry_policy.backoff(attempt);
+            log!(
+                INFO,
+                "[sequential_call_with_retries]: all providers failed with transient errors, retrying in {delay:?}",
+            );
+            self.clock.sleep(delay).await;
+            attempt += 1;
+        }
     }
 
     /// Query all providers in parallel and return all results.
@@ -97,42 +371,441 @@ impl EthRpcClient {
     /// (e.g., if different providers gave different responses).
     /// This method is useful for querying data that is critical for the system to ensure that there is no single point of failure,
     /// e.g., ethereum logs upon which ckETH will be minted.
+    ///
+    /// A provider that does not reply within `provider_timeout` is recorded as having failed with
+    /// [`HttpOutcallError::Timeout`], while the other providers still contribute to the results.
+    ///
+    /// If the canister is too low on cycles to afford querying all providers, the providers are
+    /// queried sequentially instead and only the first ok result is returned, i.e.,
+    /// the guarantee that there is no single point of failure is dropped.
     async fn parallel_call<I, O>(
         &self,
         method: impl Into<String> + Clone,
         params: I,
         response_size_estimate: ResponseSizeEstimate,
+        provider_timeout: Duration,
     ) -> MultiCallResults<O>
     where
         I: Serialize + Clone,
-        O: DeserializeOwned + HttpResponsePayload,
+        O: DeserializeOwned + HttpResponsePayload + Debug + PartialEq,
+    {
+        self.parallel_call_until(
+            method,
+            params,
+            response_size_estimate,
+            provider_timeout,
+            WaitFor::AllProviders,
+        )
+        .await
+    }
+
+    /// Same as [`Self::parallel_call`], but returns as soon as the providers replied as required
+    /// by `wait_for`. The returned results only contain the providers that replied by then:
+    /// the responses of the other ones are ignored, although their HTTPS outcalls were already
+    /// issued and are still paid for.
+    pub async fn parallel_call_until<I, O>(
+        &self,
+        method: impl Into<String> + Clone,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+        provider_timeout: Duration,
+        wait_for: WaitFor,
+    ) -> MultiCallResults<O>
+    where
+        I: Serialize + Clone,
+        O: DeserializeOwned + HttpResponsePayload + Debug + PartialEq,
     {
+        if self.budget_constrained {
+            let method = method.into();
+            log!(
+                INFO,
+                "[parallel_call]: WARNING: cycle balance is below the sequential fallback threshold, \
+                 querying {method} from a single provider instead of {} cycles for all of them: \
+                 the result may come from a single point of failure",
+                outcall_cost(self.providers().len(), response_size_estimate),
+            );
+            let (provider, result) = self
+                .sequential_call_until_ok(method, params, response_size_estimate)
+                .await;
+            return MultiCallResults::from_non_empty_iter(vec![(provider, result)]);
+        }
+        let method: String = method.into();
         let providers = self.providers();
-        let results = {
-            let mut fut = Vec::with_capacity(providers.len());
-            for provider in providers {
-                log!(DEBUG, "[parallel_call]: will call provider: {:?}", provider);
-                fut.push(eth_rpc::call(
-                    provider.url().to_string(),
+        let results: Vec<_> = match wait_for {
+            WaitFor::AllProviders => {
+                let fut: Vec<_> = providers
+                    .iter()
+                    .map(|provider| {
+                        self.call_with_timeout(
+                            provider.clone(),
+                            method.clone(),
+                            params.clone(),
+                            response_size_estimate,
+                            provider_timeout,
+                        )
+                    })
+                    .collect();
+                providers
+                    .iter()
+                    .cloned()
+                    .zip(futures::future::join_all(fut).await)
+                    .collect()
+            }
+            WaitFor::Quorum(quorum) => {
+                assert!(
+                    0 < quorum && quorum <= providers.len(),
+                    "BUG: quorum {quorum} must be between 1 and the number of providers {}",
+                    providers.len()
+                );
+                let mut pending: FuturesUnordered<_> = providers
+                    .iter()
+                    .map(|provider| {
+                        let call = self.call_with_timeout(
+                            provider.clone(),
+                            method.clone(),
+                            params.clone(),
+                            response_size_estimate,
+                            provider_timeout,
+                        );
+                        async move { (provider.clone(), call.await) }
+                    })
+                    .collect();
+                let mut completed = Vec::with_capacity(providers.len());
+                while let Some((provider, result)) = pending.next().await {
+                    let reached = match &result {
+                        Ok(JsonRpcResult::Result(value)) => {
+                            completed
+                                .iter()
+                                .filter(|(_, other)| {
+                                    matches!(other, Ok(JsonRpcResult::Result(v)) if v == value)
+                                })
+                                .count()
+                                + 1
+                                >= quorum
+                        }
+                        _ => false,
+                    };
+                    completed.push((provider, result));
+                    if reached {
+                        break;
+                    }
+                }
+                let slow_providers: Vec<_> = providers
+                    .iter()
+                    .filter(|provider| !completed.iter().any(|(p, _)| p == *provider))
+                    .collect();
+                if !slow_providers.is_empty() {
+                    log!(
+                        INFO,
+                        "[parallel_call]: quorum of {quorum} reached for {method} without waiting for {slow_providers:?}"
+                    );
+                    mutate_metrics(|m| {
+                        for provider in &slow_providers {
+                            m.record_slow_provider(provider.metric_label());
+                        }
+                    });
+                }
+                completed
+            }
+        };
+        mutate_metrics(|m| {
+            for (provider, result) in results.iter() {
+                if result.is_err() {
+                    m.record_provider_error(provider.metric_label());
+                }
+            }
+        });
+        MultiCallResults::from_non_empty_iter(results)
+    }
+
+    /// Calls the given provider, failing with [`HttpOutcallError::Timeout`] if it does not
+    /// reply within `provider_timeout`.
+    async fn call_with_timeout<I, O>(
+        &self,
+        provider: RpcNodeProvider,
+        method: String,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+        provider_timeout: Duration,
+    ) -> HttpOutcallResult<JsonRpcResult<O>>
+    where
+        I: Serialize,
+        O: DeserializeOwned + HttpResponsePayload,
+    {
+        log!(DEBUG, "[parallel_call]: will call provider: {:?}", provider);
+        let call = self
+            .transport
+            .call(provider.clone(), method, params, response_size_estimate);
+        self.with_timeout(provider, call, provider_timeout).await
+    }
+
+    /// Fails with [`HttpOutcallError::Timeout`] if the given call to `provider` does not
+    /// complete within `provider_timeout`.
+    async fn with_timeout<R>(
+        &self,
+        provider: RpcNodeProvider,
+        call: LocalBoxFuture<'_, HttpOutcallResult<R>>,
+        provider_timeout: Duration,
+    ) -> HttpOutcallResult<R> {
+        let timeout = self.clock.sleep(provider_timeout);
+        match futures::future::select(call, timeout).await {
+            Either::Left((result, _)) => result,
+            Either::Right(((), _)) => {
+                log!(
+                    INFO,
+                    "[parallel_call]: provider {provider:?} did not reply within {provider_timeout:?}"
+                );
+                Err(HttpOutcallError::Timeout {
+                    after: provider_timeout,
+                })
+            }
+        }
+    }
+
+    /// Same as [`Self::parallel_call`], but queries all providers again as allowed by the client's
+    /// retry policy when all of them failed with transient errors.
+    async fn parallel_call_with_retries<I, O>(
+        &self,
+        method: impl Into<String> + Clone,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+        provider_timeout: Duration,
+    ) -> MultiCallResults<O>
+    where
+        I: Serialize + Clone,
+        O: DeserializeOwned + HttpResponsePayload + Debug + PartialEq,
+    {
+        let mut attempt = 1;
+        loop {
+            let results = self
+                .parallel_call(
                     method.clone(),
                     params.clone(),
                     response_size_estimate,
-                ));
+                    provider_timeout,
+                )
+                .await;
+            let all_transient = results
+                .iter()
+                .all(|(_provider, result)| matches!(result, Err(e) if e.is_transient()));
+            if !all_transient || attempt >= self.retry_policy.max_attempts {
+                return results;
             }
-            futures::future::join_all(fut).await
+            let delay = self.retry_policy.backoff(attempt);
+            log!(
+                INFO,
+                "[parallel_call_with_retries]: all providers failed with transient errors, retrying in {delay:?}",
+            );
+            self.clock.sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Query all providers in parallel and return as soon as `quorum` of them returned the same
+    /// ok result, without waiting for the remaining providers, see [`WaitFor::Quorum`].
+    /// The providers that had not replied by then are reported as slow.
+    /// If no quorum is reached once all providers replied, the results are reduced as in
+    /// [`MultiCallResults::reduce_with_equality`].
+    ///
+    /// This method is useful for latency-sensitive critical reads, where waiting for a
+    /// lagging provider would only delay an outcome that is already settled.
+    pub async fn parallel_call_quorum<I, O>(
+        &self,
+        method: impl Into<String> + Clone,
+        params: I,
+        response_size_estimate: ResponseSizeEstimate,
+        quorum: usize,
+    ) -> QuorumCallOutcome<O>
+    where
+        I: Serialize + Clone,
+        O: DeserializeOwned + HttpResponsePayload + Debug + PartialEq,
+    {
+        let providers = self.providers();
+        let results = self
+            .parallel_call_until(
+                method,
+                params,
+                response_size_estimate,
+                DEFAULT_PROVIDER_TIMEOUT,
+                WaitFor::Quorum(quorum),
+            )
+            .await;
+        // When low on cycles, a single provider is queried and none of the others is slow.
+        let slow_providers = if self.budget_constrained {
+            BTreeSet::new()
+        } else {
+            providers
+                .into_iter()
+                .filter(|provider| !results.results.contains_key(provider))
+                .collect()
+        };
+        let result = if results.has_quorum(quorum) {
+            results.reduce_with_threshold(quorum)
+        } else {
+            results.reduce_with_equality()
         };
-        MultiCallResults::from_non_empty_iter(providers.iter().cloned().zip(results.into_iter()))
+        QuorumCallOutcome {
+            result,
+            slow_providers,
+        }
+    }
+
+    /// Same as [`Self::parallel_map`], but sends all the requests to each provider in a single
+    /// JSON-RPC batch, i.e., with one HTTPS outcall per provider instead of one per request.
+    /// When the cycle balance is below the sequential fallback threshold, the requests are sent
+    /// as in [`Self::parallel_map`] instead, i.e., each of them to a single provider.
+    pub async fn parallel_batch_call<I, O>(
+        &self,
+        method: impl Into<String> + Clone,
+        params_list: Vec<I>,
+        response_size_estimate: ResponseSizeEstimate,
+        provider_timeout: Duration,
+    ) -> Vec<MultiCallResults<O>>
+    where
+        I: Serialize + Clone,
+        O: DeserializeOwned + HttpResponsePayload + Debug + PartialEq,
+    {
+        if self.budget_constrained || params_list.is_empty() {
+            return self
+                .parallel_map(
+                    method,
+                    params_list,
+                    response_size_estimate,
+                    provider_timeout,
+                )
+                .await;
+        }
+        let method: String = method.into();
+        let providers = self.providers();
+        let fut: Vec<_> = providers
+            .iter()
+            .map(|provider| {
+                log!(
+                    DEBUG,
+                    "[parallel_batch_call]: will call provider: {:?}",
+                    provider
+                );
+                let call = self.transport.batch_call(
+                    provider.clone(),
+                    method.clone(),
+                    params_list.clone(),
+                    response_size_estimate,
+                );
+                self.with_timeout(provider.clone(), call, provider_timeout)
+            })
+            .collect();
+        let replies = futures::future::join_all(fut).await;
+        let mut results: Vec<Vec<_>> = params_list
+            .iter()
+            .map(|_| Vec::with_capacity(providers.len()))
+            .collect();
+        for (provider, reply) in providers.into_iter().zip(replies) {
+            match reply {
+                Ok(replies) => {
+                    for (results, reply) in results.iter_mut().zip(replies) {
+                        results.push((provider.clone(), Ok(reply)));
+                    }
+                }
+                Err(e) => {
+                    mutate_metrics(|m| m.record_provider_error(provider.metric_label()));
+                    for results in results.iter_mut() {
+                        results.push((provider.clone(), Err(e.clone())));
+                    }
+                }
+            }
+        }
+        results
+            .into_iter()
+            .map(MultiCallResults::from_non_empty_iter)
+            .collect()
+    }
+
+    /// Query all providers in parallel for each of the given parameters.
+    /// The returned results are aligned with `params_list`, i.e.,
+    /// the i-th element contains the results for the i-th parameters.
+    pub async fn parallel_map<I, O>(
+        &self,
+        method: impl Into<String> + Clone,
+        params_list: Vec<I>,
+        response_size_estimate: ResponseSizeEstimate,
+        provider_timeout: Duration,
+    ) -> Vec<MultiCallResults<O>>
+    where
+        I: Serialize + Clone,
+        O: DeserializeOwned + HttpResponsePayload + Debug + PartialEq,
+    {
+        let fut: Vec<_> = params_list
+            .into_iter()
+            .map(|params| {
+                self.parallel_call(
+                    method.clone(),
+                    params,
+                    response_size_estimate,
+                    provider_timeout,
+                )
+            })
+            .collect();
+        futures::future::join_all(fut).await
     }
 
     pub async fn eth_get_logs(
         &self,
         params: GetLogsParam,
+        reduction: ReductionStrategy,
     ) -> Result<Vec<LogEntry>, MultiCallError<Vec<LogEntry>>> {
         // We expect most of the calls to contain zero events.
         let results: MultiCallResults<Vec<LogEntry>> = self
-            .parallel_call("eth_getLogs", vec![params], ResponseSizeEstimate::new(100))
+            .parallel_call(
+                "eth_getLogs",
+                vec![params],
+                ResponseSizeEstimate::new(100),
+                GET_LOGS_PROVIDER_TIMEOUT,
+            )
             .await;
-        results.reduce_with_equality()
+        results.reduce(reduction)
+    }
+
+    /// Fetches the logs matching `params` between the blocks `from` and `to` (inclusive) with
+    /// one `eth_getLogs` call per chunk of at most `chunk_size` blocks, since many providers limit
+    /// the number of blocks a single call may span. The block range of `params` is ignored.
+    /// The logs of all chunks are concatenated in order, skipping logs that were already returned
+    /// for a previous chunk, as identified by their block number and log index.
+    /// Fails with the error of the first chunk that could not be fetched.
+    pub async fn eth_get_logs_in_chunks(
+        &self,
+        params: GetLogsParam,
+        from: BlockNumber,
+        to: BlockNumber,
+        chunk_size: u64,
+        reduction: ReductionStrategy,
+    ) -> Result<Vec<LogEntry>, MultiCallError<Vec<LogEntry>>> {
+        let mut logs = vec![];
+        let mut seen = BTreeSet::new();
+        for (chunk_from, chunk_to) in block_range_chunks(from, to, chunk_size) {
+            let chunk_logs = self
+                .eth_get_logs(
+                    GetLogsParam {
+                        from_block: chunk_from.into(),
+                        to_block: chunk_to.into(),
+                        ..params.clone()
+                    },
+                    reduction,
+                )
+                .await?;
+            for log in chunk_logs {
+                if let (Some(block_number), Some(log_index)) = (log.block_number, log.log_index) {
+                    if !seen.insert((block_number, log_index)) {
+                        log!(
+                            DEBUG,
+                            "[eth_get_logs_in_chunks]: skipping duplicate log {log:?}"
+                        );
+                        continue;
+                    }
+                }
+                logs.push(log);
+            }
+        }
+        Ok(logs)
     }
 
     pub async fn eth_get_block_by_number(
@@ -149,6 +822,7 @@ impl EthRpcClient {
                     include_full_transactions: false,
                 },
                 ResponseSizeEstimate::new(6 * 1024),
+                DEFAULT_PROVIDER_TIMEOUT,
             )
             .await;
         results.reduce_with_equality()
@@ -163,59 +837,392 @@ impl EthRpcClient {
                 "eth_getTransactionByHash",
                 vec![tx_hash],
                 ResponseSizeEstimate::new(1200),
+                DEFAULT_PROVIDER_TIMEOUT,
             )
             .await;
         results.reduce_with_equality()
     }
 
+    /// Fetches the receipt of the given transaction, see [`Self::eth_get_transaction_receipts`].
     pub async fn eth_get_transaction_receipt(
         &self,
         tx_hash: Hash,
+        reduction: ReductionStrategy,
     ) -> Result<Option<TransactionReceipt>, MultiCallError<Option<TransactionReceipt>>> {
-        let results: MultiCallResults<Option<TransactionReceipt>> = self
-            .parallel_call(
+        self.get_transaction_receipts(vec![tx_hash], |results| results.reduce(reduction))
+            .await
+            .pop()
+            .expect("BUG: one receipt per transaction")
+    }
+
+    /// Fetches the receipts of the given transactions from all providers, with a single JSON-RPC
+    /// batch per provider. The receipt of each transaction is the one returned by a strict
+    /// majority of the providers.
+    /// The returned results are aligned with `tx_hashes`, i.e.,
+    /// the i-th element contains the receipt of the i-th transaction.
+    pub async fn eth_get_transaction_receipts(
+        &self,
+        tx_hashes: Vec<Hash>,
+    ) -> Vec<Result<Option<TransactionReceipt>, MultiCallError<Option<TransactionReceipt>>>> {
+        self.get_transaction_receipts(tx_hashes, MultiCallResults::reduce_with_strict_majority)
+            .await
+    }
+
+    /// Providers may return inconsistent receipts while a transaction is being mined, e.g. when
+    /// one of them has not seen the block containing it yet. In that case, the receipts of the
+    /// affected transactions are queried once more and the second round is reduced instead,
+    /// after logging the providers that changed their answer between rounds.
+    async fn get_transaction_receipts(
+        &self,
+        tx_hashes: Vec<Hash>,
+        reduce: impl Fn(
+            MultiCallResults<Option<TransactionReceipt>>,
+        ) -> Result<
+            Option<TransactionReceipt>,
+            MultiCallError<Option<TransactionReceipt>>,
+        >,
+    ) -> Vec<Result<Option<TransactionReceipt>, MultiCallError<Option<TransactionReceipt>>>> {
+        let query_receipts = |tx_hashes: Vec<Hash>| {
+            self.parallel_batch_call::<_, Option<TransactionReceipt>>(
                 "eth_getTransactionReceipt",
-                vec![tx_hash],
+                tx_hashes.into_iter().map(|tx_hash| vec![tx_hash]).collect(),
                 ResponseSizeEstimate::new(700),
+                DEFAULT_PROVIDER_TIMEOUT,
             )
-            .await;
-        results.reduce_with_equality()
+        };
+        let first_round = query_receipts(tx_hashes.clone()).await;
+        let mut receipts: Vec<_> = first_round.iter().cloned().map(&reduce).collect();
+        let inconsistent: Vec<usize> = receipts
+            .iter()
+            .enumerate()
+            .filter(|(_, receipt)| matches!(receipt, Err(MultiCallError::InconsistentResults(_))))
+            .map(|(index, _)| index)
+            .collect();
+        if inconsistent.is_empty() {
+            return receipts;
+        }
+        let second_round =
+            query_receipts(inconsistent.iter().map(|index| tx_hashes[*index]).collect()).await;
+        for (index, results) in inconsistent.into_iter().zip(second_round) {
+            let unstable_providers = first_round[index]
+                .clone()
+                .merge(results.clone())
+                .unstable_providers();
+            if !unstable_providers.is_empty() {
+                log!(
+                    INFO,
+                    "[eth_get_transaction_receipt]: providers {unstable_providers:?} changed their receipt of {} between rounds",
+                    tx_hashes[index]
+                );
+            }
+            receipts[index] = reduce(results);
+        }
+        receipts
     }
 
+    /// Returns the element-wise median of the fee histories returned by the providers,
+    /// see [`MultiCallResults::reduce_with_median`], so that the gas price estimate
+    /// does not depend on a single provider.
+    /// If all providers failed with transient errors, they are queried again
+    /// as allowed by the client's retry policy.
     pub async fn eth_fee_history(
         &self,
         params: FeeHistoryParams,
-    ) -> HttpOutcallResult<JsonRpcResult<FeeHistory>> {
+    ) -> Result<FeeHistory, MultiCallError<FeeHistory>> {
         // A typical response is slightly above 300 bytes.
-        self.sequential_call_until_ok("eth_feeHistory", params, ResponseSizeEstimate::new(512))
-            .await
+        let results: MultiCallResults<FeeHistory> = self
+            .parallel_call_with_retries(
+                "eth_feeHistory",
+                params,
+                ResponseSizeEstimate::new(512),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .await;
+        results.reduce_with_median()
     }
 
+    /// Sends the transaction to the providers in sequence until one of them accepts it,
+    /// and reports how each of the queried providers handled it.
+    /// If all providers failed with transient errors, the transaction is sent again
+    /// as allowed by the client's retry policy.
     pub async fn eth_send_raw_transaction(
         &self,
         raw_signed_transaction_hex: String,
-    ) -> HttpOutcallResult<JsonRpcResult<SendRawTransactionResult>> {
+    ) -> SendOutcome {
         // A successful reply is under 256 bytes, but we expect most calls to end with an error
         // since we submit the same transaction from multiple nodes.
-        self.sequential_call_until_ok(
-            "eth_sendRawTransaction",
-            vec![raw_signed_transaction_hex],
-            ResponseSizeEstimate::new(256),
-        )
-        .await
+        let results: MultiCallResults<SendRawTransactionResult> = self
+            .sequential_call_with_retries(
+                "eth_sendRawTransaction",
+                vec![raw_signed_transaction_hex],
+                ResponseSizeEstimate::new(256),
+            )
+            .await;
+        SendOutcome::from(results)
     }
 
+    /// Returns the results of the providers as soon as a strict majority of them agree on the
+    /// transaction count, without waiting for the remaining providers.
     pub async fn eth_get_transaction_count(
         &self,
         params: GetTransactionCountParams,
     ) -> MultiCallResults<TransactionCount> {
-        self.parallel_call(
+        self.parallel_call_until(
             "eth_getTransactionCount",
             params,
             ResponseSizeEstimate::new(50),
+            DEFAULT_PROVIDER_TIMEOUT,
+            WaitFor::Quorum(self.providers().len() / 2 + 1),
         )
         .await
     }
+
+    /// Returns the balance in wei of the given address at the given block.
+    /// All providers must agree on the balance.
+    /// When the block is given by a tag, such as `latest`, providers may legitimately return
+    /// different balances because they are not at the same height. In that case, the tag is
+    /// resolved to a block number agreed upon by all providers, and the balance is queried again
+    /// at that block.
+    pub async fn eth_get_balance(
+        &self,
+        address: Address,
+        block: BlockSpec,
+    ) -> Result<Wei, MultiCallError<Wei>> {
+        let (tag, error) = match (self.eth_get_balance_at(address, block.clone()).await, block) {
+            (Err(error @ MultiCallError::InconsistentResults(_)), BlockSpec::Tag(tag)) => {
+                log!(
+                    INFO,
+                    "[eth_get_balance]: inconsistent balances at block {tag:?}, possibly because providers are at different heights: {error:?}",
+                );
+                (tag, error)
+            }
+            (result, _) => return result,
+        };
+        let block = match self.eth_get_block_by_number(BlockSpec::Tag(tag)).await {
+            Ok(block) => block,
+            Err(e) => {
+                log!(
+                    INFO,
+                    "[eth_get_balance]: failed to resolve block {tag:?} to a block number: {e:?}",
+                );
+                return Err(error);
+            }
+        };
+        self.eth_get_balance_at(address, BlockSpec::Number(block.number))
+            .await
+    }
+
+    async fn eth_get_balance_at(
+        &self,
+        address: Address,
+        block: BlockSpec,
+    ) -> Result<Wei, MultiCallError<Wei>> {
+        let results: MultiCallResults<Wei> = self
+            .parallel_call(
+                "eth_getBalance",
+                GetBalanceParams { address, block },
+                ResponseSizeEstimate::new(50),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .await;
+        results.reduce_with_equality()
+    }
+
+    /// Checks that all providers serve the client's network by querying their chain id,
+    /// e.g. to detect a Sepolia provider configured for Ethereum mainnet.
+    /// The returned error names every provider that reported another chain id
+    /// or whose chain id could not be retrieved.
+    pub async fn verify_chain_id(&self) -> Result<(), String> {
+        let results: MultiCallResults<ChainId> = self
+            .parallel_call(
+                "eth_chainId",
+                Vec::<()>::new(),
+                ResponseSizeEstimate::new(50),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .await;
+        let mismatches: Vec<String> = results
+            .iter()
+            .filter_map(|(provider, result)| match result {
+                Ok(JsonRpcResult::Result(chain_id)) => self
+                    .chain
+                    .validate_chain_id(*chain_id)
+                    .err()
+                    .map(|_| format!("{provider:?} reported chain id {chain_id}")),
+                Ok(JsonRpcResult::Error { code, message }) => Some(format!(
+                    "{provider:?} returned JSON-RPC error {code}: {message}"
+                )),
+                Err(e) => Some(format!("{provider:?} could not be queried: {e:?}")),
+            })
+            .collect();
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "expected chain id {} for {}, but {}",
+            self.chain.chain_id(),
+            self.chain,
+            mismatches.join(", ")
+        ))
+    }
+
+    /// Returns the transaction count of the given address including the transactions
+    /// that are still in the mempool, as needed to assign the next transaction nonce.
+    /// A strict majority of the providers must agree on the count.
+    pub async fn eth_get_pending_transaction_count(
+        &self,
+        address: Address,
+    ) -> Result<TransactionCount, MultiCallError<TransactionCount>> {
+        self.eth_get_transaction_count(GetTransactionCountParams {
+            address,
+            block: BlockSpec::Tag(BlockTag::Pending),
+        })
+        .await
+        .reduce_with_strict_majority()
+    }
+}
+
+impl MultiCallResults<FeeHistory> {
+    /// Returns the element-wise (lower) median of the fee histories returned by the providers,
+    /// so that a single provider returning a stale or manipulated fee cannot skew the estimate.
+    /// A strict majority of the providers must return a fee history, otherwise the result is
+    /// [`MultiCallError::InconsistentResults`], unless all of them failed, in which case the
+    /// error is reported as in [`Self::reduce_with_equality`].
+    /// Fee histories starting at different blocks or of different lengths cover different blocks,
+    /// cannot be combined, and are also reported as [`MultiCallError::InconsistentResults`].
+    pub fn reduce_with_median(self) -> Result<FeeHistory, MultiCallError<FeeHistory>> {
+        let median = {
+            let fee_histories: Vec<&FeeHistory> = self
+                .results
+                .values()
+                .filter_map(|result| match result {
+                    Ok(JsonRpcResult::Result(fee_history)) => Some(fee_history),
+                    _ => None,
+                })
+                .collect();
+            if fee_histories.is_empty() {
+                None
+            } else if 2 * fee_histories.len() <= self.results.len() {
+                Some(None)
+            } else {
+                Some(element_wise_median(&fee_histories))
+            }
+        };
+        match median {
+            Some(Some(median)) => Ok(median),
+            Some(None) => {
+                let error = MultiCallError::InconsistentResults(self);
+                log!(
+                    INFO,
+                    "[reduce_with_median]: no strict majority of fee histories covering the same blocks {error:?}"
+                );
+                mutate_metrics(|m| m.inconsistent_rpc_results += 1);
+                Err(error)
+            }
+            None => match self.all_ok() {
+                Err(error) => Err(error),
+                Ok(_) => panic!("BUG: expected all results to be errors"),
+            },
+        }
+    }
+}
+
+/// Element-wise median of the given non-empty list of fee histories,
+/// or `None` if they start at different blocks or their arrays have different lengths.
+fn element_wise_median(fee_histories: &[&FeeHistory]) -> Option<FeeHistory> {
+    let first = fee_histories
+        .first()
+        .expect("BUG: expected at least one fee history");
+    let same_blocks = fee_histories.iter().all(|fee_history| {
+        fee_history.oldest_block == first.oldest_block
+            && fee_history.base_fee_per_gas.len() == first.base_fee_per_gas.len()
+            && fee_history.reward.len() == first.reward.len()
+            && fee_history
+                .reward
+                .iter()
+                .zip(&first.reward)
+                .all(|(left, right)| left.len() == right.len())
+    });
+    if !same_blocks {
+        return None;
+    }
+    Some(FeeHistory {
+        oldest_block: first.oldest_block,
+        base_fee_per_gas: (0..first.base_fee_per_gas.len())
+            .map(|i| median(fee_histories.iter().map(|f| f.base_fee_per_gas[i])))
+            .collect(),
+        reward: first
+            .reward
+            .iter()
+            .enumerate()
+            .map(|(i, percentiles)| {
+                (0..percentiles.len())
+                    .map(|j| median(fee_histories.iter().map(|f| f.reward[i][j])))
+                    .collect()
+            })
+            .collect(),
+    })
+}
+
+/// Lower median of the given non-empty values.
+fn median<T: Ord>(values: impl Iterator<Item = T>) -> T {
+    let mut values: Vec<T> = values.collect();
+    values.sort_unstable();
+    values.swap_remove((values.len() - 1) / 2)
+}
+
+/// Splits the blocks between `from` and `to` (inclusive) into consecutive ranges
+/// of at most `chunk_size` blocks.
+fn block_range_chunks(
+    from: BlockNumber,
+    to: BlockNumber,
+    chunk_size: u64,
+) -> Vec<(BlockNumber, BlockNumber)> {
+    assert!(chunk_size > 0, "BUG: chunk size must be positive");
+    let mut chunks = vec![];
+    let mut chunk_from = from;
+    while chunk_from <= to {
+        let chunk_to = chunk_from
+            .checked_add_blocks(chunk_size - 1)
+            .map_or(to, |chunk_to| chunk_to.min(to));
+        chunks.push((chunk_from, chunk_to));
+        match chunk_to.checked_increment() {
+            Some(next) => chunk_from = next,
+            None => break,
+        }
+    }
+    chunks
+}
+
+/// How often a call is retried when all providers failed with transient errors.
+/// Retries are delayed with an exponential backoff, using timers so that other messages
+/// are processed in the meantime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of times the list of providers is walked, including the first time.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled before each following retry.
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Walk the list of providers once.
+    pub const NO_RETRY: Self = Self {
+        max_attempts: 1,
+        base_delay: Duration::ZERO,
+    };
+
+    /// Delay before the given retry, starting at 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2_u32.saturating_pow(retry.saturating_sub(1)))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NO_RETRY
+    }
 }
 
 /// Aggregates responses of different providers to the same query.
@@ -237,9 +1244,83 @@ impl<T> MultiCallResults<T> {
         }
         Self { results }
     }
+
+    /// Iterates over the outcome of each provider, ordered by provider.
+    pub(crate) fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&RpcNodeProvider, &HttpOutcallResult<JsonRpcResult<T>>)> {
+        self.results.iter()
+    }
+
+    /// Returns the number of providers that were queried.
+    #[allow(clippy::len_without_is_empty)] // never empty by construction
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Combines the results of two rounds of the same query, e.g. when retrying an
+    /// inconsistent call, so that a caller can detect providers that changed their answer.
+    pub(crate) fn merge(self, other: MultiCallResults<T>) -> MultiRoundResults<T> {
+        let mut results: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (provider, result) in self.results.into_iter().chain(other.results) {
+            results.entry(provider).or_default().push(result);
+        }
+        MultiRoundResults::from_non_empty_map(results)
+    }
+}
+
+/// Aggregates responses of different providers to the same query over several rounds.
+/// Guaranteed to be non-empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiRoundResults<T> {
+    results: BTreeMap<RpcNodeProvider, Vec<HttpOutcallResult<JsonRpcResult<T>>>>,
+}
+
+impl<T> MultiRoundResults<T> {
+    fn from_non_empty_map(
+        results: BTreeMap<RpcNodeProvider, Vec<HttpOutcallResult<JsonRpcResult<T>>>>,
+    ) -> Self {
+        if results.is_empty() || results.values().any(Vec::is_empty) {
+            panic!("BUG: MultiRoundResults cannot be empty!")
+        }
+        Self { results }
+    }
+
+    /// Returns the results of the given provider, in the order of the rounds.
+    pub(crate) fn get(
+        &self,
+        provider: &RpcNodeProvider,
+    ) -> Option<&[HttpOutcallResult<JsonRpcResult<T>>]> {
+        self.results.get(provider).map(Vec::as_slice)
+    }
+}
+
+impl<T: PartialEq> MultiRoundResults<T> {
+    /// Returns the providers whose results differ between rounds.
+    pub(crate) fn unstable_providers(&self) -> BTreeSet<RpcNodeProvider> {
+        self.results
+            .iter()
+            .filter(|(_provider, results)| results.windows(2).any(|w| w[0] != w[1]))
+            .map(|(provider, _results)| provider.clone())
+            .collect()
+    }
 }
 
 impl<T: PartialEq> MultiCallResults<T> {
+    /// Whether at least `quorum` providers returned the same ok result.
+    fn has_quorum(&self, quorum: usize) -> bool {
+        self.results.values().any(|result| match result {
+            Ok(JsonRpcResult::Result(value)) => {
+                self.results
+                    .values()
+                    .filter(|other| matches!(other, Ok(JsonRpcResult::Result(v)) if v == value))
+                    .count()
+                    >= quorum
+            }
+            _ => false,
+        })
+    }
+
     /// Expects all results to be ok or return the following error:
     /// * MultiCallError::ConsistentJsonRpcError: all errors are the same JSON-RPC error.
     /// * MultiCallError::ConsistentHttpOutcallError: all errors are the same HTTP outcall error.
@@ -258,6 +1339,7 @@ impl<T: PartialEq> MultiCallResults<T> {
                     }
                     Some((first_error_provider, error)) => {
                         if !are_errors_consistent(&error, &result) {
+                            mutate_metrics(|m| m.inconsistent_rpc_results += 1);
                             return Err(MultiCallError::InconsistentResults(
                                 MultiCallResults::from_non_empty_iter(vec![
                                     (first_error_provider, error),
@@ -283,6 +1365,36 @@ impl<T: PartialEq> MultiCallResults<T> {
     }
 }
 
+/// Outcome of [`EthRpcClient::parallel_call_quorum`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct QuorumCallOutcome<T> {
+    pub result: Result<T, MultiCallError<T>>,
+    /// The providers that had not replied yet when the quorum was reached.
+    pub(crate) slow_providers: BTreeSet<RpcNodeProvider>,
+}
+
+/// How many providers must have replied before the results of a parallel call are returned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WaitFor {
+    /// Wait for all providers, e.g., to scrape logs where every provider's answer matters.
+    #[default]
+    AllProviders,
+    /// Return as soon as the given number of providers returned the same ok result,
+    /// without waiting for the slower ones.
+    Quorum(usize),
+}
+
+/// How the results of providers queried in parallel are reduced to a single result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReductionStrategy {
+    /// All providers must return the same result, see [`MultiCallResults::reduce_with_equality`].
+    #[default]
+    Equality,
+    /// At least the given number of providers must return the same result,
+    /// see [`MultiCallResults::reduce_with_threshold`].
+    Threshold(usize),
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum MultiCallError<T> {
     ConsistentHttpOutcallError(HttpOutcallError),
@@ -290,6 +1402,83 @@ pub enum MultiCallError<T> {
     InconsistentResults(MultiCallResults<T>),
 }
 
+/// How a single provider handled an `eth_sendRawTransaction` request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SendStatus {
+    /// The provider accepted the transaction.
+    /// Since replicas must agree on the outcome of the HTTPS outcall, this also covers
+    /// "already known" replies that were sanitized by the response transform.
+    Accepted,
+    /// The provider already had the transaction in its mempool,
+    /// e.g., because it was relayed by another provider.
+    AlreadyKnown,
+    /// The provider refused the transaction.
+    Rejected(SendRawTransactionResult),
+    /// The provider returned an unrecognized JSON-RPC error.
+    JsonRpcError { code: i64, message: String },
+    /// The HTTPS outcall to the provider failed.
+    HttpOutcallError(HttpOutcallError),
+}
+
+impl From<HttpOutcallResult<JsonRpcResult<SendRawTransactionResult>>> for SendStatus {
+    fn from(result: HttpOutcallResult<JsonRpcResult<SendRawTransactionResult>>) -> Self {
+        match result {
+            Ok(JsonRpcResult::Result(SendRawTransactionResult::Ok)) => SendStatus::Accepted,
+            Ok(JsonRpcResult::Result(rejection)) => SendStatus::Rejected(rejection),
+            // Replies escaping the response transform still contain the raw provider error.
+            Ok(JsonRpcResult::Error { code, message }) => {
+                match Parser::new().try_parse_send_raw_transaction_error(code, message.clone()) {
+                    Some(SendRawTransactionError::AlreadyKnown) => SendStatus::AlreadyKnown,
+                    Some(SendRawTransactionError::InsufficientFunds) => {
+                        SendStatus::Rejected(SendRawTransactionResult::InsufficientFunds)
+                    }
+                    Some(SendRawTransactionError::NonceTooLow) => {
+                        SendStatus::Rejected(SendRawTransactionResult::NonceTooLow)
+                    }
+                    Some(SendRawTransactionError::NonceTooHigh) => {
+                        SendStatus::Rejected(SendRawTransactionResult::NonceTooHigh)
+                    }
+                    None => SendStatus::JsonRpcError { code, message },
+                }
+            }
+            Err(error) => SendStatus::HttpOutcallError(error),
+        }
+    }
+}
+
+/// Aggregates how each provider handled the same `eth_sendRawTransaction` request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SendOutcome {
+    statuses: BTreeMap<RpcNodeProvider, SendStatus>,
+}
+
+impl SendOutcome {
+    /// The transaction was sent to the network if at least one provider accepted it.
+    /// Providers that already knew the transaction are tolerated,
+    /// since the transaction is then in their mempool as well.
+    pub fn is_success(&self) -> bool {
+        self.statuses
+            .values()
+            .any(|status| matches!(status, SendStatus::Accepted | SendStatus::AlreadyKnown))
+    }
+
+    pub fn statuses(&self) -> impl Iterator<Item = &SendStatus> {
+        self.statuses.values()
+    }
+}
+
+impl From<MultiCallResults<SendRawTransactionResult>> for SendOutcome {
+    fn from(results: MultiCallResults<SendRawTransactionResult>) -> Self {
+        Self {
+            statuses: results
+                .results
+                .into_iter()
+                .map(|(provider, result)| (provider, SendStatus::from(result)))
+                .collect(),
+        }
+    }
+}
+
 impl<T: Debug + PartialEq> MultiCallResults<T> {
     pub fn reduce_with_equality(self) -> Result<T, MultiCallError<T>> {
         let mut results = self.all_ok()?.into_iter();
@@ -310,19 +1499,118 @@ impl<T: Debug + PartialEq> MultiCallResults<T> {
                 INFO,
                 "[reduce_with_equality]: inconsistent results {error:?}"
             );
+            mutate_metrics(|m| m.inconsistent_rpc_results += 1);
             return Err(error);
         }
         Ok(base_result)
     }
 
+    /// Returns the result agreed upon by at least `min_agreement` providers, so that a lagging or
+    /// flaky provider does not prevent reaching a result when `min_agreement` is lower than the
+    /// number of providers.
+    /// If all providers failed, the error is reported as in [`Self::reduce_with_equality`].
+    /// If no result, or more than one result, reaches the threshold, the results of all providers,
+    /// including the dissenting ones, are returned as [`MultiCallError::InconsistentResults`].
+    pub fn reduce_with_threshold(self, min_agreement: usize) -> Result<T, MultiCallError<T>> {
+        assert!(min_agreement > 0, "BUG: min_agreement must be positive");
+        // The first provider that returned each distinct result, along with its number of votes.
+        let mut votes: Vec<(RpcNodeProvider, &T, usize)> = vec![];
+        for (provider, result) in self.results.iter() {
+            if let Ok(JsonRpcResult::Result(value)) = result {
+                match votes
+                    .iter_mut()
+                    .find(|(_provider, other, _count)| *other == value)
+                {
+                    Some((_provider, _value, count)) => *count += 1,
+                    None => votes.push((provider.clone(), value, 1)),
+                }
+            }
+        }
+        if votes.is_empty() {
+            return match self.all_ok() {
+                Err(error) => Err(error),
+                Ok(_) => panic!("BUG: expected all results to be errors"),
+            };
+        }
+        let mut agreed = votes
+            .iter()
+            .filter(|(_provider, _value, count)| *count >= min_agreement)
+            .map(|(provider, _value, _count)| provider.clone());
+        if let (Some(provider), None) = (agreed.next(), agreed.next()) {
+            let mut results = self.results;
+            return match results.remove(&provider) {
+                Some(Ok(JsonRpcResult::Result(value))) => Ok(value),
+                _ => panic!("BUG: expected an ok result from provider {provider:?}"),
+            };
+        }
+        let error = MultiCallError::InconsistentResults(self);
+        log!(
+            INFO,
+            "[reduce_with_threshold]: no result agreed upon by {min_agreement} providers {error:?}"
+        );
+        mutate_metrics(|m| m.inconsistent_rpc_results += 1);
+        Err(error)
+    }
+
+    /// Reduces the results with the given strategy.
+    pub fn reduce(self, strategy: ReductionStrategy) -> Result<T, MultiCallError<T>> {
+        match strategy {
+            ReductionStrategy::Equality => self.reduce_with_equality(),
+            ReductionStrategy::Threshold(min_agreement) => {
+                self.reduce_with_threshold(min_agreement)
+            }
+        }
+    }
+
+    /// Returns the result returned by a strict majority of all providers.
+    /// If all providers failed, the error is reported as in [`Self::reduce_with_equality`].
+    pub fn reduce_with_strict_majority(self) -> Result<T, MultiCallError<T>>
+    where
+        T: Ord + Clone,
+    {
+        let mut votes: BTreeMap<&T, usize> = BTreeMap::new();
+        for result in self.results.values() {
+            if let Ok(JsonRpcResult::Result(value)) = result {
+                *votes.entry(value).or_default() += 1;
+            }
+        }
+        let num_providers = self.results.len();
+        let majority = votes
+            .iter()
+            .find(|(_value, count)| 2 * **count > num_providers)
+            .map(|(value, _count)| (*value).clone());
+        if let Some(value) = majority {
+            return Ok(value);
+        }
+        if votes.is_empty() {
+            return match self.all_ok() {
+                Err(error) => Err(error),
+                Ok(_) => panic!("BUG: expected all results to be errors"),
+            };
+        }
+        let error = MultiCallError::InconsistentResults(self);
+        log!(
+            INFO,
+            "[reduce_with_strict_majority]: no strict majority {error:?}"
+        );
+        mutate_metrics(|m| m.inconsistent_rpc_results += 1);
+        Err(error)
+    }
+
+    /// Returns the result with the smallest key. Ties are broken by the provider URL,
+    /// so that the same results always reduce to the same value.
     pub fn reduce_with_min_by_key<F: FnMut(&T) -> K, K: Ord>(
         self,
-        extractor: F,
+        mut extractor: F,
     ) -> Result<T, MultiCallError<T>> {
-        let min = self
+        let (_provider, min) = self
             .all_ok()?
-            .into_values()
-            .min_by_key(extractor)
+            .into_iter()
+            .min_by(|(left_provider, left), (right_provider, right)| {
+                extractor(left)
+                    .cmp(&extractor(right))
+                    .then_with(|| left_provider.url().cmp(right_provider.url()))
+            })
             .expect("BUG: MultiCallResults is guaranteed to be non-empty");
         Ok(min)
     }
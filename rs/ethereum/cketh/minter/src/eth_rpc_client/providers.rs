@@ -1,3 +1,8 @@
+use crate::endpoints::redact_url;
+use candid::CandidType;
+use minicbor::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
 pub(crate) const MAINNET_PROVIDERS: [RpcNodeProvider; 2] = [
     RpcNodeProvider::Ethereum(EthereumProvider::Ankr),
     RpcNodeProvider::Ethereum(EthereumProvider::Cloudflare),
@@ -8,10 +13,38 @@ pub(crate) const SEPOLIA_PROVIDERS: [RpcNodeProvider; 2] = [
     RpcNodeProvider::Sepolia(SepoliaProvider::BlockPi),
 ];
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
-pub(crate) enum RpcNodeProvider {
+pub(crate) const MAINNET_FALLBACK_PROVIDERS: [RpcNodeProvider; 2] = [
+    RpcNodeProvider::Ethereum(EthereumProvider::PublicNode),
+    RpcNodeProvider::Ethereum(EthereumProvider::LlamaNodes),
+];
+
+pub(crate) const SEPOLIA_FALLBACK_PROVIDERS: [RpcNodeProvider; 2] = [
+    RpcNodeProvider::Sepolia(SepoliaProvider::PublicNode),
+    RpcNodeProvider::Sepolia(SepoliaProvider::SepoliaOrg),
+];
+
+/// The set of providers the minter queries, switched manually, e.g. to fail over to the
+/// fallback providers during a disaster-recovery drill.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, CandidType, Encode, Decode,
+)]
+#[cbor(index_only)]
+pub enum ProviderGroup {
+    #[default]
+    #[n(0)]
+    Primary,
+    #[n(1)]
+    Fallback,
+}
+
+// Nominally public because it appears in the signature of `RpcTransport::call`,
+// but not reachable from outside the crate since this module is private.
+#[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum RpcNodeProvider {
     Ethereum(EthereumProvider),
     Sepolia(SepoliaProvider),
+    /// A provider set by the controllers at runtime, see [`parse_provider_override`].
+    Custom(RpcUrl),
 }
 
 impl RpcNodeProvider {
@@ -19,16 +52,101 @@ impl RpcNodeProvider {
         match self {
             Self::Ethereum(provider) => provider.ethereum_mainnet_endpoint_url(),
             Self::Sepolia(provider) => provider.ethereum_sepolia_endpoint_url(),
+            Self::Custom(url) => url.as_str(),
+        }
+    }
+
+    /// Label of the provider in the metrics, with the API key of a custom URL redacted.
+    pub(crate) fn metric_label(&self) -> String {
+        match self {
+            Self::Custom(url) => redact_url(url.as_str()),
+            _ => format!("{self:?}"),
+        }
+    }
+}
+
+/// URL of a JSON-RPC provider that was checked to be an HTTPS URL with a host and
+/// without credentials.
+/// Its debug representation is redacted since the URL may embed an API key.
+#[derive(Clone, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct RpcUrl(String);
+
+impl RpcUrl {
+    pub(crate) fn parse(url: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| -> Result<Self, String> {
+            Err(format!("invalid URL {}: {reason}", redact_url(url)))
+        };
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return invalid("must not contain whitespace");
+        }
+        let rest = match url.strip_prefix("https://") {
+            Some(rest) => rest,
+            None => return invalid("the scheme must be https"),
+        };
+        let authority = rest
+            .split(|c| c == '/' || c == '?' || c == '#')
+            .next()
+            .unwrap_or_default();
+        if authority.contains('@') {
+            return invalid("must not contain credentials");
+        }
+        let host = match authority.rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => host,
+            Some(_) => return invalid("the port must be a number"),
+            None => authority,
+        };
+        if host.is_empty()
+            || !host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        {
+            return invalid("must have a host name");
+        }
+        Ok(Self(url.to_string()))
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for RpcUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RpcUrl").field(&redact_url(&self.0)).finish()
+    }
+}
+
+/// Parses the URLs of the providers to query instead of the ones of the active provider group:
+/// there must be at least one URL, each of them valid as per [`RpcUrl::parse`],
+/// and no URL may be listed twice.
+pub(crate) fn parse_provider_override(urls: &[String]) -> Result<Vec<RpcNodeProvider>, String> {
+    if urls.is_empty() {
+        return Err("the list of providers must not be empty".to_string());
+    }
+    let mut providers: Vec<RpcNodeProvider> = Vec::with_capacity(urls.len());
+    for url in urls {
+        let provider = RpcNodeProvider::Custom(RpcUrl::parse(url)?);
+        if providers.contains(&provider) {
+            return Err(format!(
+                "provider {} is listed more than once",
+                redact_url(url)
+            ));
         }
+        providers.push(provider);
     }
+    Ok(providers)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
-pub(crate) enum EthereumProvider {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum EthereumProvider {
     //https://www.ankr.com/rpc/
     Ankr,
     //https://developers.cloudflare.com/web3/ethereum-gateway/
     Cloudflare,
+    //https://publicnode.com/
+    PublicNode,
+    //https://llamanodes.com/
+    LlamaNodes,
 }
 
 impl EthereumProvider {
@@ -36,16 +154,22 @@ impl EthereumProvider {
         match self {
             EthereumProvider::Ankr => "https://rpc.ankr.com/eth",
             EthereumProvider::Cloudflare => "https://cloudflare-eth.com",
+            EthereumProvider::PublicNode => "https://ethereum.publicnode.com",
+            EthereumProvider::LlamaNodes => "https://eth.llamarpc.com",
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
-pub(crate) enum SepoliaProvider {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum SepoliaProvider {
     //https://www.ankr.com/rpc/
     Ankr,
     //https://public.blockpi.io/
     BlockPi,
+    //https://publicnode.com/
+    PublicNode,
+    //https://sepolia.dev/
+    SepoliaOrg,
 }
 
 impl SepoliaProvider {
@@ -53,6 +177,8 @@ impl SepoliaProvider {
         match self {
             SepoliaProvider::Ankr => "https://rpc.ankr.com/eth_sepolia",
             SepoliaProvider::BlockPi => "https://ethereum-sepolia.blockpi.network/v1/rpc/public",
+            SepoliaProvider::PublicNode => "https://ethereum-sepolia.publicnode.com",
+            SepoliaProvider::SepoliaOrg => "https://rpc.sepolia.org",
         }
     }
 }
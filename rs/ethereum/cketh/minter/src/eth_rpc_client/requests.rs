@@ -8,7 +8,8 @@ use serde::Serialize;
 pub struct GetTransactionCountParams {
     /// The address for which the transaction count is requested.
     pub address: Address,
-    /// Integer block number, or "latest" for the last mined block or "pending", "earliest" for not yet mined transactions.
+    /// Integer block number, or a block tag such as "latest" for the last mined block
+    /// or "pending" to also count the transactions that are not mined yet.
     pub block: BlockSpec,
 }
 
@@ -17,3 +18,19 @@ impl From<GetTransactionCountParams> for (Address, BlockSpec) {
         (params.address, params.block)
     }
 }
+
+/// Parameters of the [`eth_getBalance`](https://ethereum.org/en/developers/docs/apis/json-rpc/#eth_getbalance) call.
+#[derive(Debug, Serialize, Clone)]
+#[serde(into = "(Address, BlockSpec)")]
+pub struct GetBalanceParams {
+    /// The address for which the balance is requested.
+    pub address: Address,
+    /// Integer block number, or a block tag such as "latest" for the last mined block.
+    pub block: BlockSpec,
+}
+
+impl From<GetBalanceParams> for (Address, BlockSpec) {
+    fn from(params: GetBalanceParams) -> Self {
+        (params.address, params.block)
+    }
+}
@@ -1,9 +1,9 @@
 use crate::address::Address;
 use crate::eth_rpc::{Hash, HttpResponsePayload, Quantity, ResponseTransform};
 use crate::numeric::{BlockNumber, Wei};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
 #[serde(rename_all = "camelCase")]
 pub struct TransactionReceipt {
     /// The hash of the block containing the transaction.
@@ -27,6 +27,7 @@ pub struct TransactionReceipt {
     pub contract_address: Option<Address>,
 
     /// The amount of gas used by this specific transaction alone
+    #[serde(deserialize_with = "crate::serde_quantity::deserialize")]
     pub gas_used: Quantity,
 
     /// Status of the transaction.
@@ -39,7 +40,13 @@ pub struct TransactionReceipt {
     /// A Block has a limit of 30 million gas and a transaction costs at least 21_000 gas,
     /// meaning that a block contains at most 1428 transactions, see
     /// https://ethereum.org/en/developers/docs/gas/#block-size
+    #[serde(deserialize_with = "crate::serde_quantity::deserialize")]
     pub transaction_index: Quantity,
+
+    /// ABI-encoded data returned by the EVM when the execution of the transaction reverted.
+    /// Only returned by some clients (e.g., Besu), `None` otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
 }
 
 impl HttpResponsePayload for TransactionReceipt {
@@ -48,7 +55,7 @@ impl HttpResponsePayload for TransactionReceipt {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
 #[serde(try_from = "ethnum::u256")]
 pub enum TransactionStatus {
     /// Transaction was mined and executed successfully.
@@ -62,6 +69,16 @@ pub enum TransactionStatus {
     Failure,
 }
 
+// Serialize as a quantity so that the result of the receipt response transform can be parsed again.
+impl Serialize for TransactionStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TransactionStatus::Success => serializer.serialize_str("0x1"),
+            TransactionStatus::Failure => serializer.serialize_str("0x0"),
+        }
+    }
+}
+
 impl TryFrom<ethnum::u256> for TransactionStatus {
     type Error = String;
 
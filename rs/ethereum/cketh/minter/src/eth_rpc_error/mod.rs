@@ -50,6 +50,121 @@ impl<T> From<SendRawTransactionError> for JsonRpcResult<T> {
     }
 }
 
+/// Error code returned by Ethereum clients when the execution of `eth_call` or `eth_estimateGas`
+/// reverted, in which case the error `data` field contains the ABI-encoded revert data.
+pub const EXECUTION_REVERTED_ERROR_CODE: i64 = 3;
+
+/// Selector of the Solidity `Error(string)` error, i.e. the first 4 bytes of
+/// `keccak256("Error(string)")`, used by `require` and `revert` with a message.
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const ABI_WORD_SIZE: usize = 32;
+
+/// Reason why the execution of `eth_call` or `eth_estimateGas` reverted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// The contract reverted without any data, e.g. `revert()` or a failed `require` without message.
+    Empty,
+    /// The contract reverted with a standard `Error(string)`.
+    Message(String),
+    /// The contract reverted with any other error, e.g. a
+    /// [Solidity custom error](https://docs.soliditylang.org/en/latest/contracts.html#errors-and-the-revert-statement).
+    /// Only the selector is interpreted, the remaining data is left as is.
+    CustomError { selector: [u8; 4], data: Vec<u8> },
+    /// The revert data could not be decoded,
+    /// e.g. it is shorter than a selector or is a truncated `Error(string)`.
+    Malformed(Vec<u8>),
+}
+
+impl RevertReason {
+    /// Decodes the revert data returned by a reverted call.
+    pub fn decode(data: &[u8]) -> Self {
+        if data.is_empty() {
+            return RevertReason::Empty;
+        }
+        if data.len() < 4 {
+            return RevertReason::Malformed(data.to_vec());
+        }
+        let (selector, payload) = data.split_at(4);
+        let selector: [u8; 4] = selector.try_into().expect("BUG: selector has 4 bytes");
+        if selector != ERROR_STRING_SELECTOR {
+            return RevertReason::CustomError {
+                selector,
+                data: payload.to_vec(),
+            };
+        }
+        decode_abi_string(payload)
+            .map(RevertReason::Message)
+            .unwrap_or_else(|| RevertReason::Malformed(data.to_vec()))
+    }
+
+    /// Decodes hex-encoded revert data, as found in the `data` field of a JSON-RPC error.
+    pub fn decode_hex(data: &str) -> Self {
+        let hex_data = data.strip_prefix("0x").unwrap_or(data);
+        match hex::decode(hex_data) {
+            Ok(bytes) => Self::decode(&bytes),
+            Err(_) => RevertReason::Malformed(data.as_bytes().to_vec()),
+        }
+    }
+}
+
+/// Displays the message of an `Error(string)` as is, and any other revert data as hex.
+impl std::fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RevertReason::Empty => write!(f, "0x"),
+            RevertReason::Message(message) => write!(f, "{message}"),
+            RevertReason::CustomError { selector, data } => {
+                write!(f, "0x{}{}", hex::encode(selector), hex::encode(data))
+            }
+            RevertReason::Malformed(data) => write!(f, "0x{}", hex::encode(data)),
+        }
+    }
+}
+
+/// Decodes the ABI encoding of a single `string` argument:
+/// the offset of the string, its length and its UTF-8 bytes padded to a multiple of 32 bytes.
+fn decode_abi_string(payload: &[u8]) -> Option<String> {
+    let offset = decode_abi_usize(payload.get(..ABI_WORD_SIZE)?)?;
+    let length_end = offset.checked_add(ABI_WORD_SIZE)?;
+    let length = decode_abi_usize(payload.get(offset..length_end)?)?;
+    let bytes = payload.get(length_end..length_end.checked_add(length)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+fn decode_abi_usize(word: &[u8]) -> Option<usize> {
+    let (padding, value) = word.split_at(ABI_WORD_SIZE - std::mem::size_of::<u64>());
+    if padding.iter().any(|b| *b != 0) {
+        return None;
+    }
+    usize::try_from(u64::from_be_bytes(value.try_into().ok()?)).ok()
+}
+
+/// The execution of `eth_call` or `eth_estimateGas` reverted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionReverted {
+    /// The error message returned by the provider, e.g. `execution reverted: not enough funds`.
+    pub message: String,
+    pub reason: RevertReason,
+}
+
+impl ExecutionReverted {
+    /// Recognizes a reverted execution from the components of a JSON-RPC error.
+    /// Returns `None` if the error is unrelated to a revert.
+    pub fn try_from_json_rpc_error(code: i64, message: &str, data: Option<&str>) -> Option<Self> {
+        let is_revert = code == EXECUTION_REVERTED_ERROR_CODE
+            || message.to_lowercase().contains("execution reverted");
+        if !is_revert {
+            return None;
+        }
+        Some(Self {
+            message: message.to_string(),
+            reason: data
+                .map(RevertReason::decode_hex)
+                .unwrap_or(RevertReason::Empty),
+        })
+    }
+}
+
 pub trait ErrorParser {
     fn try_parse_send_raw_transaction_error(
         &self,
@@ -63,3 +63,107 @@ fn check_sanitize_send_raw_transaction_result<T: AsRef<[u8]>>(
 fn sanitized_ok_response() -> Vec<u8> {
     br#"{"id":1,"jsonrpc":"2.0","result":"Ok"}"#.to_vec()
 }
+
+mod revert_reason {
+    use crate::eth_rpc_error::{ExecutionReverted, RevertReason};
+    use assert_matches::assert_matches;
+
+    // Revert data of `require(false, "Not enough Ether provided.")`
+    const ERROR_STRING_DATA: &str = "0x08c379a0\
+        0000000000000000000000000000000000000000000000000000000000000020\
+        000000000000000000000000000000000000000000000000000000000000001a\
+        4e6f7420656e6f7567682045746865722070726f76696465642e000000000000";
+
+    #[test]
+    fn should_decode_error_string() {
+        assert_eq!(
+            RevertReason::decode_hex(ERROR_STRING_DATA),
+            RevertReason::Message("Not enough Ether provided.".to_string())
+        );
+    }
+
+    #[test]
+    fn should_decode_custom_error() {
+        // Revert data of `error InsufficientBalance(uint256 available, uint256 required)`
+        let data = "0xcf479181\
+            0000000000000000000000000000000000000000000000000000000000000001\
+            0000000000000000000000000000000000000000000000000000000000000002";
+
+        let reason = RevertReason::decode_hex(data);
+
+        let mut expected_data = vec![0_u8; 64];
+        expected_data[31] = 1;
+        expected_data[63] = 2;
+        assert_eq!(
+            reason,
+            RevertReason::CustomError {
+                selector: [0xcf, 0x47, 0x91, 0x81],
+                data: expected_data,
+            }
+        );
+    }
+
+    #[test]
+    fn should_display_message_as_is_and_other_data_as_hex() {
+        assert_eq!(
+            RevertReason::decode_hex(ERROR_STRING_DATA).to_string(),
+            "Not enough Ether provided."
+        );
+        assert_eq!(
+            RevertReason::decode_hex("0xcf47918101").to_string(),
+            "0xcf47918101"
+        );
+        assert_eq!(RevertReason::decode_hex("0x").to_string(), "0x");
+    }
+
+    #[test]
+    fn should_decode_empty_revert_data() {
+        assert_eq!(RevertReason::decode_hex("0x"), RevertReason::Empty);
+        assert_eq!(RevertReason::decode(&[]), RevertReason::Empty);
+    }
+
+    #[test]
+    fn should_not_panic_on_malformed_revert_data() {
+        let truncated = &ERROR_STRING_DATA[..ERROR_STRING_DATA.len() - 64];
+        for data in ["0x08c3", "0x08c379a0", truncated, "0xnothex"] {
+            assert_matches!(
+                RevertReason::decode_hex(data),
+                RevertReason::Malformed(_),
+                "unexpected revert reason for {data}"
+            );
+        }
+
+        let huge_offset =
+            "0x08c379a0ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+        assert_matches!(
+            RevertReason::decode_hex(huge_offset),
+            RevertReason::Malformed(_)
+        );
+    }
+
+    #[test]
+    fn should_recognize_reverted_execution() {
+        assert_eq!(
+            ExecutionReverted::try_from_json_rpc_error(
+                3,
+                "execution reverted: Not enough Ether provided.",
+                Some(ERROR_STRING_DATA)
+            ),
+            Some(ExecutionReverted {
+                message: "execution reverted: Not enough Ether provided.".to_string(),
+                reason: RevertReason::Message("Not enough Ether provided.".to_string()),
+            })
+        );
+        assert_eq!(
+            ExecutionReverted::try_from_json_rpc_error(-32_000, "execution reverted", None),
+            Some(ExecutionReverted {
+                message: "execution reverted".to_string(),
+                reason: RevertReason::Empty,
+            })
+        );
+        assert_eq!(
+            ExecutionReverted::try_from_json_rpc_error(-32_000, "nonce too low", None),
+            None
+        );
+    }
+}
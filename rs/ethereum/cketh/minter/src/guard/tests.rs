@@ -110,6 +110,7 @@ fn init_state() {
                 ethereum_block_height: Default::default(),
                 minimum_withdrawal_amount: wei_from_milli_ether(10).into(),
                 next_transaction_nonce: Default::default(),
+                min_provider_agreement: None,
             })
             .expect("init args should be valid"),
         );
@@ -11,8 +11,10 @@ pub mod guard;
 pub mod lifecycle;
 pub mod logs;
 pub mod management;
+pub mod metrics;
 pub mod numeric;
 mod serde_data;
+mod serde_quantity;
 pub mod state;
 pub mod transactions;
 pub mod tx;
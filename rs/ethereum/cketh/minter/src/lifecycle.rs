@@ -1,6 +1,7 @@
 //! Module dealing with the lifecycle methods of the ckETH Minter.
 use crate::lifecycle::init::InitArg;
 use crate::lifecycle::upgrade::UpgradeArg;
+use crate::numeric::ChainId;
 use candid::{CandidType, Deserialize};
 use minicbor::{Decode, Encode};
 use serde::Serialize;
@@ -43,12 +44,24 @@ pub enum EthereumNetwork {
 }
 
 impl EthereumNetwork {
+    /// The EIP-155 chain id of the network, which is part of every signed transaction.
     pub fn chain_id(&self) -> u64 {
         match self {
             EthereumNetwork::Mainnet => 1,
             EthereumNetwork::Sepolia => 11155111,
         }
     }
+
+    /// Checks that a chain id, e.g. as reported by a JSON-RPC provider, is the one of this network.
+    pub fn validate_chain_id(&self, chain_id: ChainId) -> Result<(), String> {
+        if chain_id != ChainId::from(self.chain_id()) {
+            return Err(format!(
+                "expected chain id {} for {self}, got {chain_id}",
+                self.chain_id()
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Display for EthereumNetwork {
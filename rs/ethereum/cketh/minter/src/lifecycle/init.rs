@@ -26,6 +26,8 @@ pub struct InitArg {
     pub minimum_withdrawal_amount: Nat,
     #[cbor(n(7), with = "crate::cbor::nat")]
     pub next_transaction_nonce: Nat,
+    #[n(8)]
+    pub min_provider_agreement: Option<u64>,
 }
 
 impl TryFrom<InitArg> for State {
@@ -39,6 +41,7 @@ impl TryFrom<InitArg> for State {
             ethereum_block_height,
             minimum_withdrawal_amount,
             next_transaction_nonce,
+            min_provider_agreement,
         }: InitArg,
     ) -> Result<Self, Self::Error> {
         use std::str::FromStr;
@@ -59,6 +62,18 @@ impl TryFrom<InitArg> for State {
             ecdsa_key_name,
             ethereum_contract_address,
             next_transaction_nonce: initial_nonce,
+            deposit_event: Default::default(),
+            sequential_fallback_cycles_threshold: None,
+            min_confirmations: None,
+            min_provider_agreement,
+            response_size_estimates: Default::default(),
+            provider_call_counters: Default::default(),
+            circuit_breaker: Default::default(),
+            last_rpc_failure: None,
+            solvency_snapshot: None,
+            active_provider_group: Default::default(),
+            provider_override: None,
+            max_scanning_lag: None,
             retrieve_eth_principals: Default::default(),
             eth_transactions: EthTransactions::new(initial_nonce),
             ledger_id,
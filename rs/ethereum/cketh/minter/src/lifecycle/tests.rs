@@ -1,4 +1,5 @@
 mod init {
+    use crate::eth_rpc_client::ReductionStrategy;
     use crate::lifecycle::init::InitArg;
     use crate::numeric::{TransactionNonce, Wei};
     use crate::state::{InvalidStateError, State};
@@ -59,6 +60,17 @@ mod init {
             }),
             Err(InvalidStateError::InvalidTransactionNonce(_))
         );
+
+        for min_provider_agreement in [0, 1, 3] {
+            assert_matches!(
+                State::try_from(InitArg {
+                    min_provider_agreement: Some(min_provider_agreement),
+                    ..valid_init_arg()
+                }),
+                Err(InvalidStateError::InvalidMinProviderAgreement(_)),
+                "unexpectedly accepted a minimum agreement of {min_provider_agreement} out of 2 providers"
+            );
+        }
     }
 
     #[test]
@@ -73,6 +85,7 @@ mod init {
         assert_eq!(state.ledger_id, init_arg.ledger_id);
         assert_eq!(state.minimum_withdrawal_amount, Wei::TWO);
         assert_eq!(state.next_transaction_nonce, TransactionNonce::ZERO);
+        assert_eq!(state.reduction_strategy(), ReductionStrategy::Equality);
     }
 
     fn valid_init_arg() -> InitArg {
@@ -85,13 +98,16 @@ mod init {
             ethereum_block_height: Default::default(),
             minimum_withdrawal_amount: Wei::TWO.into(),
             next_transaction_nonce: TransactionNonce::ZERO.into(),
+            min_provider_agreement: None,
         }
     }
 }
 
 mod upgrade {
     use crate::address::Address;
-    use crate::eth_rpc::BlockTag;
+    use crate::eth_logs::{DepositEventAbi, DepositEventConfig};
+    use crate::eth_rpc::{BlockTag, FixedSizeData};
+    use crate::eth_rpc_client::{ProviderGroup, ReductionStrategy};
     use crate::lifecycle::upgrade::UpgradeArg;
     use crate::numeric::{wei_from_milli_ether, TransactionNonce, Wei};
     use crate::state::{InvalidStateError, State};
@@ -141,6 +157,27 @@ mod upgrade {
             }),
             Err(InvalidStateError::InvalidEthereumContractAddress(_))
         );
+
+        let mut state = initial_state();
+        assert_matches!(
+            state.upgrade(UpgradeArg {
+                deposit_event_topic: Some("0x257e057bb61920d8d0ed2cb7b720ac7f".to_string()),
+                ..Default::default()
+            }),
+            Err(InvalidStateError::InvalidDepositEventTopic(_))
+        );
+
+        for min_provider_agreement in [0, 1, 3] {
+            let mut state = initial_state();
+            assert_matches!(
+                state.upgrade(UpgradeArg {
+                    min_provider_agreement: Some(min_provider_agreement),
+                    ..Default::default()
+                }),
+                Err(InvalidStateError::InvalidMinProviderAgreement(_)),
+                "unexpectedly accepted a minimum agreement of {min_provider_agreement} out of 2 providers"
+            );
+        }
     }
 
     #[test]
@@ -154,6 +191,15 @@ mod upgrade {
                 "0xb44B5e756A894775FC32EDdf3314Bb1B1944dC34".to_string(),
             ),
             ethereum_block_height: Some(CandidBlockTag::Safe),
+            deposit_event_topic: Some(
+                "0x1111111111111111111111111111111111111111111111111111111111111111".to_string(),
+            ),
+            deposit_event_abi: Some(DepositEventAbi::ReceivedEth),
+            sequential_fallback_cycles_threshold: Some(Nat::from(1_000_000_000_000_u64)),
+            min_confirmations: Some(12),
+            active_provider_group: Some(ProviderGroup::Fallback),
+            max_scanning_lag: Some(100),
+            min_provider_agreement: Some(2),
         };
 
         state.upgrade(upgrade_arg).expect("valid upgrade args");
@@ -165,6 +211,22 @@ mod upgrade {
             Some(Address::from_str("0xb44B5e756A894775FC32EDdf3314Bb1B1944dC34").unwrap())
         );
         assert_eq!(state.ethereum_block_height, BlockTag::Safe);
+        assert_eq!(
+            state.deposit_event,
+            DepositEventConfig {
+                topic: FixedSizeData([0x11; 32]),
+                abi: DepositEventAbi::ReceivedEth,
+            }
+        );
+        assert_eq!(
+            state.sequential_fallback_cycles_threshold,
+            Some(1_000_000_000_000)
+        );
+        assert_eq!(state.min_confirmations, Some(12));
+        assert_eq!(state.active_provider_group, ProviderGroup::Fallback);
+        assert_eq!(state.max_scanning_lag, Some(100));
+        assert_eq!(state.min_provider_agreement, Some(2));
+        assert_eq!(state.reduction_strategy(), ReductionStrategy::Threshold(2));
     }
 
     fn initial_state() -> State {
@@ -179,7 +241,30 @@ mod upgrade {
             ethereum_block_height: Default::default(),
             minimum_withdrawal_amount: wei_from_milli_ether(10).into(),
             next_transaction_nonce: Default::default(),
+            min_provider_agreement: None,
         })
         .expect("valid init args")
     }
 }
+
+mod ethereum_network {
+    use crate::lifecycle::EthereumNetwork;
+    use crate::numeric::ChainId;
+
+    #[test]
+    fn should_return_chain_id_of_each_network() {
+        assert_eq!(EthereumNetwork::Mainnet.chain_id(), 1);
+        assert_eq!(EthereumNetwork::Sepolia.chain_id(), 11155111);
+    }
+
+    #[test]
+    fn should_validate_chain_id() {
+        let mainnet = ChainId::from(1_u64);
+        let sepolia = ChainId::from(11155111_u64);
+
+        assert_eq!(EthereumNetwork::Mainnet.validate_chain_id(mainnet), Ok(()));
+        assert_eq!(EthereumNetwork::Sepolia.validate_chain_id(sepolia), Ok(()));
+        assert!(EthereumNetwork::Mainnet.validate_chain_id(sepolia).is_err());
+        assert!(EthereumNetwork::Sepolia.validate_chain_id(mainnet).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::endpoints::CandidBlockTag;
+use crate::eth_logs::DepositEventAbi;
+use crate::eth_rpc_client::ProviderGroup;
 use crate::logs::INFO;
 use crate::state::mutate_state;
 use crate::state::STATE;
@@ -17,6 +19,20 @@ pub struct UpgradeArg {
     pub ethereum_contract_address: Option<String>,
     #[n(3)]
     pub ethereum_block_height: Option<CandidBlockTag>,
+    #[n(4)]
+    pub deposit_event_topic: Option<String>,
+    #[n(5)]
+    pub deposit_event_abi: Option<DepositEventAbi>,
+    #[cbor(n(6), with = "crate::cbor::nat::option")]
+    pub sequential_fallback_cycles_threshold: Option<Nat>,
+    #[n(7)]
+    pub min_confirmations: Option<u64>,
+    #[n(8)]
+    pub active_provider_group: Option<ProviderGroup>,
+    #[n(9)]
+    pub max_scanning_lag: Option<u64>,
+    #[n(10)]
+    pub min_provider_agreement: Option<u64>,
 }
 
 pub fn post_upgrade(upgrade_args: Option<UpgradeArg>) {
@@ -5,22 +5,25 @@ use ic_cdk::api::stable::StableWriter;
 use ic_cdk_macros::{init, post_upgrade, pre_upgrade, query, update};
 use ic_cketh_minter::address::{validate_address_as_destination, Address};
 use ic_cketh_minter::endpoints::WithdrawalError;
-use ic_cketh_minter::endpoints::{Eip1559TransactionPrice, RetrieveEthRequest, RetrieveEthStatus};
-use ic_cketh_minter::eth_logs::report_transaction_error;
-use ic_cketh_minter::eth_rpc::FeeHistory;
-use ic_cketh_minter::eth_rpc::{JsonRpcResult, SendRawTransactionResult};
-use ic_cketh_minter::eth_rpc_client::EthRpcClient;
+use ic_cketh_minter::endpoints::{
+    Eip1559TransactionPrice, FailedTransaction, MintSource, PendingDeposit, RetrieveEthRequest,
+    RetrieveEthStatus, RpcFailure, ScanningProgress, Solvency,
+};
+use ic_cketh_minter::eth_logs::{report_transaction_error, DepositEventAbi, LogScan};
+use ic_cketh_minter::eth_rpc::{FeeHistory, LogEntry};
+use ic_cketh_minter::eth_rpc_client::responses::TransactionStatus;
+use ic_cketh_minter::eth_rpc_client::{EthRpcClient, MultiCallError};
 use ic_cketh_minter::guard::{retrieve_eth_guard, TimerGuard};
 use ic_cketh_minter::lifecycle::MinterArg;
 use ic_cketh_minter::logs::{DEBUG, INFO};
+use ic_cketh_minter::metrics::{encode_metrics, mutate_metrics};
 use ic_cketh_minter::numeric::{BlockNumber, LedgerBurnIndex, LedgerMintIndex, Wei};
 use ic_cketh_minter::state::{
-    lazy_call_ecdsa_public_key, mutate_state, read_state, MintedEvent, State, TaskType, STATE,
+    lazy_call_ecdsa_public_key, mutate_state, read_state, MintedEvent, SolvencySnapshot, State,
+    TaskType, STATE,
 };
 use ic_cketh_minter::transactions::EthWithdrawalRequest;
-use ic_cketh_minter::tx::{
-    estimate_transaction_price, AccessList, ConfirmedEip1559Transaction, Eip1559TransactionRequest,
-};
+use ic_cketh_minter::tx::{estimate_transaction_price, ConfirmedEip1559Transaction};
 use ic_cketh_minter::{eth_logs, eth_rpc};
 use ic_icrc1_client_cdk::{CdkRuntime, ICRC1Client};
 use icrc_ledger_types::icrc2::transfer_from::TransferFromArgs;
@@ -33,8 +36,10 @@ mod dashboard;
 const SCRAPPING_ETH_LOGS_INTERVAL: Duration = Duration::from_secs(3 * 60);
 const PROCESS_ETH_RETRIEVE_TRANSACTIONS_INTERVAL: Duration = Duration::from_secs(15);
 const MINT_RETRY_DELAY: Duration = Duration::from_secs(3 * 60);
-
-pub const SEPOLIA_TEST_CHAIN_ID: u64 = 11155111;
+/// Maximum number of blocks scraped before the progress is recorded, so that a failure only
+/// requires to scrape the last window again.
+const MAX_BLOCK_SPREAD: u64 = 1024;
+const REFRESH_SOLVENCY_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 #[init]
 #[candid_method(init)]
@@ -57,9 +62,9 @@ fn init(arg: MinterArg) {
 fn setup_timers() {
     ic_cdk_timers::set_timer(Duration::from_secs(0), || {
         // Initialize the minter's public key to make the address known.
-        ic_cdk::spawn(async {
-            let _ = lazy_call_ecdsa_public_key().await;
-        })
+        ic_cdk::spawn(reconcile_nonce());
+        ic_cdk::spawn(verify_chain_id());
+        ic_cdk::spawn(refresh_solvency());
     });
     ic_cdk_timers::set_timer_interval(SCRAPPING_ETH_LOGS_INTERVAL, || {
         ic_cdk::spawn(scrap_eth_logs())
@@ -67,6 +72,39 @@ fn setup_timers() {
     ic_cdk_timers::set_timer_interval(PROCESS_ETH_RETRIEVE_TRANSACTIONS_INTERVAL, || {
         ic_cdk::spawn(process_retrieve_eth_requests())
     });
+    ic_cdk_timers::set_timer_interval(REFRESH_SOLVENCY_INTERVAL, || {
+        ic_cdk::spawn(refresh_solvency())
+    });
+}
+
+/// Warns if a JSON-RPC provider does not serve the minter's network,
+/// since deposits and withdrawals would then be processed against the wrong chain.
+async fn verify_chain_id() {
+    if let Err(e) = read_state(EthRpcClient::from_state).verify_chain_id().await {
+        log!(
+            INFO,
+            "[verify_chain_id]: WARNING: JSON-RPC providers are misconfigured, minting decisions may be based on the wrong chain: {e}"
+        );
+    }
+}
+
+/// Raises the nonce of the next transaction to the pending transaction count of the minter's
+/// address, e.g. if the state was restored from a snapshot that predates some sent transactions,
+/// since reusing their nonces would get the new transactions rejected.
+async fn reconcile_nonce() {
+    let minter_address = Address::from_pubkey(&lazy_call_ecdsa_public_key().await);
+    match read_state(EthRpcClient::from_state)
+        .eth_get_pending_transaction_count(minter_address)
+        .await
+    {
+        Ok(count) => {
+            mutate_state(|s| s.reconcile_nonce(count.change_units()));
+        }
+        Err(e) => log!(
+            INFO,
+            "[reconcile_nonce]: failed to retrieve the pending transaction count of {minter_address}: {e:?}"
+        ),
+    }
 }
 
 async fn scrap_eth_logs() {
@@ -85,45 +123,64 @@ async fn scrap_eth_logs() {
         }
     };
     let mut last_scraped_block_number = read_state(|s| s.last_scraped_block_number);
-    let last_queried_block_number = update_last_observed_block_number().await;
+    let last_queried_block_number = match update_last_observed_block_number().await {
+        Ok(block_number) => block_number,
+        Err(e) => {
+            record_rpc_failure("eth_getBlockByNumber", &e);
+            return;
+        }
+    };
     while last_scraped_block_number < last_queried_block_number {
-        last_scraped_block_number = scrap_eth_logs_between(
+        last_scraped_block_number = match scrap_eth_logs_between(
             contract_address,
             last_scraped_block_number,
             last_queried_block_number,
         )
-        .await;
+        .await
+        {
+            Ok(block_number) => block_number,
+            Err(e) => {
+                record_rpc_failure("eth_getLogs", &e);
+                return;
+            }
+        };
     }
 }
 
-/// Scraps Ethereum logs between `from` and `min(from + 1024, to)` since certain RPC providers
-/// require that the number of blocks queried is no greater than 1024.
-/// Returns the last block number that was scraped (which is `min(from + 1024, to)`).
+/// Remembers a failure to aggregate the responses of the JSON-RPC providers, so that it can be
+/// queried with `last_rpc_failure`. Minting stalls until the next successful attempt.
+fn record_rpc_failure<T: std::fmt::Debug>(method: &str, error: &MultiCallError<T>) {
+    let failure = RpcFailure::new(method, ic_cdk::api::time(), error);
+    mutate_state(|s| s.record_rpc_failure(failure));
+}
+
+/// Scraps Ethereum logs between `from + 1` and `min(from + 1 + MAX_BLOCK_SPREAD, to)`,
+/// where `from` is the last block that was already scraped.
+/// Returns the last block number that was scraped (which is `min(from + 1 + MAX_BLOCK_SPREAD, to)`),
+/// or the error of the providers if the logs could not be retrieved, in which case nothing is recorded.
 async fn scrap_eth_logs_between(
     contract_address: Address,
     from: BlockNumber,
     to: BlockNumber,
-) -> BlockNumber {
-    const MAX_BLOCK_SPREAD: u16 = 1024;
+) -> Result<BlockNumber, MultiCallError<Vec<LogEntry>>> {
     match from.cmp(&to) {
         Ordering::Less => {
-            let max_to = from
-                .checked_add(BlockNumber::from(MAX_BLOCK_SPREAD))
-                .unwrap_or(BlockNumber::MAX);
-            let last_scraped_block_number = min(max_to, to);
+            let first_unscraped_block_number = next_block(from);
+            let last_scraped_block_number = scraping_window_end(first_unscraped_block_number, to);
             log!(
                 DEBUG,
                 "Scrapping ETH logs from block {:?} to block {:?}...",
-                from,
+                first_unscraped_block_number,
                 last_scraped_block_number
             );
 
             let (transaction_events, errors) = eth_logs::last_received_eth_events(
                 contract_address,
-                from,
+                first_unscraped_block_number,
                 last_scraped_block_number,
+                LogScan::Forward,
             )
-            .await;
+            .await?;
             let has_new_events = !transaction_events.is_empty();
             for event in transaction_events {
                 log!(
@@ -141,14 +198,14 @@ async fn scrap_eth_logs_between(
                 mutate_state(|s| report_transaction_error(s, error));
             }
             mutate_state(|s| s.last_scraped_block_number = last_scraped_block_number);
-            last_scraped_block_number
+            Ok(last_scraped_block_number)
         }
         Ordering::Equal => {
             log!(
                 DEBUG,
                 "[scrap_eth_logs] Skipping scrapping ETH logs: no new blocks",
             );
-            to
+            Ok(to)
         }
         Ordering::Greater => {
             ic_cdk::trap(&format!(
@@ -159,16 +216,52 @@ async fn scrap_eth_logs_between(
     }
 }
 
-async fn update_last_observed_block_number() -> BlockNumber {
-    use eth_rpc::{Block, BlockSpec};
+fn next_block(block_number: BlockNumber) -> BlockNumber {
+    block_number
+        .checked_add_blocks(1)
+        .unwrap_or(BlockNumber::MAX)
+}
 
-    let finalized_block: Block = read_state(EthRpcClient::from_state)
-        .eth_get_block_by_number(BlockSpec::Tag(read_state(State::ethereum_block_height)))
-        .await
-        .expect("HTTP call failed");
-    let block_number = finalized_block.number;
+fn scraping_window_end(from: BlockNumber, to: BlockNumber) -> BlockNumber {
+    let max_to = from
+        .checked_add_blocks(MAX_BLOCK_SPREAD)
+        .unwrap_or(BlockNumber::MAX);
+    min(max_to, to)
+}
+
+/// Returns the highest block whose logs can be scraped, see [`State::last_mintable_block_number`].
+async fn update_last_observed_block_number() -> Result<BlockNumber, MultiCallError<eth_rpc::Block>>
+{
+    let (block_number, last_mintable_block_number) = fetch_last_mintable_block_number().await?;
     mutate_state(|s| s.last_observed_block_number = Some(block_number));
-    block_number
+    Ok(last_mintable_block_number)
+}
+
+/// Returns the number of the block with the `ethereum_block_height` tag, and the highest block
+/// whose logs can be scraped.
+async fn fetch_last_mintable_block_number(
+) -> Result<(BlockNumber, BlockNumber), MultiCallError<eth_rpc::Block>> {
+    use eth_rpc::{Block, BlockSpec, BlockTag};
+
+    let client = read_state(EthRpcClient::from_state);
+    let block_tag = read_state(State::ethereum_block_height);
+    let finalized_block: Block = client
+        .eth_get_block_by_number(BlockSpec::Tag(block_tag))
+        .await?;
+    let block_number = finalized_block.number;
+    let latest_block_number = match (read_state(|s| s.min_confirmations), block_tag) {
+        (None, _) | (_, BlockTag::Latest) => block_number,
+        (Some(_), _) => {
+            let latest_block: Block = client
+                .eth_get_block_by_number(BlockSpec::Tag(BlockTag::Latest))
+                .await?;
+            latest_block.number
+        }
+    };
+    Ok((
+        block_number,
+        read_state(|s| s.last_mintable_block_number(block_number, latest_block_number)),
+    ))
 }
 
 async fn mint_cketh() {
@@ -220,6 +313,7 @@ async fn mint_cketh() {
                 mint_block_index: LedgerMintIndex::new(block_index),
             })
         });
+        mutate_metrics(|m| m.mints += 1);
         log!(
             INFO,
             "Minted {} ckWei to {} in block {block_index}",
@@ -299,19 +393,13 @@ async fn create_transaction() -> Result<(), String> {
         }
     };
 
-    let (nonce, chain_id) =
-        mutate_state(|s| (s.get_and_increment_nonce(), s.ethereum_network.chain_id()));
-    let transaction = Eip1559TransactionRequest {
-        chain_id,
-        nonce,
-        max_priority_fee_per_gas: transaction_price.max_priority_fee_per_gas,
-        max_fee_per_gas: transaction_price.max_fee_per_gas,
-        gas_limit: transaction_price.gas_limit,
-        destination: withdrawal_request.destination,
-        amount: tx_amount,
-        data: Vec::new(),
-        access_list: AccessList::new(),
-    };
+    let transaction = mutate_state(|s| {
+        s.create_transaction(
+            withdrawal_request.destination,
+            tx_amount,
+            &transaction_price,
+        )
+    });
     mutate_state(|s| {
         s.eth_transactions
             .record_created_transaction(withdrawal_request, transaction.clone())
@@ -343,25 +431,21 @@ async fn send_transaction() -> Result<(), String> {
         Some(signed_tx) => signed_tx,
         None => return Ok(()),
     };
-    let result = read_state(EthRpcClient::from_state)
+    let outcome = read_state(EthRpcClient::from_state)
         .eth_send_raw_transaction(signed_tx.raw_transaction_hex())
-        .await
-        .expect("HTTP call failed");
-    log!(DEBUG, "Sent transaction {signed_tx:?}: {result:?}");
-    match result {
-        JsonRpcResult::Result(tx_result) if tx_result == SendRawTransactionResult::Ok => {
-            mutate_state(|s| {
-                s.eth_transactions
-                    .record_sent_transaction(signed_tx.clone())
-            });
-            Ok(())
-        }
-        JsonRpcResult::Result(tx_result) => Err(format!(
-            "Failed to send transaction {signed_tx:?}: {tx_result:?}. Will retry later.",
-        )),
-        JsonRpcResult::Error { code, message } => Err(format!(
-            "Failed to send transaction {signed_tx:?}: {message} (error code = {code}). Will retry later.",
-        )),
+        .await;
+    log!(DEBUG, "Sent transaction {signed_tx:?}: {outcome:?}");
+    if outcome.is_success() {
+        mutate_state(|s| {
+            s.eth_transactions
+                .record_sent_transaction(signed_tx.clone())
+        });
+        mutate_metrics(|m| m.transactions_submitted += 1);
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to send transaction {signed_tx:?}: {outcome:?}. Will retry later.",
+        ))
     }
 }
 
@@ -376,6 +460,30 @@ async fn confirm_transaction() -> Result<(), String> {
     match result {
         Ok(Some(tx)) => {
             if let Some((block_hash, block_number, _transaction_index)) = tx.mined_in_block() {
+                let receipt = match read_state(EthRpcClient::from_state)
+                    .eth_get_transaction_receipt(
+                        sent_tx.hash(),
+                        read_state(State::reduction_strategy),
+                    )
+                    .await
+                {
+                    Ok(Some(receipt)) => receipt,
+                    Ok(None) => {
+                        return Err(format!(
+                            "Receipt of transaction {sent_tx:?} not found. Will retry later.",
+                        ))
+                    }
+                    Err(e) => {
+                        return Err(format!(
+                            "Failed to get receipt of transaction {sent_tx:?}: {e:?}. Will retry later.",
+                        ))
+                    }
+                };
+                if receipt.status == TransactionStatus::Failure {
+                    let failed_tx =
+                        mutate_state(|s| s.eth_transactions.record_failed_transaction(&receipt));
+                    log!(INFO, "Transaction failed: {failed_tx:?}");
+                }
                 let confirmed_tx =
                     ConfirmedEip1559Transaction::new(sent_tx, block_hash, block_number);
                 log!(INFO, "Confirmed transaction: {confirmed_tx:?}");
@@ -383,6 +491,7 @@ async fn confirm_transaction() -> Result<(), String> {
                     s.eth_transactions
                         .record_confirmed_transaction(confirmed_tx.clone())
                 });
+                mutate_metrics(|m| m.transactions_confirmed += 1);
                 Ok(())
             } else {
                 Err(format!(
@@ -430,6 +539,221 @@ async fn eip_1559_transaction_price() -> Eip1559TransactionPrice {
     Eip1559TransactionPrice::from(transaction_price)
 }
 
+/// List the deposits that the minter will credit: the ones waiting to be minted and the ones
+/// that are not scraped yet, up to the last mintable block. Nothing is recorded nor minted.
+/// This is an update call because scanning the logs requires HTTPS outcalls.
+/// Only controllers of the minter may call this endpoint.
+#[update]
+#[candid_method(update)]
+async fn preview_deposits() -> Result<Vec<PendingDeposit>, String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can preview deposits".to_string());
+    }
+    let mut scraped = vec![];
+    if let Some(contract_address) = read_state(|s| s.ethereum_contract_address) {
+        let from = read_state(|s| s.last_scraped_block_number);
+        let (_, to) = fetch_last_mintable_block_number()
+            .await
+            .map_err(|e| format!("failed to fetch the last mintable block: {e:?}"))?;
+        if from < to {
+            let (events, _errors) = eth_logs::last_received_eth_events(
+                contract_address,
+                next_block(from),
+                to,
+                LogScan::Forward,
+            )
+            .await
+            .map_err(|e| {
+                format!(
+                    "failed to fetch the logs of blocks {} to {to}: {e:?}",
+                    next_block(from)
+                )
+            })?;
+            scraped.extend(events);
+        }
+    }
+    Ok(read_state(|s| s.preview_deposits(scraped))
+        .into_iter()
+        .map(PendingDeposit::from)
+        .collect())
+}
+
+/// Rescan the given range of already scraped blocks for deposits that were missed, e.g. due to a
+/// bug, and mint them. Deposits that were already minted, are waiting to be minted or were found
+/// to be invalid are skipped, so that reprocessing a range never mints a deposit twice.
+/// Returns the recovered deposits. Only controllers of the minter may call this endpoint.
+#[update]
+#[candid_method(update)]
+async fn reprocess_block_range(from: Nat, to: Nat) -> Result<Vec<PendingDeposit>, String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can reprocess blocks".to_string());
+    }
+    let from = BlockNumber::try_from(from).map_err(|e| format!("invalid block number: {e}"))?;
+    let to = BlockNumber::try_from(to).map_err(|e| format!("invalid block number: {e}"))?;
+    let last_scraped_block_number = read_state(|s| s.last_scraped_block_number);
+    if from > to || to > last_scraped_block_number {
+        return Err(format!(
+            "invalid block range {from} to {to}: only blocks up to the last scraped block {last_scraped_block_number} can be reprocessed"
+        ));
+    }
+    let contract_address = read_state(|s| s.ethereum_contract_address)
+        .ok_or_else(|| "no helper smart contract address configured".to_string())?;
+    // Prevent the regular scraping from recording the same deposits concurrently.
+    let _guard = TimerGuard::new(TaskType::ScrapEthLogs)
+        .map_err(|_| "the minter is currently scraping logs, try again later".to_string())?;
+
+    log!(
+        INFO,
+        "[reprocess_block_range]: reprocessing blocks {from} to {to}"
+    );
+    let (events, errors) =
+        eth_logs::last_received_eth_events(contract_address, from, to, LogScan::Reprocess)
+            .await
+            .map_err(|e| format!("failed to fetch the logs of blocks {from} to {to}: {e:?}"))?;
+    let missed = mutate_state(|s| s.record_missed_deposits(events));
+    for error in errors {
+        mutate_state(|s| report_transaction_error(s, error));
+    }
+    log!(
+        INFO,
+        "[reprocess_block_range]: recovered {} missed deposits in blocks {from} to {to}",
+        missed.len()
+    );
+    if !missed.is_empty() {
+        ic_cdk_timers::set_timer(Duration::from_secs(0), || ic_cdk::spawn(mint_cketh()));
+    }
+    Ok(missed.into_iter().map(PendingDeposit::from).collect())
+}
+
+/// Query the JSON-RPC providers at the given HTTPS URLs instead of the ones of the active provider
+/// group, e.g. to stop querying a provider that went offline or to add a new one without upgrading
+/// the minter, or go back to the active provider group if `None`.
+/// Only controllers of the minter may call this endpoint.
+#[update]
+#[candid_method(update)]
+fn set_provider_override(urls: Option<Vec<String>>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can change the JSON-RPC providers".to_string());
+    }
+    mutate_state(|s| s.set_provider_override(urls))?;
+    ic_cdk::spawn(verify_chain_id());
+    Ok(())
+}
+
+/// Scrape the deposit events with the given topic, i.e. the hash of the event signature, and layout
+/// instead of the current ones, e.g. after migrating to a helper smart contract emitting different
+/// events. Only controllers of the minter may call this endpoint.
+#[update]
+#[candid_method(update)]
+fn set_deposit_event(topic: String, abi: DepositEventAbi) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can change the deposit event".to_string());
+    }
+    mutate_state(|s| s.set_deposit_event(&topic, abi)).map_err(|e| format!("{e:?}"))
+}
+
+/// Return the Ethereum deposit that was credited in the given ckETH ledger block,
+/// or `None` if the block is not a mint of the minter.
+#[query]
+#[candid_method(query)]
+fn get_mint_source(ledger_block_index: u64) -> Option<MintSource> {
+    read_state(|s| {
+        s.find_minted_event(LedgerMintIndex::new(ledger_block_index))
+            .map(|minted| MintSource::from(&minted.deposit_event))
+    })
+}
+
+/// List the transactions issued by the minter that were mined but whose execution failed.
+#[query]
+#[candid_method(query)]
+fn failed_transactions() -> Vec<FailedTransaction> {
+    read_state(|s| {
+        s.eth_transactions
+            .failed_transactions()
+            .map(FailedTransaction::from)
+            .collect()
+    })
+}
+
+/// Return why the responses of the JSON-RPC providers could not be aggregated the last time
+/// it happened, e.g. to find out why minting stalled. Provider URLs have their API keys redacted.
+#[query]
+#[candid_method(query)]
+fn last_rpc_failure() -> Option<RpcFailure> {
+    read_state(|s| s.last_rpc_failure.clone())
+}
+
+/// Report how far the scanning of deposits lags behind the last block observed by the minter.
+/// The result is `None` until the minter observed a block for the first time.
+#[query]
+#[candid_method(query)]
+fn scanning_progress() -> Option<ScanningProgress> {
+    read_state(|s| {
+        s.last_observed_block_number
+            .map(|block_number| s.scanning_progress(block_number).into())
+    })
+}
+
+/// Fetches the ETH balance of the minter and the ckETH supply, so that the `solvency` query
+/// does not make HTTPS outcalls. Keeps the previous values if either cannot be fetched or the
+/// ledger returns a supply that does not fit into a u256.
+async fn refresh_solvency() {
+    use eth_rpc::BlockSpec;
+
+    let minter_address = Address::from_pubkey(&lazy_call_ecdsa_public_key().await);
+    let eth_balance = match read_state(EthRpcClient::from_state)
+        .eth_get_balance(
+            minter_address,
+            BlockSpec::Tag(read_state(State::ethereum_block_height)),
+        )
+        .await
+    {
+        Ok(balance) => balance,
+        Err(e) => {
+            log!(
+                INFO,
+                "[refresh_solvency]: failed to retrieve the minter's ETH balance: {e:?}"
+            );
+            return;
+        }
+    };
+    let ledger_id = read_state(|s| s.ledger_id);
+    let cketh_supply = match ic_cdk::call::<_, (Nat,)>(ledger_id, "icrc1_total_supply", ()).await {
+        Ok((supply,)) => match Wei::try_from(supply) {
+            Ok(supply) => supply,
+            Err(e) => {
+                log!(
+                    INFO,
+                    "[refresh_solvency]: invalid ckETH supply returned by the ledger: {e}"
+                );
+                return;
+            }
+        },
+        Err((code, message)) => {
+            log!(
+                INFO,
+                "[refresh_solvency]: failed to retrieve the ckETH supply: {code:?} {message}"
+            );
+            return;
+        }
+    };
+    mutate_state(|s| {
+        s.solvency_snapshot = Some(SolvencySnapshot {
+            eth_balance,
+            cketh_supply,
+        })
+    });
+}
+
+/// Compare the ckETH supply against the ETH held by the minter, net of the pending withdrawals.
+/// The ETH balance and the ckETH supply are refreshed periodically, the result is `None` until
+/// they were fetched for the first time.
+#[query]
+#[candid_method(query)]
+fn solvency() -> Option<Solvency> {
+    read_state(State::solvency)
+}
+
 #[update]
 #[candid_method(update)]
 async fn withdraw(amount: Nat, recipient: String) -> Result<RetrieveEthRequest, WithdrawalError> {
@@ -530,8 +854,7 @@ async fn eth_fee_history() -> FeeHistory {
             reward_percentiles: vec![20],
         })
         .await
-        .expect("HTTP call failed")
-        .unwrap()
+        .expect("failed to get fee history")
 }
 
 #[update]
@@ -561,39 +884,6 @@ fn http_request(req: HttpRequest) -> HttpResponse {
     if req.path() == "/metrics" {
         let mut writer = MetricsEncoder::new(vec![], ic_cdk::api::time() as i64 / 1_000_000);
 
-        fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
-            read_state(|s| {
-                w.gauge_vec("cycle_balance", "Cycle balance of this canister.")?
-                    .value(
-                        &[("canister", "cketh-minter")],
-                        ic_cdk::api::canister_balance128() as f64,
-                    )?;
-
-                w.encode_gauge(
-                    "cketh_minter_last_observed_block",
-                    s.last_observed_block_number
-                        .map(|n| n.as_f64())
-                        .unwrap_or(0.0),
-                    "The last Ethereum block the ckETH minter observed.",
-                )?;
-
-                w.encode_gauge(
-                    "cketh_minter_last_processed_block",
-                    s.last_scraped_block_number.as_f64(),
-                    "The last Ethereum block the ckETH minter checked for deposits.",
-                )?;
-
-                w.gauge_vec(
-                    "cketh_minter_accepted_deposits",
-                    "The number of deposits the ckETH minter processed, by status.",
-                )?
-                .value(&[("status", "accepted")], s.minted_events.len() as f64)?
-                .value(&[("status", "rejected")], s.invalid_events.len() as f64)?;
-
-                Ok(())
-            })
-        }
-
         match encode_metrics(&mut writer) {
             Ok(()) => HttpResponseBuilder::ok()
                 .header("Content-Type", "text/plain; version=0.0.4")
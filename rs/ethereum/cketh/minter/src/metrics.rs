@@ -0,0 +1,248 @@
+#[cfg(test)]
+mod tests;
+
+use crate::state::{read_state, State};
+use ic_metrics_encoder::MetricsEncoder;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+thread_local! {
+    static METRICS: RefCell<MinterMetrics> = RefCell::default();
+}
+
+/// Upper bounds (in bytes) of the buckets of the response size histogram.
+/// The last bound is the largest response an HTTPS outcall can return,
+/// larger responses are counted in an additional `+Inf` bucket.
+const RESPONSE_SIZE_BUCKETS: [u64; 15] = [
+    128, 256, 512, 1_024, 2_048, 4_096, 8_192, 16_384, 32_768, 65_536, 131_072, 262_144, 524_288,
+    1_048_576, 2_097_152,
+];
+
+/// Distribution of the sizes of JSON-RPC responses received for a single method.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResponseSizeHistogram {
+    /// Number of responses per bucket of `RESPONSE_SIZE_BUCKETS`,
+    /// followed by the number of responses larger than all of them.
+    buckets: [u64; RESPONSE_SIZE_BUCKETS.len() + 1],
+    /// Total number of bytes received.
+    sum: u64,
+}
+
+impl ResponseSizeHistogram {
+    pub fn observe(&mut self, num_bytes: u64) {
+        let bucket = RESPONSE_SIZE_BUCKETS
+            .iter()
+            .position(|upper_bound| num_bytes <= *upper_bound)
+            .unwrap_or(RESPONSE_SIZE_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum = self.sum.saturating_add(num_bytes);
+    }
+
+    /// Number of observed responses.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns the upper bound of the bucket containing the `q`-th quantile
+    /// (e.g. 0.95 for the 95th percentile) of the observed sizes, which is `u64::MAX` for
+    /// responses larger than all buckets, or `None` if nothing was observed.
+    /// Used to tune the response size estimates, see [`State::tune_response_size_estimate`].
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((q * count as f64).ceil() as u64).clamp(1, count);
+        let mut cumulative_count = 0;
+        self.buckets
+            .iter()
+            .zip(
+                RESPONSE_SIZE_BUCKETS
+                    .iter()
+                    .chain(std::iter::once(&u64::MAX)),
+            )
+            .find_map(|(bucket_count, upper_bound)| {
+                cumulative_count += bucket_count;
+                (cumulative_count >= rank).then_some(*upper_bound)
+            })
+    }
+
+    pub fn iter_buckets(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        RESPONSE_SIZE_BUCKETS
+            .iter()
+            .map(|upper_bound| *upper_bound as f64)
+            .chain(std::iter::once(f64::INFINITY))
+            .zip(self.buckets.iter())
+            .map(|(upper_bound, count)| (upper_bound, *count as f64))
+    }
+}
+
+/// Counters describing the activity of the minter since the last upgrade.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MinterMetrics {
+    /// Number of log entries received when scraping deposits.
+    pub eth_logs_scanned: u64,
+    /// Number of successful ckETH mints.
+    pub mints: u64,
+    /// Number of transactions accepted by an Ethereum node.
+    pub transactions_submitted: u64,
+    /// Number of transactions found in a block.
+    pub transactions_confirmed: u64,
+    /// Number of queries for which the providers returned inconsistent results.
+    pub inconsistent_rpc_results: u64,
+    /// Number of failed HTTPS outcalls, by provider.
+    pub provider_errors: BTreeMap<String, u64>,
+    /// Number of times a quorum was reached without waiting for a provider, by provider.
+    pub slow_providers: BTreeMap<String, u64>,
+    /// Sizes of the received JSON-RPC response bodies, by method.
+    pub response_sizes: BTreeMap<String, ResponseSizeHistogram>,
+}
+
+impl MinterMetrics {
+    pub fn record_provider_error(&mut self, provider: impl ToString) {
+        *self
+            .provider_errors
+            .entry(provider.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn record_slow_provider(&mut self, provider: impl ToString) {
+        *self.slow_providers.entry(provider.to_string()).or_default() += 1;
+    }
+
+    pub fn observe_response_size(&mut self, method: impl ToString, num_bytes: u64) {
+        self.response_sizes
+            .entry(method.to_string())
+            .or_default()
+            .observe(num_bytes);
+    }
+
+    pub fn encode(&self, w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
+        w.encode_counter(
+            "cketh_minter_eth_logs_scanned",
+            self.eth_logs_scanned as f64,
+            "The number of log entries the ckETH minter received when scraping deposits.",
+        )?;
+        w.encode_counter(
+            "cketh_minter_mints",
+            self.mints as f64,
+            "The number of ckETH mints the minter performed.",
+        )?;
+        w.counter_vec(
+            "cketh_minter_transactions",
+            "The number of withdrawal transactions, by status.",
+        )?
+        .value(
+            &[("status", "submitted")],
+            self.transactions_submitted as f64,
+        )?
+        .value(
+            &[("status", "confirmed")],
+            self.transactions_confirmed as f64,
+        )?;
+        w.encode_counter(
+            "cketh_minter_inconsistent_rpc_results",
+            self.inconsistent_rpc_results as f64,
+            "The number of queries for which the JSON-RPC providers returned inconsistent results.",
+        )?;
+        let mut provider_errors = w.counter_vec(
+            "cketh_minter_provider_errors",
+            "The number of failed HTTPS outcalls to JSON-RPC providers, by provider.",
+        )?;
+        for (provider, count) in &self.provider_errors {
+            provider_errors = provider_errors.value(&[("provider", provider)], *count as f64)?;
+        }
+        let mut slow_providers = w.counter_vec(
+            "cketh_minter_slow_providers",
+            "The number of times a quorum of JSON-RPC providers was reached without waiting for a provider, by provider.",
+        )?;
+        for (provider, count) in &self.slow_providers {
+            slow_providers = slow_providers.value(&[("provider", provider)], *count as f64)?;
+        }
+        let mut response_sizes = w.histogram_vec(
+            "cketh_minter_response_size_bytes",
+            "The size of the JSON-RPC response bodies the minter received, by method.",
+        )?;
+        for (method, histogram) in &self.response_sizes {
+            response_sizes = response_sizes.histogram(
+                &[("method", method)],
+                histogram.iter_buckets(),
+                histogram.sum as f64,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn read_metrics<R>(f: impl FnOnce(&MinterMetrics) -> R) -> R {
+    METRICS.with(|m| f(&m.borrow()))
+}
+
+pub fn mutate_metrics<R>(f: impl FnOnce(&mut MinterMetrics) -> R) -> R {
+    METRICS.with(|m| f(&mut m.borrow_mut()))
+}
+
+pub fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
+    w.gauge_vec("cycle_balance", "Cycle balance of this canister.")?
+        .value(
+            &[("canister", "cketh-minter")],
+            ic_cdk::api::canister_balance128() as f64,
+        )?;
+    read_state(|s| encode_state_metrics(s, w))?;
+    read_metrics(|m| m.encode(w))
+}
+
+/// Encodes the gauges derived from the minter state.
+pub fn encode_state_metrics(s: &State, w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
+    w.encode_gauge(
+        "cketh_minter_last_observed_block",
+        s.last_observed_block_number
+            .map(|n| n.as_f64())
+            .unwrap_or(0.0),
+        "The last Ethereum block the ckETH minter observed.",
+    )?;
+
+    w.encode_gauge(
+        "cketh_minter_last_processed_block",
+        s.last_scraped_block_number.as_f64(),
+        "The last Ethereum block the ckETH minter checked for deposits.",
+    )?;
+
+    let mut consecutive_failures = w.gauge_vec(
+        "cketh_minter_provider_consecutive_failures",
+        "The number of failed calls to JSON-RPC providers since their last successful one, by provider.",
+    )?;
+    for (provider, count) in s.circuit_breaker.consecutive_failures() {
+        consecutive_failures =
+            consecutive_failures.value(&[("provider", &provider.metric_label())], count as f64)?;
+    }
+
+    let mut provider_calls = w.counter_vec(
+        "cketh_minter_provider_calls",
+        "The number of calls to JSON-RPC providers, by provider and outcome.",
+    )?;
+    for (provider, counters) in &s.provider_call_counters {
+        provider_calls = provider_calls
+            .value(
+                &[("provider", provider), ("outcome", "ok")],
+                counters.ok as f64,
+            )?
+            .value(
+                &[("provider", provider), ("outcome", "json_rpc_error")],
+                counters.json_rpc_error as f64,
+            )?
+            .value(
+                &[("provider", provider), ("outcome", "http_outcall_error")],
+                counters.http_outcall_error as f64,
+            )?;
+    }
+
+    w.gauge_vec(
+        "cketh_minter_accepted_deposits",
+        "The number of deposits the ckETH minter processed, by status.",
+    )?
+    .value(&[("status", "accepted")], s.minted_events.len() as f64)?
+    .value(&[("status", "rejected")], s.invalid_events.len() as f64)?;
+
+    Ok(())
+}
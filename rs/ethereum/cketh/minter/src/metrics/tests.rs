@@ -0,0 +1,233 @@
+use crate::metrics::{mutate_metrics, read_metrics, MinterMetrics};
+use ic_metrics_encoder::MetricsEncoder;
+
+const NOW_MILLIS: i64 = 1_700_000_000_000;
+
+fn encode(metrics: &MinterMetrics) -> String {
+    let mut writer = MetricsEncoder::new(vec![], NOW_MILLIS);
+    metrics.encode(&mut writer).unwrap();
+    String::from_utf8(writer.into_inner()).unwrap()
+}
+
+#[test]
+fn should_encode_counters() {
+    let mut metrics = MinterMetrics {
+        eth_logs_scanned: 10,
+        mints: 3,
+        transactions_submitted: 2,
+        transactions_confirmed: 1,
+        inconsistent_rpc_results: 4,
+        ..Default::default()
+    };
+    metrics.record_provider_error("Ethereum(Ankr)");
+    metrics.record_provider_error("Ethereum(Ankr)");
+    metrics.record_provider_error("Ethereum(Cloudflare)");
+
+    let encoded = encode(&metrics);
+
+    for line in [
+        format!("cketh_minter_eth_logs_scanned 10 {NOW_MILLIS}"),
+        format!("cketh_minter_mints 3 {NOW_MILLIS}"),
+        format!("cketh_minter_transactions{{status=\"submitted\"}} 2 {NOW_MILLIS}"),
+        format!("cketh_minter_transactions{{status=\"confirmed\"}} 1 {NOW_MILLIS}"),
+        format!("cketh_minter_inconsistent_rpc_results 4 {NOW_MILLIS}"),
+        format!("cketh_minter_provider_errors{{provider=\"Ethereum(Ankr)\"}} 2 {NOW_MILLIS}"),
+        format!("cketh_minter_provider_errors{{provider=\"Ethereum(Cloudflare)\"}} 1 {NOW_MILLIS}"),
+    ] {
+        assert!(
+            encoded.lines().any(|l| l == line),
+            "missing line {line} in {encoded}"
+        );
+    }
+}
+
+#[test]
+fn should_describe_every_metric() {
+    let encoded = encode(&MinterMetrics::default());
+
+    for name in [
+        "cketh_minter_eth_logs_scanned",
+        "cketh_minter_mints",
+        "cketh_minter_transactions",
+        "cketh_minter_inconsistent_rpc_results",
+        "cketh_minter_provider_errors",
+    ] {
+        assert!(encoded.contains(&format!("# HELP {name} ")));
+        assert!(encoded.contains(&format!("# TYPE {name} counter")));
+    }
+    assert!(encoded.contains("# HELP cketh_minter_response_size_bytes "));
+    assert!(encoded.contains("# TYPE cketh_minter_response_size_bytes histogram"));
+}
+
+#[test]
+fn should_record_response_sizes_in_buckets() {
+    let mut metrics = MinterMetrics::default();
+    for size in [600, 650, 690, 3_000] {
+        metrics.observe_response_size("eth_getTransactionReceipt", size);
+    }
+    metrics.observe_response_size("eth_feeHistory", 100);
+
+    let encoded = encode(&metrics);
+
+    for line in [
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_getTransactionReceipt\",le=\"512\"}} 0 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_getTransactionReceipt\",le=\"1024\"}} 3 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_getTransactionReceipt\",le=\"2048\"}} 3 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_getTransactionReceipt\",le=\"4096\"}} 4 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_getTransactionReceipt\",le=\"+Inf\"}} 4 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_sum{{method=\"eth_getTransactionReceipt\"}} 4940 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_count{{method=\"eth_getTransactionReceipt\"}} 4 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_feeHistory\",le=\"128\"}} 1 {NOW_MILLIS}"),
+    ] {
+        assert!(
+            encoded.lines().any(|l| l == line),
+            "missing line {line} in {encoded}"
+        );
+    }
+}
+
+#[test]
+fn should_record_oversized_responses_in_overflow_bucket() {
+    let mut metrics = MinterMetrics::default();
+    metrics.observe_response_size("eth_getLogs", 2_097_152);
+    metrics.observe_response_size("eth_getLogs", 10_000_000);
+
+    let encoded = encode(&metrics);
+
+    for line in [
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_getLogs\",le=\"2097152\"}} 1 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_bucket{{method=\"eth_getLogs\",le=\"+Inf\"}} 2 {NOW_MILLIS}"),
+        format!("cketh_minter_response_size_bytes_count{{method=\"eth_getLogs\"}} 2 {NOW_MILLIS}"),
+    ] {
+        assert!(
+            encoded.lines().any(|l| l == line),
+            "missing line {line} in {encoded}"
+        );
+    }
+    assert_eq!(
+        encoded.matches("le=\"+Inf\"").count(),
+        1,
+        "expected a single +Inf bucket in {encoded}"
+    );
+}
+
+#[test]
+fn should_mutate_thread_local_metrics() {
+    assert_eq!(read_metrics(|m| m.clone()), MinterMetrics::default());
+
+    mutate_metrics(|m| {
+        m.mints += 1;
+        m.record_provider_error("Ethereum(Ankr)");
+    });
+
+    assert_eq!(read_metrics(|m| m.mints), 1);
+    assert_eq!(
+        read_metrics(|m| m.provider_errors.get("Ethereum(Ankr)").copied()),
+        Some(1)
+    );
+}
+
+#[test]
+fn should_render_state_gauges_and_counters() {
+    use crate::lifecycle::init::InitArg;
+    use crate::metrics::encode_state_metrics;
+    use crate::numeric::{wei_from_milli_ether, BlockNumber};
+    use crate::state::{ProviderCallCounters, State};
+    use candid::Principal;
+
+    let mut state = State::try_from(InitArg {
+        ethereum_network: Default::default(),
+        ecdsa_key_name: "test_key_1".to_string(),
+        ethereum_contract_address: None,
+        ledger_id: Principal::from_text("apia6-jaaaa-aaaar-qabma-cai")
+            .expect("BUG: invalid principal"),
+        ethereum_block_height: Default::default(),
+        minimum_withdrawal_amount: wei_from_milli_ether(10).into(),
+        next_transaction_nonce: Default::default(),
+        min_provider_agreement: None,
+    })
+    .expect("init args should be valid");
+    state.last_observed_block_number = Some(BlockNumber::new(4_000_000));
+    state.provider_call_counters.insert(
+        "Ethereum(Ankr)".to_string(),
+        ProviderCallCounters {
+            ok: 3,
+            json_rpc_error: 1,
+            http_outcall_error: 2,
+        },
+    );
+
+    let render = |state: &State| {
+        let mut writer = MetricsEncoder::new(vec![], NOW_MILLIS);
+        encode_state_metrics(state, &mut writer).unwrap();
+        read_metrics(|m| m.encode(&mut writer)).unwrap();
+        String::from_utf8(writer.into_inner()).unwrap()
+    };
+
+    let encoded = render(&state);
+    for name in [
+        "cketh_minter_last_observed_block",
+        "cketh_minter_last_processed_block",
+        "cketh_minter_accepted_deposits",
+        "cketh_minter_mints",
+        "cketh_minter_provider_calls",
+    ] {
+        assert!(
+            encoded.contains(&format!("# HELP {name} ")),
+            "missing metric {name} in {encoded}"
+        );
+    }
+    for (outcome, count) in [("ok", 3), ("json_rpc_error", 1), ("http_outcall_error", 2)] {
+        let line = format!(
+            "cketh_minter_provider_calls{{provider=\"Ethereum(Ankr)\",outcome=\"{outcome}\"}} {count} {NOW_MILLIS}"
+        );
+        assert!(
+            encoded.lines().any(|l| l == line),
+            "missing line {line} in {encoded}"
+        );
+    }
+    assert!(encoded
+        .lines()
+        .any(|l| l == format!("cketh_minter_last_observed_block 4000000 {NOW_MILLIS}")));
+    assert!(encoded
+        .lines()
+        .any(|l| l == format!("cketh_minter_mints 0 {NOW_MILLIS}")));
+
+    mutate_metrics(|m| m.mints += 2);
+
+    let encoded = render(&state);
+    assert!(encoded
+        .lines()
+        .any(|l| l == format!("cketh_minter_mints 2 {NOW_MILLIS}")));
+}
+
+#[test]
+fn should_compute_quantiles_from_buckets() {
+    use crate::metrics::ResponseSizeHistogram;
+
+    let mut histogram = ResponseSizeHistogram::default();
+    assert_eq!(histogram.quantile(0.95), None);
+
+    for _ in 0..19 {
+        histogram.observe(100);
+    }
+    histogram.observe(5_000);
+
+    assert_eq!(histogram.count(), 20);
+    assert_eq!(histogram.quantile(0.5), Some(128));
+    assert_eq!(histogram.quantile(0.95), Some(128));
+    assert_eq!(histogram.quantile(1.0), Some(8_192));
+}
+
+#[test]
+fn should_compute_quantile_in_overflow_bucket() {
+    use crate::metrics::ResponseSizeHistogram;
+
+    let mut histogram = ResponseSizeHistogram::default();
+    histogram.observe(100);
+    histogram.observe(10_000_000);
+
+    assert_eq!(histogram.count(), 2);
+    assert_eq!(histogram.quantile(0.5), Some(128));
+    assert_eq!(histogram.quantile(1.0), Some(u64::MAX));
+}
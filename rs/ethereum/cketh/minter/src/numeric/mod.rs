@@ -32,8 +32,28 @@ pub enum TransactionCountTag {}
 pub type TransactionCount = CheckedAmountOf<TransactionCountTag>;
 
 pub enum BlockNumberTag {}
+/// Number of an Ethereum block, (de)serialized as a hexadecimal string (e.g. `"0x10eb3c6"`).
 pub type BlockNumber = CheckedAmountOf<BlockNumberTag>;
 
+impl BlockNumber {
+    /// Returns the number of the block `count` blocks after this one.
+    pub fn checked_add_blocks(self, count: u64) -> Option<Self> {
+        self.checked_add(Self::from(count))
+    }
+
+    /// Returns the number of the block `count` blocks before this one,
+    /// or the genesis block if the chain is not that long.
+    /// E.g., with `self` being the latest block, `self.saturating_sub_blocks(depth)`
+    /// is the most recent block with at least `depth` blocks on top of it.
+    pub fn saturating_sub_blocks(self, count: u64) -> Self {
+        self.checked_sub(Self::from(count)).unwrap_or(Self::ZERO)
+    }
+}
+
+pub enum ChainIdTag {}
+/// [EIP-155](https://eips.ethereum.org/EIPS/eip-155) chain id, as reported by the `eth_chainId` call.
+pub type ChainId = CheckedAmountOf<ChainIdTag>;
+
 pub enum EthLogIndexTag {}
 pub type LogIndex = CheckedAmountOf<EthLogIndexTag>;
 pub enum BurnIndexTag {}
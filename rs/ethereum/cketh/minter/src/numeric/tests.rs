@@ -74,3 +74,81 @@ mod wei {
         );
     }
 }
+
+mod block_number {
+    use crate::eth_rpc::{BlockSpec, BlockTag};
+    use crate::numeric::BlockNumber;
+    use proptest::{prelude::any, prop_assert_eq, proptest};
+    use std::str::FromStr;
+
+    proptest! {
+        #[test]
+        fn should_round_trip_through_hex(n in any::<u128>()) {
+            let block_number = BlockNumber::new(n);
+            let serialized = serde_json::to_string(&block_number).unwrap();
+            prop_assert_eq!(
+                serde_json::from_str::<BlockNumber>(&serialized).unwrap(),
+                block_number
+            );
+            prop_assert_eq!(
+                BlockNumber::from_str_hex(&format!("{:#x}", n)).unwrap(),
+                block_number
+            );
+        }
+    }
+
+    #[test]
+    fn should_deserialize_from_hex() {
+        assert_eq!(
+            serde_json::from_str::<BlockNumber>("\"0x10eb3c6\"").unwrap(),
+            BlockNumber::new(0x10eb3c6)
+        );
+    }
+
+    #[test]
+    fn should_saturate_when_subtracting_more_blocks_than_available() {
+        assert_eq!(
+            BlockNumber::new(100).saturating_sub_blocks(12),
+            BlockNumber::new(88)
+        );
+        assert_eq!(
+            BlockNumber::new(12).saturating_sub_blocks(12),
+            BlockNumber::ZERO
+        );
+        assert_eq!(
+            BlockNumber::new(5).saturating_sub_blocks(12),
+            BlockNumber::ZERO
+        );
+        assert_eq!(
+            BlockNumber::ZERO.saturating_sub_blocks(1),
+            BlockNumber::ZERO
+        );
+    }
+
+    #[test]
+    fn should_not_overflow_when_adding_blocks() {
+        assert_eq!(
+            BlockNumber::new(1).checked_add_blocks(1024),
+            Some(BlockNumber::new(1025))
+        );
+        assert_eq!(BlockNumber::MAX.checked_add_blocks(1), None);
+    }
+
+    #[test]
+    fn should_convert_to_and_from_block_spec() {
+        let block_number = BlockNumber::new(0x3ca487);
+
+        assert_eq!(
+            BlockSpec::from(block_number),
+            BlockSpec::from_str("0x3ca487").unwrap()
+        );
+        assert_eq!(
+            BlockNumber::try_from(BlockSpec::from(block_number)),
+            Ok(block_number)
+        );
+        assert_eq!(
+            BlockNumber::try_from(BlockSpec::Tag(BlockTag::Finalized)),
+            Err(BlockTag::Finalized)
+        );
+    }
+}
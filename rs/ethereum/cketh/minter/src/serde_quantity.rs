@@ -0,0 +1,79 @@
+//! Deserialization of Ethereum QUANTITY values that tolerates non-conforming providers.
+//! Quantities are specified as `0x`-prefixed hex strings, but some providers return them as
+//! decimal strings or as JSON numbers, which would otherwise fail to deserialize.
+//! Encodings that are not human-readable, e.g. the CBOR-encoded minter state,
+//! are deserialized as before.
+use ethnum::u256;
+use serde::de::{Error, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+/// Deserializes a quantity encoded as a `0x`-prefixed hex string, a decimal string
+/// or a JSON number.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<u256, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if !deserializer.is_human_readable() {
+        return u256::deserialize(deserializer);
+    }
+    deserializer.deserialize_any(QuantityVisitor)
+}
+
+/// Same as [`deserialize`] for an optional quantity.
+pub fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<u256>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Quantity(#[serde(deserialize_with = "deserialize")] u256);
+
+    Option::<Quantity>::deserialize(deserializer).map(|quantity| quantity.map(|q| q.0))
+}
+
+struct QuantityVisitor;
+
+impl<'de> Visitor<'de> for QuantityVisitor {
+    type Value = u256;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a hex-encoded QUANTITY string, a decimal string or a non-negative integer"
+        )
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        if value.starts_with("0x") {
+            u256::from_str_hex(value).map_err(Error::custom)
+        } else {
+            u256::from_str_radix(value, 10).map_err(Error::custom)
+        }
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(u256::from(value))
+    }
+
+    fn visit_u128<E>(self, value: u128) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(u256::from(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        u64::try_from(value)
+            .map(u256::from)
+            .map_err(|_| Error::custom(format!("negative QUANTITY {value}")))
+    }
+}
@@ -1,15 +1,30 @@
 use crate::address::Address;
-use crate::eth_logs::{EventSource, EventSourceError, ReceivedEthEvent};
-use crate::eth_rpc::BlockTag;
+use crate::endpoints::{RpcFailure, Solvency};
+use crate::eth_logs::{
+    DepositEventAbi, DepositEventConfig, EventSource, EventSourceError, ReceivedEthEvent,
+};
+use crate::eth_rpc::{
+    BlockTag, FixedSizeData, HttpOutcallResult, JsonRpcResult, ResponseSizeEstimate,
+    MAX_PAYLOAD_SIZE,
+};
+use crate::eth_rpc_client::circuit_breaker::{
+    CircuitBreaker, CircuitState, COOLDOWN, FAILURE_THRESHOLD,
+};
+use crate::eth_rpc_client::{
+    group_providers, parse_provider_override, ProviderGroup, ReductionStrategy, RpcNodeProvider,
+};
 use crate::lifecycle::upgrade::UpgradeArg;
 use crate::lifecycle::EthereumNetwork;
-use crate::logs::DEBUG;
+use crate::logs::{DEBUG, INFO};
+use crate::metrics::ResponseSizeHistogram;
 use crate::numeric::{BlockNumber, LedgerMintIndex, TransactionNonce, Wei};
 use crate::transactions::EthTransactions;
+use crate::tx::{AccessList, Eip1559TransactionRequest, TransactionPrice};
 use candid::Principal;
 use ic_canister_log::log;
 use ic_cdk::api::management_canister::ecdsa::EcdsaPublicKeyResponse;
 use ic_crypto_ecdsa_secp256k1::PublicKey;
+use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::collections::{btree_map, BTreeMap, BTreeSet, HashSet};
@@ -50,6 +65,56 @@ pub struct State {
     pub invalid_events: BTreeMap<EventSource, EventSourceError>,
     pub eth_transactions: EthTransactions,
     pub next_transaction_nonce: TransactionNonce,
+    /// The deposit event scraped from the helper smart contract logs.
+    #[serde(default)]
+    pub deposit_event: DepositEventConfig,
+    /// When the cycle balance of the minter is below this threshold, queries that would
+    /// be sent to all providers in parallel are sent to one provider at a time instead.
+    /// The parallel queries are never degraded if `None`.
+    #[serde(default)]
+    pub sequential_fallback_cycles_threshold: Option<u128>,
+    /// The minimum number of confirmations a block must have before its deposits are minted,
+    /// on top of being at or below the block with the `ethereum_block_height` tag.
+    /// Only the block tag is taken into account if `None`.
+    #[serde(default)]
+    pub min_confirmations: Option<u64>,
+    /// The minimum number of JSON-RPC providers that must agree on the deposit logs and the
+    /// transaction receipts, so that a single lagging provider does not halt minting.
+    /// All providers must agree if `None`. Always a strict majority of the queried providers,
+    /// see [`validate_min_provider_agreement`].
+    #[serde(default)]
+    pub min_provider_agreement: Option<u64>,
+    /// Response size estimates tuned from the observed response sizes, by JSON-RPC method.
+    /// Methods without an entry use their static estimate.
+    #[serde(default)]
+    pub response_size_estimates: BTreeMap<String, u64>,
+    /// Outcomes of the calls to the JSON-RPC providers, by provider label (see
+    /// [`RpcNodeProvider::metric_label`]), so that API keys are never exposed.
+    #[serde(default)]
+    pub provider_call_counters: BTreeMap<String, ProviderCallCounters>,
+    /// Consecutive failures of the JSON-RPC providers, to skip the ones that keep failing.
+    /// Not persisted: all providers are queried again after an upgrade.
+    #[serde(skip)]
+    pub circuit_breaker: CircuitBreaker,
+    /// Why the responses of the JSON-RPC providers could not be aggregated the last time it
+    /// happened, for troubleshooting. Not persisted.
+    #[serde(skip)]
+    pub last_rpc_failure: Option<RpcFailure>,
+    /// The ETH balance of the minter and the ckETH supply, as last fetched by the periodic
+    /// solvency check. Not persisted: they are fetched again after an upgrade.
+    #[serde(skip)]
+    pub solvency_snapshot: Option<SolvencySnapshot>,
+    /// The group of JSON-RPC providers the minter queries.
+    #[serde(default)]
+    pub active_provider_group: ProviderGroup,
+    /// The JSON-RPC providers queried instead of the ones of `active_provider_group`, if set,
+    /// e.g. to stop querying a provider that went offline without upgrading the minter.
+    #[serde(default)]
+    pub(crate) provider_override: Option<Vec<RpcNodeProvider>>,
+    /// The number of blocks the scanning of deposits may lag behind the latest block before
+    /// it is reported as unhealthy. Scanning is always reported as healthy if `None`.
+    #[serde(default)]
+    pub max_scanning_lag: Option<u64>,
 
     /// Per-principal lock for pending_retrieve_eth_requests
     #[serde(skip)]
@@ -65,6 +130,23 @@ pub struct State {
     pub http_request_counter: u64,
 }
 
+/// How far the scanning of deposits lags behind the head of the chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanningProgress {
+    pub last_scanned_block: BlockNumber,
+    pub latest_block: BlockNumber,
+    pub blocks_behind: BlockNumber,
+    /// Whether `blocks_behind` is within the configured maximum scanning lag.
+    pub healthy: bool,
+}
+
+/// The ETH balance of the minter and the ckETH supply at a given time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolvencySnapshot {
+    pub eth_balance: Wei,
+    pub cketh_supply: Wei,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum InvalidStateError {
     InvalidTransactionNonce(String),
@@ -72,6 +154,9 @@ pub enum InvalidStateError {
     InvalidLedgerId(String),
     InvalidEthereumContractAddress(String),
     InvalidMinimumWithdrawalAmount(String),
+    InvalidDepositEventTopic(String),
+    InvalidSequentialFallbackCyclesThreshold(String),
+    InvalidMinProviderAgreement(String),
 }
 
 impl State {
@@ -100,9 +185,29 @@ impl State {
                 "minimum_withdrawal_amount must be positive".to_string(),
             ));
         }
+        validate_min_provider_agreement(self.min_provider_agreement, self.num_providers())
+            .map_err(InvalidStateError::InvalidMinProviderAgreement)?;
         Ok(())
     }
 
+    /// The number of JSON-RPC providers queried by the minter, i.e. the ones of the provider
+    /// override if set, or else the ones of the active provider group.
+    pub fn num_providers(&self) -> usize {
+        match &self.provider_override {
+            Some(providers) => providers.len(),
+            None => group_providers(self.ethereum_network, self.active_provider_group).len(),
+        }
+    }
+
+    /// How the results of the JSON-RPC providers are reduced for the deposit logs and the
+    /// transaction receipts, see [`Self::min_provider_agreement`].
+    pub fn reduction_strategy(&self) -> ReductionStrategy {
+        match self.min_provider_agreement {
+            Some(min_agreement) => ReductionStrategy::Threshold(min_agreement as usize),
+            None => ReductionStrategy::Equality,
+        }
+    }
+
     pub fn minter_address(&self) -> Option<Address> {
         let pubkey = PublicKey::deserialize_sec1(&self.ecdsa_public_key.as_ref()?.public_key)
             .unwrap_or_else(|e| {
@@ -125,6 +230,50 @@ impl State {
         self.events_to_mint.insert(event);
     }
 
+    /// Returns the deposits that will be credited: the ones already waiting to be minted and the
+    /// newly `scraped` ones that the minter does not know about yet, without recording anything.
+    pub fn preview_deposits(&self, scraped: Vec<ReceivedEthEvent>) -> BTreeSet<ReceivedEthEvent> {
+        let mut deposits = self.events_to_mint.clone();
+        for event in scraped {
+            let source = event.source();
+            if self.minted_events.contains_key(&source) || self.invalid_events.contains_key(&source)
+            {
+                continue;
+            }
+            deposits.insert(event);
+        }
+        deposits
+    }
+
+    /// Records the deposits among the rescanned `scraped` ones that the minter does not know
+    /// about, i.e., that were neither minted, nor are waiting to be minted, nor were found to be
+    /// invalid, and returns them. Since every other deposit is skipped, reprocessing blocks whose
+    /// deposits were already processed records nothing and no deposit is ever minted twice.
+    pub fn record_missed_deposits(
+        &mut self,
+        scraped: Vec<ReceivedEthEvent>,
+    ) -> Vec<ReceivedEthEvent> {
+        let mut missed = vec![];
+        for event in scraped {
+            let source = event.source();
+            if self.minted_events.contains_key(&source)
+                || self.invalid_events.contains_key(&source)
+                || self.events_to_mint.iter().any(|e| e.source() == source)
+            {
+                continue;
+            }
+            log!(
+                INFO,
+                "[record_missed_deposits]: recovered missed deposit {event:?}; will mint {} wei to {}",
+                event.value,
+                event.principal
+            );
+            self.record_event_to_mint(event.clone());
+            missed.push(event);
+        }
+        missed
+    }
+
     pub fn record_invalid_deposit(&mut self, source: EventSource, error: EventSourceError) -> bool {
         debug_assert!(
             self.events_to_mint.iter().all(|e| e.source() != source),
@@ -162,7 +311,21 @@ impl State {
         );
     }
 
-    pub fn get_and_increment_nonce(&mut self) -> TransactionNonce {
+    /// Returns the minted deposit that was credited in the given ckETH ledger block, if any.
+    /// Together with `minted_events`, which is keyed by the deposit source, this allows to trace
+    /// a mint back to its Ethereum deposit and vice versa.
+    pub fn find_minted_event(&self, mint_block_index: LedgerMintIndex) -> Option<&MintedEvent> {
+        self.minted_events
+            .values()
+            .find(|minted| minted.mint_block_index == mint_block_index)
+    }
+
+    /// Reserves the nonce of the next transaction to be created.
+    ///
+    /// Nonces are allocated locally and strictly increase, so that two transactions can never
+    /// be assigned the same nonce, even if some JSON-RPC providers lag behind and report a stale
+    /// transaction count. The counter is only ever moved forward by [`State::reconcile_nonce`].
+    pub fn reserve_nonce(&mut self) -> TransactionNonce {
         let current_nonce = self.next_transaction_nonce;
         self.next_transaction_nonce = self
             .next_transaction_nonce
@@ -171,6 +334,27 @@ impl State {
         current_nonce
     }
 
+    /// Creates the transaction sending `amount` to `destination` with the next nonce, on the
+    /// chain of the minter's network as required by EIP-155 replay protection.
+    pub fn create_transaction(
+        &mut self,
+        destination: Address,
+        amount: Wei,
+        transaction_price: &TransactionPrice,
+    ) -> Eip1559TransactionRequest {
+        Eip1559TransactionRequest {
+            chain_id: self.ethereum_network.chain_id(),
+            nonce: self.reserve_nonce(),
+            max_priority_fee_per_gas: transaction_price.max_priority_fee_per_gas,
+            max_fee_per_gas: transaction_price.max_fee_per_gas,
+            gas_limit: transaction_price.gas_limit,
+            destination,
+            amount,
+            data: Vec::new(),
+            access_list: AccessList::new(),
+        }
+    }
+
     pub fn next_request_id(&mut self) -> u64 {
         let current_request_id = self.http_request_counter;
         // overflow is not an issue here because we only use `next_request_id` to correlate
@@ -179,10 +363,26 @@ impl State {
         current_request_id
     }
 
-    pub fn update_next_transaction_nonce(&mut self, new_nonce: TransactionNonce) {
-        self.next_transaction_nonce = new_nonce;
+    /// Reconciles the local nonce counter with the transaction count observed on chain.
+    ///
+    /// The counter is only raised, never lowered: a chain nonce below the next local nonce
+    /// means that either the reserved nonces are not yet mined or the provider is lagging,
+    /// and in both cases reusing a nonce would risk replacing a pending transaction.
+    /// Returns `true` if the counter was updated.
+    pub fn reconcile_nonce(&mut self, chain_nonce: TransactionNonce) -> bool {
+        if chain_nonce <= self.next_transaction_nonce {
+            return false;
+        }
+        log!(
+            DEBUG,
+            "[reconcile_nonce]: raising next transaction nonce from {:?} to {:?}",
+            self.next_transaction_nonce,
+            chain_nonce
+        );
+        self.next_transaction_nonce = chain_nonce;
         self.eth_transactions
-            .update_next_transaction_nonce(new_nonce);
+            .update_next_transaction_nonce(chain_nonce);
+        true
     }
 
     pub const fn ethereum_network(&self) -> EthereumNetwork {
@@ -193,6 +393,210 @@ impl State {
         self.ethereum_block_height
     }
 
+    /// Returns the highest block whose deposits can be minted, i.e., the highest block that is
+    /// both at or below `tagged_block` (the block with the `ethereum_block_height` tag, e.g.
+    /// the finalized head) and at least `min_confirmations` deep with respect to `latest_block`.
+    /// The latest block counts as the first confirmation of itself.
+    pub fn last_mintable_block_number(
+        &self,
+        tagged_block: BlockNumber,
+        latest_block: BlockNumber,
+    ) -> BlockNumber {
+        match self.min_confirmations {
+            None | Some(0) => tagged_block,
+            Some(min_confirmations) => {
+                let deep_enough = latest_block.saturating_sub_blocks(min_confirmations - 1);
+                tagged_block.min(deep_enough)
+            }
+        }
+    }
+
+    /// Returns the last block whose deposits were scanned.
+    pub fn last_scanned_block(&self) -> BlockNumber {
+        self.last_scraped_block_number
+    }
+
+    /// Returns how far the scanning of deposits lags behind `latest_block`,
+    /// the number of the last observed block.
+    pub fn scanning_progress(&self, latest_block: BlockNumber) -> ScanningProgress {
+        let last_scanned_block = self.last_scanned_block();
+        let blocks_behind = latest_block
+            .checked_sub(last_scanned_block)
+            .unwrap_or(BlockNumber::ZERO);
+        let healthy = self
+            .max_scanning_lag
+            .map_or(true, |max_lag| blocks_behind <= BlockNumber::from(max_lag));
+        ScanningProgress {
+            last_scanned_block,
+            latest_block,
+            blocks_behind,
+            healthy,
+        }
+    }
+
+    /// Returns the tuned response size estimate for the given JSON-RPC method,
+    /// or `default` if the method was not tuned yet.
+    /// The tuned estimate may be lower than `default`, to avoid paying for unused bytes.
+    pub fn response_size_estimate(
+        &self,
+        method: &str,
+        default: ResponseSizeEstimate,
+    ) -> ResponseSizeEstimate {
+        self.response_size_estimates
+            .get(method)
+            .map(|num_bytes| ResponseSizeEstimate::new(*num_bytes))
+            .unwrap_or(default)
+    }
+
+    /// Remembers why the responses of the JSON-RPC providers could not be aggregated,
+    /// so that it can be queried with `last_rpc_failure`.
+    pub fn record_rpc_failure(&mut self, failure: RpcFailure) {
+        log!(INFO, "[record_rpc_failure]: {failure:?}");
+        self.last_rpc_failure = Some(failure);
+    }
+
+    /// Compares the last fetched ETH balance and ckETH supply against the current pending
+    /// withdrawals, or returns `None` if they were not fetched yet.
+    pub fn solvency(&self) -> Option<Solvency> {
+        self.solvency_snapshot.as_ref().map(|snapshot| {
+            Solvency::new(
+                snapshot.eth_balance,
+                snapshot.cketh_supply,
+                self.eth_transactions.pending_withdrawal_amount(),
+            )
+        })
+    }
+
+    /// Counts the outcome of a call to the given JSON-RPC provider.
+    pub(crate) fn record_provider_call<T>(
+        &mut self,
+        provider: &RpcNodeProvider,
+        result: &HttpOutcallResult<JsonRpcResult<T>>,
+    ) {
+        let counters = self
+            .provider_call_counters
+            .entry(provider.metric_label())
+            .or_default();
+        match result {
+            Ok(JsonRpcResult::Result(_)) => counters.ok += 1,
+            Ok(JsonRpcResult::Error { .. }) => counters.json_rpc_error += 1,
+            Err(_) => counters.http_outcall_error += 1,
+        }
+    }
+
+    /// Updates the circuit breaker of the given provider with the outcome of a call to it,
+    /// made at the given time in nanoseconds since the Unix epoch.
+    /// Only failed HTTPS outcalls count as failures: a JSON-RPC error is a reply.
+    pub(crate) fn record_provider_availability<T>(
+        &mut self,
+        provider: &RpcNodeProvider,
+        result: &HttpOutcallResult<JsonRpcResult<T>>,
+        now: u64,
+    ) {
+        match result {
+            Ok(_) => self.circuit_breaker.record_success(provider.clone()),
+            Err(_) => {
+                let was_open = self.circuit_breaker.state(provider, now) == CircuitState::Open;
+                self.circuit_breaker.record_failure(provider.clone(), now);
+                if !was_open && self.circuit_breaker.state(provider, now) == CircuitState::Open {
+                    log!(
+                        INFO,
+                        "[record_provider_availability]: skipping provider {provider:?} for {COOLDOWN:?} after at least {FAILURE_THRESHOLD} consecutive failures",
+                    );
+                }
+            }
+        }
+    }
+
+    /// Sets the response size estimate for the given JSON-RPC method to the 95th percentile of
+    /// the observed response sizes, plus some headroom and capped at `MAX_PAYLOAD_SIZE`.
+    /// Nothing is tuned until enough responses were observed for the percentile to be meaningful.
+    pub fn tune_response_size_estimate(&mut self, method: &str, sizes: &ResponseSizeHistogram) {
+        const MIN_OBSERVED_RESPONSES: u64 = 20;
+        if sizes.count() < MIN_OBSERVED_RESPONSES {
+            return;
+        }
+        if let Some(p95) = sizes.quantile(0.95) {
+            let estimate = p95.saturating_add(p95 / 5).clamp(1, MAX_PAYLOAD_SIZE);
+            self.response_size_estimates
+                .insert(method.to_string(), estimate);
+        }
+    }
+
+    /// Selects the group of JSON-RPC providers queried by the minter,
+    /// without changing the Ethereum network.
+    /// Fails without changing anything if `min_provider_agreement` cannot be met by the providers
+    /// of the group, see [`validate_min_provider_agreement`].
+    pub fn set_active_provider_group(
+        &mut self,
+        group: ProviderGroup,
+    ) -> Result<(), InvalidStateError> {
+        if self.provider_override.is_none() {
+            validate_min_provider_agreement(
+                self.min_provider_agreement,
+                group_providers(self.ethereum_network, group).len(),
+            )
+            .map_err(InvalidStateError::InvalidMinProviderAgreement)?;
+        }
+        if self.active_provider_group != group {
+            log!(
+                INFO,
+                "[set_active_provider_group]: switching JSON-RPC providers from {:?} to {:?}",
+                self.active_provider_group,
+                group
+            );
+        }
+        self.active_provider_group = group;
+        Ok(())
+    }
+
+    /// Sets the URLs of the JSON-RPC providers queried instead of the ones of the active provider
+    /// group, or goes back to the active provider group if `urls` is `None`.
+    /// The URLs must be valid, see [`parse_provider_override`], and `min_provider_agreement` must
+    /// be achievable by the resulting providers, see [`validate_min_provider_agreement`].
+    /// Whether the providers serve the minter's network can only be checked by querying them.
+    pub fn set_provider_override(&mut self, urls: Option<Vec<String>>) -> Result<(), String> {
+        let providers = urls
+            .map(|urls| parse_provider_override(&urls))
+            .transpose()?;
+        let num_providers = match &providers {
+            Some(providers) => providers.len(),
+            None => group_providers(self.ethereum_network, self.active_provider_group).len(),
+        };
+        validate_min_provider_agreement(self.min_provider_agreement, num_providers)?;
+        log!(
+            INFO,
+            "[set_provider_override]: switching JSON-RPC provider override from {:?} to {:?}",
+            self.provider_override,
+            providers
+        );
+        self.provider_override = providers;
+        Ok(())
+    }
+
+    /// Scrapes the deposit events with the given topic, i.e. the hex-encoded hash of the event
+    /// signature, and layout instead of the current ones, e.g. after migrating to a helper smart
+    /// contract emitting different events.
+    pub fn set_deposit_event(
+        &mut self,
+        topic: &str,
+        abi: DepositEventAbi,
+    ) -> Result<(), InvalidStateError> {
+        use std::str::FromStr;
+
+        let topic = FixedSizeData::from_str(topic)
+            .map_err(|e| InvalidStateError::InvalidDepositEventTopic(format!("ERROR: {}", e)))?;
+        let deposit_event = DepositEventConfig { topic, abi };
+        log!(
+            INFO,
+            "[set_deposit_event]: switching deposit event from {:?} to {:?}",
+            self.deposit_event,
+            deposit_event
+        );
+        self.deposit_event = deposit_event;
+        Ok(())
+    }
+
     pub fn upgrade(&mut self, upgrade_args: UpgradeArg) -> Result<(), InvalidStateError> {
         use std::str::FromStr;
 
@@ -201,6 +605,13 @@ impl State {
             minimum_withdrawal_amount,
             ethereum_contract_address,
             ethereum_block_height,
+            deposit_event_topic,
+            deposit_event_abi,
+            sequential_fallback_cycles_threshold,
+            min_confirmations,
+            active_provider_group,
+            max_scanning_lag,
+            min_provider_agreement,
         } = upgrade_args;
         if let Some(nonce) = next_transaction_nonce {
             let nonce = TransactionNonce::try_from(nonce)
@@ -223,10 +634,75 @@ impl State {
         if let Some(block_height) = ethereum_block_height {
             self.ethereum_block_height = block_height.into();
         }
+        if let Some(topic) = deposit_event_topic {
+            self.deposit_event.topic = FixedSizeData::from_str(&topic).map_err(|e| {
+                InvalidStateError::InvalidDepositEventTopic(format!("ERROR: {}", e))
+            })?;
+        }
+        if let Some(abi) = deposit_event_abi {
+            self.deposit_event.abi = abi;
+        }
+        if let Some(threshold) = sequential_fallback_cycles_threshold {
+            let threshold = threshold.0.to_u128().ok_or_else(|| {
+                InvalidStateError::InvalidSequentialFallbackCyclesThreshold(format!(
+                    "ERROR: {} does not fit into u128",
+                    threshold
+                ))
+            })?;
+            self.sequential_fallback_cycles_threshold = Some(threshold);
+        }
+        if let Some(min_confirmations) = min_confirmations {
+            self.min_confirmations = Some(min_confirmations);
+        }
+        if let Some(max_scanning_lag) = max_scanning_lag {
+            self.max_scanning_lag = Some(max_scanning_lag);
+        }
+        if let Some(min_provider_agreement) = min_provider_agreement {
+            self.min_provider_agreement = Some(min_provider_agreement);
+        }
+        if let Some(group) = active_provider_group {
+            self.set_active_provider_group(group)?;
+        }
         self.validate_config()
     }
 }
 
+/// Checks that `min_provider_agreement`, if set, is a strict majority of the `num_providers`
+/// queried providers: fewer would let a minority of the providers decide, and more could
+/// never be met.
+pub(crate) fn validate_min_provider_agreement(
+    min_provider_agreement: Option<u64>,
+    num_providers: usize,
+) -> Result<(), String> {
+    let min_agreement = match min_provider_agreement {
+        Some(min_agreement) => min_agreement,
+        None => return Ok(()),
+    };
+    let num_providers = num_providers as u64;
+    if min_agreement > num_providers {
+        return Err(format!(
+            "min_provider_agreement {min_agreement} exceeds the number of providers {num_providers}"
+        ));
+    }
+    if 2 * min_agreement <= num_providers {
+        return Err(format!(
+            "min_provider_agreement {min_agreement} is not a strict majority of the {num_providers} providers"
+        ));
+    }
+    Ok(())
+}
+
+/// Outcomes of the calls to a JSON-RPC provider.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderCallCounters {
+    /// Number of calls that returned a result.
+    pub ok: u64,
+    /// Number of calls that returned a JSON-RPC error.
+    pub json_rpc_error: u64,
+    /// Number of calls whose HTTPS outcall failed.
+    pub http_outcall_error: u64,
+}
+
 pub fn read_state<R>(f: impl FnOnce(&State) -> R) -> R {
     STATE.with(|s| f(s.borrow().as_ref().expect("BUG: state is not initialized")))
 }
@@ -25,6 +25,484 @@ mod next_request_id {
     }
 }
 
+mod nonce {
+    use crate::numeric::TransactionNonce;
+    use crate::state::tests::a_state;
+    use crate::transactions::EthTransactions;
+
+    #[test]
+    fn should_reserve_distinct_nonces() {
+        let mut state = a_state();
+
+        let first = state.reserve_nonce();
+        let second = state.reserve_nonce();
+
+        assert_eq!(first, TransactionNonce::ZERO);
+        assert_eq!(second, TransactionNonce::ONE);
+        assert_eq!(state.next_transaction_nonce, TransactionNonce::from(2_u64));
+    }
+
+    #[test]
+    fn should_raise_nonce_when_chain_is_ahead() {
+        let mut state = a_state();
+        let _ = state.reserve_nonce();
+
+        assert!(state.reconcile_nonce(TransactionNonce::from(10_u64)));
+
+        assert_eq!(state.next_transaction_nonce, TransactionNonce::from(10_u64));
+        assert_eq!(
+            state.eth_transactions,
+            EthTransactions::new(TransactionNonce::from(10_u64))
+        );
+        assert_eq!(state.reserve_nonce(), TransactionNonce::from(10_u64));
+    }
+
+    #[test]
+    fn should_never_lower_nonce() {
+        let mut state = a_state();
+        for _ in 0..5 {
+            let _ = state.reserve_nonce();
+        }
+        let expected_state = state.eth_transactions.clone();
+
+        assert!(!state.reconcile_nonce(TransactionNonce::from(3_u64)));
+        assert!(!state.reconcile_nonce(TransactionNonce::from(5_u64)));
+
+        assert_eq!(state.next_transaction_nonce, TransactionNonce::from(5_u64));
+        assert_eq!(state.eth_transactions, expected_state);
+        assert_eq!(state.reserve_nonce(), TransactionNonce::from(5_u64));
+    }
+}
+
+mod create_transaction {
+    use crate::address::Address;
+    use crate::eth_rpc::Quantity;
+    use crate::lifecycle::EthereumNetwork;
+    use crate::numeric::{TransactionNonce, Wei};
+    use crate::state::tests::a_state;
+    use crate::tx::{Eip1559Signature, SignedEip1559TransactionRequest, TransactionPrice};
+
+    #[test]
+    fn should_sign_transaction_for_chain_of_minter_network() {
+        let price = TransactionPrice {
+            gas_limit: Quantity::new(21_000),
+            max_fee_per_gas: Wei::new(0x598653cd),
+            max_priority_fee_per_gas: Wei::new(0x59682f00),
+        };
+        for network in [EthereumNetwork::Mainnet, EthereumNetwork::Sepolia] {
+            let mut state = a_state();
+            state.ethereum_network = network;
+
+            let transaction =
+                state.create_transaction(Address::new([1; 20]), Wei::new(1_000), &price);
+            assert_eq!(transaction.nonce, TransactionNonce::ZERO);
+            let raw_transaction =
+                SignedEip1559TransactionRequest::from((transaction, Eip1559Signature::default()))
+                    .raw_bytes();
+
+            // 0x02 || rlp([chain_id, nonce, ...])
+            assert_eq!(raw_transaction[0], 0x02);
+            let chain_id: u64 = rlp::Rlp::new(&raw_transaction[1..]).val_at(0).unwrap();
+            assert_eq!(chain_id, network.chain_id());
+        }
+    }
+}
+
+mod last_mintable_block_number {
+    use crate::numeric::BlockNumber;
+    use crate::state::tests::a_state;
+
+    #[test]
+    fn should_only_rely_on_block_tag_by_default() {
+        let state = a_state();
+
+        assert_eq!(
+            state.last_mintable_block_number(BlockNumber::new(100), BlockNumber::new(101)),
+            BlockNumber::new(100)
+        );
+    }
+
+    #[test]
+    fn should_not_mint_deposit_confirmed_by_count_but_not_finalized() {
+        let mut state = a_state();
+        state.min_confirmations = Some(12);
+        let finalized_block = BlockNumber::new(100);
+        let latest_block = BlockNumber::new(200);
+        let deposit_block = BlockNumber::new(150);
+
+        let last_mintable = state.last_mintable_block_number(finalized_block, latest_block);
+
+        assert_eq!(last_mintable, finalized_block);
+        assert!(deposit_block > last_mintable);
+    }
+
+    #[test]
+    fn should_not_mint_deposit_finalized_but_not_confirmed_by_count() {
+        let mut state = a_state();
+        state.min_confirmations = Some(12);
+        let finalized_block = BlockNumber::new(100);
+        let latest_block = BlockNumber::new(105);
+        let deposit_block = BlockNumber::new(98);
+
+        let last_mintable = state.last_mintable_block_number(finalized_block, latest_block);
+
+        // block 94 has 12 confirmations: blocks 94 to 105
+        assert_eq!(last_mintable, BlockNumber::new(94));
+        assert!(deposit_block > last_mintable);
+    }
+
+    #[test]
+    fn should_not_underflow_when_chain_is_too_short() {
+        let mut state = a_state();
+        state.min_confirmations = Some(12);
+
+        assert_eq!(
+            state.last_mintable_block_number(BlockNumber::new(5), BlockNumber::new(5)),
+            BlockNumber::ZERO
+        );
+    }
+}
+
+mod scanning_progress {
+    use crate::numeric::BlockNumber;
+    use crate::state::tests::a_state;
+    use crate::state::ScanningProgress;
+
+    #[test]
+    fn should_report_lag_between_cursor_and_head() {
+        let mut state = a_state();
+        state.last_scraped_block_number = BlockNumber::new(4_000_000);
+        state.max_scanning_lag = Some(100);
+
+        assert_eq!(state.last_scanned_block(), BlockNumber::new(4_000_000));
+        assert_eq!(
+            state.scanning_progress(BlockNumber::new(4_000_042)),
+            ScanningProgress {
+                last_scanned_block: BlockNumber::new(4_000_000),
+                latest_block: BlockNumber::new(4_000_042),
+                blocks_behind: BlockNumber::new(42),
+                healthy: true,
+            }
+        );
+    }
+
+    #[test]
+    fn should_be_unhealthy_when_lagging_too_far_behind() {
+        let mut state = a_state();
+        state.last_scraped_block_number = BlockNumber::new(4_000_000);
+        state.max_scanning_lag = Some(100);
+
+        assert!(state.scanning_progress(BlockNumber::new(4_000_100)).healthy);
+        let progress = state.scanning_progress(BlockNumber::new(4_000_101));
+        assert_eq!(progress.blocks_behind, BlockNumber::new(101));
+        assert!(!progress.healthy);
+
+        state.max_scanning_lag = None;
+        assert!(state.scanning_progress(BlockNumber::new(5_000_000)).healthy);
+    }
+}
+
+mod solvency {
+    use crate::address::Address;
+    use crate::endpoints::SolvencyBalance;
+    use crate::numeric::{LedgerBurnIndex, Wei};
+    use crate::state::tests::a_state;
+    use crate::state::SolvencySnapshot;
+    use crate::transactions::EthWithdrawalRequest;
+    use candid::Nat;
+    use std::str::FromStr;
+
+    #[test]
+    fn should_be_unknown_until_snapshot_is_fetched() {
+        assert_eq!(a_state().solvency(), None);
+    }
+
+    #[test]
+    fn should_subtract_current_pending_withdrawals_from_snapshot() {
+        let mut state = a_state();
+        state.solvency_snapshot = Some(SolvencySnapshot {
+            eth_balance: Wei::new(10_000_000_000_000_000_000),
+            cketh_supply: Wei::new(8_000_000_000_000_000_000),
+        });
+        assert_eq!(
+            state.solvency().unwrap().balance,
+            SolvencyBalance::Surplus(Nat::from(2_000_000_000_000_000_000_u64))
+        );
+
+        state
+            .eth_transactions
+            .record_withdrawal_request(EthWithdrawalRequest {
+                withdrawal_amount: Wei::new(1_500_000_000_000_000_000),
+                destination: Address::from_str("0xb44B5e756A894775FC32EDdf3314Bb1B1944dC34")
+                    .unwrap(),
+                ledger_burn_index: LedgerBurnIndex::new(7),
+            });
+        let solvency = state.solvency().unwrap();
+        assert_eq!(
+            solvency.pending_withdrawals,
+            Nat::from(1_500_000_000_000_000_000_u64)
+        );
+        assert_eq!(
+            solvency.balance,
+            SolvencyBalance::Surplus(Nat::from(500_000_000_000_000_000_u64))
+        );
+    }
+}
+
+mod response_size_estimate {
+    use crate::eth_rpc::{ResponseSizeEstimate, MAX_PAYLOAD_SIZE};
+    use crate::metrics::ResponseSizeHistogram;
+    use crate::state::tests::a_state;
+
+    const METHOD: &str = "eth_getTransactionReceipt";
+
+    #[test]
+    fn should_use_default_estimate_until_enough_responses_observed() {
+        let mut state = a_state();
+        let mut sizes = ResponseSizeHistogram::default();
+        for _ in 0..19 {
+            sizes.observe(600);
+        }
+
+        state.tune_response_size_estimate(METHOD, &sizes);
+
+        assert_eq!(
+            state.response_size_estimate(METHOD, ResponseSizeEstimate::new(700)),
+            ResponseSizeEstimate::new(700)
+        );
+    }
+
+    #[test]
+    fn should_grow_estimate_towards_p95_of_observed_sizes() {
+        let mut state = a_state();
+        let mut sizes = ResponseSizeHistogram::default();
+        for _ in 0..20 {
+            sizes.observe(600);
+        }
+        state.tune_response_size_estimate(METHOD, &sizes);
+        // 20% of headroom above the 1024 bytes bucket.
+        assert_eq!(
+            state.response_size_estimate(METHOD, ResponseSizeEstimate::new(700)),
+            ResponseSizeEstimate::new(1228)
+        );
+
+        // A few larger responses don't move the 95th percentile...
+        sizes.observe(3_000);
+        state.tune_response_size_estimate(METHOD, &sizes);
+        assert_eq!(
+            state.response_size_estimate(METHOD, ResponseSizeEstimate::new(700)),
+            ResponseSizeEstimate::new(1228)
+        );
+
+        // ...but once they are more than 5% of the responses, the estimate grows.
+        for _ in 0..10 {
+            sizes.observe(3_000);
+        }
+        state.tune_response_size_estimate(METHOD, &sizes);
+        assert_eq!(
+            state.response_size_estimate(METHOD, ResponseSizeEstimate::new(700)),
+            ResponseSizeEstimate::new(4915)
+        );
+
+        // Other methods are not affected.
+        assert_eq!(
+            state.response_size_estimate("eth_getLogs", ResponseSizeEstimate::new(100)),
+            ResponseSizeEstimate::new(100)
+        );
+    }
+
+    #[test]
+    fn should_shrink_estimate_below_default_for_small_responses() {
+        let mut state = a_state();
+        let mut sizes = ResponseSizeHistogram::default();
+        for _ in 0..20 {
+            sizes.observe(40);
+        }
+
+        state.tune_response_size_estimate("eth_getLogs", &sizes);
+
+        // 20% of headroom above the 128 bytes bucket.
+        assert_eq!(
+            state.response_size_estimate("eth_getLogs", ResponseSizeEstimate::new(1_000)),
+            ResponseSizeEstimate::new(153)
+        );
+    }
+
+    #[test]
+    fn should_cap_estimate_at_max_payload_size() {
+        let mut state = a_state();
+        let mut sizes = ResponseSizeHistogram::default();
+        for _ in 0..20 {
+            sizes.observe(10_000_000);
+        }
+
+        state.tune_response_size_estimate(METHOD, &sizes);
+
+        assert_eq!(
+            state.response_size_estimate(METHOD, ResponseSizeEstimate::new(700)),
+            ResponseSizeEstimate::new(MAX_PAYLOAD_SIZE)
+        );
+    }
+}
+
+mod provider_call_counters {
+    use crate::eth_rpc::{HttpOutcallError, HttpOutcallResult, JsonRpcResult};
+    use crate::eth_rpc_client::parse_provider_override;
+    use crate::state::tests::a_state;
+    use crate::state::ProviderCallCounters;
+    use ic_cdk::api::call::RejectionCode;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn should_count_outcomes_by_provider_with_redacted_api_keys() {
+        let mut state = a_state();
+        let providers = parse_provider_override(&[
+            "https://cloudflare-eth.com".to_string(),
+            "https://rpc.example.com/v1/0123456789abcdef0123456789".to_string(),
+        ])
+        .unwrap();
+        let (cloudflare, custom) = (&providers[0], &providers[1]);
+        let ok: HttpOutcallResult<JsonRpcResult<u64>> = Ok(JsonRpcResult::Result(1));
+        let json_rpc_error: HttpOutcallResult<JsonRpcResult<u64>> = Ok(JsonRpcResult::Error {
+            code: -32000,
+            message: "already known".to_string(),
+        });
+        let http_outcall_error: HttpOutcallResult<JsonRpcResult<u64>> =
+            Err(HttpOutcallError::IcError {
+                code: RejectionCode::SysTransient,
+                message: "transient".to_string(),
+            });
+
+        state.record_provider_call(cloudflare, &ok);
+        state.record_provider_call(cloudflare, &ok);
+        state.record_provider_call(cloudflare, &http_outcall_error);
+        state.record_provider_call(custom, &json_rpc_error);
+
+        assert_eq!(
+            state.provider_call_counters,
+            BTreeMap::from([
+                (
+                    "https://cloudflare-eth.com".to_string(),
+                    ProviderCallCounters {
+                        ok: 2,
+                        json_rpc_error: 0,
+                        http_outcall_error: 1,
+                    }
+                ),
+                (
+                    "https://rpc.example.com/v1/[REDACTED]".to_string(),
+                    ProviderCallCounters {
+                        ok: 0,
+                        json_rpc_error: 1,
+                        http_outcall_error: 0,
+                    }
+                ),
+            ])
+        );
+    }
+}
+
+mod deposit_event {
+    use crate::eth_logs::{DepositEventAbi, DepositEventConfig};
+    use crate::eth_rpc::FixedSizeData;
+    use crate::state::tests::a_state;
+    use crate::state::InvalidStateError;
+    use assert_matches::assert_matches;
+    use std::str::FromStr;
+
+    const TOPIC: &str = "0x4e6f7420656e6f7567682045746865722070726f76696465642e000000000000";
+
+    #[test]
+    fn should_set_deposit_event() {
+        let mut state = a_state();
+
+        assert_eq!(
+            state.set_deposit_event(TOPIC, DepositEventAbi::ReceivedEth),
+            Ok(())
+        );
+
+        assert_eq!(
+            state.deposit_event,
+            DepositEventConfig {
+                topic: FixedSizeData::from_str(TOPIC).unwrap(),
+                abi: DepositEventAbi::ReceivedEth,
+            }
+        );
+    }
+
+    #[test]
+    fn should_keep_deposit_event_when_topic_is_invalid() {
+        let mut state = a_state();
+
+        assert_matches!(
+            state.set_deposit_event("0x1234", DepositEventAbi::ReceivedEth),
+            Err(InvalidStateError::InvalidDepositEventTopic(_))
+        );
+
+        assert_eq!(state.deposit_event, DepositEventConfig::default());
+    }
+}
+
+mod provider_override {
+    use crate::state::tests::a_state;
+
+    #[test]
+    fn should_set_and_clear_provider_override() {
+        let mut state = a_state();
+
+        assert_eq!(
+            state.set_provider_override(Some(vec!["https://rpc.example.com".to_string()])),
+            Ok(())
+        );
+        assert_eq!(
+            state
+                .provider_override
+                .as_ref()
+                .map(|providers| providers.iter().map(|p| p.url()).collect::<Vec<_>>()),
+            Some(vec!["https://rpc.example.com"])
+        );
+
+        assert_eq!(state.set_provider_override(None), Ok(()));
+        assert_eq!(state.provider_override, None);
+    }
+
+    #[test]
+    fn should_reject_provider_override_that_cannot_meet_min_provider_agreement() {
+        let mut state = a_state();
+        state.min_provider_agreement = Some(2);
+
+        assert!(state
+            .set_provider_override(Some(vec!["https://rpc.example.com".to_string()]))
+            .is_err());
+        assert_eq!(state.provider_override, None);
+
+        let four_providers: Vec<String> = (1..=4)
+            .map(|i| format!("https://rpc{i}.example.com"))
+            .collect();
+        assert!(state
+            .set_provider_override(Some(four_providers.clone()))
+            .is_err());
+        assert_eq!(state.provider_override, None);
+
+        assert_eq!(
+            state.set_provider_override(Some(four_providers[..3].to_vec())),
+            Ok(())
+        );
+        assert_eq!(state.num_providers(), 3);
+    }
+
+    #[test]
+    fn should_keep_provider_override_when_url_is_invalid() {
+        let mut state = a_state();
+
+        assert!(state
+            .set_provider_override(Some(vec!["http://rpc.example.com".to_string()]))
+            .is_err());
+
+        assert_eq!(state.provider_override, None);
+    }
+}
+
 fn a_state() -> State {
     use candid::Principal;
     State::try_from(InitArg {
@@ -36,6 +514,7 @@ fn a_state() -> State {
         ethereum_block_height: Default::default(),
         minimum_withdrawal_amount: wei_from_milli_ether(10).into(),
         next_transaction_nonce: Default::default(),
+        min_provider_agreement: None,
     })
     .expect("init args should be valid")
 }
@@ -234,6 +234,7 @@ fn address_display() {
 mod rlp_encoding {
     use crate::address::Address;
     use crate::eth_rpc::Quantity;
+    use crate::lifecycle::EthereumNetwork;
     use crate::numeric::{TransactionNonce, Wei};
     use crate::tx::{
         AccessList, Eip1559Signature, Eip1559TransactionRequest, SignedEip1559TransactionRequest,
@@ -242,8 +243,6 @@ mod rlp_encoding {
     use rlp::Encodable;
     use std::str::FromStr;
 
-    const SEPOLIA_TEST_CHAIN_ID: u64 = 11155111;
-
     #[test]
     fn test_rlp_encoding() {
         use crate::tx::{AccessList, Eip1559TransactionRequest};
@@ -274,7 +273,7 @@ mod rlp_encoding {
             chain_id: Some(1.into()),
         };
         let minter_tx = Eip1559TransactionRequest {
-            chain_id: 1,
+            chain_id: EthereumNetwork::Mainnet.chain_id(),
             destination: Address::new(address_bytes),
             nonce: 0_u64.into(),
             gas_limit: 1_u32.into(),
@@ -340,7 +339,7 @@ mod rlp_encoding {
             .unwrap(),
         };
         let transaction = Eip1559TransactionRequest {
-            chain_id: SEPOLIA_TEST_CHAIN_ID,
+            chain_id: EthereumNetwork::Sepolia.chain_id(),
             nonce: TransactionNonce::from(6_u8),
             max_priority_fee_per_gas: Wei::new(0x59682f00),
             max_fee_per_gas: Wei::new(0x598653cd),
@@ -891,3 +890,37 @@ mod eth_get_transaction_by_hash {
         )
     }
 }
+
+mod solvency {
+    use crate::endpoints::{Solvency, SolvencyBalance};
+    use crate::numeric::Wei;
+    use candid::Nat;
+
+    #[test]
+    fn should_report_surplus() {
+        let solvency = Solvency::new(
+            Wei::new(10_000_000_000_000_000_000),
+            Wei::new(8_000_000_000_000_000_000),
+            Wei::new(1_500_000_000_000_000_000),
+        );
+
+        assert_eq!(
+            solvency.balance,
+            SolvencyBalance::Surplus(Nat::from(500_000_000_000_000_000_u64))
+        );
+    }
+
+    #[test]
+    fn should_report_deficit() {
+        let solvency = Solvency::new(
+            Wei::new(9_000_000_000_000_000_000),
+            Wei::new(8_000_000_000_000_000_000),
+            Wei::new(1_500_000_000_000_000_000),
+        );
+
+        assert_eq!(
+            solvency.balance,
+            SolvencyBalance::Deficit(Nat::from(500_000_000_000_000_000_u64))
+        );
+    }
+}
@@ -3,7 +3,10 @@ mod tests;
 
 use crate::address::Address;
 use crate::endpoints::{EthTransaction, RetrieveEthStatus};
-use crate::numeric::{LedgerBurnIndex, TransactionNonce, Wei};
+use crate::eth_rpc::{Hash, Quantity};
+use crate::eth_rpc_client::responses::{TransactionReceipt, TransactionStatus};
+use crate::eth_rpc_error::RevertReason;
+use crate::numeric::{BlockNumber, LedgerBurnIndex, TransactionNonce, Wei};
 use crate::tx::{
     ConfirmedEip1559Transaction, Eip1559TransactionRequest, SignedEip1559TransactionRequest,
 };
@@ -34,6 +37,20 @@ struct PendingEthTx<T> {
     status: RetrieveEthStatus,
 }
 
+/// Transaction issued by the minter that was mined but whose execution failed,
+/// kept so that operators can be alerted and investigate.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct FailedTransaction {
+    pub ledger_burn_index: LedgerBurnIndex,
+    pub nonce: TransactionNonce,
+    pub transaction_hash: Hash,
+    pub block_number: BlockNumber,
+    pub gas_used: Quantity,
+    pub effective_gas_price: Wei,
+    /// Reason of the revert, if returned by the JSON-RPC provider.
+    pub revert_reason: Option<String>,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 struct TxCreated(Eip1559TransactionRequest);
 
@@ -62,6 +79,8 @@ pub struct EthTransactions {
     pending_sent_tx: Option<PendingEthTx<TxSent>>,
     confirmed_transactions_by_nonce: BTreeMap<TransactionNonce, ConfirmedEip1559Transaction>,
     confirmed_transactions_by_burn_index: BTreeMap<LedgerBurnIndex, TransactionNonce>,
+    #[serde(default)]
+    failed_transactions_by_nonce: BTreeMap<TransactionNonce, FailedTransaction>,
     next_nonce: TransactionNonce,
 }
 
@@ -74,6 +93,7 @@ impl EthTransactions {
             pending_sent_tx: None,
             confirmed_transactions_by_nonce: BTreeMap::new(),
             confirmed_transactions_by_burn_index: BTreeMap::new(),
+            failed_transactions_by_nonce: BTreeMap::new(),
             next_nonce,
         }
     }
@@ -298,6 +318,52 @@ impl EthTransactions {
         );
     }
 
+    /// Records the failure of the pending sent transaction, given its receipt.
+    /// The transaction still needs to be confirmed with `record_confirmed_transaction`,
+    /// since its nonce was consumed.
+    pub fn record_failed_transaction(&mut self, receipt: &TransactionReceipt) -> FailedTransaction {
+        assert_eq!(
+            receipt.status,
+            TransactionStatus::Failure,
+            "BUG: transaction {:?} did not fail",
+            receipt.transaction_hash
+        );
+        let sent_tx = self
+            .pending_sent_tx
+            .as_ref()
+            .expect("BUG: no pending sent transaction");
+        assert_eq!(
+            sent_tx.transaction.0.hash(),
+            receipt.transaction_hash,
+            "BUG: pending transaction does not match the receipt",
+        );
+
+        let failed_tx = FailedTransaction {
+            ledger_burn_index: sent_tx.request.ledger_burn_index,
+            nonce: sent_tx.transaction.0.nonce(),
+            transaction_hash: receipt.transaction_hash,
+            block_number: receipt.block_number,
+            gas_used: receipt.gas_used,
+            effective_gas_price: receipt.effective_gas_price,
+            revert_reason: receipt
+                .revert_reason
+                .as_deref()
+                .map(|data| RevertReason::decode_hex(data).to_string()),
+        };
+        assert_eq!(
+            self.failed_transactions_by_nonce
+                .insert(failed_tx.nonce, failed_tx.clone()),
+            None,
+            "BUG: a failed transaction with nonce {:?} already exists",
+            failed_tx.nonce
+        );
+        failed_tx
+    }
+
+    pub fn failed_transactions(&self) -> impl Iterator<Item = &FailedTransaction> {
+        self.failed_transactions_by_nonce.values()
+    }
+
     pub fn transaction_status(&self, burn_index: &LedgerBurnIndex) -> RetrieveEthStatus {
         if self
             .withdrawal_requests
@@ -325,6 +391,25 @@ impl EthTransactions {
         RetrieveEthStatus::NotFound
     }
 
+    /// Returns the total amount of the withdrawals whose ckETH was burned
+    /// but whose transaction is not confirmed yet.
+    pub fn pending_withdrawal_amount(&self) -> Wei {
+        let pending_tx_requests = self
+            .pending_created_tx
+            .iter()
+            .map(|tx| &tx.request)
+            .chain(self.pending_signed_tx.iter().map(|tx| &tx.request))
+            .chain(self.pending_sent_tx.iter().map(|tx| &tx.request));
+        self.withdrawal_requests
+            .iter()
+            .chain(pending_tx_requests)
+            .fold(Wei::ZERO, |total, request| {
+                total
+                    .checked_add(request.withdrawal_amount)
+                    .expect("BUG: pending withdrawal amount overflows")
+            })
+    }
+
     pub fn withdrawal_requests_iter(&self) -> Iter<'_, EthWithdrawalRequest> {
         self.withdrawal_requests.iter()
     }
@@ -1,5 +1,6 @@
 use crate::address::Address;
 use crate::eth_rpc::{Hash, Quantity};
+use crate::lifecycle::EthereumNetwork;
 use crate::numeric::{BlockNumber, LedgerBurnIndex, TransactionNonce, Wei};
 use crate::transactions::EthWithdrawalRequest;
 use crate::tx::{
@@ -9,7 +10,7 @@ use crate::tx::{
 
 mod eth_transactions {
     use crate::endpoints::{EthTransaction, RetrieveEthStatus};
-    use crate::numeric::{LedgerBurnIndex, TransactionNonce};
+    use crate::numeric::{LedgerBurnIndex, TransactionNonce, Wei};
     use crate::transactions::tests::{
         confirmed_transaction, dummy_signature, eip_1559_transaction_request_with_nonce,
         expect_panic_with_message, withdrawal_request_with_index,
@@ -122,6 +123,97 @@ mod eth_transactions {
             Some(first_request)
         );
     }
+
+    #[test]
+    fn should_sum_pending_withdrawal_amounts() {
+        let mut transactions = EthTransactions::new(TransactionNonce::ZERO);
+        assert_eq!(transactions.pending_withdrawal_amount(), Wei::ZERO);
+
+        let first_request = withdrawal_request_with_index(LedgerBurnIndex::new(15));
+        let second_request = withdrawal_request_with_index(LedgerBurnIndex::new(16));
+        transactions.record_withdrawal_request(first_request.clone());
+        transactions.record_withdrawal_request(second_request);
+        let tx = eip_1559_transaction_request_with_nonce(TransactionNonce::ZERO);
+        transactions.record_created_transaction(first_request, tx);
+
+        assert_eq!(
+            transactions.pending_withdrawal_amount(),
+            Wei::new(2_200_000_000_000_000)
+        );
+    }
+
+    #[test]
+    fn should_record_failed_transaction_from_reverted_receipt() {
+        use crate::address::Address;
+        use crate::eth_rpc::{Hash, Quantity};
+        use crate::eth_rpc_client::responses::{TransactionReceipt, TransactionStatus};
+        use crate::numeric::BlockNumber;
+        use crate::transactions::FailedTransaction;
+        use std::str::FromStr;
+
+        let mut transactions = EthTransactions::new(TransactionNonce::ZERO);
+        let index = LedgerBurnIndex::new(15);
+        let withdrawal_request = withdrawal_request_with_index(index);
+        transactions.record_withdrawal_request(withdrawal_request.clone());
+        let tx = eip_1559_transaction_request_with_nonce(TransactionNonce::ZERO);
+        transactions.record_created_transaction(withdrawal_request, tx.clone());
+        let signed_tx = SignedEip1559TransactionRequest::from((tx, dummy_signature()));
+        transactions.record_signed_transaction(signed_tx.clone());
+        transactions.record_sent_transaction(signed_tx.clone());
+
+        let receipt = TransactionReceipt {
+            block_hash: Hash::from_str(
+                "0xce67a85c9fb8bc50213815c32814c159fd75160acf7cb8631e8e7b7cf7f1d472",
+            )
+            .unwrap(),
+            block_number: BlockNumber::new(4190269),
+            effective_gas_price: Wei::new(0xfefbee3e),
+            from: Address::from_str("0x1789f79e95324a47c5fd6693071188e82e9a3558").unwrap(),
+            to: Some(Address::from_str("0xdd2851cdd40ae6536831558dd46db62fac7a844d").unwrap()),
+            contract_address: None,
+            gas_used: Quantity::new(0x5208),
+            status: TransactionStatus::Failure,
+            transaction_hash: signed_tx.hash(),
+            transaction_index: Quantity::new(0x32),
+            // ABI encoding of `Error("insufficient funds")`.
+            revert_reason: Some(
+                concat!(
+                    "0x08c379a0",
+                    "0000000000000000000000000000000000000000000000000000000000000020",
+                    "0000000000000000000000000000000000000000000000000000000000000012",
+                    "696e73756666696369656e742066756e64730000000000000000000000000000"
+                )
+                .to_string(),
+            ),
+        };
+
+        let expected_failed_tx = FailedTransaction {
+            ledger_burn_index: index,
+            nonce: TransactionNonce::ZERO,
+            transaction_hash: signed_tx.hash(),
+            block_number: BlockNumber::new(4190269),
+            gas_used: Quantity::new(0x5208),
+            effective_gas_price: Wei::new(0xfefbee3e),
+            revert_reason: Some("insufficient funds".to_string()),
+        };
+        assert_eq!(
+            transactions.record_failed_transaction(&receipt),
+            expected_failed_tx
+        );
+        assert_eq!(
+            transactions.failed_transactions().collect::<Vec<_>>(),
+            vec![&expected_failed_tx]
+        );
+
+        // The nonce was consumed, so the transaction is still confirmed.
+        transactions.record_confirmed_transaction(confirmed_transaction(signed_tx.clone()));
+        assert_eq!(
+            transactions.transaction_status(&index),
+            RetrieveEthStatus::TxConfirmed(EthTransaction {
+                transaction_hash: signed_tx.hash().to_string()
+            })
+        );
+    }
 }
 
 mod eth_withdrawal_request {
@@ -158,9 +250,8 @@ fn withdrawal_request_with_index(ledger_burn_index: LedgerBurnIndex) -> EthWithd
 
 fn eip_1559_transaction_request_with_nonce(nonce: TransactionNonce) -> Eip1559TransactionRequest {
     use std::str::FromStr;
-    const SEPOLIA_TEST_CHAIN_ID: u64 = 11155111;
     Eip1559TransactionRequest {
-        chain_id: SEPOLIA_TEST_CHAIN_ID,
+        chain_id: EthereumNetwork::Sepolia.chain_id(),
         nonce,
         max_priority_fee_per_gas: Wei::new(0x59682f00),
         max_fee_per_gas: Wei::new(0x598653cd),
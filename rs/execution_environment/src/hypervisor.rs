@@ -227,8 +227,20 @@ impl Hypervisor {
         dirty_page_overhead: NumInstructions,
         fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
     ) -> Self {
-        let mut embedder_config = config.embedders_config.clone();
-        embedder_config.subnet_type = own_subnet_type;
+        let mut embedder_config = config
+            .embedders_config
+            .clone()
+            .for_subnet_type(own_subnet_type);
+        if let Err(err) = embedder_config.validate_sandbox_counts() {
+            panic!("Invalid embedders config: {}", err);
+        }
+        if embedder_config
+            .feature_flags
+            .development_custom_section_limits
+            == FlagStatus::Enabled
+        {
+            embedder_config = embedder_config.with_development_custom_section_limits();
+        }
         embedder_config.dirty_page_overhead = dirty_page_overhead;
 
         let wasm_executor: Arc<dyn WasmExecutor> = match config.canister_sandboxing_flag {
@@ -260,7 +272,10 @@ impl Hypervisor {
             own_subnet_type,
             log,
             cycles_account_manager,
-            compilation_cache: Arc::new(CompilationCache::new(config.max_compilation_cache_size)),
+            compilation_cache: Arc::new(match config.embedders_config.compilation_cache {
+                FlagStatus::Enabled => CompilationCache::new(config.max_compilation_cache_size),
+                FlagStatus::Disabled => CompilationCache::disabled(),
+            }),
             deterministic_time_slicing: config.deterministic_time_slicing,
             cost_to_compile_wasm_instruction: config
                 .embedders_config
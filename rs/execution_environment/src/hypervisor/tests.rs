@@ -3792,6 +3792,68 @@ fn can_extract_exported_custom_sections() {
     assert_eq!(execution_state.metadata.custom_sections().len(), 3);
 }
 
+// Returns an empty Wasm module with the given number of public custom sections.
+fn wasm_with_custom_sections(count: usize) -> Vec<u8> {
+    let mut binary = wat::parse_str("(module)").unwrap();
+    for i in 0..count {
+        let name = format!("icp:public name{}", i);
+        let payload = [0, 1];
+        // Custom section: id 0, size, name length, name, payload. All sizes fit in a
+        // single LEB128 byte.
+        binary.push(0);
+        binary.push((1 + name.len() + payload.len()) as u8);
+        binary.push(name.len() as u8);
+        binary.extend_from_slice(name.as_bytes());
+        binary.extend_from_slice(&payload);
+    }
+    binary
+}
+
+#[test]
+fn too_many_custom_sections_are_rejected_by_default() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let err = test
+        .canister_from_binary(wasm_with_custom_sections(17))
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterInvalidWasm, err.code());
+    assert!(
+        err.description().contains(
+            "Wasm module defined 17 custom sections with `icp:public` or `icp:private` \
+             visibility, which exceeds the maximum number allowed 16."
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn development_custom_section_limits_accept_more_custom_sections() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_development_custom_section_limits()
+        .build();
+    let canister_id = test
+        .canister_from_binary(wasm_with_custom_sections(17))
+        .unwrap();
+    assert_eq!(
+        test.execution_state(canister_id)
+            .metadata
+            .custom_sections()
+            .len(),
+        17
+    );
+
+    let err = test
+        .canister_from_binary(wasm_with_custom_sections(65))
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterInvalidWasm, err.code());
+    assert!(
+        err.description()
+            .contains("Wasm module defined 65 custom sections"),
+        "{}",
+        err
+    );
+}
+
 #[test]
 fn execute_with_huge_cycle_balance() {
     let mut test = ExecutionTestBuilder::new().build();
@@ -255,6 +255,22 @@ where
         let key = ((category, followee_id.clone()), follower_id.clone());
         self.category_followee_follower_to_null.contains_key(&key)
     }
+
+    /// Removes up to `max_entries` entries from the index and returns the number of removed
+    /// entries. This is for recovery purpose: the index can be cleared in bounded batches before
+    /// being rebuilt from the primary storage.
+    pub fn remove_entries(&mut self, max_entries: usize) -> usize {
+        let keys: Vec<_> = self
+            .category_followee_follower_to_null
+            .iter()
+            .take(max_entries)
+            .map(|(key, _)| key)
+            .collect();
+        for key in &keys {
+            self.category_followee_follower_to_null.remove(key);
+        }
+        keys.len()
+    }
 }
 
 impl<NeuronId, Category, M> NeuronFollowingIndex<NeuronId, Category>
@@ -155,6 +155,22 @@ where
         let key = (principal_id, neuron_id.clone());
         self.principal_id_and_neuron_id_set.contains_key(&key)
     }
+
+    /// Removes up to `max_entries` entries from the index and returns the number of removed
+    /// entries. This is for recovery purpose: the index can be cleared in bounded batches before
+    /// being rebuilt from the primary storage.
+    pub fn remove_entries(&mut self, max_entries: usize) -> usize {
+        let keys: Vec<_> = self
+            .principal_id_and_neuron_id_set
+            .iter()
+            .take(max_entries)
+            .map(|(key, _)| key)
+            .collect();
+        for key in &keys {
+            self.principal_id_and_neuron_id_set.remove(key);
+        }
+        keys.len()
+    }
 }
 
 impl<NeuronId, M> NeuronPrincipalIndex<NeuronId> for StableNeuronPrincipalIndex<NeuronId, M>
@@ -39,6 +39,7 @@ use ic_nns_governance::{
         BitcoinNetwork, BitcoinSetConfigProposal, Environment, Governance, HeapGrowthPotential,
         TimeWarp,
     },
+    is_rebuild_neuron_indexes_on_upgrade_enabled,
     pb::v1::{
         claim_or_refresh_neuron_from_account_response::Result as ClaimOrRefreshNeuronFromAccountResponseResult,
         governance::GovernanceCachedMetrics,
@@ -377,6 +378,11 @@ fn canister_post_upgrade() {
     };
 
     canister_init_(proto);
+
+    if is_rebuild_neuron_indexes_on_upgrade_enabled() {
+        println!("{}Starting to rebuild the neuron indexes", LOG_PREFIX);
+        governance_mut().start_rebuilding_neuron_indexes();
+    }
 }
 
 #[cfg(feature = "test")]
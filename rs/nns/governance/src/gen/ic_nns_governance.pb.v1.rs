@@ -2286,6 +2286,9 @@ pub mod governance {
         pub neuron_indexes_migration: ::core::option::Option<Migration>,
         #[prost(message, optional, tag = "2")]
         pub copy_inactive_neurons_to_stable_memory_migration: ::core::option::Option<Migration>,
+        /// Rebuilds the stable neuron indexes from the neurons in the heap and stable memory.
+        #[prost(message, optional, tag = "4")]
+        pub rebuild_neuron_indexes: ::core::option::Option<Migration>,
     }
 }
 /// Proposals with restricted voting are not included unless the caller
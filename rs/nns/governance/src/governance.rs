@@ -89,6 +89,7 @@ use std::{
     string::ToString,
 };
 
+use crate::migrations::maybe_run_neuron_indexes_rebuild;
 use crate::neuron_store::NeuronStore;
 #[cfg(target_arch = "wasm32")]
 use dfn_core::println;
@@ -1724,6 +1725,25 @@ impl Governance {
         }
     }
 
+    /// Returns whether the neuron currently has an in-flight command.
+    pub fn has_in_flight_command(&self, neuron_id: &NeuronId) -> bool {
+        self.heap_data
+            .in_flight_commands
+            .contains_key(&neuron_id.id)
+    }
+
+    /// Returns the ids of all neurons that currently have an in-flight command, in ascending order.
+    pub fn neuron_ids_with_in_flight_commands(&self) -> Vec<NeuronId> {
+        let mut neuron_ids = self
+            .heap_data
+            .in_flight_commands
+            .keys()
+            .map(|id| NeuronId { id: *id })
+            .collect::<Vec<_>>();
+        neuron_ids.sort();
+        neuron_ids
+    }
+
     /// Updates a neuron in the list of neurons.
     ///
     /// Preconditions:
@@ -6469,6 +6489,7 @@ impl Governance {
             self.copy_next_batch_of_inactive_neurons_to_stable_memory();
         }
 
+        self.maybe_rebuild_next_batch_of_neuron_indexes();
         self.unstake_maturity_of_dissolved_neurons();
         self.maybe_gc();
     }
@@ -6494,6 +6515,32 @@ impl Governance {
         }
     }
 
+    /// Starts rebuilding the stable neuron indexes from the neurons, which `run_periodic_tasks`
+    /// then carries out in batches, picking up from where it left off each time. This is a
+    /// recovery tool for when `NeuronStore::verify_indexes` detects inconsistencies, and is started
+    /// by `canister_post_upgrade` when `is_rebuild_neuron_indexes_on_upgrade_enabled`. A rebuild
+    /// that is already in progress starts over.
+    pub fn start_rebuilding_neuron_indexes(&mut self) {
+        let mut migrations = self.heap_data.migrations.clone().unwrap_or_default();
+        migrations.rebuild_neuron_indexes = Some(Migration {
+            status: Some(MigrationStatus::InProgress as i32),
+            failure_reason: None,
+            progress: None,
+        });
+        self.heap_data.migrations = Some(migrations);
+    }
+
+    fn maybe_rebuild_next_batch_of_neuron_indexes(&mut self) {
+        let mut migrations = match &self.heap_data.migrations {
+            Some(migrations) if migrations.rebuild_neuron_indexes.is_some() => migrations.clone(),
+            _ => return,
+        };
+        migrations.rebuild_neuron_indexes = migrations
+            .rebuild_neuron_indexes
+            .map(|migration| maybe_run_neuron_indexes_rebuild(migration, &mut self.neuron_store));
+        self.heap_data.migrations = Some(migrations);
+    }
+
     fn copy_next_batch_of_inactive_neurons_to_stable_memory(&mut self) {
         // Pick up from where we left off last time.
         let mut migrations = self.heap_data.migrations.clone().unwrap_or_default();
@@ -2037,6 +2037,69 @@ fn test_should_copy_next_batch_of_inactive_neurons_to_stable_memory_migration_ex
     }
 }
 
+#[test]
+fn test_rebuild_neuron_indexes_across_periodic_tasks() {
+    let neurons: BTreeMap<u64, Neuron> = (1..=3)
+        .map(|id| {
+            let neuron = Neuron {
+                id: Some(NeuronId { id }),
+                account: [id as u8; 32].to_vec(),
+                controller: Some(PrincipalId::new_user_test_id(id)),
+                ..Default::default()
+            };
+            (id, neuron)
+        })
+        .collect();
+    let mut governance = Governance::new(
+        GovernanceProto {
+            neurons,
+            ..Default::default()
+        },
+        Box::new(MockEnvironment {
+            expected_call_canister_method_calls: Arc::new(Mutex::new(Default::default())),
+            now: Arc::new(Mutex::new(0)),
+        }),
+        Box::new(StubIcpLedger {}),
+        Box::new(StubCMC {}),
+    );
+    let rebuild_migration = |governance: &Governance| {
+        governance
+            .heap_data
+            .migrations
+            .clone()
+            .unwrap_or_default()
+            .rebuild_neuron_indexes
+    };
+
+    // Nothing happens until a rebuild is started.
+    governance.maybe_rebuild_next_batch_of_neuron_indexes();
+    assert_eq!(rebuild_migration(&governance), None);
+    assert!(governance.neuron_store.verify_indexes().is_err());
+
+    governance.start_rebuilding_neuron_indexes();
+
+    // The first batch clears the (empty) indexes, and the second one adds all the neurons.
+    governance.maybe_rebuild_next_batch_of_neuron_indexes();
+    assert_eq!(
+        rebuild_migration(&governance),
+        Some(Migration {
+            status: Some(MigrationStatus::InProgress as i32),
+            failure_reason: None,
+            progress: Some(migration::Progress::LastNeuronId(NeuronId { id: 0 })),
+        })
+    );
+    governance.maybe_rebuild_next_batch_of_neuron_indexes();
+    assert_eq!(
+        rebuild_migration(&governance),
+        Some(Migration {
+            status: Some(MigrationStatus::Succeeded as i32),
+            failure_reason: None,
+            progress: None,
+        })
+    );
+    assert_eq!(governance.neuron_store.verify_indexes(), Ok(()));
+}
+
 lazy_static! {
     static ref ORIGINAL_HEAP_NEURONS: BTreeMap<u64, Neuron> = {
         fn new_neuron(id: u64, maturity_e8s_equivalent: u64) -> Neuron {
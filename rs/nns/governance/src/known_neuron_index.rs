@@ -49,6 +49,22 @@ impl<M: Memory> KnownNeuronIndex<M> {
             .unwrap_or_default()
     }
 
+    /// Removes up to `max_entries` entries from the index and returns the number of removed
+    /// entries. This is for recovery purpose: the index can be cleared in bounded batches before
+    /// being rebuilt from the primary storage.
+    pub fn remove_entries(&mut self, max_entries: usize) -> usize {
+        let keys: Vec<_> = self
+            .known_neuron_name_to_id
+            .iter()
+            .take(max_entries)
+            .map(|(key, _)| key)
+            .collect();
+        for key in &keys {
+            self.known_neuron_name_to_id.remove(key);
+        }
+        keys.len()
+    }
+
     /// Adds a known neuron to the index. Returns error if nothing is added.
     /// The reason the known neuron might not gets added into the index might be that:
     /// (1) the known neuron name already exists (caller should call `contains_known_neuron_name`
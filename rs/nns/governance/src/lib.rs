@@ -448,3 +448,9 @@ pub fn encode_metrics(
 fn is_copy_inactive_neurons_to_stable_memory_enabled() -> bool {
     cfg! { any(test, feature = "test") }
 }
+
+/// Whether `canister_post_upgrade` starts rebuilding the stable neuron indexes. This is meant to be
+/// turned on for a release that repairs indexes found inconsistent by `verify_indexes`.
+pub fn is_rebuild_neuron_indexes_on_upgrade_enabled() -> bool {
+    false
+}
@@ -1,6 +1,6 @@
 use crate::{
     governance::LOG_PREFIX,
-    neuron_store::NeuronStore,
+    neuron_store::{NeuronStore, RebuildIndexesProgress},
     pb::v1::governance::{
         migration::{MigrationStatus, Progress},
         Migration,
@@ -10,6 +10,10 @@ use ic_nns_common::pb::v1::NeuronId;
 
 pub const NEURON_INDEXES_MIGRATION_BATCH_SIZE: usize = 1000;
 
+/// The number of index entries removed, or of neurons added to the indexes, in each step of
+/// rebuilding the neuron indexes.
+pub const NEURON_INDEXES_REBUILD_BATCH_SIZE: usize = 1000;
+
 /// Runs neuron indexes migration when possible.
 #[allow(dead_code)]
 pub(crate) fn maybe_run_neuron_index_migration(
@@ -58,14 +62,58 @@ pub(crate) fn maybe_run_neuron_index_migration(
     }
 }
 
+/// Runs the next step of rebuilding the neuron indexes if a rebuild is in progress. The rebuild is
+/// started by setting the status to `InProgress` without progress, which means that the indexes
+/// are being cleared. Afterwards, the progress is the id of the last neuron added to the indexes.
+pub(crate) fn maybe_run_neuron_indexes_rebuild(
+    migration: Migration,
+    neuron_store: &mut NeuronStore,
+) -> Migration {
+    let migration_status = migration.status.and_then(MigrationStatus::from_i32);
+    if migration_status != Some(MigrationStatus::InProgress) {
+        return migration;
+    }
+
+    let progress = match migration.progress {
+        None => RebuildIndexesProgress::Clearing,
+        Some(Progress::LastNeuronId(last_neuron_id)) => {
+            RebuildIndexesProgress::Adding { last_neuron_id }
+        }
+    };
+
+    match neuron_store.rebuild_indexes_step(progress, NEURON_INDEXES_REBUILD_BATCH_SIZE) {
+        Err(failure_reason) => Migration {
+            status: Some(MigrationStatus::Failed as i32),
+            failure_reason: Some(failure_reason),
+            progress: None,
+        },
+        Ok(RebuildIndexesProgress::Clearing) => Migration {
+            status: Some(MigrationStatus::InProgress as i32),
+            failure_reason: None,
+            progress: None,
+        },
+        Ok(RebuildIndexesProgress::Adding { last_neuron_id }) => Migration {
+            status: Some(MigrationStatus::InProgress as i32),
+            failure_reason: None,
+            progress: Some(Progress::LastNeuronId(last_neuron_id)),
+        },
+        Ok(RebuildIndexesProgress::Done) => Migration {
+            status: Some(MigrationStatus::Succeeded as i32),
+            failure_reason: None,
+            progress: None,
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::pb::v1::Neuron;
+    use crate::{pb::v1::Neuron, storage::NEURON_INDEXES};
     use ic_base_types::PrincipalId;
     use ic_nns_common::pb::v1::NeuronId;
     use maplit::btreemap;
+    use std::collections::BTreeMap;
 
     #[test]
     fn migrate_neuron_indexes_one_neuron_succeeded() {
@@ -150,6 +198,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rebuild_neuron_indexes_resumes_from_persisted_progress() {
+        let neurons: BTreeMap<u64, Neuron> = (1..=2500)
+            .map(|id| {
+                let neuron = Neuron {
+                    id: Some(NeuronId { id }),
+                    account: id.to_le_bytes().repeat(4),
+                    controller: Some(PrincipalId::new_user_test_id(id)),
+                    ..Default::default()
+                };
+                (id, neuron)
+            })
+            .collect();
+        let mut neuron_store = NeuronStore::new(neurons);
+        // Some neurons are only in stable memory.
+        for id in [7, 1500, 2500] {
+            neuron_store.move_to_stable(NeuronId { id }).unwrap();
+        }
+        // Corrupt the indexes with the entries of an unknown neuron.
+        NEURON_INDEXES
+            .with(|indexes| {
+                indexes.borrow_mut().add_neuron(&Neuron {
+                    id: Some(NeuronId { id: 3000 }),
+                    account: [0xff; 32].to_vec(),
+                    ..Default::default()
+                })
+            })
+            .unwrap();
+        assert!(neuron_store.verify_indexes().is_err());
+
+        // A rebuild that was not requested does nothing.
+        assert_eq!(
+            maybe_run_neuron_indexes_rebuild(Migration::default(), &mut neuron_store),
+            Migration::default()
+        );
+
+        // Runs a rebuild to completion, one step per call as in successive heartbeats, and
+        // returns the persisted progress after each step.
+        let rebuild = |neuron_store: &mut NeuronStore| {
+            let mut migration = Migration {
+                status: Some(MigrationStatus::InProgress as i32),
+                failure_reason: None,
+                progress: None,
+            };
+            let mut progresses = vec![];
+            while migration.status == Some(MigrationStatus::InProgress as i32) {
+                migration = maybe_run_neuron_indexes_rebuild(migration, neuron_store);
+                progresses.push(migration.progress.clone());
+            }
+            assert_eq!(migration.status, Some(MigrationStatus::Succeeded as i32));
+            progresses
+        };
+
+        // The 7 corrupted entries are cleared in one step, and the neurons are added in 3 steps.
+        assert_eq!(
+            rebuild(&mut neuron_store),
+            vec![
+                Some(Progress::LastNeuronId(NeuronId { id: 0 })),
+                Some(Progress::LastNeuronId(NeuronId { id: 1000 })),
+                Some(Progress::LastNeuronId(NeuronId { id: 2000 })),
+                None,
+            ]
+        );
+        assert_eq!(neuron_store.verify_indexes(), Ok(()));
+
+        // Rebuilding again clears the 5000 entries over 6 steps, and has no further effect.
+        assert_eq!(
+            rebuild(&mut neuron_store),
+            vec![
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(Progress::LastNeuronId(NeuronId { id: 0 })),
+                Some(Progress::LastNeuronId(NeuronId { id: 1000 })),
+                Some(Progress::LastNeuronId(NeuronId { id: 2000 })),
+                None,
+            ]
+        );
+        assert_eq!(neuron_store.verify_indexes(), Ok(()));
+    }
+
     #[test]
     fn migrate_neuron_indexes_already_failed() {
         let mut neuron_store = NeuronStore::new(btreemap! {
@@ -1,9 +1,13 @@
 use crate::{
     governance::{Environment, LOG_PREFIX, MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS},
     is_copy_inactive_neurons_to_stable_memory_enabled,
+    migrations::NEURON_INDEXES_REBUILD_BATCH_SIZE,
     neuron::neuron_id_range_to_u64_range,
     pb::v1::{governance_error::ErrorType, GovernanceError, Neuron, NeuronState, Topic},
-    storage::{neuron_indexes::CorruptedNeuronIndexes, NEURON_INDEXES, STABLE_NEURON_STORE},
+    storage::{
+        neuron_indexes::{CorruptedNeuronIndexes, NeuronIndexDefect},
+        NEURON_INDEXES, STABLE_NEURON_STORE,
+    },
 };
 #[cfg(target_arch = "wasm32")]
 use dfn_core::println;
@@ -42,6 +46,10 @@ pub enum NeuronStoreError {
         old_subaccount: Subaccount,
         new_subaccount: Subaccount,
     },
+    StableNeuronStoreFailure {
+        neuron_id: NeuronId,
+        reason: String,
+    },
 }
 
 impl NeuronStoreError {
@@ -71,6 +79,10 @@ impl NeuronStoreError {
             new_subaccount,
         }
     }
+
+    pub fn stable_neuron_store_failure(neuron_id: NeuronId, reason: String) -> Self {
+        NeuronStoreError::StableNeuronStoreFailure { neuron_id, reason }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -130,10 +142,30 @@ impl From<NeuronStoreError> for GovernanceError {
                     old_subaccount, new_subaccount
                 ),
             ),
+            NeuronStoreError::StableNeuronStoreFailure { neuron_id, reason } => {
+                GovernanceError::new_with_message(
+                    ErrorType::PreconditionFailed,
+                    format!(
+                        "Failed to access neuron {} in stable memory: {}",
+                        neuron_id.id, reason
+                    ),
+                )
+            }
         }
     }
 }
 
+/// Progress of rebuilding the stable indexes with `NeuronStore::rebuild_indexes_step`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RebuildIndexesProgress {
+    /// The entries of the stable indexes are being removed.
+    Clearing,
+    /// The neurons whose id is greater than `last_neuron_id` have yet to be added.
+    Adding { last_neuron_id: NeuronId },
+    /// The stable indexes have been rebuilt.
+    Done,
+}
+
 /// This struct stores and provides access to all neurons within NNS Governance, which can live
 /// in either heap memory or stable memory.
 #[cfg_attr(test, derive(Clone, Debug, PartialEq))]
@@ -181,6 +213,43 @@ impl NeuronStore {
         std::mem::take(&mut self.heap_neurons)
     }
 
+    /// Copies all the neurons, so that they can later be restored with `import`. This is meant
+    /// for snapshotting test fixtures.
+    pub fn export_heap(&self) -> BTreeMap<u64, Neuron> {
+        self.heap_neurons.clone()
+    }
+
+    /// Replaces all the neurons with the given ones (typically obtained from `export_heap`) and
+    /// rebuilds the indexes. Like in `with_neuron_mut`, neurons that `is_neuron_inactive` are
+    /// also written through to stable memory, while the other ones are removed from it.
+    pub fn import(
+        &mut self,
+        heap_neurons: BTreeMap<u64, Neuron>,
+        is_neuron_inactive: impl Fn(&Neuron) -> bool,
+    ) {
+        let dropped_neuron_ids: Vec<NeuronId> = self
+            .heap_neurons
+            .keys()
+            .filter(|id| !heap_neurons.contains_key(id))
+            .map(|id| NeuronId { id: *id })
+            .collect();
+
+        *self = Self::new(heap_neurons);
+
+        if is_copy_inactive_neurons_to_stable_memory_enabled() {
+            STABLE_NEURON_STORE.with(|stable_neuron_store| {
+                let mut stable_neuron_store = stable_neuron_store.borrow_mut();
+                for neuron_id in dropped_neuron_ids {
+                    // The neuron was only in stable memory if it was inactive.
+                    let _ignore_result = stable_neuron_store.delete(neuron_id);
+                }
+            });
+            for neuron in self.heap_neurons.values() {
+                write_through_to_stable_neuron_store(&is_neuron_inactive, neuron);
+            }
+        }
+    }
+
     pub fn new_neuron_id(&self, env: &mut dyn Environment) -> NeuronId {
         loop {
             let id = env
@@ -270,6 +339,73 @@ impl NeuronStore {
             .map(|(_id, neuron)| neuron.clone())
     }
 
+    /// Returns a chunk of at most `limit` Neurons, from both the heap and stable memory, whose ID is
+    /// greater than `cursor` (or starting from the first Neuron if `cursor` is `None`), in ID
+    /// order, together with the cursor to pass to get the next chunk. The returned cursor is
+    /// `None` once there are no more Neurons. A Neuron that is in both the heap and stable memory
+    /// is returned once, as found in the heap, which is where it is kept up to date.
+    ///
+    /// No state is kept between calls, so that a snapshot of all Neurons can be taken in chunks
+    /// across several messages rather than in one long-running loop. Neurons that are added or
+    /// modified between calls may or may not be reflected in the snapshot, depending on their ID.
+    pub fn snapshot_chunk(
+        &self,
+        cursor: Option<NeuronId>,
+        limit: usize,
+    ) -> (Vec<Neuron>, Option<NeuronId>) {
+        let begin = match cursor {
+            None => 0,
+            Some(NeuronId { id }) => match id.checked_add(1) {
+                Some(begin) => begin,
+                None => return (vec![], None),
+            },
+        };
+        // Only the first `limit` Neurons of each source can make it into the chunk.
+        let mut neurons: BTreeMap<u64, Neuron> = STABLE_NEURON_STORE.with(|stable_neuron_store| {
+            stable_neuron_store
+                .borrow()
+                .range_neurons(NeuronId { id: begin }..)
+                .take(limit)
+                .filter_map(|neuron| neuron.id.map(|id| (id.id, neuron)))
+                .collect()
+        });
+        neurons.extend(
+            self.heap_neurons
+                .range(begin..)
+                .take(limit)
+                .map(|(id, neuron)| (*id, neuron.clone())),
+        );
+        let chunk: Vec<Neuron> = neurons.into_values().take(limit).collect();
+
+        let next_cursor = if chunk.len() < limit {
+            None
+        } else {
+            chunk.last().and_then(|neuron| neuron.id)
+        };
+        (chunk, next_cursor)
+    }
+
+    /// Returns Neurons in heap whose ID is within the given range and that satisfy the predicate,
+    /// in ID order. The predicate is evaluated on references, so that only the Neurons that match
+    /// are cloned, and lazily, so that callers can stop early (e.g. with `take`).
+    pub fn range_heap_neurons_filtered<'a, R, F>(
+        &'a self,
+        range: R,
+        predicate: F,
+    ) -> impl Iterator<Item = Neuron> + 'a
+    where
+        R: RangeBounds<NeuronId>,
+        F: Fn(&Neuron) -> bool + 'a,
+    {
+        let range = neuron_id_range_to_u64_range(&range);
+
+        self.heap_neurons
+            .range(range)
+            .map(|(_id, neuron)| neuron)
+            .filter(move |neuron| predicate(neuron))
+            .cloned()
+    }
+
     /// Internal - map over neurons after filtering
     fn map_heap_neurons_filtered<R>(
         &self,
@@ -424,6 +560,109 @@ impl NeuronStore {
         Ok(new_last_neuron_id)
     }
 
+    /// Returns the cursor that `batch_add_heap_neurons_to_stable_indexes` would return when called
+    /// with the same arguments (unless it fails), without adding any neuron to the stable indexes.
+    /// This is useful to plan the migration ahead of actually performing it.
+    #[allow(dead_code)] // TODO(NNS1-2409): Re-enable clippy.
+    pub(crate) fn peek_next_batch_boundary(
+        &self,
+        last_neuron_id: NeuronId,
+        batch_size: usize,
+    ) -> Option<NeuronId> {
+        let (count, new_last_neuron_id) = self
+            .heap_neurons
+            .range(last_neuron_id.id + 1..)
+            .take(batch_size)
+            .fold((0, None), |(count, _), (neuron_id, _)| {
+                (count + 1, Some(NeuronId { id: *neuron_id }))
+            });
+
+        if count < batch_size {
+            // No more neurons to migrate
+            return None;
+        }
+        new_last_neuron_id
+    }
+
+    /// Checks that the stable indexes are consistent with the neurons, i.e., that they contain
+    /// exactly the entries derived from the neurons in the heap and stable memory. A neuron that
+    /// is in both is checked as found in the heap, which is where it is kept up to date.
+    pub fn verify_indexes(&self) -> Result<(), Vec<NeuronIndexDefect>> {
+        let stable_only_neurons: Vec<Neuron> = STABLE_NEURON_STORE.with(|stable_neuron_store| {
+            stable_neuron_store
+                .borrow()
+                .range_neurons(..)
+                .filter(|neuron| {
+                    neuron
+                        .id
+                        .map_or(true, |id| !self.heap_neurons.contains_key(&id.id))
+                })
+                .collect()
+        });
+        NEURON_INDEXES.with(|indexes| {
+            indexes
+                .borrow()
+                .verify(self.heap_neurons.values().chain(stable_only_neurons.iter()))
+        })
+    }
+
+    /// Clears the stable indexes and re-derives them from the neurons in the heap and stable
+    /// memory, running all the steps of `rebuild_indexes_step` in chunks of
+    /// `NEURON_INDEXES_REBUILD_BATCH_SIZE` within the current message. Rebuilding consistent
+    /// indexes has no effect. When there are too many neurons to do this in a single message, use
+    /// `Governance::start_rebuilding_neuron_indexes` instead, which spreads the steps over several
+    /// periodic tasks.
+    ///
+    /// Returns `Err(failure_reason)` if a neuron could not be added to the indexes.
+    pub(crate) fn rebuild_indexes(&mut self) -> Result<(), String> {
+        let mut progress = RebuildIndexesProgress::Clearing;
+        while progress != RebuildIndexesProgress::Done {
+            progress = self.rebuild_indexes_step(progress, NEURON_INDEXES_REBUILD_BATCH_SIZE)?;
+        }
+        Ok(())
+    }
+
+    /// Performs one step of clearing the stable indexes and re-deriving them from the neurons in
+    /// the heap and stable memory, which are the source of truth. This is a recovery tool for when
+    /// `verify_indexes` detects inconsistencies. Each step removes at most `batch_size` entries
+    /// or adds at most `batch_size` neurons, so that the rebuild can be spread over several
+    /// messages by persisting the returned progress and passing it to the next step, starting
+    /// from `RebuildIndexesProgress::Clearing`. Once done, a rebuild has no further effect.
+    ///
+    /// Returns `Err(failure_reason)` if a neuron could not be added to the indexes.
+    pub(crate) fn rebuild_indexes_step(
+        &mut self,
+        progress: RebuildIndexesProgress,
+        batch_size: usize,
+    ) -> Result<RebuildIndexesProgress, String> {
+        match progress {
+            RebuildIndexesProgress::Clearing => {
+                let removed =
+                    NEURON_INDEXES.with(|indexes| indexes.borrow_mut().remove_entries(batch_size));
+                if removed < batch_size {
+                    Ok(RebuildIndexesProgress::Adding {
+                        last_neuron_id: NeuronId { id: 0 },
+                    })
+                } else {
+                    Ok(RebuildIndexesProgress::Clearing)
+                }
+            }
+            RebuildIndexesProgress::Adding { last_neuron_id } => {
+                let (neurons, next_cursor) = self.snapshot_chunk(Some(last_neuron_id), batch_size);
+                for neuron in &neurons {
+                    NEURON_INDEXES
+                        .with(|indexes| indexes.borrow_mut().add_neuron(neuron))
+                        .map_err(|error| GovernanceError::from(error).error_message)?;
+                }
+                Ok(match next_cursor {
+                    Some(last_neuron_id) => RebuildIndexesProgress::Adding { last_neuron_id },
+                    None => RebuildIndexesProgress::Done,
+                })
+            }
+            RebuildIndexesProgress::Done => Ok(RebuildIndexesProgress::Done),
+        }
+    }
+
     /// Does what the name says: copies inactive Neurons from heap to stable memory.
     ///
     /// Why not pass (begin, size) instead of batch: Unfortunately, it is not enough to have the
@@ -492,6 +731,106 @@ impl NeuronStore {
         })
     }
 
+    /// Moves a neuron from heap to stable memory: the neuron is written to the stable neuron store
+    /// and then removed from the heap, together with its entries in the (heap) indexes. Since the
+    /// heap indexes no longer cover the neuron, it is added to the stable indexes if they do not
+    /// contain it yet. If the write to stable memory fails, nothing is changed.
+    ///
+    /// Returns `NeuronNotFound` if the neuron is not in the heap.
+    pub fn move_to_stable(&mut self, neuron_id: NeuronId) -> Result<(), NeuronStoreError> {
+        let neuron = self
+            .heap_neurons
+            .get(&neuron_id.id)
+            .ok_or_else(|| NeuronStoreError::not_found(&neuron_id))?;
+
+        // Here, upsert is used instead of create, because inactive neurons might already have
+        // been copied to stable memory by `with_neuron_mut`.
+        STABLE_NEURON_STORE
+            .with(|stable_neuron_store| stable_neuron_store.borrow_mut().upsert(neuron.clone()))
+            .map_err(|governance_error| {
+                NeuronStoreError::stable_neuron_store_failure(
+                    neuron_id,
+                    governance_error.error_message,
+                )
+            })?;
+
+        // The neuron is already in the stable indexes unless the indexes migration has not
+        // reached it yet.
+        NEURON_INDEXES.with(|indexes| {
+            let mut indexes = indexes.borrow_mut();
+            let is_indexed = neuron
+                .subaccount()
+                .map(|subaccount| indexes.subaccount().contains_entry(neuron_id, &subaccount))
+                .unwrap_or_default();
+            if is_indexed {
+                Ok(())
+            } else {
+                indexes.add_neuron(neuron)
+            }
+        })?;
+
+        let neuron = self
+            .heap_neurons
+            .remove(&neuron_id.id)
+            .expect("Neuron was just found in the heap");
+        self.remove_neuron_from_heap_indexes(&neuron);
+        Ok(())
+    }
+
+    /// Moves a neuron from stable memory to the heap: the neuron is deleted from the stable neuron
+    /// store and inserted into the heap, and the (heap) indexes are updated accordingly. If a heap
+    /// copy of the neuron already exists, it is replaced by the one from stable memory.
+    ///
+    /// Returns `NeuronNotFound` if the neuron is not in stable memory.
+    pub fn move_to_heap(&mut self, neuron_id: NeuronId) -> Result<(), NeuronStoreError> {
+        let neuron = STABLE_NEURON_STORE.with(|stable_neuron_store| {
+            let mut stable_neuron_store = stable_neuron_store.borrow_mut();
+            let neuron = stable_neuron_store
+                .read(neuron_id)
+                .map_err(|_| NeuronStoreError::not_found(&neuron_id))?;
+            stable_neuron_store
+                .delete(neuron_id)
+                .map_err(|governance_error| {
+                    NeuronStoreError::stable_neuron_store_failure(
+                        neuron_id,
+                        governance_error.error_message,
+                    )
+                })?;
+            Ok(neuron)
+        })?;
+
+        if let Some(previous_neuron) = self.heap_neurons.remove(&neuron_id.id) {
+            self.remove_neuron_from_heap_indexes(&previous_neuron);
+        }
+        self.add_neuron_to_heap_indexes(&neuron);
+        self.heap_neurons.insert(neuron_id.id, neuron);
+        Ok(())
+    }
+
+    fn add_neuron_to_heap_indexes(&mut self, neuron: &Neuron) {
+        let neuron_id = neuron.id.expect("Neuron must have an id");
+        self.add_neuron_to_principal_to_neuron_ids_index(
+            neuron_id,
+            neuron.principal_ids_with_special_permissions(),
+        );
+        self.add_neuron_to_topic_followee_index(neuron_id, neuron.topic_followee_pairs());
+        if let Some(known_neuron_data) = &neuron.known_neuron_data {
+            self.add_known_neuron_to_index(&known_neuron_data.name);
+        }
+    }
+
+    fn remove_neuron_from_heap_indexes(&mut self, neuron: &Neuron) {
+        let neuron_id = neuron.id.expect("Neuron must have an id");
+        self.remove_neuron_from_principal_to_neuron_ids_index(
+            neuron_id,
+            neuron.principal_ids_with_special_permissions(),
+        );
+        self.remove_neuron_from_topic_followee_index(neuron_id, neuron.topic_followee_pairs());
+        if let Some(known_neuron_data) = &neuron.known_neuron_data {
+            self.remove_known_neuron_from_index(&known_neuron_data.name);
+        }
+    }
+
     // Below are indexes related methods. They don't have a unified interface yet, but NNS1-2507 will change that.
 
     /// Update `index` to map all the given Neuron's hot keys and controller to
@@ -1,10 +1,10 @@
 use super::*;
 use crate::{
     governance::{Governance, MockEnvironment},
-    pb::v1::Governance as GovernanceProto,
+    pb::v1::{Governance as GovernanceProto, KnownNeuronData, NeuronInFlightCommand},
 };
 use ic_nervous_system_common::{cmc::MockCMC, ledger::MockIcpLedger};
-use maplit::btreemap;
+use maplit::{btreemap, hashmap, hashset};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 fn simple_neuron(id: u64) -> Neuron {
@@ -65,6 +65,36 @@ fn test_batch_add_heap_neurons_to_stable_indexes_three_batches_last_empty() {
     );
 }
 
+#[test]
+fn test_peek_next_batch_boundary_matches_batch_add_heap_neurons_to_stable_indexes() {
+    let mut neuron_store = NeuronStore::new(btreemap! {
+        1 => simple_neuron(1),
+        3 => simple_neuron(3),
+        7 => simple_neuron(7),
+        12 => simple_neuron(12),
+        13 => simple_neuron(13),
+    });
+
+    let mut last_neuron_id = NeuronId { id: 0 };
+    loop {
+        let peeked = neuron_store.peek_next_batch_boundary(last_neuron_id, 2);
+        // Peeking does not change the outcome of the next peek.
+        assert_eq!(
+            neuron_store.peek_next_batch_boundary(last_neuron_id, 2),
+            peeked
+        );
+
+        let added = neuron_store.batch_add_heap_neurons_to_stable_indexes(last_neuron_id, 2);
+
+        assert_eq!(added, Ok(peeked));
+        match peeked {
+            Some(next) => last_neuron_id = next,
+            None => break,
+        }
+    }
+    assert_eq!(last_neuron_id, NeuronId { id: 12 });
+}
+
 #[test]
 fn test_batch_add_heap_neurons_to_stable_indexes_failure() {
     let mut neuron_store = NeuronStore::new(btreemap! {
@@ -84,6 +114,92 @@ fn test_batch_add_heap_neurons_to_stable_indexes_failure() {
     assert!(error.contains("already exists in the index"), "{}", error);
 }
 
+/// Rebuilds the stable indexes in small steps, as `maybe_run_neuron_indexes_rebuild` does across
+/// heartbeats.
+fn rebuild_indexes_in_small_steps(neuron_store: &mut NeuronStore) -> Result<(), String> {
+    let mut progress = RebuildIndexesProgress::Clearing;
+    while progress != RebuildIndexesProgress::Done {
+        progress = neuron_store.rebuild_indexes_step(progress, 2)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rebuild_indexes_restores_consistency() {
+    use crate::pb::v1::neuron::Followees;
+    use maplit::hashmap;
+
+    let known_neuron = Neuron {
+        controller: Some(PrincipalId::new_user_test_id(1)),
+        hot_keys: vec![PrincipalId::new_user_test_id(2)],
+        known_neuron_data: Some(KnownNeuronData {
+            name: "known neuron".to_string(),
+            description: None,
+        }),
+        ..simple_neuron(1)
+    };
+    let follower = Neuron {
+        controller: Some(PrincipalId::new_user_test_id(3)),
+        followees: hashmap! {
+            Topic::Governance as i32 => Followees { followees: vec![NeuronId { id: 1 }] },
+        },
+        ..simple_neuron(2)
+    };
+    let mut neuron_store = NeuronStore::new(btreemap! {
+        1 => known_neuron.clone(),
+        2 => follower.clone(),
+        3 => simple_neuron(3),
+    });
+    // Neurons in stable memory are indexed too.
+    assert_eq!(neuron_store.move_to_stable(NeuronId { id: 3 }), Ok(()));
+    assert_eq!(rebuild_indexes_in_small_steps(&mut neuron_store), Ok(()));
+    assert_eq!(neuron_store.verify_indexes(), Ok(()));
+
+    // Corrupt the indexes: entries of a neuron go missing, and entries of an unknown neuron show up.
+    NEURON_INDEXES.with(|indexes| {
+        let mut indexes = indexes.borrow_mut();
+        indexes.remove_neuron(&follower).unwrap();
+        indexes.add_neuron(&simple_neuron(4)).unwrap();
+    });
+    let defects = neuron_store.verify_indexes().unwrap_err();
+    assert!(
+        defects.contains(&NeuronIndexDefect::Following {
+            reason: "Following index has 0 entries while 1 are expected".to_string(),
+        }),
+        "{:?}",
+        defects
+    );
+    assert!(
+        defects.iter().any(|defect| matches!(
+            defect,
+            NeuronIndexDefect::Subaccount { reason } if reason.contains("neuron 2 is missing")
+        )),
+        "{:?}",
+        defects
+    );
+
+    assert_eq!(neuron_store.rebuild_indexes(), Ok(()));
+    assert_eq!(neuron_store.verify_indexes(), Ok(()));
+
+    // Rebuilding consistent indexes has no effect.
+    assert_eq!(rebuild_indexes_in_small_steps(&mut neuron_store), Ok(()));
+    assert_eq!(neuron_store.verify_indexes(), Ok(()));
+    assert_eq!(
+        NEURON_INDEXES.with(|indexes| indexes
+            .borrow()
+            .subaccount()
+            .get_neuron_id_by_subaccount(&follower.subaccount().unwrap())),
+        Some(NeuronId { id: 2 })
+    );
+    assert_eq!(
+        NEURON_INDEXES.with(|indexes| indexes
+            .borrow()
+            .subaccount()
+            .get_neuron_id_by_subaccount(&simple_neuron(3).subaccount().unwrap())),
+        Some(NeuronId { id: 3 })
+    );
+}
+
 #[test]
 fn test_batch_add_inactive_neurons_to_stable_memory() {
     // Step 1: Prepare the world.
@@ -188,6 +304,69 @@ fn test_heap_range_with_begin_and_limit() {
     assert_eq!(observed_neurons, vec![simple_neuron(3), simple_neuron(7)],);
 }
 
+#[test]
+fn test_snapshot_chunks_cover_all_neurons_exactly_once() {
+    let neurons: BTreeMap<u64, Neuron> = (1..=10).map(|id| (id, simple_neuron(id))).collect();
+    let mut neuron_store = NeuronStore::new(neurons.clone());
+    // Neurons 3, 6 and 9 are only in stable memory, while neuron 4 is in both the heap and
+    // stable memory.
+    for id in [3, 6, 9] {
+        assert_eq!(neuron_store.move_to_stable(NeuronId { id }), Ok(()));
+    }
+    STABLE_NEURON_STORE
+        .with(|s| s.borrow_mut().upsert(simple_neuron(4)))
+        .unwrap();
+
+    let mut snapshot = vec![];
+    let mut cursor = None;
+    let mut num_chunks = 0;
+    loop {
+        let (chunk, next_cursor) = neuron_store.snapshot_chunk(cursor, 3);
+        assert!(chunk.len() <= 3);
+        snapshot.extend(chunk);
+        num_chunks += 1;
+        if next_cursor.is_none() {
+            break;
+        }
+        cursor = next_cursor;
+    }
+
+    assert_eq!(num_chunks, 4);
+    assert_eq!(snapshot, neurons.into_values().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_range_heap_neurons_filtered() {
+    let neuron_store = NeuronStore::new(btreemap! {
+        1 => simple_neuron(1),
+        2 => simple_neuron(2),
+        3 => simple_neuron(3),
+        6 => simple_neuron(6),
+        7 => simple_neuron(7),
+        12 => simple_neuron(12),
+    });
+    let is_even = |neuron: &Neuron| neuron.id.unwrap().id % 2 == 0;
+
+    let observed_neurons: Vec<_> = neuron_store
+        .range_heap_neurons_filtered(NeuronId { id: 2 }.., is_even)
+        .collect();
+    assert_eq!(
+        observed_neurons,
+        vec![simple_neuron(2), simple_neuron(6), simple_neuron(12)]
+    );
+
+    let observed_neurons: Vec<_> = neuron_store
+        .range_heap_neurons_filtered(NeuronId { id: 3 }..NeuronId { id: 12 }, is_even)
+        .take(1)
+        .collect();
+    assert_eq!(observed_neurons, vec![simple_neuron(6)]);
+
+    let observed_neurons: Vec<_> = neuron_store
+        .range_heap_neurons_filtered(NeuronId { id: 8 }..NeuronId { id: 12 }, is_even)
+        .collect();
+    assert!(observed_neurons.is_empty(), "{:#?}", observed_neurons);
+}
+
 #[test]
 fn test_with_neuron_mut_inactive_neuron() {
     // Step 1: Prepare the world.
@@ -309,3 +488,269 @@ fn test_with_neuron_mut_inactive_neuron() {
         }
     }
 }
+
+#[test]
+fn test_export_import_round_trip() {
+    // Step 1: Prepare the world.
+
+    // Step 1.1: A mix of active (funded) and inactive (unfunded) neurons, some of which show up in
+    // the indexes.
+    let controller = PrincipalId::new_user_test_id(1);
+    let funded_neuron = Neuron {
+        cached_neuron_stake_e8s: 1, // Funded. Thus, no stable memory.
+        controller: Some(controller),
+        known_neuron_data: Some(KnownNeuronData {
+            name: "known neuron".to_string(),
+            description: None,
+        }),
+        ..simple_neuron(42)
+    };
+    let unfunded_neuron = Neuron {
+        cached_neuron_stake_e8s: 0, // Unfunded. Thus, should be copied to stable memory.
+        controller: Some(controller),
+        ..simple_neuron(777)
+    };
+    let previously_inactive_neuron = simple_neuron(1);
+
+    let proposals = Default::default();
+    let in_flight_commands = Default::default();
+    let is_neuron_inactive = |neuron: &Neuron| neuron.is_inactive(&proposals, &in_flight_commands);
+
+    // Step 1.2: Snapshot a store holding the neurons.
+    let original_neuron_store = NeuronStore::new(btreemap! {
+        42 => funded_neuron.clone(),
+        777 => unfunded_neuron.clone(),
+    });
+    let exported_neurons = original_neuron_store.export_heap();
+
+    // Step 1.3: The store to restore the snapshot into already has a neuron in stable memory.
+    let mut neuron_store = NeuronStore::new(btreemap! {
+        1 => previously_inactive_neuron.clone(),
+    });
+    neuron_store.import(
+        btreemap! { 1 => previously_inactive_neuron },
+        is_neuron_inactive,
+    );
+    assert!(STABLE_NEURON_STORE
+        .with(|s| s.borrow().read(NeuronId { id: 1 }))
+        .is_ok());
+
+    // Step 2: Call the code under test.
+    neuron_store.import(exported_neurons.clone(), is_neuron_inactive);
+
+    // Step 3: Verify.
+
+    // Step 3.1: Neurons and indexes are restored.
+    assert_eq!(neuron_store, original_neuron_store);
+    assert_eq!(neuron_store.export_heap(), exported_neurons);
+    assert_eq!(
+        neuron_store.get_neuron_ids_readable_by_caller(controller),
+        hashset! { NeuronId { id: 42 }, NeuronId { id: 777 } }
+    );
+    assert!(neuron_store.contains_known_neuron_name("known neuron"));
+
+    // Step 3.2: Only the inactive neuron is in stable memory.
+    fn read(neuron_id: u64) -> Result<Neuron, GovernanceError> {
+        STABLE_NEURON_STORE.with(|s| s.borrow().read(NeuronId { id: neuron_id }))
+    }
+    assert_eq!(read(777), Ok(unfunded_neuron));
+    for neuron_id in [1, 42] {
+        let read_result = read(neuron_id);
+        match &read_result {
+            Err(err) => assert_eq!(
+                ErrorType::from_i32(err.error_type),
+                Some(ErrorType::NotFound),
+                "{:?}",
+                err
+            ),
+            Ok(_) => panic!("{:#?}", read_result),
+        }
+    }
+}
+
+#[test]
+fn test_neuron_with_in_flight_command_is_not_copied_to_stable_memory() {
+    let neuron_id = NeuronId { id: 42 };
+    fn read(neuron_id: NeuronId) -> Result<Neuron, GovernanceError> {
+        STABLE_NEURON_STORE.with(|s| s.borrow().read(neuron_id))
+    }
+
+    // Step 1: Restore a Governance whose (otherwise inactive) neuron has an in-flight command.
+    let mut governance = {
+        let governance_proto = GovernanceProto {
+            neurons: btreemap! {
+                42 => simple_neuron(42),
+            },
+            in_flight_commands: hashmap! {
+                42 => NeuronInFlightCommand::default(),
+            },
+            ..Default::default()
+        };
+
+        let mut environment = MockEnvironment::new();
+        let now_timestamp_seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        environment.expect_now().return_const(now_timestamp_seconds);
+
+        Governance::new(
+            governance_proto,
+            Box::new(environment),
+            Box::new(MockIcpLedger::new()),
+            Box::new(MockCMC::new()),
+        )
+    };
+    assert!(governance.has_in_flight_command(&neuron_id));
+    assert_eq!(
+        governance.neuron_ids_with_in_flight_commands(),
+        vec![neuron_id]
+    );
+
+    // Step 2: While there is an in-flight command, the neuron is treated as active.
+    governance
+        .with_neuron_mut(&neuron_id, |neuron| neuron.cached_neuron_stake_e8s = 0)
+        .unwrap();
+
+    let read_result = read(neuron_id);
+    match &read_result {
+        Err(err) => assert_eq!(
+            ErrorType::from_i32(err.error_type),
+            Some(ErrorType::NotFound),
+            "{:?}",
+            err
+        ),
+        Ok(_) => panic!("{:#?}", read_result),
+    }
+
+    // Step 3: Once the command is no longer in flight, the neuron is copied.
+    governance
+        .heap_data
+        .in_flight_commands
+        .remove(&neuron_id.id);
+    assert!(!governance.has_in_flight_command(&neuron_id));
+    assert_eq!(governance.neuron_ids_with_in_flight_commands(), vec![]);
+
+    governance
+        .with_neuron_mut(&neuron_id, |neuron| neuron.account = vec![1, 2, 3])
+        .unwrap();
+
+    assert_eq!(
+        read(neuron_id),
+        Ok(Neuron {
+            account: vec![1, 2, 3],
+            ..simple_neuron(42)
+        })
+    );
+}
+
+#[test]
+fn test_move_to_stable_keeps_neurons_in_stable_indexes() {
+    let neuron = |id: u64| Neuron {
+        controller: Some(PrincipalId::new_user_test_id(id)),
+        ..simple_neuron(id)
+    };
+    let mut neuron_store = NeuronStore::new(btreemap! {
+        1 => neuron(1),
+        2 => neuron(2),
+    });
+    // Only neuron 1 has been added to the stable indexes by the migration so far.
+    NEURON_INDEXES
+        .with(|indexes| indexes.borrow_mut().add_neuron(&neuron(1)))
+        .unwrap();
+    let lookup = |id: u64| {
+        NEURON_INDEXES.with(|indexes| {
+            let indexes = indexes.borrow();
+            (
+                indexes
+                    .subaccount()
+                    .get_neuron_id_by_subaccount(&neuron(id).subaccount().unwrap()),
+                indexes
+                    .principal()
+                    .get_neuron_ids(PrincipalId::new_user_test_id(id)),
+            )
+        })
+    };
+
+    for id in [1, 2] {
+        assert_eq!(neuron_store.move_to_stable(NeuronId { id }), Ok(()));
+        assert!(neuron_store
+            .get_neuron_ids_readable_by_caller(PrincipalId::new_user_test_id(id))
+            .is_empty());
+        assert_eq!(
+            lookup(id),
+            (Some(NeuronId { id }), hashset! { NeuronId { id } })
+        );
+    }
+    assert_eq!(
+        NEURON_INDEXES.with(|indexes| indexes.borrow().verify([neuron(1), neuron(2)].iter())),
+        Ok(())
+    );
+
+    // Moving a neuron back to the heap leaves the stable indexes unchanged.
+    assert_eq!(neuron_store.move_to_heap(NeuronId { id: 2 }), Ok(()));
+    assert_eq!(
+        lookup(2),
+        (Some(NeuronId { id: 2 }), hashset! { NeuronId { id: 2 } })
+    );
+}
+
+#[test]
+fn test_move_neuron_between_heap_and_stable_memory() {
+    let controller = PrincipalId::new_user_test_id(1);
+    let neuron = Neuron {
+        controller: Some(controller),
+        known_neuron_data: Some(KnownNeuronData {
+            name: "known neuron".to_string(),
+            description: None,
+        }),
+        ..simple_neuron(42)
+    };
+    let neuron_id = neuron.id.unwrap();
+    let mut neuron_store = NeuronStore::new(btreemap! {
+        42 => neuron.clone(),
+    });
+    fn is_in_stable_memory(neuron_id: NeuronId) -> bool {
+        STABLE_NEURON_STORE.with(|s| s.borrow().read(neuron_id).is_ok())
+    }
+
+    // Step 1: Move the neuron to stable memory. It is no longer in the heap or the heap indexes.
+    assert_eq!(neuron_store.move_to_stable(neuron_id), Ok(()));
+    assert!(!neuron_store.contains(neuron_id));
+    assert!(is_in_stable_memory(neuron_id));
+    assert_eq!(
+        STABLE_NEURON_STORE.with(|s| s.borrow().read(neuron_id)),
+        Ok(neuron.clone())
+    );
+    assert!(neuron_store
+        .get_neuron_ids_readable_by_caller(controller)
+        .is_empty());
+    assert!(!neuron_store.contains_known_neuron_name("known neuron"));
+
+    // Step 2: Moving it to stable memory again fails, since it is no longer in the heap.
+    assert_eq!(
+        neuron_store.move_to_stable(neuron_id),
+        Err(NeuronStoreError::not_found(&neuron_id))
+    );
+
+    // Step 3: Move the neuron back to the heap. It is no longer in stable memory, and the heap
+    // indexes are restored.
+    assert_eq!(neuron_store.move_to_heap(neuron_id), Ok(()));
+    assert!(neuron_store.contains(neuron_id));
+    assert!(!is_in_stable_memory(neuron_id));
+    assert_eq!(
+        neuron_store.with_neuron(&neuron_id, |neuron| neuron.clone()),
+        Ok(neuron)
+    );
+    assert_eq!(
+        neuron_store.get_neuron_ids_readable_by_caller(controller),
+        hashset! { neuron_id }
+    );
+    assert!(neuron_store.contains_known_neuron_name("known neuron"));
+
+    // Step 4: Moving it to the heap again fails, since it is no longer in stable memory.
+    assert_eq!(
+        neuron_store.move_to_heap(neuron_id),
+        Err(NeuronStoreError::not_found(&neuron_id))
+    );
+}
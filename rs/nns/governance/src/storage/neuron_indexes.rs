@@ -619,6 +619,146 @@ where
         }
     }
 
+    /// Removes up to `max_entries` entries from the indexes (in the order of subaccount, principal,
+    /// following and known neuron indexes), and returns the number of removed entries. The indexes
+    /// are empty once it returns less than `max_entries`.
+    pub fn remove_entries(&mut self, max_entries: usize) -> usize {
+        let mut removed = self.subaccount.remove_entries(max_entries);
+        removed += self.principal.remove_entries(max_entries - removed);
+        removed += self.following.remove_entries(max_entries - removed);
+        removed += self.known_neuron.remove_entries(max_entries - removed);
+        removed
+    }
+
+    /// Checks that the indexes contain exactly the entries derived from `neurons`: every entry of
+    /// each neuron should be in the indexes, and the indexes should not have any other entry.
+    pub fn verify<'a>(
+        &self,
+        neurons: impl IntoIterator<Item = &'a Neuron>,
+    ) -> Result<(), Vec<NeuronIndexDefect>> {
+        let mut defects = vec![];
+        let mut num_subaccount_entries = 0;
+        let mut num_principal_entries = 0;
+        let mut num_following_entries = 0;
+        let mut num_known_neuron_entries = 0;
+
+        for neuron in neurons {
+            // Neurons without id are never added to the indexes, see `validate_neuron`.
+            let neuron_id = match neuron.id {
+                Some(neuron_id) => neuron_id,
+                None => continue,
+            };
+
+            match neuron.subaccount() {
+                Ok(subaccount) => {
+                    num_subaccount_entries += 1;
+                    if !self.subaccount.contains_entry(neuron_id, &subaccount) {
+                        defects.push(NeuronIndexDefect::Subaccount {
+                            reason: format!("Subaccount of neuron {} is missing", neuron_id.id),
+                        });
+                    }
+                }
+                Err(_) => defects.push(NeuronIndexDefect::Subaccount {
+                    reason: format!("Neuron {} has an invalid subaccount", neuron_id.id),
+                }),
+            }
+
+            for principal_id in neuron.principal_ids_with_special_permissions() {
+                num_principal_entries += 1;
+                if !self.principal.contains_entry(&neuron_id.id, principal_id) {
+                    defects.push(NeuronIndexDefect::Principal {
+                        reason: format!(
+                            "Principal {} of neuron {} is missing",
+                            principal_id, neuron_id.id
+                        ),
+                    });
+                }
+            }
+
+            for (topic, followee) in neuron.topic_followee_pairs() {
+                num_following_entries += 1;
+                let topic = Topic::from(topic as i32);
+                if !self
+                    .following
+                    .contains_entry(topic, &followee.id, &neuron_id.id)
+                {
+                    defects.push(NeuronIndexDefect::Following {
+                        reason: format!(
+                            "Topic-followee pair {:?} of neuron {} is missing",
+                            (topic, followee.id),
+                            neuron_id.id
+                        ),
+                    });
+                }
+            }
+
+            if let Some(known_neuron_data) = neuron.known_neuron_data.as_ref() {
+                num_known_neuron_entries += 1;
+                if !self
+                    .known_neuron
+                    .contains_entry(neuron_id, &known_neuron_data.name)
+                {
+                    defects.push(NeuronIndexDefect::KnownNeuron {
+                        reason: format!(
+                            "Known neuron name {} of neuron {} is missing",
+                            known_neuron_data.name, neuron_id.id
+                        ),
+                    });
+                }
+            }
+        }
+
+        // Entries that do not belong to any neuron are only detected by counting.
+        let unexpected_num_entries = |name: &str, actual: usize, expected: usize| {
+            format!(
+                "{} index has {} entries while {} are expected",
+                name, actual, expected
+            )
+        };
+        if self.subaccount.num_entries() != num_subaccount_entries {
+            defects.push(NeuronIndexDefect::Subaccount {
+                reason: unexpected_num_entries(
+                    "Subaccount",
+                    self.subaccount.num_entries(),
+                    num_subaccount_entries,
+                ),
+            });
+        }
+        if self.principal.num_entries() != num_principal_entries {
+            defects.push(NeuronIndexDefect::Principal {
+                reason: unexpected_num_entries(
+                    "Principal",
+                    self.principal.num_entries(),
+                    num_principal_entries,
+                ),
+            });
+        }
+        if self.following.num_entries() != num_following_entries {
+            defects.push(NeuronIndexDefect::Following {
+                reason: unexpected_num_entries(
+                    "Following",
+                    self.following.num_entries(),
+                    num_following_entries,
+                ),
+            });
+        }
+        if self.known_neuron.num_entries() != num_known_neuron_entries {
+            defects.push(NeuronIndexDefect::KnownNeuron {
+                reason: unexpected_num_entries(
+                    "Known neuron",
+                    self.known_neuron.num_entries(),
+                    num_known_neuron_entries,
+                ),
+            });
+        }
+
+        if defects.is_empty() {
+            Ok(())
+        } else {
+            Err(defects)
+        }
+    }
+
     fn indexes_mut(&mut self) -> Vec<&mut dyn NeuronIndex> {
         vec![
             &mut self.subaccount,
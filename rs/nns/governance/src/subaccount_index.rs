@@ -34,6 +34,22 @@ impl<M: Memory> NeuronSubaccountIndex<M> {
             .unwrap_or_default()
     }
 
+    /// Removes up to `max_entries` entries from the index and returns the number of removed
+    /// entries. This is for recovery purpose: the index can be cleared in bounded batches before
+    /// being rebuilt from the primary storage.
+    pub fn remove_entries(&mut self, max_entries: usize) -> usize {
+        let keys: Vec<_> = self
+            .subaccount_to_id
+            .iter()
+            .take(max_entries)
+            .map(|(key, _)| key)
+            .collect();
+        for key in &keys {
+            self.subaccount_to_id.remove(key);
+        }
+        keys.len()
+    }
+
     /// Adds a neuron into the index. Returns error if the subaccount already exists
     /// in the index and the index should remain unchanged.
     pub fn add_neuron_subaccount(
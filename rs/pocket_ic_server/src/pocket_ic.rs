@@ -1,59 +1,648 @@
+use crate::state_api::state::CanisterStatus;
 use crate::state_api::state::HasStateLabel;
 use crate::state_api::state::OpOut;
 use crate::state_api::state::StateLabel;
 use crate::OpId;
 use crate::Operation;
+use ic_certification::verify_certificate;
 use ic_config::execution_environment;
+use ic_config::execution_environment::BitcoinConfig;
 use ic_config::subnet_config::SubnetConfig;
+use ic_crypto::threshold_sig_public_key_to_der;
+use ic_crypto_ecdsa_secp256k1::PrivateKey;
 use ic_crypto_sha2::Sha256;
 use ic_ic00_types::CanisterInstallMode;
+use ic_ic00_types::{self as ic00, Payload};
+use ic_registry_routing_table::{
+    canister_id_into_u64, CanisterIdRange, RoutingTable, CANISTER_IDS_PER_SUBNET,
+};
+use ic_registry_subnet_features::SubnetFeatures as RegistrySubnetFeatures;
 use ic_registry_subnet_type::SubnetType;
+use ic_state_machine_tests::CryptoHashOfState;
 use ic_state_machine_tests::Cycles;
+use ic_state_machine_tests::Label;
+use ic_state_machine_tests::PayloadBuilder;
 use ic_state_machine_tests::StateMachine;
 use ic_state_machine_tests::StateMachineBuilder;
 use ic_state_machine_tests::StateMachineConfig;
 use ic_state_machine_tests::Time;
-use ic_types::{CanisterId, PrincipalId};
+use ic_state_machine_tests::WasmResult;
+use ic_types::batch::XNetPayload;
+use ic_types::ingress::{IngressState, IngressStatus};
+use ic_types::messages::{SignedIngress, SignedRequestBytes};
+use ic_types::xnet::StreamIndex;
+use ic_types::{CanisterId, CanisterTimer, PrincipalId, SubnetId};
+use ic_validator_ingress_message::{HttpRequestVerifier, IngressMessageVerifier, TimeProvider};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
 
 pub struct PocketIc {
-    subnet: StateMachine,
+    /// The subnets of the instance, keyed by their id.
+    subnets: BTreeMap<SubnetId, StateMachine>,
+    /// The subnet that canisters are created on unless stated otherwise. It also serves requests
+    /// that are not addressed to a particular canister, e.g. for the root key.
+    main_subnet_id: SubnetId,
+    /// The parameters each subnet was built with, which are persisted in snapshots.
+    subnet_setups: BTreeMap<SubnetId, SubnetSetup>,
+    /// The subnets that canisters were migrated to, see [`MigrateCanister`].
+    migrated_canisters: BTreeMap<CanisterId, SubnetId>,
+    features: SubnetFeatures,
+    cycles_minting_rate: CyclesMintingRate,
+    auto_progress_time: Option<AutoProgressTime>,
     nonce: u64,
     time: Time,
 }
 
+/// The type and registry a subnet of a PocketIc instance was built with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SubnetSetup {
+    subnet_type: SubnetType,
+    registry: RegistrySnapshot,
+}
+
+/// The rate at which ICP is converted to cycles, as by the Cycles Minting Canister, see
+/// [`MintCyclesFromIcp`]. Defaults to 1 XDR per ICP and 1T cycles per XDR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CyclesMintingRate {
+    /// Number of 1/10,000ths of XDR that 1 ICP is worth.
+    pub xdr_permyriad_per_icp: u64,
+    /// Number of cycles that 1 XDR is worth.
+    pub cycles_per_xdr: u128,
+}
+
+impl Default for CyclesMintingRate {
+    fn default() -> Self {
+        Self {
+            xdr_permyriad_per_icp: 10_000,
+            cycles_per_xdr: 1_000_000_000_000,
+        }
+    }
+}
+
+impl CyclesMintingRate {
+    /// Returns the number of cycles that `amount_e8s` ICP (in e8s) are worth.
+    pub fn to_cycles(&self, amount_e8s: u64) -> u128 {
+        (amount_e8s as u128)
+            .saturating_mul(self.xdr_permyriad_per_icp as u128)
+            .saturating_mul(self.cycles_per_xdr)
+            / (100_000_000 * 10_000)
+    }
+}
+
+/// Makes every round executed by [`Tick`] advance the time of a PocketIc instance by a fixed
+/// increment, like the wall-clock time advances between the rounds of a real subnet, so that
+/// heartbeats and timers make progress without interleaving [`SetTime`] operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoProgressTime {
+    pub increment: Duration,
+}
+
+/// The features enabled on the subnet of a PocketIc instance. Management canister calls that
+/// rely on a disabled feature are rejected. All features are enabled by default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubnetFeatures {
+    /// Canister HTTP outcalls (`http_request`).
+    pub http_requests: bool,
+    /// Threshold ECDSA (`ecdsa_public_key` and `sign_with_ecdsa`).
+    pub ecdsa_signing: bool,
+    /// Routing of the bitcoin API (e.g. `bitcoin_get_balance`) to the bitcoin canisters.
+    pub bitcoin: bool,
+}
+
+impl Default for SubnetFeatures {
+    fn default() -> Self {
+        Self {
+            http_requests: true,
+            ecdsa_signing: true,
+            bitcoin: true,
+        }
+    }
+}
+
+/// The registry records describing the topology seen by the subnet of a PocketIc instance,
+/// for canisters that depend on it, e.g. to discover subnets. Unset fields keep the defaults
+/// of a standalone subnet.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    /// The id of the subnet of the instance.
+    pub subnet_id: Option<PrincipalId>,
+    /// The id of the NNS subnet. Defaults to the subnet of the instance.
+    pub nns_subnet_id: Option<PrincipalId>,
+    /// The number of nodes of the subnet of the instance.
+    pub subnet_size: Option<usize>,
+    /// The canister ranges of the routing table along with the subnets they are routed to.
+    /// If empty, the subnet of the instance is assigned the default canister range.
+    pub routing_table: Vec<RoutingTableEntry>,
+    /// The id of the first canister created on the subnet of the instance. If set, the subnet of
+    /// the instance is assigned the canister range of the default size starting at this id, taking
+    /// precedence over the routing table, so that instances assign the same canister ids to the
+    /// same sequence of canister creations.
+    #[serde(default)]
+    pub canister_id_base: Option<CanisterId>,
+}
+
+impl RegistrySnapshot {
+    /// Returns the routing table made of the entries of `routing_table`, or an error if they
+    /// overlap or are otherwise not well formed.
+    fn routing_table(&self) -> Result<RoutingTable, String> {
+        let mut routing_table = RoutingTable::new();
+        for entry in &self.routing_table {
+            routing_table
+                .insert(
+                    CanisterIdRange {
+                        start: entry.start,
+                        end: entry.end,
+                    },
+                    SubnetId::from(entry.subnet_id),
+                )
+                .map_err(|e| format!("malformed routing table: {:?}", e))?;
+        }
+        Ok(routing_table)
+    }
+}
+
+/// Routes the canisters from `start` to `end` (inclusive) to the subnet `subnet_id`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutingTableEntry {
+    pub start: CanisterId,
+    pub end: CanisterId,
+    pub subnet_id: PrincipalId,
+}
+
 #[allow(clippy::new_without_default)]
 impl PocketIc {
     pub fn new() -> Self {
-        let hypervisor_config = execution_environment::Config {
+        Self::new_with_features(SubnetFeatures::default())
+    }
+
+    pub fn new_with_features(features: SubnetFeatures) -> Self {
+        Self::new_with_config(
+            SubnetType::System,
+            SubnetConfig::new(SubnetType::System),
+            features,
+            RegistrySnapshot::default(),
+        )
+    }
+
+    /// Creates an instance whose canister ids are assigned starting from the given id, see
+    /// [`RegistrySnapshot::canister_id_base`].
+    pub fn new_with_canister_id_base(canister_id_base: CanisterId) -> Self {
+        Self::new_with_config(
+            SubnetType::System,
+            SubnetConfig::new(SubnetType::System),
+            SubnetFeatures::default(),
+            RegistrySnapshot {
+                canister_id_base: Some(canister_id_base),
+                ..RegistrySnapshot::default()
+            },
+        )
+    }
+
+    /// Creates an instance whose registry is seeded with the given snapshot.
+    /// Returns an error if the routing table of the snapshot is not well formed.
+    pub fn new_with_registry_snapshot(
+        features: SubnetFeatures,
+        registry: RegistrySnapshot,
+    ) -> Result<Self, String> {
+        registry.routing_table()?;
+        Ok(Self::new_with_config(
+            SubnetType::System,
+            SubnetConfig::new(SubnetType::System),
+            features,
+            registry,
+        ))
+    }
+
+    fn new_with_config(
+        subnet_type: SubnetType,
+        subnet_config: SubnetConfig,
+        features: SubnetFeatures,
+        registry: RegistrySnapshot,
+    ) -> Self {
+        let subnet = Self::subnet_builder(subnet_type, subnet_config, &features, &registry).build();
+        let main_subnet_id = subnet.get_subnet_id();
+        Self {
+            subnets: BTreeMap::from([(main_subnet_id, subnet)]),
+            main_subnet_id,
+            subnet_setups: BTreeMap::from([(
+                main_subnet_id,
+                SubnetSetup {
+                    subnet_type,
+                    registry,
+                },
+            )]),
+            migrated_canisters: BTreeMap::new(),
+            features,
+            cycles_minting_rate: CyclesMintingRate::default(),
+            auto_progress_time: None,
+            nonce: 0,
+            time: Time::from_nanos_since_unix_epoch(0),
+        }
+    }
+
+    /// Creates an instance hosting a system subnet and an application subnet, which route the
+    /// canister ranges of both subnets to each other, so that canisters on different subnets can
+    /// call each other. Canisters are created on the system subnet unless they are created on the
+    /// application subnet directly.
+    pub fn new_with_application_subnet() -> Self {
+        let system_subnet_id = PrincipalId::new_subnet_test_id(1);
+        let application_subnet_id = PrincipalId::new_subnet_test_id(2);
+        let routing_table: Vec<_> = [system_subnet_id, application_subnet_id]
+            .into_iter()
+            .zip(0..)
+            .map(|(subnet_id, index)| RoutingTableEntry {
+                start: CanisterId::from_u64(index * CANISTER_IDS_PER_SUBNET),
+                end: CanisterId::from_u64((index + 1) * CANISTER_IDS_PER_SUBNET - 1),
+                subnet_id,
+            })
+            .collect();
+        let mut pic = Self::new_with_config(
+            SubnetType::System,
+            SubnetConfig::new(SubnetType::System),
+            SubnetFeatures::default(),
+            RegistrySnapshot {
+                subnet_id: Some(system_subnet_id),
+                routing_table: routing_table.clone(),
+                ..RegistrySnapshot::default()
+            },
+        );
+        pic.insert_subnet(
+            SubnetType::Application,
+            RegistrySnapshot {
+                subnet_id: Some(application_subnet_id),
+                nns_subnet_id: Some(system_subnet_id),
+                routing_table,
+                ..RegistrySnapshot::default()
+            },
+        );
+        pic
+    }
+
+    /// Adds an application subnet to this instance and returns its id.
+    /// Canisters can be migrated to the new subnet with [`MigrateCanister`].
+    pub fn add_subnet(&mut self) -> SubnetId {
+        let subnet_id = (2..)
+            .map(|id| SubnetId::from(PrincipalId::new_subnet_test_id(id)))
+            .find(|id| !self.subnets.contains_key(id))
+            .expect("BUG: ran out of subnet ids");
+        let nns_subnet_id = self
+            .registry()
+            .nns_subnet_id
+            .unwrap_or_else(|| self.main_subnet_id.get());
+        self.insert_subnet(
+            SubnetType::Application,
+            RegistrySnapshot {
+                subnet_id: Some(subnet_id.get()),
+                nns_subnet_id: Some(nns_subnet_id),
+                ..RegistrySnapshot::default()
+            },
+        )
+    }
+
+    /// Builds a subnet of the given type with the given registry and adds it to this instance.
+    fn insert_subnet(&mut self, subnet_type: SubnetType, registry: RegistrySnapshot) -> SubnetId {
+        let subnet = Self::subnet_builder(
+            subnet_type,
+            SubnetConfig::new(subnet_type),
+            &self.features,
+            &registry,
+        )
+        .build();
+        let subnet_id = subnet.get_subnet_id();
+        self.subnets.insert(subnet_id, subnet);
+        self.subnet_setups.insert(
+            subnet_id,
+            SubnetSetup {
+                subnet_type,
+                registry,
+            },
+        );
+        subnet_id
+    }
+
+    fn main_subnet(&self) -> &StateMachine {
+        &self.subnets[&self.main_subnet_id]
+    }
+
+    /// The registry of the main subnet.
+    fn registry(&self) -> &RegistrySnapshot {
+        &self.subnet_setups[&self.main_subnet_id].registry
+    }
+
+    /// Returns the subnet hosting the given canister. Canisters that do not exist are attributed
+    /// to the subnet that the routing table assigns them to if it belongs to the instance, and to
+    /// the main subnet otherwise.
+    fn subnet_hosting(&self, canister_id: CanisterId) -> &StateMachine {
+        self.subnets
+            .values()
+            .find(|subnet| subnet.canister_exists(canister_id))
+            .or_else(|| {
+                self.main_subnet()
+                    .route(canister_id)
+                    .and_then(|subnet_id| self.subnets.get(&subnet_id))
+            })
+            .unwrap_or_else(|| self.main_subnet())
+    }
+
+    /// Executes a single round on every subnet, in which a subnet also inducts the XNet messages
+    /// and signals that the other subnets of the instance have for it.
+    fn execute_round(&self) {
+        for into in self.subnets.values() {
+            let stream_slices = self
+                .subnets
+                .values()
+                .filter(|from| from.get_subnet_id() != into.get_subnet_id())
+                .filter(|from| has_xnet_traffic(from, into))
+                .filter_map(|from| {
+                    let begin = into
+                        .get_latest_state()
+                        .get_stream(&from.get_subnet_id())
+                        .map(|stream| stream.signals_end());
+                    from.generate_xnet_payload(into.get_subnet_id(), begin, begin, None, None)
+                        .ok()
+                })
+                .flat_map(|payload| payload.stream_slices)
+                .collect();
+            into.tick_with_xnet_payload(XNetPayload { stream_slices });
+        }
+    }
+
+    /// Returns whether any subnet has messages to execute or to induct into another subnet.
+    fn has_pending_messages(&self) -> bool {
+        self.subnets
+            .values()
+            .any(|subnet| subnet.has_pending_messages())
+            || self.subnets.values().any(|from| {
+                self.subnets.values().any(|into| {
+                    from.get_subnet_id() != into.get_subnet_id() && has_xnet_traffic(from, into)
+                })
+            })
+    }
+
+    fn instructions_consumed(&self) -> f64 {
+        self.subnets
+            .values()
+            .map(|subnet| subnet.instructions_consumed())
+            .sum()
+    }
+
+    fn num_messages_executed(&self) -> u64 {
+        self.subnets
+            .values()
+            .map(|subnet| subnet.num_messages_executed())
+            .sum()
+    }
+
+    /// Sets the time of the instance and of all of its subnets.
+    fn set_time(&mut self, time: Time) {
+        for subnet in self.subnets.values() {
+            subnet.set_time(time.into());
+        }
+        self.time = time;
+    }
+
+    fn subnet_builder(
+        subnet_type: SubnetType,
+        subnet_config: SubnetConfig,
+        features: &SubnetFeatures,
+        registry: &RegistrySnapshot,
+    ) -> StateMachineBuilder {
+        let mut hypervisor_config = execution_environment::Config {
             default_provisional_cycles_balance: Cycles::new(0),
             ..Default::default()
         };
-        let config =
-            StateMachineConfig::new(SubnetConfig::new(SubnetType::System), hypervisor_config);
-        let sm = StateMachineBuilder::new()
+        if !features.bitcoin {
+            hypervisor_config.bitcoin = BitcoinConfig::default();
+        }
+        let config = StateMachineConfig::new(subnet_config, hypervisor_config);
+        let mut builder = StateMachineBuilder::new()
             .with_config(Some(config))
+            .with_subnet_type(subnet_type)
+            .with_features(RegistrySubnetFeatures {
+                http_requests: features.http_requests,
+                ..RegistrySubnetFeatures::default()
+            })
             // essential for calculating state hashes
             // TODO: this degrades performance. enable only on demand.
-            .with_checkpoints_enabled(true)
+            .with_checkpoints_enabled(true);
+        if !features.ecdsa_signing {
+            builder = builder.with_ecdsa_keys(vec![]);
+        }
+        if let Some(subnet_id) = registry.subnet_id {
+            builder = builder.with_subnet_id(SubnetId::from(subnet_id));
+        }
+        if let Some(nns_subnet_id) = registry.nns_subnet_id {
+            builder = builder.with_nns_subnet_id(SubnetId::from(nns_subnet_id));
+        }
+        if let Some(subnet_size) = registry.subnet_size {
+            builder = builder.with_subnet_size(subnet_size);
+        }
+        if !registry.routing_table.is_empty() {
+            let routing_table = registry
+                .routing_table()
+                .expect("BUG: the routing table was validated when creating the instance");
+            builder = builder.with_routing_table(routing_table);
+        }
+        if let Some(base) = registry.canister_id_base {
+            let end = canister_id_into_u64(base).saturating_add(CANISTER_IDS_PER_SUBNET - 1);
+            builder = builder.with_extra_canister_range(base..=CanisterId::from_u64(end));
+        }
+        builder
+    }
+
+    /// Writes a snapshot of this instance to the given directory, which must not exist yet, so
+    /// that it can be restored with [`Self::load_from_path`], e.g. in a different test binary.
+    /// The snapshot consists of the checkpointed state, type and registry of every subnet along
+    /// with the nonce, time, features and canister migrations of the instance. Test ECDSA keys
+    /// and custom subnet configurations are not part of the snapshot.
+    pub fn persist_to_path(&self, path: &Path) -> Result<(), String> {
+        let subnets = self
+            .subnets
+            .iter()
+            .map(|(subnet_id, subnet)| SubnetSnapshotMetadata {
+                subnet_id: *subnet_id,
+                setup: self.subnet_setups[subnet_id].clone(),
+                nonce: subnet.nonce(),
+                time: Time::try_from(subnet.time())
+                    .expect("subnet time out of range")
+                    .as_nanos_since_unix_epoch(),
+            })
+            .collect();
+        let metadata = SnapshotMetadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            main_subnet_id: self.main_subnet_id,
+            subnets,
+            migrated_canisters: self.migrated_canisters.clone().into_iter().collect(),
+            features: self.features.clone(),
+            cycles_minting_rate: self.cycles_minting_rate,
+            auto_progress_time: self.auto_progress_time,
+            nonce: self.nonce,
+            time: self.time.as_nanos_since_unix_epoch(),
+        };
+        std::fs::create_dir(path)
+            .map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+        for (subnet_id, subnet) in &self.subnets {
+            copy_dir_all(
+                subnet.state_dir.path(),
+                &path.join(SNAPSHOT_STATE_DIR).join(subnet_id.to_string()),
+            )?;
+        }
+        let metadata = serde_json::to_vec(&metadata).expect("failed to encode snapshot metadata");
+        std::fs::write(path.join(SNAPSHOT_METADATA_FILE), metadata)
+            .map_err(|e| format!("failed to write snapshot metadata: {}", e))
+    }
+
+    /// Restores an instance from a snapshot written by [`Self::persist_to_path`]. The snapshot
+    /// itself is left untouched. Fails if the snapshot was written by a different version of the
+    /// PocketIc server.
+    pub fn load_from_path(path: &Path) -> Result<Self, String> {
+        let metadata = std::fs::read(path.join(SNAPSHOT_METADATA_FILE))
+            .map_err(|e| format!("failed to read snapshot metadata: {}", e))?;
+        let metadata: SnapshotMetadata = serde_json::from_slice(&metadata)
+            .map_err(|e| format!("failed to decode snapshot metadata: {}", e))?;
+        if metadata.version != env!("CARGO_PKG_VERSION") {
+            return Err(format!(
+                "snapshot was written by PocketIc server version {}, but this is version {}",
+                metadata.version,
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+        let mut subnets = BTreeMap::new();
+        let mut subnet_setups = BTreeMap::new();
+        for subnet in metadata.subnets {
+            let state_dir = TempDir::new()
+                .map_err(|e| format!("failed to create a temporary directory: {}", e))?;
+            copy_dir_all(
+                &path
+                    .join(SNAPSHOT_STATE_DIR)
+                    .join(subnet.subnet_id.to_string()),
+                state_dir.path(),
+            )?;
+            let state_machine = Self::subnet_builder(
+                subnet.setup.subnet_type,
+                SubnetConfig::new(subnet.setup.subnet_type),
+                &metadata.features,
+                &subnet.setup.registry,
+            )
+            .with_state_dir(state_dir)
+            .with_nonce(subnet.nonce)
+            .with_time(Time::from_nanos_since_unix_epoch(subnet.time))
             .build();
-        Self {
-            subnet: sm,
-            nonce: 0,
-            time: Time::from_nanos_since_unix_epoch(0),
+            subnets.insert(subnet.subnet_id, state_machine);
+            subnet_setups.insert(subnet.subnet_id, subnet.setup);
+        }
+        if !subnets.contains_key(&metadata.main_subnet_id) {
+            return Err(format!(
+                "snapshot does not contain the main subnet {}",
+                metadata.main_subnet_id
+            ));
+        }
+        for (canister_id, subnet_id) in &metadata.migrated_canisters {
+            for subnet in subnets.values() {
+                subnet.reroute_canister_range(*canister_id..=*canister_id, *subnet_id);
+            }
+        }
+        Ok(Self {
+            subnets,
+            main_subnet_id: metadata.main_subnet_id,
+            subnet_setups,
+            migrated_canisters: metadata.migrated_canisters.into_iter().collect(),
+            features: metadata.features,
+            cycles_minting_rate: metadata.cycles_minting_rate,
+            auto_progress_time: metadata.auto_progress_time,
+            nonce: metadata.nonce,
+            time: Time::from_nanos_since_unix_epoch(metadata.time),
+        })
+    }
+}
+
+const SNAPSHOT_METADATA_FILE: &str = "pocket_ic.json";
+const SNAPSHOT_STATE_DIR: &str = "state";
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotMetadata {
+    version: String,
+    main_subnet_id: SubnetId,
+    subnets: Vec<SubnetSnapshotMetadata>,
+    #[serde(default)]
+    migrated_canisters: Vec<(CanisterId, SubnetId)>,
+    features: SubnetFeatures,
+    #[serde(default)]
+    cycles_minting_rate: CyclesMintingRate,
+    #[serde(default)]
+    auto_progress_time: Option<AutoProgressTime>,
+    nonce: u64,
+    time: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubnetSnapshotMetadata {
+    subnet_id: SubnetId,
+    setup: SubnetSetup,
+    nonce: u64,
+    time: u64,
+}
+
+/// Returns whether the stream from `from` to `into` holds messages that `into` has not inducted
+/// yet, or signals for messages that `into` has not garbage collected yet.
+fn has_xnet_traffic(from: &StateMachine, into: &StateMachine) -> bool {
+    let from_state = from.get_latest_state();
+    let Some(stream) = from_state.get_stream(&into.get_subnet_id()) else {
+        return false;
+    };
+    let into_state = into.get_latest_state();
+    let reverse_stream = into_state.get_stream(&from.get_subnet_id());
+    let signals_end = reverse_stream.map_or(StreamIndex::new(0), |s| s.signals_end());
+    let messages_begin = reverse_stream.map_or(StreamIndex::new(0), |s| s.messages_begin());
+    stream.messages_end() > signals_end || stream.signals_end() > messages_begin
+}
+
+/// Recursively copies the contents of `from` into `to`, creating `to` if needed.
+fn copy_dir_all(from: &Path, to: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| format!("failed to create {}: {}", to.display(), e))?;
+    let entries =
+        std::fs::read_dir(from).map_err(|e| format!("failed to read {}: {}", from.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("failed to read {}: {}", from.display(), e))?;
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)
+                .map_err(|e| format!("failed to copy {}: {}", entry.path().display(), e))?;
         }
     }
+    Ok(())
 }
 
 impl HasStateLabel for PocketIc {
     fn get_state_label(&self) -> StateLabel {
-        let subnet_state_hash = self
-            .subnet
-            .state_manager
-            .latest_state_certification_hash()
-            .map(|(_, h)| h.0)
-            .unwrap_or_else(|| [0u8; 32].to_vec());
         let mut hasher = Sha256::new();
-        hasher.write(&subnet_state_hash[..]);
+        for subnet in self.subnets.values() {
+            let subnet_state_hash = subnet
+                .state_manager
+                .latest_state_certification_hash()
+                .map(|(_, h)| h.0)
+                .unwrap_or_else(|| [0u8; 32].to_vec());
+            hasher.write(&subnet_state_hash[..]);
+            // Test keys are not part of the replicated state, so they are hashed separately.
+            for (key_id, public_key) in subnet.ecdsa_test_public_keys() {
+                hasher.write(key_id.to_string().as_bytes());
+                hasher.write(&public_key);
+            }
+        }
+        if let Some(canister_id_base) = self.registry().canister_id_base {
+            hasher.write(canister_id_base.get().as_slice());
+        }
+        hasher.write(&self.cycles_minting_rate.xdr_permyriad_per_icp.to_be_bytes());
+        hasher.write(&self.cycles_minting_rate.cycles_per_xdr.to_be_bytes());
+        let auto_progress_increment = self
+            .auto_progress_time
+            .map_or(0, |auto_progress| auto_progress.increment.as_nanos());
+        hasher.write(&auto_progress_increment.to_be_bytes());
         hasher.write(&self.nonce.to_be_bytes());
         hasher.write(&self.time.as_nanos_since_unix_epoch().to_be_bytes());
         StateLabel(hasher.finish())
@@ -74,8 +663,7 @@ impl Operation for SetTime {
     fn compute(self, pic: &mut PocketIc) -> OpOut {
         // set time for all subnets; but also for the whole PocketIC
         // subnets won't have their own time field in the future.
-        pic.subnet.set_time(self.time.into());
-        pic.time = self.time;
+        pic.set_time(self.time);
         OpOut::NoOutput
     }
 
@@ -100,22 +688,133 @@ impl Operation for GetTime {
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct Tick {}
+/// Executes a single round by default. If `max_rounds` is given, rounds are executed until there
+/// are no more messages in the system or `max_rounds` rounds ran. If `instruction_budget` is
+/// given, no further round is started once the executed rounds consumed that many instructions.
+/// Returns the number of executed rounds along with the number of messages executed in them,
+/// so that callers can tick until no more work happens. If [`AutoProgressTime`] is set, the time
+/// is advanced by its increment before every round. Every round is executed on all subnets of
+/// the instance, which exchange their XNet messages in between.
+#[derive(Clone, Debug, Default)]
+pub struct Tick {
+    pub max_rounds: Option<u64>,
+    pub instruction_budget: Option<u64>,
+}
 
 impl Operation for Tick {
     type TargetType = PocketIc;
 
     fn compute(self, pic: &mut PocketIc) -> OpOut {
-        pic.subnet.tick();
+        let max_rounds = self.max_rounds.unwrap_or(1);
+        let instructions_before = pic.instructions_consumed();
+        let messages_before = pic.num_messages_executed();
+        let mut rounds = 0;
+        while rounds < max_rounds {
+            // The first round is always executed so that heartbeats and timers can run.
+            if rounds > 0 && !pic.has_pending_messages() {
+                break;
+            }
+            if let Some(budget) = self.instruction_budget {
+                if pic.instructions_consumed() - instructions_before >= budget as f64 {
+                    break;
+                }
+            }
+            if let Some(auto_progress) = pic.auto_progress_time {
+                pic.set_time(pic.time + auto_progress.increment);
+            }
+            pic.execute_round();
+            rounds += 1;
+        }
+        OpOut::Ticked {
+            rounds,
+            executed_messages: pic.num_messages_executed() - messages_before,
+        }
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "tick({:?},{:?})",
+            self.max_rounds, self.instruction_budget
+        ))
+    }
+}
+
+/// Set or unset the [`AutoProgressTime`] of the instance.
+#[derive(Clone, Debug)]
+pub struct SetAutoProgressTime(pub Option<AutoProgressTime>);
+
+impl Operation for SetAutoProgressTime {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        pic.auto_progress_time = self.0;
         OpOut::NoOutput
     }
 
     fn id(&self) -> OpId {
-        OpId("tick".to_string())
+        OpId(format!(
+            "set_auto_progress_time({:?})",
+            self.0
+                .map(|auto_progress| auto_progress.increment.as_nanos())
+        ))
+    }
+}
+
+/// Fires the global timer of the given canister: if the timer is set, the time is advanced to
+/// its deadline (unless the deadline has already passed) and a single round is executed on all
+/// subnets.
+/// Returns whether the timer fired in that round. If the timer is not set, nothing happens.
+///
+/// # Panics
+///
+/// Panics if the canister does not exist.
+#[derive(Clone, Debug)]
+pub struct FireTimers {
+    pub canister_id: CanisterId,
+}
+
+impl FireTimers {
+    fn global_timer(&self, pic: &PocketIc) -> CanisterTimer {
+        pic.subnet_hosting(self.canister_id)
+            .get_latest_state()
+            .canister_state(&self.canister_id)
+            .unwrap_or_else(|| panic!("Canister {} does not exist", self.canister_id))
+            .system_state
+            .global_timer
+    }
+}
+
+impl Operation for FireTimers {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let deadline = match self.global_timer(pic) {
+            CanisterTimer::Inactive => return OpOut::TimerFired(false),
+            CanisterTimer::Active(deadline) => deadline,
+        };
+        if pic.subnet_hosting(self.canister_id).time() < SystemTime::from(deadline) {
+            pic.set_time(deadline);
+        }
+        pic.execute_round();
+        // The timer is deactivated right before it is executed, so it is still set to the same
+        // deadline only if it did not fire.
+        let fired = self.global_timer(pic) != CanisterTimer::Active(deadline);
+        OpOut::TimerFired(fired)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("fire_timers({})", self.canister_id))
     }
 }
 
+/// The maximum number of rounds that [`ExecuteIngressMessage`] executes while waiting for the
+/// message to complete.
+const MAX_INGRESS_ROUNDS: usize = 100;
+
+/// Submits an ingress message to the subnet hosting the called canister and executes rounds on
+/// all subnets until the message completed, so that the canister can make calls to canisters on
+/// other subnets. Returns an error if the message did not complete within
+/// [`MAX_INGRESS_ROUNDS`] rounds.
 #[derive(Clone, Debug)]
 pub struct ExecuteIngressMessage(pub CanisterCall);
 
@@ -123,14 +822,30 @@ impl Operation for ExecuteIngressMessage {
     type TargetType = PocketIc;
 
     fn compute(self, pic: &mut PocketIc) -> OpOut {
-        pic.subnet
-            .execute_ingress_as(
-                self.0.sender,
-                self.0.canister_id,
-                self.0.method,
-                self.0.payload,
-            )
-            .into()
+        let subnet = pic.subnet_hosting(self.0.canister_id);
+        let msg_id = subnet.send_ingress(
+            self.0.sender,
+            self.0.canister_id,
+            self.0.method,
+            self.0.payload,
+        );
+        for _ in 0..MAX_INGRESS_ROUNDS {
+            match subnet.ingress_status(&msg_id) {
+                IngressStatus::Known {
+                    state: IngressState::Completed(result),
+                    ..
+                } => return OpOut::WasmResult(result),
+                IngressStatus::Known {
+                    state: IngressState::Failed(e),
+                    ..
+                } => return OpOut::IcUserErr(e),
+                _ => pic.execute_round(),
+            }
+        }
+        OpOut::Error(format!(
+            "ingress message {} did not complete after {} rounds",
+            msg_id, MAX_INGRESS_ROUNDS
+        ))
     }
 
     fn id(&self) -> OpId {
@@ -139,12 +854,53 @@ impl Operation for ExecuteIngressMessage {
     }
 }
 
+/// Submit a signed ingress message, e.g. one produced by an agent, to the subnet hosting the
+/// called canister. The envelope is the CBOR-encoded request as sent to the `call` endpoint of a
+/// replica. Like a replica, the subnet only accepts the message if it has not expired and its
+/// signature (including delegations) is valid for its sender. Returns the id of the message,
+/// whose result becomes available once rounds are executed, or the error if the envelope is
+/// malformed or invalid.
+#[derive(Clone, Debug)]
+pub struct SubmitSignedIngress {
+    pub envelope_bytes: Vec<u8>,
+}
+
+impl Operation for SubmitSignedIngress {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let msg = match SignedIngress::try_from(SignedRequestBytes::from(self.envelope_bytes)) {
+            Ok(msg) => msg,
+            Err(e) => return OpOut::Error(format!("malformed ingress envelope: {}", e)),
+        };
+        let subnet = pic.subnet_hosting(msg.canister_id());
+        let time = Time::try_from(subnet.time()).expect("subnet time out of range");
+        let verifier = IngressMessageVerifier::builder()
+            .with_root_of_trust(subnet.root_key())
+            .with_time_provider(TimeProvider::Constant(time))
+            .build();
+        if let Err(e) = verifier.validate_request(msg.as_ref()) {
+            return OpOut::Error(format!("invalid ingress message: {}", e));
+        }
+        let message_id = msg.id();
+        subnet.execute_payload(PayloadBuilder::new().signed_ingress(msg));
+        OpOut::MessageId(message_id)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "submit_signed_ingress({})",
+            Digest(Sha256::hash(&self.envelope_bytes))
+        ))
+    }
+}
+
 pub struct Query(pub CanisterCall);
 
 impl Operation for Query {
     type TargetType = PocketIc;
     fn compute(self, pic: &mut PocketIc) -> OpOut {
-        pic.subnet
+        pic.subnet_hosting(self.0.canister_id)
             .query_as(
                 self.0.sender,
                 self.0.canister_id,
@@ -160,6 +916,191 @@ impl Operation for Query {
     }
 }
 
+/// Moves the state of a canister from the subnet hosting it to the target subnet, which must be
+/// hosted by the instance (see [`PocketIc::add_subnet`]), and routes the canister to the target
+/// subnet on all subnets. Subsequent calls to the canister are executed on the target subnet.
+/// Fails if the canister does not exist or the target subnet cannot host it.
+#[derive(Clone, Debug)]
+pub struct MigrateCanister {
+    pub canister_id: CanisterId,
+    pub target_subnet: SubnetId,
+}
+
+impl Operation for MigrateCanister {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let source = pic.subnet_hosting(self.canister_id);
+        if !source.canister_exists(self.canister_id) {
+            return OpOut::Error(format!("canister {} does not exist", self.canister_id));
+        }
+        let target = match pic.subnets.get(&self.target_subnet) {
+            Some(target) => target,
+            None => {
+                return OpOut::Error(format!(
+                    "subnet {} is not hosted by this instance",
+                    self.target_subnet
+                ))
+            }
+        };
+        if source.get_subnet_id() == self.target_subnet {
+            return OpOut::Error(format!(
+                "canister {} is already hosted by subnet {}",
+                self.canister_id, self.target_subnet
+            ));
+        }
+        if target.canister_exists(self.canister_id) {
+            return OpOut::Error(format!(
+                "subnet {} already hosts a canister with id {}",
+                self.target_subnet, self.canister_id
+            ));
+        }
+        if let Err(e) = source.move_canister_state_to(target, self.canister_id) {
+            return OpOut::Error(e);
+        }
+        for subnet in pic.subnets.values() {
+            subnet.reroute_canister_range(self.canister_id..=self.canister_id, self.target_subnet);
+        }
+        pic.migrated_canisters
+            .insert(self.canister_id, self.target_subnet);
+        OpOut::NoOutput
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "migrate_canister({},{})",
+            self.canister_id, self.target_subnet
+        ))
+    }
+}
+
+impl AsRef<CanisterCall> for ExecuteIngressMessage {
+    fn as_ref(&self) -> &CanisterCall {
+        &self.0
+    }
+}
+
+impl AsRef<CanisterCall> for Query {
+    fn as_ref(&self) -> &CanisterCall {
+        &self.0
+    }
+}
+
+/// Wraps an [`ExecuteIngressMessage`] or a [`Query`] to also report the cycles burned by the
+/// call, computed from the balance of the called canister before and after the call. This lets
+/// tests assert that a canister stays within its cycles budget. Note that queries are not charged,
+/// and that cycles received by the canister during the call are deducted from the burn.
+#[derive(Clone, Debug)]
+pub struct WithCyclesBurned<O>(pub O);
+
+impl<O> WithCyclesBurned<O> {
+    /// Returns the cycles balance of the canister on the subnet hosting it, or zero if there is
+    /// no such canister (e.g. for calls to the management canister).
+    fn cycle_balance(pic: &PocketIc, canister_id: CanisterId) -> u128 {
+        pic.subnet_hosting(canister_id)
+            .get_latest_state()
+            .canister_state(&canister_id)
+            .map_or(0, |canister| canister.system_state.balance().get())
+    }
+}
+
+impl<O> Operation for WithCyclesBurned<O>
+where
+    O: Operation<TargetType = PocketIc> + AsRef<CanisterCall>,
+{
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let canister_id = self.0.as_ref().canister_id;
+        let balance_before = Self::cycle_balance(pic, canister_id);
+        let result = self.0.compute(pic);
+        let balance_after = Self::cycle_balance(pic, canister_id);
+        OpOut::CyclesBurned {
+            result: Box::new(result),
+            cycles: balance_before.saturating_sub(balance_after),
+        }
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("with_cycles_burned({})", self.0.id().0))
+    }
+}
+
+/// Wraps a mutating operation to also report the label of the state it produced, so that tests
+/// can assert a state transition without a separate `get_state_label` call. If
+/// `return_state_label` is not set, the output of the wrapped operation is returned unchanged.
+#[derive(Clone, Debug)]
+pub struct WithStateLabel<O> {
+    pub op: O,
+    pub return_state_label: bool,
+}
+
+impl<O> Operation for WithStateLabel<O>
+where
+    O: Operation<TargetType = PocketIc>,
+{
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let result = self.op.compute(pic);
+        if !self.return_state_label {
+            return result;
+        }
+        OpOut::WithStateLabel {
+            result: Box::new(result),
+            state_label: pic.get_state_label(),
+        }
+    }
+
+    fn id(&self) -> OpId {
+        if self.return_state_label {
+            OpId(format!("with_state_label({})", self.op.id().0))
+        } else {
+            self.op.id()
+        }
+    }
+}
+
+/// Executes a query as the anonymous principal against the retained checkpoint with the given
+/// state hash rather than the latest state, e.g. to observe stale reads. Returns an error if the
+/// checkpoint has already been pruned.
+#[derive(Clone, Debug)]
+pub struct QueryAt {
+    pub canister_id: CanisterId,
+    pub method: String,
+    pub payload: Vec<u8>,
+    pub state_hash: CryptoHashOfState,
+}
+
+impl Operation for QueryAt {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        pic.subnet_hosting(self.canister_id)
+            .query_as_at_state_hash(
+                PrincipalId::new_anonymous(),
+                self.canister_id,
+                self.method,
+                self.payload,
+                &self.state_hash,
+            )
+            .into()
+    }
+
+    fn id(&self) -> OpId {
+        let mut hasher = Sha256::new();
+        hasher.write(&self.payload);
+        let hash = Digest(hasher.finish());
+        OpId(format!(
+            "canister_query_at({},{},{},{})",
+            self.canister_id,
+            self.method,
+            hash,
+            hex::encode(&self.state_hash.get_ref().0)
+        ))
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct CanisterCall {
     pub sender: PrincipalId,
@@ -194,7 +1135,7 @@ impl Operation for InstallCanisterAsController {
     type TargetType = PocketIc;
 
     fn compute(self, pic: &mut PocketIc) -> OpOut {
-        pic.subnet
+        pic.subnet_hosting(self.canister_id)
             .install_wasm_in_mode(self.canister_id, self.mode, self.module, self.payload)
             .into()
     }
@@ -204,162 +1145,2095 @@ impl Operation for InstallCanisterAsController {
     }
 }
 
+/// Create a canister with the given settings on the main subnet, as the anonymous principal.
+/// Returns the id of the new canister, or the error if the settings could not be applied (e.g.
+/// because the subnet cannot satisfy the requested allocations). As the output of an operation
+/// is cached per state and operation id, the `nonce` tells apart otherwise identical creations.
 #[derive(Clone, Debug)]
-pub struct CyclesBalance {
-    canister_id: CanisterId,
+pub struct CreateCanister {
+    pub settings: Option<ic00::CanisterSettingsArgs>,
+    pub nonce: u64,
 }
 
-impl Operation for CyclesBalance {
+impl Operation for CreateCanister {
     type TargetType = PocketIc;
+
     fn compute(self, pic: &mut PocketIc) -> OpOut {
-        let result = pic.subnet.cycle_balance(self.canister_id);
-        OpOut::Cycles(result)
+        let result = pic.main_subnet().execute_ingress_as(
+            PrincipalId::new_anonymous(),
+            ic00::IC_00,
+            ic00::Method::ProvisionalCreateCanisterWithCycles,
+            ic00::ProvisionalCreateCanisterWithCyclesArgs {
+                amount: None,
+                settings: self.settings,
+                specified_id: None,
+                sender_canister_version: None,
+            }
+            .encode(),
+        );
+        match result {
+            Ok(WasmResult::Reply(bytes)) => match ic00::CanisterIdRecord::decode(&bytes) {
+                Ok(record) => OpOut::CanisterId(record.get_canister_id()),
+                Err(e) => OpOut::Error(format!("failed to decode canister id record: {}", e)),
+            },
+            other => other.into(),
+        }
+    }
+
+    fn id(&self) -> OpId {
+        let settings = self
+            .settings
+            .as_ref()
+            .map(|settings| Digest(Sha256::hash(&settings.encode())).to_string())
+            .unwrap_or_else(|| "None".to_string());
+        OpId(format!("create_canister({},{})", settings, self.nonce))
+    }
+}
+
+/// Delete a canister through the management canister's `delete_canister`, as its first
+/// controller. Like on a real subnet, the canister must be stopped first. Returns the error if
+/// the canister cannot be deleted, e.g. because it does not exist or is still running.
+#[derive(Clone, Debug)]
+pub struct DeleteCanister {
+    pub canister_id: CanisterId,
+}
+
+impl Operation for DeleteCanister {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        pic.subnet_hosting(self.canister_id)
+            .delete_canister(self.canister_id)
+            .map(|_| ())
+            .into()
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("delete_canister_{}", self.canister_id))
+    }
+}
+
+/// Set the compute and/or memory allocation of a canister, see [`UpdateCanisterSettings`].
+/// Allocations that are left unspecified are not changed. Returns the error if the subnet cannot
+/// satisfy the requested allocations.
+#[derive(Clone, Debug)]
+pub struct SetAllocations {
+    pub canister_id: CanisterId,
+    /// The compute allocation in percent.
+    pub compute_allocation: Option<u64>,
+    /// The memory allocation in bytes.
+    pub memory_allocation: Option<u64>,
+}
+
+impl Operation for SetAllocations {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let mut settings = ic00::CanisterSettingsArgsBuilder::new();
+        if let Some(compute_allocation) = self.compute_allocation {
+            settings = settings.with_compute_allocation(compute_allocation);
+        }
+        if let Some(memory_allocation) = self.memory_allocation {
+            settings = settings.with_memory_allocation(memory_allocation);
+        }
+        UpdateCanisterSettings {
+            canister_id: self.canister_id,
+            settings: settings.build(),
+        }
+        .compute(pic)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "set_allocations({},{:?},{:?})",
+            self.canister_id, self.compute_allocation, self.memory_allocation
+        ))
+    }
+}
+
+/// Update the settings of a canister (e.g. its controllers, compute allocation or freezing
+/// threshold) through the management canister's `update_settings`, as its first controller.
+/// Settings that are left unspecified are not changed. Returns the error if the update was
+/// rejected, e.g. because the canister does not exist.
+#[derive(Clone, Debug)]
+pub struct UpdateCanisterSettings {
+    pub canister_id: CanisterId,
+    pub settings: ic00::CanisterSettingsArgs,
+}
+
+impl Operation for UpdateCanisterSettings {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        pic.subnet_hosting(self.canister_id)
+            .update_settings(&self.canister_id, self.settings)
+            .into()
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "update_canister_settings({},{})",
+            self.canister_id,
+            Digest(Sha256::hash(&self.settings.encode()))
+        ))
+    }
+}
+
+/// The maximum number of rounds that [`StartCanister`] and [`StopCanister`] execute while waiting
+/// for the management canister to change the status of the canister.
+const MAX_STATUS_CHANGE_ROUNDS: usize = 100;
+
+/// Sends a `start_canister` or `stop_canister` message for the given canister as its first
+/// controller and executes rounds on all subnets until the message completed, so that calls of
+/// the canister to other subnets can complete, for at most
+/// [`MAX_STATUS_CHANGE_ROUNDS`] rounds. Returns the resulting status of the canister, or the
+/// error if the message was rejected.
+fn change_canister_status(pic: &PocketIc, canister_id: CanisterId, method: ic00::Method) -> OpOut {
+    let subnet = pic.subnet_hosting(canister_id);
+    let sender = subnet
+        .get_latest_state()
+        .canister_state(&canister_id)
+        .and_then(|canister| canister.controllers().iter().next().cloned())
+        .unwrap_or_else(PrincipalId::new_anonymous);
+    let msg_id = subnet.send_ingress(
+        sender,
+        ic00::IC_00,
+        method,
+        ic00::CanisterIdRecord::from(canister_id).encode(),
+    );
+    for _ in 0..MAX_STATUS_CHANGE_ROUNDS {
+        match subnet.ingress_status(&msg_id) {
+            IngressStatus::Known {
+                state: IngressState::Completed(_) | IngressState::Done,
+                ..
+            } => break,
+            IngressStatus::Known {
+                state: IngressState::Failed(e),
+                ..
+            } => return OpOut::IcUserErr(e),
+            _ => pic.execute_round(),
+        }
+    }
+    match subnet.get_latest_state().canister_state(&canister_id) {
+        Some(canister) => OpOut::CanisterStatus(match canister.status() {
+            ic00::CanisterStatusType::Running => CanisterStatus::Running,
+            ic00::CanisterStatusType::Stopping => CanisterStatus::Stopping,
+            ic00::CanisterStatusType::Stopped => CanisterStatus::Stopped,
+        }),
+        None => OpOut::Error(format!("canister {} does not exist", canister_id)),
+    }
+}
+
+/// Start a canister through the management canister's `start_canister`, as its first controller.
+/// Returns the resulting status of the canister, or the error if it could not be started.
+#[derive(Clone, Debug)]
+pub struct StartCanister {
+    pub canister_id: CanisterId,
+}
+
+impl Operation for StartCanister {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        change_canister_status(pic, self.canister_id, ic00::Method::StartCanister)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("start_canister({})", self.canister_id))
+    }
+}
+
+/// Stop a canister through the management canister's `stop_canister`, as its first controller.
+/// A canister with outstanding calls only stops once they completed, so rather than blocking
+/// until then, this returns the status of the canister after a bounded number of rounds: either
+/// `Stopped`, or `Stopping` if the canister has not stopped yet. Returns the error if the canister
+/// could not be stopped.
+#[derive(Clone, Debug)]
+pub struct StopCanister {
+    pub canister_id: CanisterId,
+}
+
+impl Operation for StopCanister {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        change_canister_status(pic, self.canister_id, ic00::Method::StopCanister)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("stop_canister({})", self.canister_id))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CyclesBalance {
+    canister_id: CanisterId,
+}
+
+impl Operation for CyclesBalance {
+    type TargetType = PocketIc;
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let result = pic
+            .subnet_hosting(self.canister_id)
+            .cycle_balance(self.canister_id);
+        OpOut::Cycles(result)
     }
 
     fn id(&self) -> OpId {
         OpId(format!("cycles_balance({})", self.canister_id))
     }
-}
+}
+
+/// Add cycles to a given canister.
+///
+/// # Panics
+///
+/// Panics if the canister does not exist.
+#[derive(Clone, Debug)]
+pub struct AddCycles {
+    canister_id: CanisterId,
+    amount: u128,
+}
+
+impl Operation for AddCycles {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let result = pic
+            .subnet_hosting(self.canister_id)
+            .add_cycles(self.canister_id, self.amount);
+        OpOut::Cycles(result)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("add_cycles({},{})", self.canister_id, self.amount))
+    }
+}
+
+/// Mint cycles to a given canister by topping it up from a faucet principal.
+///
+/// Unlike [`AddCycles`], which sets the balance directly, this goes through the management
+/// canister's `provisional_top_up_canister` method like any other ingress message, so the
+/// top-up is subject to the same checks as on a real subnet. Returns the resulting cycles
+/// balance, or the error if the top-up was rejected (e.g. because the canister does not exist).
+#[derive(Clone, Debug)]
+pub struct MintCycles {
+    pub to: CanisterId,
+    pub amount: u128,
+}
+
+impl Operation for MintCycles {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        // The provisional whitelist of a PocketIc subnet admits every principal, so the
+        // anonymous principal serves as the faucet.
+        let subnet = pic.subnet_hosting(self.to);
+        let result = subnet.execute_ingress_as(
+            PrincipalId::new_anonymous(),
+            ic00::IC_00,
+            ic00::Method::ProvisionalTopUpCanister,
+            ic00::ProvisionalTopUpCanisterArgs::new(self.to, self.amount).encode(),
+        );
+        match result {
+            Ok(WasmResult::Reply(_)) => OpOut::Cycles(subnet.cycle_balance(self.to)),
+            other => other.into(),
+        }
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("mint_cycles({},{})", self.to, self.amount))
+    }
+}
+
+/// Set the rate at which [`MintCyclesFromIcp`] converts ICP to cycles.
+#[derive(Clone, Debug)]
+pub struct SetCyclesMintingRate(pub CyclesMintingRate);
+
+impl Operation for SetCyclesMintingRate {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        pic.cycles_minting_rate = self.0;
+        OpOut::NoOutput
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "set_cycles_minting_rate({},{})",
+            self.0.xdr_permyriad_per_icp, self.0.cycles_per_xdr
+        ))
+    }
+}
+
+/// Get the rate at which [`MintCyclesFromIcp`] converts ICP to cycles.
+#[derive(Clone, Debug)]
+pub struct GetCyclesMintingRate;
+
+impl Operation for GetCyclesMintingRate {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        OpOut::CyclesMintingRate {
+            xdr_permyriad_per_icp: pic.cycles_minting_rate.xdr_permyriad_per_icp,
+            cycles_per_xdr: pic.cycles_minting_rate.cycles_per_xdr,
+        }
+    }
+
+    fn id(&self) -> OpId {
+        OpId("get_cycles_minting_rate".into())
+    }
+}
+
+/// Mint the cycles that the given amount of ICP (in e8s) is worth at the current
+/// [`CyclesMintingRate`] to a given canister, like a top-up through the Cycles Minting Canister
+/// would. Otherwise behaves like [`MintCycles`].
+#[derive(Clone, Debug)]
+pub struct MintCyclesFromIcp {
+    pub to: CanisterId,
+    pub amount_e8s: u64,
+}
+
+impl Operation for MintCyclesFromIcp {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let amount = pic.cycles_minting_rate.to_cycles(self.amount_e8s);
+        MintCycles {
+            to: self.to,
+            amount,
+        }
+        .compute(pic)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "mint_cycles_from_icp({},{})",
+            self.to, self.amount_e8s
+        ))
+    }
+}
+
+/// Compare the stable memories of two canisters. Returns the offset of the first differing byte,
+/// or `None` if both stable memories are identical. If one stable memory is a prefix of the
+/// other, the length of the shorter one is returned.
+///
+/// # Panics
+///
+/// Panics if either canister does not exist or has no module installed.
+#[derive(Clone, Debug)]
+pub struct CompareStableMemory {
+    pub a: CanisterId,
+    pub b: CanisterId,
+}
+
+impl Operation for CompareStableMemory {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let a = pic.subnet_hosting(self.a).stable_memory(self.a);
+        let b = pic.subnet_hosting(self.b).stable_memory(self.b);
+        let first_difference = a
+            .iter()
+            .zip(b.iter())
+            .position(|(x, y)| x != y)
+            .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())));
+        OpOut::StableMemoryDiff(first_difference.map(|offset| offset as u64))
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!("compare_stable_memory({},{})", self.a, self.b))
+    }
+}
+
+/// Read the given paths of the certified state tree. Returns the CBOR-encoded certificate whose
+/// tree is pruned to a witness covering exactly those paths, just like the replica's `read_state`.
+#[derive(Clone, Debug)]
+pub struct ReadStatePath {
+    pub paths: Vec<Vec<Label>>,
+}
+
+impl Operation for ReadStatePath {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        match pic.main_subnet().read_state(&self.paths) {
+            Ok(certificate) => OpOut::Bytes(certificate),
+            Err(e) => OpOut::Error(format!("failed to read state: {}", e)),
+        }
+    }
+
+    fn id(&self) -> OpId {
+        let paths = self
+            .paths
+            .iter()
+            .map(|path| {
+                path.iter()
+                    .map(|label| format!("/{}", label))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        OpId(format!("read_state({})", paths.join(",")))
+    }
+}
+
+/// Get the DER-encoded public key of the subnet, which is the root key that `read_state`
+/// certificates are signed with.
+#[derive(Clone, Debug)]
+pub struct GetRootKey;
+
+impl Operation for GetRootKey {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        match threshold_sig_public_key_to_der(pic.main_subnet().root_key()) {
+            Ok(der) => OpOut::Bytes(der),
+            Err(e) => OpOut::Error(format!("failed to encode the root key: {}", e)),
+        }
+    }
+
+    fn id(&self) -> OpId {
+        OpId("get_root_key".into())
+    }
+}
+
+/// Verify that the given CBOR-encoded certificate (e.g. returned by `ReadStatePath`) is well-formed
+/// and signed by the subnet's root key. The certificates of the subnet carry no delegation, so
+/// there is no canister whose range needs to be checked.
+#[derive(Clone, Debug)]
+pub struct VerifyCertificate {
+    pub certificate: Vec<u8>,
+}
+
+impl Operation for VerifyCertificate {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let root_key = pic.main_subnet().root_key();
+        let is_valid =
+            verify_certificate(&self.certificate, &CanisterId::ic_00(), &root_key).is_ok();
+        OpOut::CertificateValidity(is_valid)
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "verify_certificate({})",
+            Digest(Sha256::hash(&self.certificate))
+        ))
+    }
+}
+
+/// Install a known secp256k1 private key as the subnet's ECDSA key with the given name, so that
+/// tests can verify the signatures produced by `sign_with_ecdsa` against its public key. The key
+/// is the sec1 encoding of the private key. Returns an error if the subnet does not hold an ECDSA
+/// key with the given name (e.g. because ECDSA signing is disabled) or if the key is malformed.
+#[derive(Clone, Debug)]
+pub struct SetEcdsaTestKey {
+    pub key_name: String,
+    pub private_key_bytes: Vec<u8>,
+}
+
+impl Operation for SetEcdsaTestKey {
+    type TargetType = PocketIc;
+
+    fn compute(self, pic: &mut PocketIc) -> OpOut {
+        let key_id = ic00::EcdsaKeyId {
+            curve: ic00::EcdsaCurve::Secp256k1,
+            name: self.key_name,
+        };
+        if !pic.main_subnet().has_ecdsa_key(&key_id) {
+            return OpOut::Error(format!("ECDSA key {} is not enabled on the subnet", key_id));
+        }
+        let secret_key = match PrivateKey::deserialize_sec1(&self.private_key_bytes) {
+            Ok(secret_key) => secret_key,
+            Err(e) => return OpOut::Error(format!("invalid ECDSA private key: {:?}", e)),
+        };
+        pic.subnets
+            .get_mut(&pic.main_subnet_id)
+            .unwrap()
+            .set_ecdsa_secret_key(key_id, secret_key);
+        OpOut::NoOutput
+    }
+
+    fn id(&self) -> OpId {
+        OpId(format!(
+            "set_ecdsa_test_key({},{})",
+            self.key_name,
+            Digest(Sha256::hash(&self.private_key_bytes))
+        ))
+    }
+}
+
+struct Digest([u8; 32]);
+
+impl std::fmt::Debug for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Digest(")?;
+        self.0.iter().try_for_each(|b| write!(f, "{:02X}", b))?;
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::{Decode, Encode};
+    use ic_crypto_extended_bip32::{DerivationIndex, DerivationPath};
+    use ic_crypto_tree_hash::{LookupStatus, MixedHashTree};
+    use ic_crypto_utils_threshold_sig_der::parse_threshold_sig_key_from_der;
+    use ic_interfaces_state_manager::StateReader;
+    use ic_state_machine_tests::ErrorCode;
+    use ic_types::crypto::CryptoHash;
+    use ic_types::messages::{
+        Blob, Certificate, HttpCallContent, HttpCanisterUpdate, HttpRequestEnvelope,
+    };
+    use ic_universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
+
+    #[test]
+    fn state_label_test() {
+        let pic = PocketIc::new();
+
+        let state0 = pic.get_state_label();
+        let canister_id = pic.main_subnet().create_canister(None);
+        let state1 = pic.get_state_label();
+        let _ = pic.main_subnet().delete_canister(canister_id);
+        let state2 = pic.get_state_label();
+
+        assert!(state0 != state1);
+        assert!(state1 != state2);
+        assert!(state0 != state2);
+    }
+
+    #[test]
+    fn test_time() {
+        let mut pic = PocketIc::new();
+
+        let time = Time::from_nanos_since_unix_epoch(21);
+        compute_assert_state_change(&mut pic, SetTime { time });
+        let expected_time = OpOut::Time(21);
+        let actual_time = compute_assert_state_immutable(&mut pic, GetTime {});
+
+        assert_eq!(expected_time, actual_time);
+    }
+
+    #[test]
+    fn test_auto_progress_time() {
+        let mut pic = PocketIc::new();
+        let auto_progress = AutoProgressTime {
+            increment: Duration::from_secs(1),
+        };
+
+        compute_assert_state_change(&mut pic, SetAutoProgressTime(Some(auto_progress)));
+        let tick = Tick {
+            max_rounds: Some(3),
+            instruction_budget: None,
+        };
+        let OpOut::Ticked { rounds, .. } = compute_assert_state_change(&mut pic, tick) else {
+            unreachable!()
+        };
+        assert_eq!(rounds, 1);
+        compute_assert_state_change(&mut pic, Tick::default());
+        let expected_time = Duration::from_secs(2).as_nanos() as u64;
+        assert_eq!(
+            compute_assert_state_immutable(&mut pic, GetTime {}),
+            OpOut::Time(expected_time)
+        );
+        assert_eq!(
+            pic.main_subnet().time(),
+            SystemTime::from(Time::from_nanos_since_unix_epoch(expected_time))
+        );
+
+        compute_assert_state_change(&mut pic, SetAutoProgressTime(None));
+        compute_assert_state_change(&mut pic, Tick::default());
+        assert_eq!(
+            compute_assert_state_immutable(&mut pic, GetTime {}),
+            OpOut::Time(expected_time)
+        );
+    }
+
+    #[test]
+    fn test_execute_message() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+
+        let update = ExecuteIngressMessage(CanisterCall {
+            sender: PrincipalId::new_anonymous(),
+            canister_id,
+            method: "write".into(),
+            payload: vec![],
+        });
+
+        compute_assert_state_change(&mut pic, update);
+    }
+
+    #[test]
+    fn test_submit_signed_ingress() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (query, _) = query_update_constructors(canister_id);
+        let now = Time::try_from(pic.main_subnet().time()).unwrap();
+        let envelope = |sender: PrincipalId, ingress_expiry: Time| {
+            let envelope = HttpRequestEnvelope::<HttpCallContent> {
+                content: HttpCallContent::Call {
+                    update: HttpCanisterUpdate {
+                        canister_id: Blob(canister_id.get().into_vec()),
+                        method_name: "write".to_string(),
+                        arg: Blob(vec![]),
+                        sender: Blob(sender.into_vec()),
+                        ingress_expiry: ingress_expiry.as_nanos_since_unix_epoch(),
+                        nonce: None,
+                    },
+                },
+                sender_pubkey: None,
+                sender_sig: None,
+                sender_delegation: None,
+            };
+            SubmitSignedIngress {
+                envelope_bytes: serde_cbor::to_vec(&envelope).unwrap(),
+            }
+        };
+
+        let submit = envelope(PrincipalId::new_anonymous(), now + Duration::from_secs(60));
+        let OpOut::MessageId(message_id) = compute_assert_state_change(&mut pic, submit) else {
+            unreachable!()
+        };
+        compute_assert_state_change(&mut pic, Tick::default());
+        assert!(matches!(
+            pic.main_subnet().ingress_status(&message_id),
+            IngressStatus::Known {
+                state: IngressState::Completed(_),
+                ..
+            }
+        ));
+        assert_eq!(read_counter(&mut pic, query("read")), 1);
+
+        // expired messages are rejected
+        let submit = envelope(PrincipalId::new_anonymous(), now + Duration::from_secs(60));
+        let now = now + Duration::from_secs(120);
+        compute_assert_state_change(&mut pic, SetTime { time: now });
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, submit),
+            OpOut::Error(_)
+        ));
+
+        // so are messages of a non-anonymous sender that lack a signature
+        let submit = envelope(
+            PrincipalId::new_user_test_id(1),
+            now + Duration::from_secs(60),
+        );
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, submit),
+            OpOut::Error(_)
+        ));
+
+        // and malformed envelopes
+        let submit = SubmitSignedIngress {
+            envelope_bytes: b"not an envelope".to_vec(),
+        };
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, submit),
+            OpOut::Error(_)
+        ));
+        assert_eq!(read_counter(&mut pic, query("read")), 1);
+    }
+
+    #[test]
+    fn test_query() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (query, update) = query_update_constructors(canister_id);
+
+        use WasmResult::*;
+        let OpOut::WasmResult(Reply(initial_bytes)) =
+            compute_assert_state_immutable(&mut pic, query("read"))
+        else {
+            unreachable!()
+        };
+        compute_assert_state_change(&mut pic, update("write"));
+        let OpOut::WasmResult(Reply(updated_bytes)) =
+            compute_assert_state_immutable(&mut pic, query("read"))
+        else {
+            unreachable!()
+        };
+
+        assert_eq!(updated_bytes[0], initial_bytes[0] + 1);
+    }
+
+    #[test]
+    fn test_query_at() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (query, update) = query_update_constructors(canister_id);
+        let query_at = |state_hash| QueryAt {
+            canister_id,
+            method: "read".into(),
+            payload: vec![],
+            state_hash,
+        };
+
+        use WasmResult::*;
+        let OpOut::WasmResult(Reply(initial_bytes)) =
+            compute_assert_state_immutable(&mut pic, query("read"))
+        else {
+            unreachable!()
+        };
+        let old_state_hash = pic.main_subnet().await_state_hash();
+        compute_assert_state_change(&mut pic, update("write"));
+
+        let OpOut::WasmResult(Reply(old_bytes)) =
+            compute_assert_state_immutable(&mut pic, query_at(old_state_hash))
+        else {
+            unreachable!()
+        };
+        assert_eq!(old_bytes, initial_bytes);
+
+        let unknown_state_hash = CryptoHashOfState::new(CryptoHash(vec![0; 32]));
+        let OpOut::IcUserErr(err) =
+            compute_assert_state_immutable(&mut pic, query_at(unknown_state_hash))
+        else {
+            unreachable!()
+        };
+        assert_eq!(err.code(), ErrorCode::CertifiedStateUnavailable);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (_, update) = query_update_constructors(canister_id);
+
+        let cycles_balance = CyclesBalance { canister_id };
+        let OpOut::Cycles(orig_balance) =
+            compute_assert_state_immutable(&mut pic, cycles_balance.clone())
+        else {
+            unreachable!()
+        };
+        compute_assert_state_change(&mut pic, update("write"));
+        let OpOut::Cycles(changed_balance) =
+            compute_assert_state_immutable(&mut pic, cycles_balance)
+        else {
+            unreachable!()
+        };
+
+        // nothing is charged on a system subnet
+        assert_eq!(changed_balance, orig_balance);
+
+        let amount: u128 = 20_000_000_000_000;
+        let add_cycles = AddCycles {
+            canister_id,
+            amount,
+        };
+
+        let OpOut::Cycles(final_balance) = compute_assert_state_change(&mut pic, add_cycles) else {
+            unreachable!()
+        };
+
+        assert_eq!(final_balance, changed_balance + amount);
+    }
+
+    #[test]
+    fn test_create_canister() {
+        let mut pic = PocketIc::new();
+        let create_canister = |nonce| CreateCanister {
+            settings: None,
+            nonce,
+        };
+        assert_ne!(create_canister(0).id(), create_canister(1).id());
+
+        let OpOut::CanisterId(a) = compute_assert_state_change(&mut pic, create_canister(0)) else {
+            unreachable!()
+        };
+        let OpOut::CanisterId(b) = compute_assert_state_change(&mut pic, create_canister(1)) else {
+            unreachable!()
+        };
+        assert_ne!(a, b);
+        assert!(pic.main_subnet().canister_exists(a));
+        assert!(pic.main_subnet().canister_exists(b));
+
+        let controller = PrincipalId::new_user_test_id(1);
+        let settings = ic00::CanisterSettingsArgsBuilder::new()
+            .with_controllers(vec![controller])
+            .build();
+        let create_canister = CreateCanister {
+            settings: Some(settings),
+            nonce: 0,
+        };
+        let OpOut::CanisterId(c) = compute_assert_state_change(&mut pic, create_canister) else {
+            unreachable!()
+        };
+        assert_eq!(
+            pic.main_subnet()
+                .get_latest_state()
+                .canister_state(&c)
+                .unwrap()
+                .controllers()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![&controller]
+        );
+
+        // unsatisfiable settings are rejected
+        let settings = ic00::CanisterSettingsArgsBuilder::new()
+            .with_compute_allocation(1_000)
+            .build();
+        let create_canister = CreateCanister {
+            settings: Some(settings),
+            nonce: 0,
+        };
+        assert!(matches!(
+            create_canister.compute(&mut pic),
+            OpOut::IcUserErr(_)
+        ));
+    }
+
+    #[test]
+    fn test_update_canister_settings() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let canister_state = |pic: &PocketIc| {
+            pic.main_subnet()
+                .get_latest_state()
+                .canister_state(&canister_id)
+                .unwrap()
+                .clone()
+        };
+
+        let controller = PrincipalId::new_user_test_id(1);
+        let set_controllers = UpdateCanisterSettings {
+            canister_id,
+            settings: ic00::CanisterSettingsArgsBuilder::new()
+                .with_controllers(vec![controller])
+                .build(),
+        };
+        let set_freezing_threshold = UpdateCanisterSettings {
+            canister_id,
+            settings: ic00::CanisterSettingsArgsBuilder::new()
+                .with_freezing_threshold(1_000)
+                .build(),
+        };
+        assert_ne!(set_controllers.id(), set_freezing_threshold.id());
+
+        let res = compute_assert_state_change(&mut pic, set_controllers);
+        assert_eq!(res, OpOut::NoOutput);
+        assert_eq!(
+            canister_state(&pic)
+                .controllers()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![&controller]
+        );
+
+        // the update is sent by the new controller
+        let res = compute_assert_state_change(&mut pic, set_freezing_threshold);
+        assert_eq!(res, OpOut::NoOutput);
+        assert_eq!(
+            canister_state(&pic).system_state.freeze_threshold.get(),
+            1_000
+        );
+
+        let update_missing_canister = UpdateCanisterSettings {
+            canister_id: CanisterId::from_u64(42),
+            settings: ic00::CanisterSettingsArgsBuilder::new()
+                .with_freezing_threshold(1_000)
+                .build(),
+        };
+        assert!(matches!(
+            update_missing_canister.compute(&mut pic),
+            OpOut::IcUserErr(_)
+        ));
+    }
+
+    #[test]
+    fn test_start_stop_canister() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (query, update) = query_update_constructors(canister_id);
+
+        let res = compute_assert_state_change(&mut pic, StopCanister { canister_id });
+        assert_eq!(res, OpOut::CanisterStatus(CanisterStatus::Stopped));
+
+        // a stopped canister rejects update calls
+        let OpOut::IcUserErr(err) = compute_assert_state_change(&mut pic, update("write")) else {
+            unreachable!()
+        };
+        assert_eq!(err.code(), ErrorCode::CanisterStopped);
+
+        let res = compute_assert_state_change(&mut pic, StartCanister { canister_id });
+        assert_eq!(res, OpOut::CanisterStatus(CanisterStatus::Running));
+        compute_assert_state_change(&mut pic, update("write"));
+        assert_eq!(read_counter(&mut pic, query("read")), 1);
+
+        let res = StopCanister {
+            canister_id: CanisterId::from_u64(42),
+        }
+        .compute(&mut pic);
+        assert!(matches!(res, OpOut::IcUserErr(_)));
+    }
+
+    #[test]
+    fn test_delete_canister() {
+        let mut pic = PocketIc::new();
+
+        let create_canister = CreateCanister {
+            settings: None,
+            nonce: 0,
+        };
+        let OpOut::CanisterId(canister_id) = compute_assert_state_change(&mut pic, create_canister)
+        else {
+            unreachable!()
+        };
+
+        // a running canister cannot be deleted
+        let delete_canister = DeleteCanister { canister_id };
+        let res = compute_assert_state_change(&mut pic, delete_canister.clone());
+        assert!(matches!(res, OpOut::IcUserErr(_)));
+        assert!(pic.main_subnet().canister_exists(canister_id));
+
+        pic.main_subnet().stop_canister(canister_id).unwrap();
+        let res = compute_assert_state_change(&mut pic, delete_canister.clone());
+        assert_eq!(res, OpOut::NoOutput);
+        assert!(!pic.main_subnet().canister_exists(canister_id));
+
+        // the canister is gone
+        let res = compute_assert_state_change(&mut pic, delete_canister);
+        assert!(matches!(res, OpOut::IcUserErr(_)));
+    }
+
+    #[test]
+    fn test_compare_stable_memory() {
+        let (mut pic, a) = new_pic_counter_installed();
+        let b = pic.main_subnet().create_canister(None);
+        compute_assert_state_change(
+            &mut pic,
+            InstallCanisterAsController {
+                canister_id: b,
+                mode: CanisterInstallMode::Install,
+                module: counter_wasm(),
+                payload: vec![],
+            },
+        );
+
+        let data = vec![7u8; 100_000];
+        pic.main_subnet().set_stable_memory(a, &data);
+        pic.main_subnet().set_stable_memory(b, &data);
+        let compare = CompareStableMemory { a, b };
+        assert_eq!(
+            compute_assert_state_immutable(&mut pic, compare.clone()),
+            OpOut::StableMemoryDiff(None)
+        );
+
+        let mut mutated = data.clone();
+        mutated[70_000] = 8;
+        pic.main_subnet().set_stable_memory(b, &mutated);
+        assert_eq!(
+            compute_assert_state_immutable(&mut pic, compare),
+            OpOut::StableMemoryDiff(Some(70_000))
+        );
+    }
+
+    #[test]
+    fn test_cycles_minting_rate() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let amount_e8s = 100_000_000; // 1 ICP
+
+        let OpOut::CyclesMintingRate {
+            xdr_permyriad_per_icp,
+            cycles_per_xdr,
+        } = compute_assert_state_immutable(&mut pic, GetCyclesMintingRate)
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            CyclesMintingRate {
+                xdr_permyriad_per_icp,
+                cycles_per_xdr,
+            },
+            CyclesMintingRate::default()
+        );
+
+        let mint = |pic: &mut PocketIc| {
+            let OpOut::Cycles(orig_balance) =
+                compute_assert_state_immutable(pic, CyclesBalance { canister_id })
+            else {
+                unreachable!()
+            };
+            let OpOut::Cycles(minted_balance) = compute_assert_state_change(
+                pic,
+                MintCyclesFromIcp {
+                    to: canister_id,
+                    amount_e8s,
+                },
+            ) else {
+                unreachable!()
+            };
+            minted_balance - orig_balance
+        };
+        // 1 ICP = 1 XDR = 1T cycles.
+        assert_eq!(mint(&mut pic), 1_000_000_000_000);
+
+        // 1 ICP = 3.5 XDR = 3.5T cycles.
+        let rate = CyclesMintingRate {
+            xdr_permyriad_per_icp: 35_000,
+            ..CyclesMintingRate::default()
+        };
+        compute_assert_state_change(&mut pic, SetCyclesMintingRate(rate));
+        let OpOut::CyclesMintingRate {
+            xdr_permyriad_per_icp,
+            ..
+        } = compute_assert_state_immutable(&mut pic, GetCyclesMintingRate)
+        else {
+            unreachable!()
+        };
+        assert_eq!(xdr_permyriad_per_icp, 35_000);
+        assert_eq!(mint(&mut pic), 3_500_000_000_000);
+    }
+
+    #[test]
+    fn test_mint_cycles() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+
+        let cycles_balance = CyclesBalance { canister_id };
+        let OpOut::Cycles(orig_balance) =
+            compute_assert_state_immutable(&mut pic, cycles_balance.clone())
+        else {
+            unreachable!()
+        };
+
+        let amount: u128 = 20_000_000_000_000;
+        let mint_cycles = MintCycles {
+            to: canister_id,
+            amount,
+        };
+        let OpOut::Cycles(minted_balance) = compute_assert_state_change(&mut pic, mint_cycles)
+        else {
+            unreachable!()
+        };
+        assert_eq!(minted_balance, orig_balance + amount);
+
+        let OpOut::Cycles(final_balance) = compute_assert_state_immutable(&mut pic, cycles_balance)
+        else {
+            unreachable!()
+        };
+        assert_eq!(final_balance, minted_balance);
+    }
+
+    #[test]
+    fn test_mint_cycles_to_nonexistent_canister() {
+        let mut pic = PocketIc::new();
+
+        let mint_cycles = MintCycles {
+            to: CanisterId::from_u64(42),
+            amount: 1_000,
+        };
+        assert!(matches!(mint_cycles.compute(&mut pic), OpOut::IcUserErr(_)));
+    }
+
+    #[test]
+    fn test_read_state_path() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+
+        let canister_path = |leaf: &str| -> Vec<Vec<u8>> {
+            vec![
+                b"canister".to_vec(),
+                canister_id.get_ref().to_vec(),
+                leaf.as_bytes().to_vec(),
+            ]
+        };
+        let read_state = ReadStatePath {
+            paths: vec![canister_path("controllers")
+                .into_iter()
+                .map(Label::from)
+                .collect()],
+        };
+        let OpOut::Bytes(certificate) = compute_assert_state_immutable(&mut pic, read_state) else {
+            unreachable!()
+        };
+        let certificate: Certificate = serde_cbor::from_slice(&certificate).unwrap();
+
+        // the witness must be consistent with the certified state hash
+        let (_, certified_hash) = pic
+            .main_subnet()
+            .state_manager
+            .latest_state_certification_hash()
+            .unwrap();
+        assert_eq!(certificate.tree.digest().0.to_vec(), certified_hash.0);
+
+        let LookupStatus::Found(MixedHashTree::Leaf(controllers)) =
+            certificate.tree.lookup(&canister_path("controllers"))
+        else {
+            panic!("controllers not found in witness")
+        };
+        let controllers: Vec<PrincipalId> = serde_cbor::from_slice(controllers).unwrap();
+        assert_eq!(controllers, vec![PrincipalId::new_anonymous()]);
+
+        // paths that were not requested are pruned from the witness
+        assert!(!matches!(
+            certificate.tree.lookup(&canister_path("module_hash")),
+            LookupStatus::Found(_)
+        ));
+
+        // a path that is too long is an error, not a panic
+        let read_state = ReadStatePath {
+            paths: vec![vec![Label::from("canister"); 1_000]],
+        };
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, read_state),
+            OpOut::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_verify_certificate() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+
+        let OpOut::Bytes(root_key) = compute_assert_state_immutable(&mut pic, GetRootKey) else {
+            unreachable!()
+        };
+        assert_eq!(
+            parse_threshold_sig_key_from_der(&root_key).unwrap(),
+            pic.main_subnet().root_key()
+        );
+
+        let read_state = ReadStatePath {
+            paths: vec![vec![
+                Label::from("canister"),
+                Label::from(canister_id.get_ref().to_vec()),
+                Label::from("controllers"),
+            ]],
+        };
+        let OpOut::Bytes(certificate) = compute_assert_state_immutable(&mut pic, read_state) else {
+            unreachable!()
+        };
+
+        // a genuine certificate is valid
+        let verify = VerifyCertificate {
+            certificate: certificate.clone(),
+        };
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, verify),
+            OpOut::CertificateValidity(true)
+        ));
+
+        // a certificate with a tampered signature is not
+        let mut tampered: Certificate = serde_cbor::from_slice(&certificate).unwrap();
+        tampered.signature.0[0] ^= 1;
+        let verify = VerifyCertificate {
+            certificate: serde_cbor::to_vec(&tampered).unwrap(),
+        };
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, verify),
+            OpOut::CertificateValidity(false)
+        ));
+
+        // neither is garbage
+        let verify = VerifyCertificate {
+            certificate: b"not a certificate".to_vec(),
+        };
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, verify),
+            OpOut::CertificateValidity(false)
+        ));
+    }
+
+    #[test]
+    fn test_tick_reports_executed_messages() {
+        let mut pic = PocketIc::new();
+        let OpOut::Ticked {
+            rounds,
+            executed_messages,
+        } = Tick::default().compute(&mut pic)
+        else {
+            unreachable!()
+        };
+        assert_eq!(rounds, 1);
+        assert_eq!(executed_messages, 0);
+
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        pic.main_subnet()
+            .send_ingress(PrincipalId::new_anonymous(), canister_id, "write", vec![]);
+        let OpOut::Ticked {
+            rounds,
+            executed_messages,
+        } = compute_assert_state_change(&mut pic, Tick::default())
+        else {
+            unreachable!()
+        };
+        assert_eq!(rounds, 1);
+        assert!(executed_messages > 0);
+    }
+
+    #[test]
+    fn test_tick_until_drained() {
+        let (mut pic, canister_id) = new_pic_self_calling_installed();
+        let (query, update) = query_update_constructors(canister_id);
+
+        compute_assert_state_change(&mut pic, update("step"));
+        assert_ne!(read_counter(&mut pic, query("read")), 0);
+
+        let tick = Tick {
+            max_rounds: Some(100),
+            instruction_budget: Some(1_000_000_000),
+        };
+        let OpOut::Ticked { rounds, .. } = compute_assert_state_change(&mut pic, tick) else {
+            unreachable!()
+        };
+
+        assert!(rounds > 1 && rounds < 100);
+        assert_eq!(read_counter(&mut pic, query("read")), 0);
+        assert!(!pic.main_subnet().has_pending_messages());
+    }
+
+    #[test]
+    fn test_tick_stops_at_instruction_budget() {
+        let (mut pic, canister_id) = new_pic_self_calling_installed();
+        let (query, update) = query_update_constructors(canister_id);
+
+        compute_assert_state_change(&mut pic, update("step"));
+        let counter = read_counter(&mut pic, query("read"));
+
+        let tick = Tick {
+            max_rounds: Some(100),
+            instruction_budget: Some(1),
+        };
+        let OpOut::Ticked { rounds, .. } = compute_assert_state_change(&mut pic, tick) else {
+            unreachable!()
+        };
+
+        assert_eq!(rounds, 1);
+        assert!(read_counter(&mut pic, query("read")) < counter);
+        assert!(pic.main_subnet().has_pending_messages());
+    }
+
+    #[test]
+    fn test_fire_timers() {
+        let mut pic = PocketIc::new();
+        let canister_id = pic.main_subnet().create_canister(None);
+        let install_op = InstallCanisterAsController {
+            canister_id,
+            mode: CanisterInstallMode::Install,
+            module: wat::parse_str(TIMER_WAT).unwrap(),
+            payload: vec![],
+        };
+        compute_assert_state_change(&mut pic, install_op);
+        let (query, update) = query_update_constructors(canister_id);
+        let fire_timers = FireTimers { canister_id };
+
+        // no timer set yet
+        let res = compute_assert_state_immutable(&mut pic, fire_timers.clone());
+        assert_eq!(res, OpOut::TimerFired(false));
+
+        compute_assert_state_change(&mut pic, update("set_timer"));
+        let CanisterTimer::Active(deadline) = fire_timers.global_timer(&pic) else {
+            unreachable!()
+        };
+        assert!(pic.main_subnet().time() < SystemTime::from(deadline));
+
+        // the timer does not fire before its deadline
+        compute_assert_state_change(&mut pic, Tick::default());
+        assert_eq!(read_counter(&mut pic, query("read")), 0);
+
+        let res = compute_assert_state_change(&mut pic, fire_timers.clone());
+        assert_eq!(res, OpOut::TimerFired(true));
+        assert_eq!(pic.main_subnet().time(), SystemTime::from(deadline));
+        assert_eq!(read_counter(&mut pic, query("read")), 1);
+
+        // the timer is not set anymore
+        let res = compute_assert_state_immutable(&mut pic, fire_timers);
+        assert_eq!(res, OpOut::TimerFired(false));
+        assert_eq!(read_counter(&mut pic, query("read")), 1);
+    }
+
+    #[test]
+    fn test_ecdsa_signing_feature() {
+        for ecdsa_signing in [false, true] {
+            let mut pic = PocketIc::new_with_features(SubnetFeatures {
+                ecdsa_signing,
+                ..SubnetFeatures::default()
+            });
+            let canister_id = pic.main_subnet().create_canister(None);
+            let install_op = InstallCanisterAsController {
+                canister_id,
+                mode: CanisterInstallMode::Install,
+                module: UNIVERSAL_CANISTER_WASM.to_vec(),
+                payload: vec![],
+            };
+            compute_assert_state_change(&mut pic, install_op);
+
+            let sign_with_ecdsa = wasm()
+                .call_simple(
+                    ic00::IC_00,
+                    ic00::Method::SignWithECDSA,
+                    call_args().other_side(
+                        Encode!(&ic00::SignWithECDSAArgs {
+                            message_hash: [0; 32],
+                            derivation_path: ic00::DerivationPath::new(vec![]),
+                            // the key held by the subnet if ECDSA signing is enabled
+                            key_id: ic00::EcdsaKeyId {
+                                curve: ic00::EcdsaCurve::Secp256k1,
+                                name: "master_ecdsa_public_key".to_string(),
+                            },
+                        })
+                        .unwrap(),
+                    ),
+                )
+                .build();
+            let update = ExecuteIngressMessage(CanisterCall {
+                sender: PrincipalId::new_anonymous(),
+                canister_id,
+                method: "update".into(),
+                payload: sign_with_ecdsa,
+            });
+
+            let OpOut::WasmResult(result) = compute_assert_state_change(&mut pic, update) else {
+                unreachable!()
+            };
+            if ecdsa_signing {
+                assert!(matches!(result, WasmResult::Reply(_)), "{:?}", result);
+            } else {
+                assert!(matches!(result, WasmResult::Reject(_)), "{:?}", result);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_ecdsa_test_key() {
+        let mut pic = PocketIc::new();
+        let canister_id = pic.main_subnet().create_canister(None);
+        let install_op = InstallCanisterAsController {
+            canister_id,
+            mode: CanisterInstallMode::Install,
+            module: UNIVERSAL_CANISTER_WASM.to_vec(),
+            payload: vec![],
+        };
+        compute_assert_state_change(&mut pic, install_op);
+
+        let private_key_bytes = [7; 32].to_vec();
+        let set_key = SetEcdsaTestKey {
+            key_name: "master_ecdsa_public_key".to_string(),
+            private_key_bytes: private_key_bytes.clone(),
+        };
+        let nonce = pic.nonce;
+        compute_assert_state_change(&mut pic, set_key);
+        assert_eq!(pic.nonce, nonce);
+
+        let message_hash = [42; 32];
+        let sign_with_ecdsa = wasm()
+            .call_simple(
+                ic00::IC_00,
+                ic00::Method::SignWithECDSA,
+                call_args().other_side(
+                    Encode!(&ic00::SignWithECDSAArgs {
+                        message_hash,
+                        derivation_path: ic00::DerivationPath::new(vec![]),
+                        key_id: ic00::EcdsaKeyId {
+                            curve: ic00::EcdsaCurve::Secp256k1,
+                            name: "master_ecdsa_public_key".to_string(),
+                        },
+                    })
+                    .unwrap(),
+                ),
+            )
+            .build();
+        let update = ExecuteIngressMessage(CanisterCall {
+            sender: PrincipalId::new_anonymous(),
+            canister_id,
+            method: "update".into(),
+            payload: sign_with_ecdsa,
+        });
+        let OpOut::WasmResult(WasmResult::Reply(bytes)) =
+            compute_assert_state_change(&mut pic, update)
+        else {
+            unreachable!()
+        };
+        let reply = Decode!(&bytes, ic00::SignWithECDSAReply).unwrap();
+
+        // the signature is made with the key derived for the calling canister
+        let public_key = PrivateKey::deserialize_sec1(&private_key_bytes)
+            .unwrap()
+            .public_key();
+        let derived_public_key =
+            DerivationPath::new(vec![DerivationIndex(canister_id.get().as_slice().to_vec())])
+                .public_key_derivation(&public_key.serialize_sec1(true), &[0; 32])
+                .unwrap()
+                .derived_public_key;
+        let derived_public_key =
+            ic_crypto_ecdsa_secp256k1::PublicKey::deserialize_sec1(&derived_public_key).unwrap();
+        assert!(derived_public_key.verify_signature_prehashed(&message_hash, &reply.signature));
+    }
+
+    #[test]
+    fn test_set_ecdsa_test_key_without_ecdsa_signing() {
+        let mut pic = PocketIc::new_with_features(SubnetFeatures {
+            ecdsa_signing: false,
+            ..SubnetFeatures::default()
+        });
+        let set_key = SetEcdsaTestKey {
+            key_name: "master_ecdsa_public_key".to_string(),
+            private_key_bytes: [7; 32].to_vec(),
+        };
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, set_key),
+            OpOut::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_cycles_burned() {
+        let mut pic = PocketIc::new_with_config(
+            SubnetType::Application,
+            SubnetConfig::new(SubnetType::Application),
+            SubnetFeatures::default(),
+            RegistrySnapshot::default(),
+        );
+        let canister_id = pic.main_subnet().create_canister_with_cycles(
+            None,
+            Cycles::new(100_000_000_000_000),
+            None,
+        );
+        let install_op = InstallCanisterAsController {
+            canister_id,
+            mode: CanisterInstallMode::Install,
+            module: counter_wasm(),
+            payload: vec![],
+        };
+        compute_assert_state_change(&mut pic, install_op);
+        let (query, update) = query_update_constructors(canister_id);
+
+        let OpOut::CyclesBurned { result, cycles } =
+            compute_assert_state_change(&mut pic, WithCyclesBurned(update("write")))
+        else {
+            unreachable!()
+        };
+        assert!(matches!(*result, OpOut::WasmResult(WasmResult::Reply(_))));
+        assert!(cycles > 0);
+
+        let OpOut::CyclesBurned { result, cycles } =
+            compute_assert_state_immutable(&mut pic, WithCyclesBurned(query("read")))
+        else {
+            unreachable!()
+        };
+        assert!(matches!(*result, OpOut::WasmResult(WasmResult::Reply(_))));
+        assert_eq!(cycles, 0);
+    }
 
-/// Add cycles to a given canister.
-///
-/// # Panics
-///
-/// Panics if the canister does not exist.
-#[derive(Clone, Debug)]
-pub struct AddCycles {
-    canister_id: CanisterId,
-    amount: u128,
-}
+    #[test]
+    fn test_cycles_burned_after_migration() {
+        let mut pic = PocketIc::new();
+        let canister_id = pic.main_subnet().create_canister_with_cycles(
+            None,
+            Cycles::new(100_000_000_000_000),
+            None,
+        );
+        let install_op = InstallCanisterAsController {
+            canister_id,
+            mode: CanisterInstallMode::Install,
+            module: counter_wasm(),
+            payload: vec![],
+        };
+        compute_assert_state_change(&mut pic, install_op);
+        let (_, update) = query_update_constructors(canister_id);
 
-impl Operation for AddCycles {
-    type TargetType = PocketIc;
+        // nothing is charged on a system subnet
+        let OpOut::CyclesBurned { cycles, .. } =
+            compute_assert_state_change(&mut pic, WithCyclesBurned(update("write")))
+        else {
+            unreachable!()
+        };
+        assert_eq!(cycles, 0);
 
-    fn compute(self, pic: &mut PocketIc) -> OpOut {
-        let result = pic.subnet.add_cycles(self.canister_id, self.amount);
-        OpOut::Cycles(result)
+        // the balance is read from the application subnet the canister was migrated to
+        let target_subnet = pic.add_subnet();
+        let migrate = MigrateCanister {
+            canister_id,
+            target_subnet,
+        };
+        compute_assert_state_change(&mut pic, migrate);
+        let OpOut::Cycles(balance_before) =
+            compute_assert_state_immutable(&mut pic, CyclesBalance { canister_id })
+        else {
+            unreachable!()
+        };
+        let OpOut::CyclesBurned { result, cycles } =
+            compute_assert_state_change(&mut pic, WithCyclesBurned(update("write")))
+        else {
+            unreachable!()
+        };
+        assert!(matches!(*result, OpOut::WasmResult(WasmResult::Reply(_))));
+        assert!(cycles > 0);
+        let OpOut::Cycles(balance_after) =
+            compute_assert_state_immutable(&mut pic, CyclesBalance { canister_id })
+        else {
+            unreachable!()
+        };
+        assert_eq!(balance_before - balance_after, cycles);
     }
 
-    fn id(&self) -> OpId {
-        OpId(format!("add_cycles({},{})", self.canister_id, self.amount))
+    #[test]
+    fn test_with_state_label() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (_, update) = query_update_constructors(canister_id);
+
+        let op = WithStateLabel {
+            op: update("write"),
+            return_state_label: true,
+        };
+        let OpOut::WithStateLabel {
+            result,
+            state_label,
+        } = compute_assert_state_change(&mut pic, op)
+        else {
+            unreachable!()
+        };
+        assert!(matches!(*result, OpOut::WasmResult(WasmResult::Reply(_))));
+        assert_eq!(state_label, pic.get_state_label());
+
+        let op = WithStateLabel {
+            op: update("write"),
+            return_state_label: false,
+        };
+        let result = compute_assert_state_change(&mut pic, op);
+        assert!(matches!(result, OpOut::WasmResult(WasmResult::Reply(_))));
     }
-}
 
-struct Digest([u8; 32]);
+    #[test]
+    fn test_set_allocations() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let memory_allocation = 1 << 30;
 
-impl std::fmt::Debug for Digest {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Digest(")?;
-        self.0.iter().try_for_each(|b| write!(f, "{:02X}", b))?;
-        write!(f, ")")
+        let set_allocations = SetAllocations {
+            canister_id,
+            compute_allocation: None,
+            memory_allocation: Some(memory_allocation),
+        };
+        assert_eq!(
+            compute_assert_state_change(&mut pic, set_allocations),
+            OpOut::NoOutput
+        );
+
+        let OpOut::WasmResult(WasmResult::Reply(bytes)) = compute_assert_state_change(
+            &mut pic,
+            ExecuteIngressMessage(CanisterCall {
+                sender: PrincipalId::new_anonymous(),
+                canister_id: CanisterId::ic_00(),
+                method: ic00::Method::CanisterStatus.to_string(),
+                payload: ic00::CanisterIdRecord::from(canister_id).encode(),
+            }),
+        ) else {
+            unreachable!()
+        };
+        let status = ic00::CanisterStatusResultV2::decode(&bytes).unwrap();
+        assert_eq!(
+            status.settings().memory_allocation(),
+            candid::Nat::from(memory_allocation)
+        );
+        assert_eq!(status.settings().compute_allocation(), candid::Nat::from(0));
+
+        // compute allocations are given in percent
+        let set_allocations = SetAllocations {
+            canister_id,
+            compute_allocation: Some(101),
+            memory_allocation: None,
+        };
+        assert!(matches!(
+            set_allocations.compute(&mut pic),
+            OpOut::IcUserErr(_)
+        ));
     }
-}
 
-impl std::fmt::Display for Digest {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+    #[test]
+    fn test_persist_and_load() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (query, update) = query_update_constructors(canister_id);
+        compute_assert_state_change(&mut pic, update("write"));
+        compute_assert_state_change(
+            &mut pic,
+            SetTime {
+                time: Time::from_nanos_since_unix_epoch(21),
+            },
+        );
+
+        let snapshot_dir = tempfile::TempDir::new().unwrap();
+        let path = snapshot_dir.path().join("pocket_ic");
+        pic.persist_to_path(&path).unwrap();
+        let mut loaded = PocketIc::load_from_path(&path).unwrap();
+
+        assert_eq!(loaded.get_state_label(), pic.get_state_label());
+        assert_eq!(loaded.features, pic.features);
+        assert_eq!(read_counter(&mut loaded, query("read")), 1);
+
+        // the loaded instance evolves independently of the original one
+        compute_assert_state_change(&mut loaded, update("write"));
+        assert_eq!(read_counter(&mut loaded, query("read")), 2);
+        assert_eq!(read_counter(&mut pic, query("read")), 1);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ic_state_machine_tests::WasmResult;
+    #[test]
+    fn test_persist_and_load_application_subnet() {
+        let pic = PocketIc::new_with_config(
+            SubnetType::Application,
+            SubnetConfig::new(SubnetType::Application),
+            SubnetFeatures::default(),
+            RegistrySnapshot::default(),
+        );
+        let canister_id = pic.main_subnet().create_canister_with_cycles(
+            None,
+            Cycles::new(100_000_000_000_000),
+            None,
+        );
+
+        let snapshot_dir = tempfile::TempDir::new().unwrap();
+        let path = snapshot_dir.path().join("pocket_ic");
+        pic.persist_to_path(&path).unwrap();
+        let mut loaded = PocketIc::load_from_path(&path).unwrap();
+        assert_eq!(
+            loaded.subnet_setups[&loaded.main_subnet_id].subnet_type,
+            SubnetType::Application
+        );
+
+        // execution is still charged for, as on an application subnet
+        let install_op = InstallCanisterAsController {
+            canister_id,
+            mode: CanisterInstallMode::Install,
+            module: counter_wasm(),
+            payload: vec![],
+        };
+        compute_assert_state_change(&mut loaded, install_op);
+        let (_query, update) = query_update_constructors(canister_id);
+        let OpOut::CyclesBurned { cycles, .. } =
+            compute_assert_state_change(&mut loaded, WithCyclesBurned(update("write")))
+        else {
+            unreachable!()
+        };
+        assert!(cycles > 0);
+    }
 
     #[test]
-    fn state_label_test() {
-        let pic = PocketIc::new();
+    fn test_registry_snapshot() {
+        let own_subnet_id = PrincipalId::new_subnet_test_id(7);
+        let other_subnet_id = PrincipalId::new_subnet_test_id(8);
+        let own_range = CanisterId::from_u64(0x100000)..=CanisterId::from_u64(0x1fffff);
+        let mut pic = PocketIc::new_with_registry_snapshot(
+            SubnetFeatures::default(),
+            RegistrySnapshot {
+                subnet_id: Some(own_subnet_id),
+                nns_subnet_id: Some(other_subnet_id),
+                subnet_size: Some(4),
+                routing_table: vec![
+                    RoutingTableEntry {
+                        start: CanisterId::from_u64(0),
+                        end: CanisterId::from_u64(0xfffff),
+                        subnet_id: other_subnet_id,
+                    },
+                    RoutingTableEntry {
+                        start: *own_range.start(),
+                        end: *own_range.end(),
+                        subnet_id: own_subnet_id,
+                    },
+                ],
+                canister_id_base: None,
+            },
+        )
+        .unwrap();
+
+        // canisters are created in the range routed to the subnet of the instance
+        let canister_id = pic.main_subnet().create_canister(None);
+        assert!(own_range.contains(&canister_id));
+        let install_op = InstallCanisterAsController {
+            canister_id,
+            mode: CanisterInstallMode::Install,
+            module: UNIVERSAL_CANISTER_WASM.to_vec(),
+            payload: vec![],
+        };
+        compute_assert_state_change(&mut pic, install_op);
+        let call_from_canister = |callee: CanisterId| {
+            wasm()
+                .inter_update(
+                    callee,
+                    call_args().other_side(wasm().reply_data(b"pong").build()),
+                )
+                .build()
+        };
 
-        let state0 = pic.get_state_label();
-        let canister_id = pic.subnet.create_canister(None);
-        let state1 = pic.get_state_label();
-        let _ = pic.subnet.delete_canister(canister_id);
-        let state2 = pic.get_state_label();
+        // a call to a missing canister routed to the subnet of the instance is rejected by it
+        let local_call = ExecuteIngressMessage(CanisterCall {
+            sender: PrincipalId::new_anonymous(),
+            canister_id,
+            method: "update".into(),
+            payload: call_from_canister(CanisterId::from_u64(0x100042)),
+        });
+        assert!(matches!(
+            compute_assert_state_change(&mut pic, local_call),
+            OpOut::WasmResult(WasmResult::Reject(_))
+        ));
+
+        // whereas a call to a canister routed to the other subnet is sent off to that subnet,
+        // which is not part of the instance, so the call never completes
+        let msg_id = pic.main_subnet().send_ingress(
+            PrincipalId::new_anonymous(),
+            canister_id,
+            "update",
+            call_from_canister(CanisterId::from_u64(42)),
+        );
+        let tick = Tick {
+            max_rounds: Some(10),
+            instruction_budget: None,
+        };
+        compute_assert_state_change(&mut pic, tick);
+        assert!(matches!(
+            pic.main_subnet().ingress_status(&msg_id),
+            IngressStatus::Known {
+                state: IngressState::Processing,
+                ..
+            }
+        ));
+
+        let state = pic.main_subnet().state_manager.get_latest_state().take();
+        let topology = &state.metadata.network_topology;
+        assert_eq!(topology.nns_subnet_id, SubnetId::from(other_subnet_id));
+        assert_eq!(
+            topology
+                .subnets
+                .get(&SubnetId::from(own_subnet_id))
+                .unwrap()
+                .nodes
+                .len(),
+            4
+        );
+    }
 
-        assert!(state0 != state1);
-        assert!(state1 != state2);
-        assert!(state0 != state2);
+    #[test]
+    fn test_malformed_routing_table() {
+        let subnet_id = PrincipalId::new_subnet_test_id(7);
+        let entry = |start, end| RoutingTableEntry {
+            start: CanisterId::from_u64(start),
+            end: CanisterId::from_u64(end),
+            subnet_id,
+        };
+        let result = PocketIc::new_with_registry_snapshot(
+            SubnetFeatures::default(),
+            RegistrySnapshot {
+                routing_table: vec![entry(0, 0xfffff), entry(0x80000, 0x1fffff)],
+                ..RegistrySnapshot::default()
+            },
+        );
+        let err = result.err().unwrap();
+        assert!(err.contains("malformed routing table"), "{}", err);
     }
 
     #[test]
-    fn test_time() {
-        let mut pic = PocketIc::new();
+    fn test_canister_id_base() {
+        let base = CanisterId::from_u64(0x300000);
+        let create_canisters = |pic: &mut PocketIc| {
+            (0..2)
+                .map(|nonce| {
+                    let create_canister = CreateCanister {
+                        settings: None,
+                        nonce,
+                    };
+                    let OpOut::CanisterId(canister_id) = create_canister.compute(pic) else {
+                        unreachable!()
+                    };
+                    canister_id
+                })
+                .collect::<Vec<_>>()
+        };
 
-        let time = Time::from_nanos_since_unix_epoch(21);
-        compute_assert_state_change(&mut pic, SetTime { time });
-        let expected_time = OpOut::Time(21);
-        let actual_time = compute_assert_state_immutable(&mut pic, GetTime {});
+        let mut pic1 = PocketIc::new_with_canister_id_base(base);
+        let mut pic2 = PocketIc::new_with_canister_id_base(base);
+        assert_eq!(pic1.get_state_label(), pic2.get_state_label());
+        assert_ne!(pic1.get_state_label(), PocketIc::new().get_state_label());
 
-        assert_eq!(expected_time, actual_time);
+        let canister_ids = create_canisters(&mut pic1);
+        assert_eq!(canister_ids, vec![base, CanisterId::from_u64(0x300001)]);
+        assert_eq!(create_canisters(&mut pic2), canister_ids);
+        assert_eq!(pic1.get_state_label(), pic2.get_state_label());
     }
 
     #[test]
-    fn test_execute_message() {
-        let (mut pic, canister_id) = new_pic_counter_installed();
+    fn test_xnet_call() {
+        let mut pic = PocketIc::new_with_application_subnet();
+        let application_subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(2));
+        let caller = pic.main_subnet().create_canister(None);
+        let callee = pic.subnets[&application_subnet_id].create_canister(None);
+        for canister_id in [caller, callee] {
+            let install_op = InstallCanisterAsController {
+                canister_id,
+                mode: CanisterInstallMode::Install,
+                module: UNIVERSAL_CANISTER_WASM.to_vec(),
+                payload: vec![],
+            };
+            compute_assert_state_change(&mut pic, install_op);
+        }
+        assert!(!pic.main_subnet().canister_exists(callee));
 
+        let call = wasm()
+            .inter_update(
+                callee,
+                call_args().other_side(wasm().reply_data(b"pong").build()),
+            )
+            .build();
         let update = ExecuteIngressMessage(CanisterCall {
             sender: PrincipalId::new_anonymous(),
-            canister_id,
-            method: "write".into(),
-            payload: vec![],
+            canister_id: caller,
+            method: "update".into(),
+            payload: call,
         });
+        let res = compute_assert_state_change(&mut pic, update);
+        assert_eq!(res, OpOut::WasmResult(WasmResult::Reply(b"pong".to_vec())));
 
-        compute_assert_state_change(&mut pic, update);
+        // the streams are drained once the signals made it back
+        let tick = Tick {
+            max_rounds: Some(10),
+            instruction_budget: None,
+        };
+        compute_assert_state_change(&mut pic, tick);
+        assert!(!pic.has_pending_messages());
     }
 
     #[test]
-    fn test_query() {
-        let (mut pic, canister_id) = new_pic_counter_installed();
-        let (query, update) = query_update_constructors(canister_id);
+    fn test_stop_canister_waits_for_xnet_call() {
+        let mut pic = PocketIc::new_with_application_subnet();
+        let application_subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(2));
+        let caller = pic.main_subnet().create_canister(None);
+        let callee = pic.subnets[&application_subnet_id].create_canister(None);
+        for canister_id in [caller, callee] {
+            let install_op = InstallCanisterAsController {
+                canister_id,
+                mode: CanisterInstallMode::Install,
+                module: UNIVERSAL_CANISTER_WASM.to_vec(),
+                payload: vec![],
+            };
+            compute_assert_state_change(&mut pic, install_op);
+        }
+        let call = wasm()
+            .inter_update(
+                callee,
+                call_args().other_side(wasm().reply_data(b"pong").build()),
+            )
+            .build();
+        pic.main_subnet()
+            .send_ingress(PrincipalId::new_anonymous(), caller, "update", call);
+        pic.main_subnet().tick();
+
+        // the canister only stops once the reply from the other subnet was delivered
+        let res = compute_assert_state_change(
+            &mut pic,
+            StopCanister {
+                canister_id: caller,
+            },
+        );
+        assert_eq!(res, OpOut::CanisterStatus(CanisterStatus::Stopped));
+    }
 
-        use WasmResult::*;
-        let OpOut::WasmResult(Reply(initial_bytes)) =
-            compute_assert_state_immutable(&mut pic, query("read"))
-        else {
+    #[test]
+    fn test_xnet_round_executes_once_per_subnet() {
+        let mut pic = PocketIc::new_with_application_subnet();
+        let application_subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(2));
+        let caller = pic.main_subnet().create_canister(None);
+        let callee = pic.subnets[&application_subnet_id].create_canister(None);
+        for canister_id in [caller, callee] {
+            let install_op = InstallCanisterAsController {
+                canister_id,
+                mode: CanisterInstallMode::Install,
+                module: UNIVERSAL_CANISTER_WASM.to_vec(),
+                payload: vec![],
+            };
+            compute_assert_state_change(&mut pic, install_op);
+        }
+        let call = wasm()
+            .inter_update(
+                callee,
+                call_args().other_side(wasm().reply_data(b"pong").build()),
+            )
+            .build();
+        pic.main_subnet()
+            .send_ingress(PrincipalId::new_anonymous(), caller, "update", call);
+
+        let heights = |pic: &PocketIc| {
+            pic.subnets
+                .values()
+                .map(|subnet| subnet.state_manager.latest_state_height().get())
+                .collect::<Vec<_>>()
+        };
+        let mut inducted_xnet_messages = false;
+        for _ in 0..10 {
+            inducted_xnet_messages |= pic.has_pending_messages();
+            let before = heights(&pic);
+            pic.execute_round();
+            let after = heights(&pic);
+            assert_eq!(
+                after,
+                before.iter().map(|height| height + 1).collect::<Vec<_>>()
+            );
+        }
+        assert!(inducted_xnet_messages);
+        assert!(!pic.has_pending_messages());
+    }
+
+    #[test]
+    fn test_subnet_hosting_consults_routing_table() {
+        let pic = PocketIc::new_with_application_subnet();
+        let application_subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(2));
+        let unknown_canister = CanisterId::from_u64(CANISTER_IDS_PER_SUBNET + 42);
+        assert_eq!(
+            pic.subnet_hosting(unknown_canister).get_subnet_id(),
+            application_subnet_id
+        );
+        assert_eq!(
+            pic.subnet_hosting(CanisterId::from_u64(42)).get_subnet_id(),
+            pic.main_subnet_id
+        );
+    }
+
+    #[test]
+    fn test_cycles_operations_on_other_subnet() {
+        let mut pic = PocketIc::new_with_application_subnet();
+        let application_subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(2));
+        let canister_id = pic.subnets[&application_subnet_id].create_canister(None);
+
+        let OpOut::Cycles(added_balance) = compute_assert_state_change(
+            &mut pic,
+            AddCycles {
+                canister_id,
+                amount: 1_000_000_000_000,
+            },
+        ) else {
             unreachable!()
         };
-        compute_assert_state_change(&mut pic, update("write"));
-        let OpOut::WasmResult(Reply(updated_bytes)) =
-            compute_assert_state_immutable(&mut pic, query("read"))
-        else {
+        assert_eq!(added_balance, 1_000_000_000_000);
+
+        let OpOut::Cycles(minted_balance) = compute_assert_state_change(
+            &mut pic,
+            MintCycles {
+                to: canister_id,
+                amount: 2_000_000_000_000,
+            },
+        ) else {
             unreachable!()
         };
+        assert!(minted_balance > added_balance);
+        assert_eq!(
+            pic.subnets[&application_subnet_id].cycle_balance(canister_id),
+            minted_balance
+        );
+    }
 
-        assert_eq!(updated_bytes[0], initial_bytes[0] + 1);
+    #[test]
+    fn test_persist_and_load_all_subnets() {
+        let (mut pic, canister_id) = new_pic_counter_installed();
+        let (query, update) = query_update_constructors(canister_id);
+        compute_assert_state_change(&mut pic, update("write"));
+        let target_subnet = pic.add_subnet();
+        let migrate = MigrateCanister {
+            canister_id,
+            target_subnet,
+        };
+        compute_assert_state_change(&mut pic, migrate);
+
+        let snapshot_dir = tempfile::TempDir::new().unwrap();
+        let path = snapshot_dir.path().join("pocket_ic");
+        pic.persist_to_path(&path).unwrap();
+        let mut loaded = PocketIc::load_from_path(&path).unwrap();
+
+        assert_eq!(loaded.get_state_label(), pic.get_state_label());
+        assert_eq!(
+            loaded.subnets.keys().collect::<Vec<_>>(),
+            pic.subnets.keys().collect::<Vec<_>>()
+        );
+        assert!(loaded.subnets[&target_subnet].canister_exists(canister_id));
+        assert_eq!(loaded.main_subnet().route(canister_id), Some(target_subnet));
+        compute_assert_state_change(&mut loaded, update("write"));
+        assert_eq!(read_counter(&mut loaded, query("read")), 2);
     }
 
     #[test]
-    fn test_cycles() {
+    fn test_migrate_canister() {
         let (mut pic, canister_id) = new_pic_counter_installed();
-        let (_, update) = query_update_constructors(canister_id);
+        let (query, update) = query_update_constructors(canister_id);
+        compute_assert_state_change(&mut pic, update("write"));
 
-        let cycles_balance = CyclesBalance { canister_id };
-        let OpOut::Cycles(orig_balance) =
-            compute_assert_state_immutable(&mut pic, cycles_balance.clone())
-        else {
-            unreachable!()
+        let unknown_subnet = SubnetId::from(PrincipalId::new_subnet_test_id(42));
+        assert!(matches!(
+            compute_assert_state_immutable(
+                &mut pic,
+                MigrateCanister {
+                    canister_id,
+                    target_subnet: unknown_subnet,
+                },
+            ),
+            OpOut::Error(_)
+        ));
+
+        let target_subnet = pic.add_subnet();
+        let migrate = MigrateCanister {
+            canister_id,
+            target_subnet,
         };
+        assert_eq!(
+            compute_assert_state_change(&mut pic, migrate.clone()),
+            OpOut::NoOutput
+        );
+        assert!(!pic.main_subnet().canister_exists(canister_id));
+        assert!(pic.subnets[&target_subnet].canister_exists(canister_id));
+
+        // calls are executed on the target subnet, which kept the canister state
         compute_assert_state_change(&mut pic, update("write"));
-        let OpOut::Cycles(changed_balance) =
-            compute_assert_state_immutable(&mut pic, cycles_balance)
+        assert_eq!(read_counter(&mut pic, query("read")), 2);
+
+        assert!(matches!(
+            compute_assert_state_immutable(&mut pic, migrate),
+            OpOut::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_load_snapshot_of_other_version() {
+        let pic = PocketIc::new();
+        let snapshot_dir = tempfile::TempDir::new().unwrap();
+        let path = snapshot_dir.path().join("pocket_ic");
+        pic.persist_to_path(&path).unwrap();
+
+        let metadata_path = path.join(SNAPSHOT_METADATA_FILE);
+        let mut metadata: SnapshotMetadata =
+            serde_json::from_slice(&std::fs::read(&metadata_path).unwrap()).unwrap();
+        metadata.version = "0.0.0-other".to_string();
+        std::fs::write(&metadata_path, serde_json::to_vec(&metadata).unwrap()).unwrap();
+
+        let err = PocketIc::load_from_path(&path).err().unwrap();
+        assert!(err.contains("0.0.0-other"), "{}", err);
+    }
+
+    fn read_counter(pic: &mut PocketIc, query: Query) -> u32 {
+        let OpOut::WasmResult(WasmResult::Reply(bytes)) =
+            compute_assert_state_immutable(pic, query)
         else {
             unreachable!()
         };
+        u32::from_le_bytes(bytes[..4].try_into().unwrap())
+    }
 
-        // nothing is charged on a system subnet
-        assert_eq!(changed_balance, orig_balance);
+    fn new_pic_self_calling_installed() -> (PocketIc, CanisterId) {
+        let mut subnet_config = SubnetConfig::new(SubnetType::System);
+        // execute at most one message per canister in every round
+        subnet_config.scheduler_config.max_instructions_per_round = subnet_config
+            .scheduler_config
+            .max_instructions_per_message_without_dts;
+        let mut pic = PocketIc::new_with_config(
+            SubnetType::System,
+            subnet_config,
+            SubnetFeatures::default(),
+            RegistrySnapshot::default(),
+        );
+        let canister_id = pic.main_subnet().create_canister(None);
 
-        let amount: u128 = 20_000_000_000_000;
-        let add_cycles = AddCycles {
+        let install_op = InstallCanisterAsController {
             canister_id,
-            amount,
-        };
-
-        let OpOut::Cycles(final_balance) = compute_assert_state_change(&mut pic, add_cycles) else {
-            unreachable!()
+            mode: CanisterInstallMode::Install,
+            module: wat::parse_str(SELF_CALLING_WAT).unwrap(),
+            payload: vec![],
         };
+        compute_assert_state_change(&mut pic, install_op);
 
-        assert_eq!(final_balance, changed_balance + amount);
+        (pic, canister_id)
     }
 
     fn query_update_constructors(
@@ -383,7 +3257,7 @@ mod tests {
 
     fn new_pic_counter_installed() -> (PocketIc, CanisterId) {
         let mut pic = PocketIc::new();
-        let canister_id = pic.subnet.create_canister(None);
+        let canister_id = pic.main_subnet().create_canister(None);
 
         let module = counter_wasm();
         let install_op = InstallCanisterAsController {
@@ -457,6 +3331,105 @@ mod tests {
   (export "canister_query read" (func $read))
   (export "canister_query inc_read" (func $write))
   (export "canister_update write" (func $write))
+)
+    "#;
+
+    const TIMER_WAT: &str = r#"
+;; Sets a timer one second into the future and counts how often it fired ;;
+(module
+  (import "ic0" "msg_reply" (func $msg_reply))
+  (import "ic0" "msg_reply_data_append"
+    (func $msg_reply_data_append (param i32 i32)))
+  (import "ic0" "time" (func $time (result i64)))
+  (import "ic0" "global_timer_set"
+    (func $global_timer_set (param i64) (result i64)))
+
+  (func $read
+    (i32.store
+      (i32.const 0)
+      (global.get 0)
+    )
+    (call $msg_reply_data_append
+      (i32.const 0)
+      (i32.const 4))
+    (call $msg_reply))
+
+  (func $set_timer
+    (drop (call $global_timer_set
+      (i64.add (call $time) (i64.const 1_000_000_000))))
+    (call $msg_reply))
+
+  (func $timer
+    (global.set 0
+      (i32.add
+        (global.get 0)
+        (i32.const 1)
+      )
+    )
+  )
+
+  (memory $memory 1)
+  (export "memory" (memory $memory))
+  (global (export "counter_global") (mut i32) (i32.const 0))
+  (export "canister_query read" (func $read))
+  (export "canister_update set_timer" (func $set_timer))
+  (export "canister_global_timer" (func $timer))
+)
+    "#;
+
+    const SELF_CALLING_WAT: &str = r#"
+;; Decrements a counter and calls itself until the counter reaches zero ;;
+(module
+  (import "ic0" "msg_reply" (func $msg_reply))
+  (import "ic0" "msg_reply_data_append"
+    (func $msg_reply_data_append (param i32 i32)))
+  (import "ic0" "canister_self_size" (func $canister_self_size (result i32)))
+  (import "ic0" "canister_self_copy"
+    (func $canister_self_copy (param i32 i32 i32)))
+  (import "ic0" "call_new"
+    (func $call_new (param i32 i32 i32 i32 i32 i32 i32 i32)))
+  (import "ic0" "call_perform" (func $call_perform (result i32)))
+
+  (func $read
+    (i32.store
+      (i32.const 0)
+      (global.get 0)
+    )
+    (call $msg_reply_data_append
+      (i32.const 0)
+      (i32.const 4))
+    (call $msg_reply))
+
+  (func $step
+    (if (i32.gt_s (global.get 0) (i32.const 0))
+      (then
+        (global.set 0
+          (i32.sub
+            (global.get 0)
+            (i32.const 1)
+          )
+        )
+        (call $canister_self_copy
+          (i32.const 100)
+          (i32.const 0)
+          (call $canister_self_size))
+        (call $call_new
+          (i32.const 100) (call $canister_self_size)
+          (i32.const 200) (i32.const 4)
+          (i32.const 0) (i32.const 0)
+          (i32.const 0) (i32.const 0))
+        (drop (call $call_perform))))
+    (call $msg_reply))
+
+  (func $callback (param i32))
+
+  (table funcref (elem $callback))
+  (memory $memory 1)
+  (data (i32.const 200) "step")
+  (export "memory" (memory $memory))
+  (global (export "counter_global") (mut i32) (i32.const 5))
+  (export "canister_query read" (func $read))
+  (export "canister_update step" (func $step))
 )
     "#;
 }
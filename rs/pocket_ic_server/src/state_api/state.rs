@@ -2,6 +2,7 @@ use crate::{Computation, OpId, Operation};
 use base64;
 use ic_state_machine_tests::UserError;
 use ic_state_machine_tests::WasmResult;
+use ic_types::messages::MessageId;
 use ic_types::CanisterId;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -15,7 +16,7 @@ const DEFAULT_SYNC_WAIT_DURATION: Duration = Duration::from_millis(150);
 pub const STATE_LABEL_HASH_SIZE: usize = 32;
 
 /// Uniquely identifies a state.
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Serialize, Deserialize)]
 pub struct StateLabel(pub [u8; STATE_LABEL_HASH_SIZE]);
 
 impl std::fmt::Debug for StateLabel {
@@ -130,6 +131,49 @@ pub enum OpOut {
     CanisterId(CanisterId),
     IcUserErr(UserError),
     Cycles(u128),
+    Bytes(Vec<u8>),
+    /// The number of executed rounds along with the number of messages executed in them.
+    Ticked {
+        rounds: u64,
+        executed_messages: u64,
+    },
+    /// The offset of the first byte at which two stable memories differ,
+    /// or `None` if they are identical.
+    StableMemoryDiff(Option<u64>),
+    /// Whether a canister's global timer fired.
+    TimerFired(bool),
+    /// Whether a certificate is signed by the subnet's root key.
+    CertificateValidity(bool),
+    /// An operation that could not be applied to the instance.
+    Error(String),
+    /// The output of a canister call along with the cycles it burned.
+    CyclesBurned {
+        result: Box<OpOut>,
+        cycles: u128,
+    },
+    /// The rate at which ICP is converted to cycles.
+    CyclesMintingRate {
+        xdr_permyriad_per_icp: u64,
+        cycles_per_xdr: u128,
+    },
+    /// The output of an operation along with the label of the state it produced.
+    WithStateLabel {
+        result: Box<OpOut>,
+        state_label: StateLabel,
+    },
+    /// The status of a canister after starting or stopping it.
+    CanisterStatus(CanisterStatus),
+    /// The id of a submitted ingress message.
+    MessageId(MessageId),
+}
+
+/// The status of a canister. Stopping a canister with outstanding calls takes several rounds,
+/// during which the canister is `Stopping`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum CanisterStatus {
+    Running,
+    Stopping,
+    Stopped,
 }
 
 impl From<Result<WasmResult, UserError>> for OpOut {
@@ -157,6 +201,32 @@ impl std::fmt::Debug for OpOut {
             OpOut::Time(x) => write!(f, "Time({})", x),
             OpOut::CanisterId(cid) => write!(f, "CanisterId({})", cid),
             OpOut::Cycles(x) => write!(f, "Cycles({})", x),
+            OpOut::Bytes(bytes) => write!(f, "Bytes({})", base64::encode(bytes)),
+            OpOut::Ticked {
+                rounds,
+                executed_messages,
+            } => write!(f, "Ticked({}, {})", rounds, executed_messages),
+            OpOut::StableMemoryDiff(x) => write!(f, "StableMemoryDiff({:?})", x),
+            OpOut::TimerFired(x) => write!(f, "TimerFired({})", x),
+            OpOut::CertificateValidity(x) => write!(f, "CertificateValidity({})", x),
+            OpOut::Error(x) => write!(f, "Error({})", x),
+            OpOut::CyclesBurned { result, cycles } => {
+                write!(f, "CyclesBurned({:?}, {})", result, cycles)
+            }
+            OpOut::CyclesMintingRate {
+                xdr_permyriad_per_icp,
+                cycles_per_xdr,
+            } => write!(
+                f,
+                "CyclesMintingRate({},{})",
+                xdr_permyriad_per_icp, cycles_per_xdr
+            ),
+            OpOut::WithStateLabel {
+                result,
+                state_label,
+            } => write!(f, "WithStateLabel({:?}, {:?})", result, state_label),
+            OpOut::CanisterStatus(x) => write!(f, "CanisterStatus({:?})", x),
+            OpOut::MessageId(x) => write!(f, "MessageId({})", x),
             OpOut::IcUserErr(x) => write!(f, "{}", x),
             OpOut::WasmResult(WasmResult::Reject(x)) => write!(f, "Reject({})", x),
             OpOut::WasmResult(WasmResult::Reply(bytes)) => {
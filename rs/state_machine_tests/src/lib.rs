@@ -11,7 +11,10 @@ use ic_crypto_internal_threshold_sig_bls12381::api::{
 use ic_crypto_internal_threshold_sig_bls12381::types::SecretKeyBytes;
 use ic_crypto_internal_types::sign::threshold_sig::public_key::CspThresholdSigPublicKey;
 use ic_crypto_test_utils_keys::public_keys::valid_node_signing_public_key;
-use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
+pub use ic_crypto_tree_hash::Label;
+use ic_crypto_tree_hash::{
+    flatmap, sparse_labeled_tree_from_paths, LabeledTree, LabeledTree::SubTree, Path as LabelPath,
+};
 use ic_cycles_account_manager::CyclesAccountManager;
 pub use ic_error_types::{ErrorCode, UserError};
 use ic_execution_environment::ExecutionServices;
@@ -108,7 +111,10 @@ use std::str::FromStr;
 use std::string::ToString;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
-use std::{collections::BTreeMap, convert::TryFrom};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
 use std::{fmt, io};
 use tempfile::TempDir;
 use tokio::runtime::Runtime;
@@ -298,6 +304,8 @@ pub struct StateMachine {
     public_key: ThresholdSigPublicKey,
     secret_key: SecretKeyBytes,
     ecdsa_secret_key: PrivateKey,
+    ecdsa_secret_keys: BTreeMap<EcdsaKeyId, PrivateKey>,
+    ecdsa_key_ids: BTreeSet<EcdsaKeyId>,
     registry_data_provider: Arc<ProtoRegistryDataProvider>,
     registry_client: Arc<FakeRegistryClient>,
     pub state_manager: Arc<StateManagerImpl>,
@@ -376,11 +384,11 @@ impl StateMachineBuilder {
         Self { state_dir, ..self }
     }
 
-    fn with_nonce(self, nonce: u64) -> Self {
+    pub fn with_nonce(self, nonce: u64) -> Self {
         Self { nonce, ..self }
     }
 
-    fn with_time(self, time: Time) -> Self {
+    pub fn with_time(self, time: Time) -> Self {
         Self { time, ..self }
     }
 
@@ -469,6 +477,11 @@ impl StateMachineBuilder {
         Self { ecdsa_keys, ..self }
     }
 
+    /// Replaces the ECDSA keys held by the subnet, including the default one.
+    pub fn with_ecdsa_keys(self, ecdsa_keys: Vec<EcdsaKeyId>) -> Self {
+        Self { ecdsa_keys, ..self }
+    }
+
     pub fn with_features(self, features: SubnetFeatures) -> Self {
         Self { features, ..self }
     }
@@ -658,6 +671,7 @@ impl StateMachine {
         let ecdsa_secret_key: PrivateKey =
             PrivateKey::deserialize_sec1(private_key_bytes.as_slice()).unwrap();
 
+        let ecdsa_key_ids: BTreeSet<EcdsaKeyId> = ecdsa_keys.iter().cloned().collect();
         let mut ecdsa_subnet_public_keys = BTreeMap::new();
 
         for ecdsa_key in ecdsa_keys {
@@ -686,6 +700,8 @@ impl StateMachine {
             secret_key: secret_key_bytes.get(0).unwrap().clone(),
             public_key,
             ecdsa_secret_key,
+            ecdsa_secret_keys: BTreeMap::new(),
+            ecdsa_key_ids,
             registry_data_provider,
             registry_client,
             state_manager,
@@ -768,12 +784,7 @@ impl StateMachine {
         msg_limit: Option<usize>,
         byte_limit: Option<usize>,
     ) -> Result<XNetPayload, EncodeStreamError> {
-        if self.state_manager.latest_state_height() > self.state_manager.latest_certified_height() {
-            let state_hashes = self.state_manager.list_state_hashes_to_certify();
-            let (height, hash) = state_hashes.last().unwrap();
-            self.state_manager
-                .deliver_state_certification(self.certify_hash(height, hash));
-        }
+        self.certify_latest_state();
         self.state_manager
             .encode_certified_stream_slice(
                 remote_subnet_id,
@@ -790,7 +801,12 @@ impl StateMachine {
     /// Triggers a single round of execution without any new inputs.  The state
     /// machine will invoke heartbeats and make progress on pending async calls.
     pub fn tick(&self) {
-        let mut payload = PayloadBuilder::default();
+        self.tick_with_xnet_payload(XNetPayload::default())
+    }
+
+    /// Like [`Self::tick`], but also inducts the given XNet payload in the same round.
+    pub fn tick_with_xnet_payload(&self, xnet_payload: XNetPayload) {
+        let mut payload = PayloadBuilder::default().xnet_payload(xnet_payload);
         let state = self.state_manager.get_latest_state().take();
         let sign_with_ecdsa_contexts = state
             .metadata
@@ -808,8 +824,12 @@ impl StateMachine {
                     .map(DerivationIndex)
                     .collect::<Vec<_>>(),
             );
+            let ecdsa_secret_key = self
+                .ecdsa_secret_keys
+                .get(&ecdsa_context.key_id)
+                .unwrap_or(&self.ecdsa_secret_key);
             let signature = sign_prehashed_message_with_derived_key(
-                &self.ecdsa_secret_key,
+                ecdsa_secret_key,
                 &ecdsa_context.message_hash,
                 derivation_path,
             );
@@ -838,12 +858,7 @@ impl StateMachine {
     pub fn run_until_completion(&self, max_ticks: usize) {
         let mut reached_completion = false;
         for _tick in 0..max_ticks {
-            let state = self.state_manager.get_latest_state().take();
-            reached_completion = !state
-                .canisters_iter()
-                .any(|canister| canister.has_input() || canister.has_output())
-                && !state.subnet_queues().has_input()
-                && !state.subnet_queues().has_output();
+            reached_completion = !self.has_pending_messages();
             if reached_completion {
                 break;
             }
@@ -857,6 +872,17 @@ impl StateMachine {
         }
     }
 
+    /// Returns true if there are messages in the input or output queues of any
+    /// canister or of the subnet.
+    pub fn has_pending_messages(&self) -> bool {
+        let state = self.state_manager.get_latest_state().take();
+        state
+            .canisters_iter()
+            .any(|canister| canister.has_input() || canister.has_output())
+            || state.subnet_queues().has_input()
+            || state.subnet_queues().has_output()
+    }
+
     /// Triggers a single round of execution with block payload as an input.
     pub fn execute_payload(&self, payload: PayloadBuilder) {
         let batch_number = self.message_routing.expected_batch_height();
@@ -915,6 +941,13 @@ impl StateMachine {
         .unwrap_or(0.0)
     }
 
+    /// Returns the total number of messages executed in all execution rounds.
+    pub fn num_messages_executed(&self) -> u64 {
+        fetch_histogram_stats(&self.metrics_registry, "execution_round_messages")
+            .map(|stats| stats.sum as u64)
+            .unwrap_or(0)
+    }
+
     /// Returns the total number of Wasm instructions executed when executing subnet
     /// messages (IC00 messages addressed to the subnet).
     pub fn subnet_message_instructions(&self) -> f64 {
@@ -962,6 +995,11 @@ impl StateMachine {
         );
     }
 
+    /// Returns the nonce used to derive the ids of the next ingress messages.
+    pub fn nonce(&self) -> u64 {
+        self.nonce.load(Ordering::Relaxed)
+    }
+
     /// Returns the current state machine time.
     pub fn time(&self) -> SystemTime {
         SystemTime::UNIX_EPOCH + Duration::from_nanos(self.time.load(Ordering::Relaxed))
@@ -1416,12 +1454,7 @@ impl StateMachine {
         method: impl ToString,
         method_payload: Vec<u8>,
     ) -> Result<WasmResult, UserError> {
-        if self.state_manager.latest_state_height() > self.state_manager.latest_certified_height() {
-            let state_hashes = self.state_manager.list_state_hashes_to_certify();
-            let (height, hash) = state_hashes.last().unwrap();
-            self.state_manager
-                .deliver_state_certification(self.certify_hash(height, hash));
-        }
+        self.certify_latest_state();
 
         let path = SubTree(flatmap! {
             Label::from("canister") => SubTree(
@@ -1452,6 +1485,82 @@ impl StateMachine {
         )
     }
 
+    /// Queries the canister with the specified ID against the retained
+    /// checkpoint with the given state hash instead of the latest state.
+    ///
+    /// Returns a `CertifiedStateUnavailable` error if no retained checkpoint
+    /// has this hash, e.g., because the state has already been pruned. The
+    /// canister cannot access a data certificate during such a query.
+    pub fn query_as_at_state_hash(
+        &self,
+        sender: PrincipalId,
+        receiver: CanisterId,
+        method: impl ToString,
+        method_payload: Vec<u8>,
+        state_hash: &CryptoHashOfState,
+    ) -> Result<WasmResult, UserError> {
+        let state = self
+            .state_manager
+            .checkpoint_heights()
+            .into_iter()
+            .find(|height| {
+                matches!(self.state_manager.get_state_hash_at(*height), Ok(hash) if &hash == state_hash)
+            })
+            .and_then(|height| self.state_manager.get_state_at(height).ok())
+            .ok_or_else(|| {
+                UserError::new(
+                    ErrorCode::CertifiedStateUnavailable,
+                    format!(
+                        "No retained checkpoint has state hash {}",
+                        hex::encode(&state_hash.get_ref().0)
+                    ),
+                )
+            })?;
+        self.query_handler.query(
+            UserQuery {
+                receiver,
+                source: UserId::from(sender),
+                method_name: method.to_string(),
+                method_payload,
+                ingress_expiry: 0,
+                nonce: None,
+            },
+            state.take(),
+            vec![],
+        )
+    }
+
+    /// Returns the CBOR-encoded certificate covering the given paths of the
+    /// certified state tree, i.e., the equivalent of a `read_state` request.
+    /// Returns an error if any of the paths is too long or if the certified
+    /// state cannot be read.
+    pub fn read_state(&self, paths: &[Vec<Label>]) -> Result<Vec<u8>, String> {
+        self.certify_latest_state();
+
+        let paths: Vec<LabelPath> = paths.iter().cloned().map(LabelPath::new).collect();
+        let labeled_tree = sparse_labeled_tree_from_paths(&paths)
+            .map_err(|_| "failed to build labeled tree from paths: path too long".to_string())?;
+        let (_state, tree, certification) = self
+            .state_manager
+            .read_certified_state(&labeled_tree)
+            .ok_or_else(|| "failed to read certified state".to_string())?;
+        Ok(into_cbor(&Certificate {
+            tree,
+            signature: Blob(certification.signed.signature.signature.get().0),
+            delegation: None,
+        }))
+    }
+
+    /// Delivers a certification for the latest state if it is not certified yet.
+    fn certify_latest_state(&self) {
+        if self.state_manager.latest_state_height() > self.state_manager.latest_certified_height() {
+            let state_hashes = self.state_manager.list_state_hashes_to_certify();
+            let (height, hash) = state_hashes.last().unwrap();
+            self.state_manager
+                .deliver_state_certification(self.certify_hash(height, hash));
+        }
+    }
+
     fn certify_hash(&self, height: &Height, hash: &CryptoHashOfPartialState) -> Certification {
         let signature_bytes = Some(
             sign_message(
@@ -1635,6 +1744,17 @@ impl StateMachine {
         self.subnet_id
     }
 
+    /// Returns the subnet that the latest routing table in the registry assigns the specified
+    /// canister to, if any.
+    pub fn route(&self, canister_id: CanisterId) -> Option<SubnetId> {
+        use ic_registry_client_helpers::routing_table::RoutingTableRegistry;
+
+        self.registry_client
+            .get_routing_table(self.registry_client.get_latest_version())
+            .expect("malformed routing table")
+            .and_then(|routing_table| routing_table.route(canister_id.get()))
+    }
+
     /// Marks canisters in the specified range as being migrated to another subnet.
     pub fn prepare_canister_migrations(
         &self,
@@ -1818,6 +1938,37 @@ impl StateMachine {
         balance
     }
 
+    /// Returns true if the given ECDSA key is enabled on the subnet.
+    pub fn has_ecdsa_key(&self, key_id: &EcdsaKeyId) -> bool {
+        self.ecdsa_key_ids.contains(key_id)
+    }
+
+    /// Replaces the secret key used to answer `sign_with_ecdsa` requests for
+    /// the given ECDSA key, so that tests can verify the produced signatures
+    /// against a known public key. The master public key of the subnet is
+    /// updated accordingly and takes effect from the next executed batch.
+    pub fn set_ecdsa_secret_key(&mut self, key_id: EcdsaKeyId, secret_key: PrivateKey) {
+        self.ecdsa_subnet_public_keys.insert(
+            key_id.clone(),
+            MasterEcdsaPublicKey {
+                algorithm_id: AlgorithmId::EcdsaSecp256k1,
+                public_key: secret_key.public_key().serialize_sec1(true),
+            },
+        );
+        self.ecdsa_secret_keys.insert(key_id, secret_key);
+    }
+
+    /// Returns the sec1-encoded public keys of the ECDSA keys installed with
+    /// [`StateMachine::set_ecdsa_secret_key`], keyed by their key id.
+    pub fn ecdsa_test_public_keys(&self) -> BTreeMap<EcdsaKeyId, Vec<u8>> {
+        self.ecdsa_secret_keys
+            .iter()
+            .map(|(key_id, secret_key)| {
+                (key_id.clone(), secret_key.public_key().serialize_sec1(true))
+            })
+            .collect()
+    }
+
     /// Returns sign with ECDSA contexts from internal subnet call context manager.
     pub fn sign_with_ecdsa_contexts(&self) -> BTreeMap<CallbackId, SignWithEcdsaContext> {
         let state = self.state_manager.get_latest_state().take();
@@ -1950,6 +2101,12 @@ impl PayloadBuilder {
         self
     }
 
+    /// Adds an ingress message that was signed elsewhere, e.g. by an agent, as is.
+    pub fn signed_ingress(mut self, msg: SignedIngress) -> Self {
+        self.ingress_messages.push(msg);
+        self
+    }
+
     pub fn xnet_payload(mut self, xnet_payload: XNetPayload) -> Self {
         self.xnet_payload = xnet_payload;
         self
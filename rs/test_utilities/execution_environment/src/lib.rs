@@ -1833,6 +1833,14 @@ impl ExecutionTestBuilder {
         self
     }
 
+    pub fn with_development_custom_section_limits(mut self) -> Self {
+        self.execution_config
+            .embedders_config
+            .feature_flags
+            .development_custom_section_limits = FlagStatus::Enabled;
+        self
+    }
+
     pub fn with_metering_type(mut self, metering_type: MeteringType) -> Self {
         self.execution_config.embedders_config.metering_type = metering_type;
         self
@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::sync::Arc;
 
@@ -10,12 +11,12 @@ use ic_interfaces::execution_environment::{
 };
 use ic_logger::replica_logger::no_op_logger;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::{Global, Memory, NetworkTopology, PageMap};
+use ic_replicated_state::{Global, Memory, NetworkTopology, PageIndex, PageMap};
 use ic_system_api::{
     sandbox_safe_system_state::SandboxSafeSystemState, ExecutionParameters, InstructionLimits,
     ModificationTracking, SystemApiImpl,
 };
-use ic_types::{ComputeAllocation, MemoryAllocation, NumInstructions};
+use ic_types::{methods::FuncRef, ComputeAllocation, MemoryAllocation, NumInstructions};
 use ic_wasm_types::BinaryEncodedWasm;
 
 use crate::{
@@ -27,6 +28,21 @@ use crate::{
 
 pub const DEFAULT_NUM_INSTRUCTIONS: NumInstructions = NumInstructions::new(5_000_000_000);
 
+/// The pages dirtied by running a method, see `WasmtimeInstanceBuilder::dirty_pages_by_tracking`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DirtyPages {
+    pub heap: BTreeSet<PageIndex>,
+    pub stable: BTreeSet<PageIndex>,
+}
+
+/// The dirty pages observed with each of the two dirty page tracking implementations.
+#[derive(Debug)]
+pub struct DirtyPagesByTracking {
+    pub write_barrier: DirtyPages,
+    pub signal_handler: DirtyPages,
+}
+
+#[derive(Clone)]
 pub struct WasmtimeInstanceBuilder {
     wasm: Vec<u8>,
     wat: String,
@@ -192,6 +208,30 @@ impl WasmtimeInstanceBuilder {
         instance
     }
 
+    /// Runs `func_ref` on two instances built from this builder, one tracking dirty pages with
+    /// the write barrier and the other one with the signal handler, and returns the pages that
+    /// each of them reported as dirty. This allows tests to check that both implementations agree.
+    pub fn dirty_pages_by_tracking(self, func_ref: FuncRef) -> DirtyPagesByTracking {
+        let run = |write_barrier: FlagStatus| {
+            let mut config = self.config.clone();
+            config.feature_flags.write_barrier = write_barrier;
+            let result = self
+                .clone()
+                .with_config(config)
+                .build()
+                .run(func_ref.clone())
+                .expect("Failed to run the workload");
+            DirtyPages {
+                heap: result.dirty_pages.into_iter().collect(),
+                stable: result.stable_memory_dirty_pages.into_iter().collect(),
+            }
+        };
+        DirtyPagesByTracking {
+            write_barrier: run(FlagStatus::Enabled),
+            signal_handler: run(FlagStatus::Disabled),
+        }
+    }
+
     pub fn build(self) -> WasmtimeInstance<SystemApiImpl> {
         let instance = self.try_build();
         instance
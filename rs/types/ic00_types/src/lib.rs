@@ -955,6 +955,14 @@ impl DefiniteCanisterSettingsArgs {
         self.controllers.clone()
     }
 
+    pub fn compute_allocation(&self) -> candid::Nat {
+        self.compute_allocation.clone()
+    }
+
+    pub fn memory_allocation(&self) -> candid::Nat {
+        self.memory_allocation.clone()
+    }
+
     pub fn reserved_cycles_limit(&self) -> candid::Nat {
         self.reserved_cycles_limit.clone()
     }
@@ -99,7 +99,7 @@ impl std::fmt::Display for WasmValidationError {
             ),
             Self::TooManyCustomSections { defined, allowed } => write!(
                 f,
-                "Wasm module defined {} custom sections which exceeds the maximum number allowed {}.",
+                "Wasm module defined {} custom sections with `icp:public` or `icp:private` visibility, which exceeds the maximum number allowed {}.",
                 defined, allowed
             ),
             Self::InvalidFunctionIndex {